@@ -0,0 +1,123 @@
+use crate::dispatch::DispatchConfig;
+use crate::qrcode::image_reader::ImageWatchConfig;
+use crate::trigger::TriggerConfig;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// 配置文件名，放在 DLL 同目录下
+pub const CONFIG_FILE_NAME: &str = "wxwork_help.toml";
+
+/// 指定配置文件路径的环境变量，优先于 DLL 同目录下的配置
+pub const CONFIG_ENV: &str = "WXWORK_HELP_CONFIG";
+
+/// 辅助模块的运行配置
+#[derive(Debug, Clone, Deserialize)]
+pub struct HelperConfig {
+    #[serde(default = "default_version")]
+    pub version: String, // 目标客户端版本
+    #[serde(default = "default_triggers")]
+    pub triggers: Vec<TriggerConfig>, // 刷新二维码的触发器
+    #[serde(default)]
+    pub min_refresh_interval_secs: u64, // 两次刷新之间的最小间隔，0表示不限制
+    #[serde(default)]
+    pub image_watch: ImageWatchConfig, // 解码客户端缓存的二维码图片
+    #[serde(default)]
+    pub dispatch: DispatchConfig, // 客户端调用派发到窗口线程
+}
+
+fn default_version() -> String {
+    "4.1.38.6006".to_string()
+}
+
+// 没有配置文件时：启动后刷新一次，之后每次二维码过期再刷新；
+// 版本配置中没有状态回调、监听不到过期时每分钟刷新一次
+fn default_triggers() -> Vec<TriggerConfig> {
+    vec![
+        TriggerConfig::Once { delay_secs: 0 },
+        TriggerConfig::OnExpiry {
+            fallback_interval_secs: Some(60),
+        },
+    ]
+}
+
+impl Default for HelperConfig {
+    fn default() -> Self {
+        HelperConfig {
+            version: default_version(),
+            triggers: default_triggers(),
+            min_refresh_interval_secs: 0,
+            image_watch: ImageWatchConfig::default(),
+            dispatch: DispatchConfig::default(),
+        }
+    }
+}
+
+impl HelperConfig {
+    /// 从 TOML 文本解析
+    pub fn from_toml_str(text: &str) -> Result<Self, String> {
+        let config: HelperConfig =
+            toml::from_str(text).map_err(|e| format!("解析配置失败: {}", e))?;
+        for trigger in &config.triggers {
+            trigger.validate()?;
+        }
+        Ok(config)
+    }
+
+    /// 从文件加载
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("读取配置 {} 失败: {}", path.display(), e))?;
+        Self::from_toml_str(&text)
+    }
+
+    /// 确定配置文件路径：环境变量优先，其次是 DLL 所在目录
+    pub fn locate(module_dir: Option<&Path>) -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        module_dir
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .filter(|path| path.is_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_on_expiry_with_and_without_fallback() {
+        let config = HelperConfig::from_toml_str(
+            "[[triggers]]\nkind = \"on_expiry\"\n\n[[triggers]]\nkind = \"on_expiry\"\nfallback_interval_secs = 30\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.triggers,
+            [
+                TriggerConfig::OnExpiry {
+                    fallback_interval_secs: None
+                },
+                TriggerConfig::OnExpiry {
+                    fallback_interval_secs: Some(30)
+                },
+            ]
+        );
+
+        let error = HelperConfig::from_toml_str(
+            "[[triggers]]\nkind = \"on_expiry\"\nfallback_interval_secs = 0\n",
+        )
+        .unwrap_err();
+        assert!(error.contains("fallback_interval_secs"), "{}", error);
+    }
+
+    #[test]
+    fn default_triggers_do_not_depend_on_status_hook() {
+        let config = HelperConfig::from_toml_str("").unwrap();
+        assert!(config.triggers.iter().any(|trigger| matches!(
+            trigger,
+            TriggerConfig::OnExpiry {
+                fallback_interval_secs: Some(_)
+            }
+        )));
+    }
+}
@@ -0,0 +1,282 @@
+pub mod ui_thread;
+
+use serde::Deserialize;
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+
+/// 投递到执行器上的任务
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// 执行器：在某个线程上运行任务
+///
+/// 窗口线程上由 `ui_thread::WindowExecutor` 实现，测试时可以用自己的实现代替。
+pub trait Executor: Send + Sync {
+    /// 投递任务，不等待执行
+    fn post(&self, job: Job) -> Result<(), String>;
+
+    /// 当前线程是否就是执行任务的线程，是的话直接执行，投递后等待会死锁
+    fn is_current_thread(&self) -> bool {
+        false
+    }
+}
+
+/// 在当前线程直接执行任务，只在配置关闭派发时使用
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InlineExecutor;
+
+impl Executor for InlineExecutor {
+    fn post(&self, job: Job) -> Result<(), String> {
+        job();
+        Ok(())
+    }
+
+    fn is_current_thread(&self) -> bool {
+        true
+    }
+}
+
+/// 派发配置
+#[derive(Debug, Clone, Deserialize)]
+pub struct DispatchConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool, // 关闭时在工作线程直接调用
+    #[serde(default)]
+    pub window_class: Option<String>, // 目标窗口类名，默认取本进程第一个可见顶层窗口
+    #[serde(default)]
+    pub window_title: Option<String>, // 目标窗口标题
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64, // 等待执行结果的超时
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_timeout_ms() -> u64 {
+    5000
+}
+
+impl Default for DispatchConfig {
+    fn default() -> Self {
+        DispatchConfig {
+            enabled: default_enabled(),
+            window_class: None,
+            window_title: None,
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+}
+
+/// 把闭包派发到执行器上运行，并等待结果
+#[derive(Clone)]
+pub struct Dispatcher {
+    executor: Arc<dyn Executor>,
+    timeout: Duration,
+}
+
+impl Dispatcher {
+    pub fn new(executor: Arc<dyn Executor>, timeout: Duration) -> Self {
+        Dispatcher { executor, timeout }
+    }
+
+    /// 在当前线程直接执行的派发器
+    pub fn inline() -> Self {
+        Self::new(
+            Arc::new(InlineExecutor),
+            Duration::from_millis(default_timeout_ms()),
+        )
+    }
+
+    /// 按配置创建派发器
+    ///
+    /// 关闭派发时在调用线程上直接执行；否则等第一次运行任务时才查找目标窗口，
+    /// 找不到时任务返回错误而不是在调用线程上执行，下次运行时重新查找。
+    pub fn from_config(config: &DispatchConfig) -> Self {
+        if !config.enabled {
+            return Self::inline();
+        }
+        let executor = ui_thread::WindowExecutor::new(
+            config.window_class.clone(),
+            config.window_title.clone(),
+        );
+        Self::new(Arc::new(executor), Duration::from_millis(config.timeout_ms))
+    }
+
+    /// 运行闭包并等待结果
+    ///
+    /// 超时后返回错误，但任务仍可能在之后执行，闭包不要引用调用方栈上的数据。
+    pub fn run<R, F>(&self, f: F) -> Result<R, String>
+    where
+        R: Send + 'static,
+        F: FnOnce() -> R + Send + 'static,
+    {
+        let job = move || std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+        if self.executor.is_current_thread() {
+            return job().map_err(|_| "派发的任务执行时发生异常".to_string());
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        self.executor.post(Box::new(move || {
+            let _ = sender.send(job());
+        }))?;
+
+        match receiver.recv_timeout(self.timeout) {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(_)) => Err("派发的任务执行时发生异常".to_string()),
+            Err(RecvTimeoutError::Timeout) => Err(format!(
+                "等待任务执行超时 ({} ms)",
+                self.timeout.as_millis()
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err("任务在执行前被丢弃".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::thread::{self, ThreadId};
+
+    // 在单独的线程上按顺序执行任务，模拟窗口线程
+    struct ThreadExecutor {
+        sender: Mutex<mpsc::Sender<Job>>,
+        thread: ThreadId,
+    }
+
+    impl ThreadExecutor {
+        fn spawn() -> Arc<Self> {
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let handle = thread::spawn(move || {
+                for job in receiver {
+                    job();
+                }
+            });
+            Arc::new(ThreadExecutor {
+                sender: Mutex::new(sender),
+                thread: handle.thread().id(),
+            })
+        }
+    }
+
+    impl Executor for ThreadExecutor {
+        fn post(&self, job: Job) -> Result<(), String> {
+            self.sender
+                .lock()
+                .unwrap()
+                .send(job)
+                .map_err(|_| "执行线程已退出".to_string())
+        }
+
+        fn is_current_thread(&self) -> bool {
+            thread::current().id() == self.thread
+        }
+    }
+
+    // 保存任务但不执行，`drop_jobs` 时丢弃
+    #[derive(Default)]
+    struct StalledExecutor {
+        jobs: Mutex<Vec<Job>>,
+        drop_jobs: bool,
+    }
+
+    impl Executor for StalledExecutor {
+        fn post(&self, job: Job) -> Result<(), String> {
+            if !self.drop_jobs {
+                self.jobs.lock().unwrap().push(job);
+            }
+            Ok(())
+        }
+    }
+
+    // 投递总是失败，比如找不到目标窗口
+    struct UnavailableExecutor;
+
+    impl Executor for UnavailableExecutor {
+        fn post(&self, _job: Job) -> Result<(), String> {
+            Err("未找到目标窗口".to_string())
+        }
+    }
+
+    fn dispatcher(executor: Arc<dyn Executor>) -> Dispatcher {
+        Dispatcher::new(executor, Duration::from_millis(200))
+    }
+
+    #[test]
+    fn runs_on_executor_thread() {
+        let executor = ThreadExecutor::spawn();
+        let target = executor.thread;
+        let dispatcher = dispatcher(executor);
+        let ran_on = dispatcher.run(|| thread::current().id()).unwrap();
+        assert_eq!(ran_on, target);
+        assert_ne!(ran_on, thread::current().id());
+        assert_eq!(dispatcher.run(|| 6 * 7).unwrap(), 42);
+    }
+
+    #[test]
+    fn runs_directly_on_executor_thread() {
+        // 任务中再次派发到同一个线程，不能等待自己
+        let dispatcher = dispatcher(ThreadExecutor::spawn());
+        let nested = dispatcher.clone();
+        let result = dispatcher
+            .run(move || nested.run(|| thread::current().id()))
+            .unwrap()
+            .unwrap();
+        assert_ne!(result, thread::current().id());
+    }
+
+    #[test]
+    fn reports_post_failure_without_running() {
+        let ran = Arc::new(Mutex::new(false));
+        let flag = ran.clone();
+        let error = dispatcher(Arc::new(UnavailableExecutor))
+            .run(move || *flag.lock().unwrap() = true)
+            .unwrap_err();
+        assert_eq!(error, "未找到目标窗口");
+        assert!(!*ran.lock().unwrap());
+    }
+
+    #[test]
+    fn times_out_when_job_is_not_run() {
+        let executor = Arc::new(StalledExecutor::default());
+        let error = dispatcher(executor.clone()).run(|| 1).unwrap_err();
+        assert!(error.contains("超时"), "{}", error);
+        // 超时后任务仍然可以执行，结果没有人接收
+        for job in executor.jobs.lock().unwrap().drain(..) {
+            job();
+        }
+    }
+
+    #[test]
+    fn reports_dropped_job() {
+        let executor = Arc::new(StalledExecutor {
+            drop_jobs: true,
+            ..Default::default()
+        });
+        let error = dispatcher(executor).run(|| 1).unwrap_err();
+        assert_eq!(error, "任务在执行前被丢弃");
+    }
+
+    #[test]
+    fn reports_panicking_job() {
+        let dispatcher = dispatcher(ThreadExecutor::spawn());
+        let error = dispatcher.run(|| panic!("job failed")).unwrap_err();
+        assert_eq!(error, "派发的任务执行时发生异常");
+        // 执行线程没有受影响
+        assert_eq!(dispatcher.run(|| 1).unwrap(), 1);
+    }
+
+    #[test]
+    fn disabled_config_runs_inline() {
+        let config = DispatchConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let dispatcher = Dispatcher::from_config(&config);
+        assert_eq!(
+            dispatcher.run(|| thread::current().id()).unwrap(),
+            thread::current().id()
+        );
+    }
+}
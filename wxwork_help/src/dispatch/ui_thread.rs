@@ -0,0 +1,261 @@
+use super::{Executor, Job};
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::OsStr;
+use std::os::windows::ffi::OsStrExt;
+use std::sync::{Mutex, OnceLock};
+use winapi::shared::minwindef::{BOOL, LPARAM, LRESULT, UINT, WPARAM};
+use winapi::shared::windef::{HHOOK, HWND};
+use winapi::um::processthreadsapi::{GetCurrentProcessId, GetCurrentThreadId};
+use winapi::um::winuser::{
+    CallNextHookEx, EnumWindows, GetClassNameW, GetWindowTextW, GetWindowThreadProcessId,
+    HC_ACTION, IsWindow, IsWindowVisible, MSG, PM_REMOVE, PostMessageW, RegisterWindowMessageW,
+    SetWindowsHookExW, UnhookWindowsHookEx, WH_GETMESSAGE, WM_NULL,
+};
+
+/// 调试日志输出函数
+fn debug_log(message: &str) {
+    unsafe {
+        use winapi::um::debugapi::OutputDebugStringW;
+
+        let formatted_message = format!("[DISPATCH] {}\n", message);
+        let wide_message: Vec<u16> = OsStr::new(&formatted_message)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        OutputDebugStringW(wide_message.as_ptr());
+    }
+}
+
+// 每个窗口线程待执行的任务，键为线程ID
+static PENDING_JOBS: Mutex<BTreeMap<u32, VecDeque<Job>>> = Mutex::new(BTreeMap::new());
+
+// 通知窗口线程执行任务的消息
+fn dispatch_message() -> UINT {
+    static MESSAGE: OnceLock<UINT> = OnceLock::new();
+    *MESSAGE.get_or_init(|| {
+        let name: Vec<u16> = OsStr::new("wxwork_help_dispatch")
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        unsafe { RegisterWindowMessageW(name.as_ptr()) }
+    })
+}
+
+/// 在窗口所属线程上执行任务
+///
+/// 在目标线程上安装 WH_GETMESSAGE 钩子，投递任务后向窗口发送自定义消息；
+/// 线程的消息循环（包括模态循环）取出这条消息时，钩子在该线程上执行所有待执行任务。
+/// 不使用 APC，是因为界面线程很少进入可提醒的等待。
+pub struct UiThreadExecutor {
+    hwnd: usize,
+    thread_id: u32,
+    hook: usize,
+}
+
+impl UiThreadExecutor {
+    /// 在指定窗口的线程上安装钩子
+    pub fn new(hwnd: usize) -> Result<Self, String> {
+        let thread_id = unsafe { GetWindowThreadProcessId(hwnd as HWND, std::ptr::null_mut()) };
+        if thread_id == 0 {
+            return Err(format!("窗口 0x{:X} 无效", hwnd));
+        }
+        dispatch_message();
+
+        let hook = unsafe {
+            SetWindowsHookExW(
+                WH_GETMESSAGE,
+                Some(get_message_proc),
+                std::ptr::null_mut(),
+                thread_id,
+            )
+        };
+        if hook.is_null() {
+            return Err(format!("在线程 {} 上安装消息钩子失败", thread_id));
+        }
+
+        debug_log(&format!("派发到窗口 0x{:X} 的线程 {}", hwnd, thread_id));
+        Ok(UiThreadExecutor {
+            hwnd,
+            thread_id,
+            hook: hook as usize,
+        })
+    }
+
+    /// 按类名和标题查找本进程的可见顶层窗口，都不指定时取第一个
+    pub fn find(class_name: Option<&str>, title: Option<&str>) -> Result<Self, String> {
+        let hwnd = find_process_window(class_name, title)
+            .ok_or_else(|| format!("未找到目标窗口 (类名 {:?}, 标题 {:?})", class_name, title))?;
+        Self::new(hwnd)
+    }
+
+    /// 目标线程ID
+    pub fn thread_id(&self) -> u32 {
+        self.thread_id
+    }
+
+    /// 窗口仍然存在并且属于原来的线程
+    pub fn is_alive(&self) -> bool {
+        let hwnd = self.hwnd as HWND;
+        unsafe {
+            IsWindow(hwnd) != 0
+                && GetWindowThreadProcessId(hwnd, std::ptr::null_mut()) == self.thread_id
+        }
+    }
+}
+
+/// 在按类名和标题找到的窗口线程上执行任务
+///
+/// 投递任务时才查找窗口，找不到时返回错误，下次投递重新查找；窗口销毁或重建后
+/// （比如登录完成后登录窗口关闭）同样重新查找，钩子改装到新窗口的线程上。
+pub struct WindowExecutor {
+    class_name: Option<String>,
+    title: Option<String>,
+    current: Mutex<Option<UiThreadExecutor>>,
+}
+
+impl WindowExecutor {
+    pub fn new(class_name: Option<String>, title: Option<String>) -> Self {
+        WindowExecutor {
+            class_name,
+            title,
+            current: Mutex::new(None),
+        }
+    }
+
+    // 在当前有效的执行器上操作，窗口失效时重新查找
+    fn with_executor<R>(&self, f: impl FnOnce(&UiThreadExecutor) -> R) -> Result<R, String> {
+        let mut current = self
+            .current
+            .lock()
+            .map_err(|_| "执行器锁已损坏".to_string())?;
+        if current
+            .as_ref()
+            .is_some_and(|executor| !executor.is_alive())
+        {
+            debug_log("目标窗口已失效，重新查找");
+            // 先卸载旧钩子，丢弃的任务会通知等待方
+            *current = None;
+        }
+        if current.is_none() {
+            *current = Some(UiThreadExecutor::find(
+                self.class_name.as_deref(),
+                self.title.as_deref(),
+            )?);
+        }
+        match current.as_ref() {
+            Some(executor) => Ok(f(executor)),
+            None => Err("未找到目标窗口".to_string()),
+        }
+    }
+}
+
+impl Executor for WindowExecutor {
+    fn post(&self, job: Job) -> Result<(), String> {
+        self.with_executor(|executor| executor.post(job))?
+    }
+
+    fn is_current_thread(&self) -> bool {
+        self.with_executor(|executor| executor.thread_id() == unsafe { GetCurrentThreadId() })
+            .unwrap_or(false)
+    }
+}
+
+impl Executor for UiThreadExecutor {
+    fn post(&self, job: Job) -> Result<(), String> {
+        PENDING_JOBS
+            .lock()
+            .map_err(|_| "任务队列锁已损坏".to_string())?
+            .entry(self.thread_id)
+            .or_default()
+            .push_back(job);
+
+        let posted = unsafe { PostMessageW(self.hwnd as HWND, dispatch_message(), 0, 0) };
+        if posted == 0 {
+            take_jobs(self.thread_id);
+            return Err(format!("向窗口 0x{:X} 投递消息失败", self.hwnd));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UiThreadExecutor {
+    fn drop(&mut self) {
+        unsafe {
+            UnhookWindowsHookEx(self.hook as HHOOK);
+        }
+        // 没执行的任务直接丢弃，等待方会收到任务被丢弃的错误
+        take_jobs(self.thread_id);
+    }
+}
+
+// 取出某个线程的全部待执行任务
+fn take_jobs(thread_id: u32) -> VecDeque<Job> {
+    PENDING_JOBS
+        .lock()
+        .ok()
+        .and_then(|mut pending| pending.remove(&thread_id))
+        .unwrap_or_default()
+}
+
+// 消息钩子，在目标线程上执行
+unsafe extern "system" fn get_message_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION && wparam == PM_REMOVE as WPARAM && lparam != 0 {
+        let msg = unsafe { &mut *(lparam as *mut MSG) };
+        if msg.message == dispatch_message() {
+            // 消息本身交给窗口过程没有意义
+            msg.message = WM_NULL;
+            for job in take_jobs(unsafe { GetCurrentThreadId() }) {
+                job();
+            }
+        }
+    }
+    unsafe { CallNextHookEx(std::ptr::null_mut(), code, wparam, lparam) }
+}
+
+// 查找条件和结果
+struct WindowQuery<'a> {
+    process_id: u32,
+    class_name: Option<&'a str>,
+    title: Option<&'a str>,
+    found: Option<usize>,
+}
+
+fn find_process_window(class_name: Option<&str>, title: Option<&str>) -> Option<usize> {
+    let mut query = WindowQuery {
+        process_id: unsafe { GetCurrentProcessId() },
+        class_name,
+        title,
+        found: None,
+    };
+    unsafe {
+        EnumWindows(Some(enum_window_proc), &mut query as *mut _ as LPARAM);
+    }
+    query.found
+}
+
+unsafe extern "system" fn enum_window_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let query = unsafe { &mut *(lparam as *mut WindowQuery) };
+
+    let mut process_id = 0;
+    unsafe { GetWindowThreadProcessId(hwnd, &mut process_id) };
+    if process_id != query.process_id || unsafe { IsWindowVisible(hwnd) } == 0 {
+        return 1;
+    }
+
+    let mut buffer = [0u16; 256];
+    if let Some(class_name) = query.class_name {
+        let len = unsafe { GetClassNameW(hwnd, buffer.as_mut_ptr(), buffer.len() as i32) };
+        if String::from_utf16_lossy(&buffer[..len.max(0) as usize]) != class_name {
+            return 1;
+        }
+    }
+    if let Some(title) = query.title {
+        let len = unsafe { GetWindowTextW(hwnd, buffer.as_mut_ptr(), buffer.len() as i32) };
+        if String::from_utf16_lossy(&buffer[..len.max(0) as usize]) != title {
+            return 1;
+        }
+    }
+
+    query.found = Some(hwnd as usize);
+    0
+}
@@ -1,9 +1,30 @@
-use std::os::windows::ffi::OsStrExt;
-use std::thread;
-use winapi::shared::minwindef::{BOOL, DWORD, HINSTANCE};
+use std::ffi::c_void;
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use winapi::shared::minwindef::{BOOL, DWORD, HINSTANCE, HMODULE};
 use winapi::um::debugapi::OutputDebugStringW;
+use winapi::um::libloaderapi::{FreeLibraryAndExitThread, GetModuleFileNameW};
 use winapi::um::winnt::{DLL_PROCESS_ATTACH, DLL_PROCESS_DETACH};
-use winapi::um::winuser::{MB_ICONINFORMATION, MB_OK, MessageBoxW};
+
+mod config;
+use config::HelperConfig;
+
+pub mod dispatch;
+use dispatch::Dispatcher;
+
+pub mod qrcode;
+use qrcode::content::qrcode_content_hub;
+use qrcode::image_reader::start_image_reader;
+use qrcode::state::{QrcodeState, qrcode_state_hub};
+use qrcode::status_hook::{remove_status_hook, status_calls_in_flight};
+
+pub mod trigger;
+use trigger::{TriggerEvent, shutdown, start_triggers};
 
 mod utils;
 
@@ -22,70 +43,279 @@ fn debug_log(message: &str) {
     }
 }
 
-// 将字符串转换为宽字符串（UTF-16）
-fn to_wide_string(s: &str) -> Vec<u16> {
-    use std::ffi::OsStr;
-    OsStr::new(s)
-        .encode_wide()
-        .chain(std::iter::once(0))
-        .collect()
+// 本DLL的模块句柄和工作线程，卸载时使用
+static MODULE: AtomicUsize = AtomicUsize::new(0);
+static WORKER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+static EJECTING: AtomicBool = AtomicBool::new(false);
+
+// 卸载前等待工作线程和正在执行的hook结束的时间
+const EJECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+// 获取 DLL 所在目录
+fn module_dir(module: usize) -> Option<PathBuf> {
+    let mut buffer = [0u16; 1024];
+    let len = unsafe {
+        GetModuleFileNameW(
+            module as HMODULE,
+            buffer.as_mut_ptr(),
+            buffer.len() as DWORD,
+        )
+    } as usize;
+    if len == 0 || len >= buffer.len() {
+        return None;
+    }
+    let path = PathBuf::from(std::ffi::OsString::from_wide(&buffer[..len]));
+    path.parent().map(|dir| dir.to_path_buf())
+}
+
+// 读取配置，没有配置文件时使用默认配置
+fn load_config(module: usize) -> Result<HelperConfig, String> {
+    match HelperConfig::locate(module_dir(module).as_deref()) {
+        Some(path) => {
+            debug_log(&format!("加载配置: {}", path.display()));
+            HelperConfig::load(&path)
+        }
+        None => {
+            debug_log("未找到配置文件，使用默认配置");
+            Ok(HelperConfig::default())
+        }
+    }
 }
 
 // 线程函数
-fn worker_thread() {
+fn worker_thread(module: usize) {
     debug_log("工作线程启动");
-    if let Some(mut wxwork) = create_wxwork_instance("4.1.38.6006") {
-        // 初始化
-        let result = wxwork.init();
-        if let Err(e) = result {
-            debug_log(&format!("初始化失败: {}", e));
+    let config = match load_config(module) {
+        Ok(config) => config,
+        Err(e) => {
+            debug_log(&format!("加载配置失败: {}", e));
             return;
         }
+    };
+
+    // 二维码内容不论来自内存还是图片都从同一个广播发布
+    qrcode_content_hub().on_event(|qrcode| match qrcode.payload() {
+        Ok(payload) if payload.is_known_location() => debug_log(&format!(
+            "登录二维码 key: {} (来源 {:?})",
+            payload.key, qrcode.source
+        )),
+        Ok(payload) => debug_log(&format!(
+            "登录二维码 key: {} (来源 {:?})，位置 {}{} 与推测的不同",
+            payload.key, qrcode.source, payload.host, payload.path
+        )),
+        Err(e) => debug_log(&format!("二维码内容 {} 无法解析: {}", qrcode.content, e)),
+    });
 
-        // 循环刷新二维码
-        loop {
-            // 检查是否需要刷新登录二维码
-            unsafe {
-                let message = to_wide_string("刷新二维码");
-                let title = to_wide_string("提示");
-                MessageBoxW(
-                    std::ptr::null_mut(),
-                    message.as_ptr(),
-                    title.as_ptr(),
-                    MB_OK | MB_ICONINFORMATION,
-                );
+    // 图片解码不依赖客户端函数，先于初始化启动
+    let image_reader = match config
+        .image_watch
+        .enabled
+        .then(|| start_image_reader(&config.image_watch))
+    {
+        Some(Ok(handle)) => Some(handle),
+        Some(Err(e)) => {
+            debug_log(&format!("启动二维码图片监控失败: {}", e));
+            None
+        }
+        None => None,
+    };
+
+    run_client(&config);
+
+    // 客户端功能出错提前结束时，图片监控仍然继续，直到卸载
+    if let Some(image_reader) = image_reader {
+        while !shutdown().wait_timeout(Duration::from_secs(3600)) {}
+        let _ = image_reader.join();
+    }
+    debug_log("工作线程退出");
+}
+
+// 初始化客户端功能，按触发器刷新二维码，直到请求退出
+fn run_client(config: &HelperConfig) {
+    let Some(mut wxwork) = create_wxwork_instance(&config.version) else {
+        debug_log(&format!("不支持此版本: {}", config.version));
+        return;
+    };
+
+    // 初始化
+    let result = wxwork.init();
+    if let Err(e) = result {
+        debug_log(&format!("初始化失败: {}", e));
+        return;
+    }
+
+    // 客户端调用派发到窗口线程，找不到窗口时调用失败，下次调用重新查找
+    if !config.dispatch.enabled {
+        debug_log("派发已关闭，在工作线程直接调用客户端函数");
+    }
+    wxwork.set_dispatcher(Dispatcher::from_config(&config.dispatch));
+
+    // 二维码内容字段没有定位的版本不读取内存，二维码内容只能来自 image_watch
+    let read_content = wxwork.supports_qrcode_content();
+    if !read_content {
+        debug_log("版本配置中没有 qrcode_content，刷新后不读取二维码内容");
+    }
+
+    // 监听二维码状态，失败不影响刷新
+    qrcode_state_hub().on_transition(|transition| {
+        debug_log(&format!(
+            "二维码状态变化: {} -> {}",
+            transition.from, transition.to
+        ));
+    });
+    // 状态回调没有定位的版本不挂hook，on_expiry 触发器按 fallback_interval_secs 定时刷新
+    let status_watched = if !wxwork.supports_qrcode_status() {
+        debug_log("版本配置中没有 qrcode_status，不监听二维码状态");
+        false
+    } else {
+        match wxwork.watch_qrcode_status() {
+            Ok(()) => true,
+            Err(e) => {
+                debug_log(&format!("监听二维码状态失败: {}", e));
+                false
             }
+        }
+    };
 
-            // 刷新二维码
-            let result = wxwork.refresh_qrcode();
-            if let Err(e) = result {
-                debug_log(&format!("刷新二维码失败: {}", e));
-                return;
+    // 登录完成后登录窗口会关闭，缓存的二维码对象随之失效
+    let state_changes = qrcode_state_hub().subscribe();
+
+    // 启动触发器
+    let (sender, receiver) = mpsc::channel::<TriggerEvent>();
+    let triggers = match start_triggers(&config.triggers, status_watched, sender) {
+        Ok(triggers) => triggers,
+        Err(e) => {
+            debug_log(&format!("启动触发器失败: {}", e));
+            return;
+        }
+    };
+
+    // 每次触发刷新二维码，直到DLL卸载
+    let min_interval = Duration::from_secs(config.min_refresh_interval_secs);
+    let mut last_refresh: Option<Instant> = None;
+    while !shutdown().is_requested() {
+        let event = match receiver.recv_timeout(Duration::from_millis(500)) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if state_changes
+            .try_iter()
+            .any(|transition| transition.to == QrcodeState::Confirmed)
+        {
+            wxwork.invalidate_cache();
+        }
+
+        // 多个触发器同时触发时只刷新一次
+        if last_refresh.is_some_and(|last| last.elapsed() < min_interval) {
+            debug_log(&format!("忽略触发 {}：距上次刷新太近", event.source));
+            continue;
+        }
+        debug_log(&format!("触发刷新: {}", event.source));
+
+        // 刷新二维码
+        let result = wxwork.refresh_qrcode();
+        last_refresh = Some(Instant::now());
+        if let Err(e) = result {
+            debug_log(&format!("刷新二维码失败: {}", e));
+            continue;
+        }
+
+        // 读取二维码内容
+        if !read_content {
+            continue;
+        }
+        match wxwork.get_qrcode_content() {
+            Ok(qrcode) => qrcode_content_hub().publish(&qrcode),
+            Err(e) => debug_log(&format!("读取二维码内容失败: {}", e)),
+        }
+    }
+
+    // 触发器线程都会检查退出信号
+    for trigger in triggers {
+        let _ = trigger.join();
+    }
+}
+
+// 卸载前的清理：通知线程退出、禁用hook，等待工作线程和正在执行的hook结束
+fn prepare_eject() -> Result<(), String> {
+    shutdown().request();
+    remove_status_hook()?;
+
+    let deadline = Instant::now() + EJECT_TIMEOUT;
+    let worker = WORKER
+        .lock()
+        .map_err(|_| "工作线程句柄锁已损坏".to_string())?
+        .take();
+    if let Some(worker) = worker {
+        while !worker.is_finished() {
+            if Instant::now() >= deadline {
+                // 句柄放回去，下次卸载还能继续等
+                if let Ok(mut slot) = WORKER.lock() {
+                    *slot = Some(worker);
+                }
+                return Err("工作线程没有按时退出".to_string());
             }
+            thread::sleep(Duration::from_millis(50));
         }
-    } else {
-        unsafe {
-            let message = to_wide_string("不支持此版本");
-            let title = to_wide_string("提示");
-            MessageBoxW(
-                std::ptr::null_mut(),
-                message.as_ptr(),
-                title.as_ptr(),
-                MB_OK | MB_ICONINFORMATION,
-            );
+        let _ = worker.join();
+    }
+
+    // 计数由DLL之外的入口桩维护，为0时没有线程还在DLL的代码中
+    while status_calls_in_flight() > 0 {
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "还有 {} 个状态回调没有返回",
+                status_calls_in_flight()
+            ));
         }
+        thread::sleep(Duration::from_millis(10));
     }
+    Ok(())
+}
+
+/// 卸载DLL：停止所有线程、恢复被hook的函数后释放自身
+///
+/// 由注入工具用 CreateRemoteThread 调用。任何一步没有完成时不卸载，DLL保持加载
+/// （hook已禁用），返回1；成功时线程在 FreeLibraryAndExitThread 中结束，不会返回。
+#[unsafe(no_mangle)]
+pub extern "system" fn Eject(_parameter: *mut c_void) -> DWORD {
+    if EJECTING.swap(true, Ordering::SeqCst) {
+        return 1;
+    }
+    debug_log("开始卸载");
+    if let Err(e) = prepare_eject() {
+        debug_log(&format!("卸载失败，DLL保持加载: {}", e));
+        EJECTING.store(false, Ordering::SeqCst);
+        return 1;
+    }
+    debug_log("所有线程已退出，释放DLL");
+    unsafe { FreeLibraryAndExitThread(MODULE.load(Ordering::SeqCst) as HMODULE, 0) };
+    0
 }
 
 #[unsafe(no_mangle)] // 防止函数名被混淆，确保Windows能识别DllMain
-pub extern "stdcall" fn DllMain(_hinst: HINSTANCE, reason: DWORD, _reserved: *mut ()) -> BOOL {
+pub extern "stdcall" fn DllMain(hinst: HINSTANCE, reason: DWORD, _reserved: *mut ()) -> BOOL {
     match reason {
         DLL_PROCESS_ATTACH => {
-            thread::spawn(|| {
-                worker_thread();
+            let module = hinst as usize;
+            MODULE.store(module, Ordering::SeqCst);
+            let worker = thread::spawn(move || {
+                worker_thread(module);
             });
+            if let Ok(mut slot) = WORKER.lock() {
+                *slot = Some(worker);
+            }
+        }
+        DLL_PROCESS_DETACH => {
+            // 通知所有线程退出，加载器锁下不能等待线程结束；
+            // 不经过 Eject 直接 FreeLibrary 时线程可能还没退出，应当用 Eject 卸载
+            shutdown().request();
+            // 恢复状态回调的原始代码，否则DLL卸载后客户端会跳进已释放的内存
+            if let Err(e) = remove_status_hook() {
+                debug_log(&e);
+            }
         }
-        DLL_PROCESS_DETACH => {}
         _ => {}
     }
     1
@@ -0,0 +1,59 @@
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+// 订阅者：通道或回调
+enum Subscriber<T> {
+    Channel(Sender<T>),
+    Callback(Box<dyn Fn(&T) + Send>),
+}
+
+/// 把事件分发给所有订阅者
+///
+/// 通道订阅者的接收端被丢弃后自动移除；回调在发布事件的线程上调用，
+/// 回调里不要做耗时操作，也不要再订阅同一个广播。
+pub struct Broadcast<T> {
+    subscribers: Mutex<Vec<Subscriber<T>>>,
+}
+
+impl<T: Clone> Broadcast<T> {
+    pub const fn new() -> Self {
+        Broadcast {
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 订阅事件，返回接收端
+    pub fn subscribe(&self) -> Receiver<T> {
+        let (sender, receiver) = mpsc::channel();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(Subscriber::Channel(sender));
+        }
+        receiver
+    }
+
+    /// 注册回调
+    pub fn on_event(&self, callback: impl Fn(&T) + Send + 'static) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(Subscriber::Callback(Box::new(callback)));
+        }
+    }
+
+    /// 发布事件
+    pub fn publish(&self, event: &T) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| match subscriber {
+                Subscriber::Channel(sender) => sender.send(event.clone()).is_ok(),
+                Subscriber::Callback(callback) => {
+                    callback(event);
+                    true
+                }
+            });
+        }
+    }
+}
+
+impl<T: Clone> Default for Broadcast<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
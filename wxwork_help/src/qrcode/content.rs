@@ -0,0 +1,33 @@
+use super::broadcast::Broadcast;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use wxwork_qrcode::LoginQrPayload;
+
+/// 二维码内容的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QrcodeSource {
+    Memory,         // 从 QrcodeFrame 对象中读取
+    Image(PathBuf), // 解码客户端缓存的二维码图片
+}
+
+// 登录二维码内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrcodeContent {
+    pub content: String,      // 二维码中的文本（URL + key）
+    pub read_at: SystemTime,  // 读取时间
+    pub source: QrcodeSource, // 来源
+}
+
+impl QrcodeContent {
+    /// 解析为登录二维码
+    pub fn payload(&self) -> Result<LoginQrPayload, String> {
+        LoginQrPayload::parse(&self.content)
+    }
+}
+
+static QRCODE_CONTENT_HUB: Broadcast<QrcodeContent> = Broadcast::new();
+
+/// 获取全局二维码内容广播，内存读取和图片解码得到的内容都从这里发布
+pub fn qrcode_content_hub() -> &'static Broadcast<QrcodeContent> {
+    &QRCODE_CONTENT_HUB
+}
@@ -0,0 +1,105 @@
+use super::content::{QrcodeContent, QrcodeSource, qrcode_content_hub};
+use crate::trigger::shutdown;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+use wxwork_qrcode::ImageWatcher;
+
+/// 调试日志输出函数
+fn debug_log(message: &str) {
+    unsafe {
+        use std::ffi::OsStr;
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::debugapi::OutputDebugStringW;
+
+        let formatted_message = format!("[QRCODE_IMAGE] {}\n", message);
+        let wide_message: Vec<u16> = OsStr::new(&formatted_message)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        OutputDebugStringW(wide_message.as_ptr());
+    }
+}
+
+/// 二维码图片监控配置
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImageWatchConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub dir: Option<PathBuf>, // 默认 %USERPROFILE%\Documents\WXWork\Global\Image
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64, // 扫描间隔
+    #[serde(default = "default_skip_existing")]
+    pub skip_existing: bool, // 启动时忽略目录中已有的图片
+}
+
+fn default_poll_interval_ms() -> u64 {
+    500
+}
+
+fn default_skip_existing() -> bool {
+    true
+}
+
+impl Default for ImageWatchConfig {
+    fn default() -> Self {
+        ImageWatchConfig {
+            enabled: false,
+            dir: None,
+            poll_interval_ms: default_poll_interval_ms(),
+            skip_existing: default_skip_existing(),
+        }
+    }
+}
+
+impl ImageWatchConfig {
+    /// 实际监控的目录
+    pub fn image_dir(&self) -> Result<PathBuf, String> {
+        if let Some(dir) = &self.dir {
+            return Ok(dir.clone());
+        }
+        let user_profile = std::env::var_os("USERPROFILE")
+            .ok_or_else(|| "未设置 USERPROFILE，请在配置中指定 dir".to_string())?;
+        Ok(PathBuf::from(user_profile)
+            .join("Documents")
+            .join("WXWork")
+            .join("Global")
+            .join("Image"))
+    }
+}
+
+/// 启动图片监控线程，解码出的二维码发布到 `qrcode_content_hub`
+pub fn start_image_reader(config: &ImageWatchConfig) -> Result<JoinHandle<()>, String> {
+    let dir = config.image_dir()?;
+    let interval = Duration::from_millis(config.poll_interval_ms.max(50));
+    let mut watcher = ImageWatcher::new(&dir);
+    if config.skip_existing {
+        let count = watcher.skip_existing()?;
+        debug_log(&format!("忽略已有图片 {} 个", count));
+    }
+    debug_log(&format!("开始监控二维码图片: {}", dir.display()));
+
+    Ok(thread::spawn(move || {
+        while !shutdown().wait_timeout(interval) {
+            let result = watcher.poll(SystemTime::now());
+            for error in &result.errors {
+                debug_log(&format!("解码失败: {}", error));
+            }
+            for decoded in result.decoded {
+                debug_log(&format!(
+                    "解码二维码: {} ({})",
+                    decoded.content,
+                    decoded.path.display()
+                ));
+                qrcode_content_hub().publish(&QrcodeContent {
+                    content: decoded.content,
+                    read_at: SystemTime::now(),
+                    source: QrcodeSource::Image(decoded.path),
+                });
+            }
+        }
+        debug_log("图片监控线程退出");
+    }))
+}
@@ -0,0 +1,5 @@
+pub mod broadcast;
+pub mod content;
+pub mod image_reader;
+pub mod state;
+pub mod status_hook;
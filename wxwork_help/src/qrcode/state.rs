@@ -0,0 +1,304 @@
+use super::broadcast::Broadcast;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::mpsc::Receiver;
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+/// 登录二维码状态，对应文档中的登录流程
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QrcodeState {
+    Idle,      // 还没有收到任何状态
+    Waiting,   // 新二维码已生成，等待扫码
+    Scanned,   // 移动端已扫码，等待确认
+    Confirmed, // 移动端已确认，登录完成
+    Cancelled, // 移动端取消了登录
+    Expired,   // 二维码已过期
+}
+
+impl QrcodeState {
+    /// 是否为结束状态，结束后只有生成新二维码才能离开
+    pub fn is_final(self) -> bool {
+        matches!(
+            self,
+            QrcodeState::Confirmed | QrcodeState::Cancelled | QrcodeState::Expired
+        )
+    }
+}
+
+impl fmt::Display for QrcodeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            QrcodeState::Idle => "idle",
+            QrcodeState::Waiting => "waiting",
+            QrcodeState::Scanned => "scanned",
+            QrcodeState::Confirmed => "confirmed",
+            QrcodeState::Cancelled => "cancelled",
+            QrcodeState::Expired => "expired",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for QrcodeState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "waiting" => Ok(QrcodeState::Waiting),
+            "scanned" => Ok(QrcodeState::Scanned),
+            "confirmed" => Ok(QrcodeState::Confirmed),
+            "cancelled" => Ok(QrcodeState::Cancelled),
+            "expired" => Ok(QrcodeState::Expired),
+            _ => Err(format!("未知的二维码状态: {}", s)),
+        }
+    }
+}
+
+/// 一次状态转换
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrcodeTransition {
+    pub from: QrcodeState,
+    pub to: QrcodeState,
+    pub at: SystemTime,        // 观察到新状态的时间
+    pub raw_code: Option<i32>, // 回调收到的原始状态码
+}
+
+/// 二维码状态机
+///
+/// 只接收观察到的状态，不依赖客户端，可以直接喂录制下来的状态序列。
+/// 轮询会反复报告同一状态，重复的状态不产生转换；结束状态之后只接受新的
+/// waiting（刷新了二维码），其余不合法的转换直接忽略。
+#[derive(Debug, Clone)]
+pub struct QrcodeStateMachine {
+    state: QrcodeState,
+}
+
+impl QrcodeStateMachine {
+    pub fn new() -> Self {
+        QrcodeStateMachine {
+            state: QrcodeState::Idle,
+        }
+    }
+
+    /// 当前状态
+    pub fn state(&self) -> QrcodeState {
+        self.state
+    }
+
+    /// 输入一次观察到的状态，状态发生变化时返回转换
+    pub fn feed(
+        &mut self,
+        observed: QrcodeState,
+        at: SystemTime,
+        raw_code: Option<i32>,
+    ) -> Option<QrcodeTransition> {
+        if !Self::is_allowed(self.state, observed) {
+            return None;
+        }
+        let transition = QrcodeTransition {
+            from: self.state,
+            to: observed,
+            at,
+            raw_code,
+        };
+        self.state = observed;
+        Some(transition)
+    }
+
+    /// 按顺序输入多个状态，返回产生的所有转换
+    pub fn replay<I>(&mut self, events: I) -> Vec<QrcodeTransition>
+    where
+        I: IntoIterator<Item = (QrcodeState, SystemTime)>,
+    {
+        events
+            .into_iter()
+            .filter_map(|(observed, at)| self.feed(observed, at, None))
+            .collect()
+    }
+
+    // 判断转换是否合法
+    fn is_allowed(from: QrcodeState, to: QrcodeState) -> bool {
+        use QrcodeState::*;
+        match (from, to) {
+            (_, Idle) => false,
+            (from, to) if from == to => false,
+            // 任何时候刷新出新二维码都回到等待扫码
+            (_, Waiting) => true,
+            (from, _) if from.is_final() => false,
+            // 轮询可能错过中间状态，所以允许从等待直接到结束状态
+            (Idle | Waiting, Scanned) => true,
+            (Idle | Waiting | Scanned, Confirmed | Cancelled | Expired) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Default for QrcodeStateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 状态机加订阅者，状态发生变化时通知所有订阅者
+pub struct QrcodeStateHub {
+    machine: Mutex<QrcodeStateMachine>,
+    transitions: Broadcast<QrcodeTransition>,
+}
+
+impl QrcodeStateHub {
+    pub fn new() -> Self {
+        QrcodeStateHub {
+            machine: Mutex::new(QrcodeStateMachine::new()),
+            transitions: Broadcast::new(),
+        }
+    }
+
+    /// 当前状态
+    pub fn state(&self) -> QrcodeState {
+        self.machine
+            .lock()
+            .map(|machine| machine.state())
+            .unwrap_or(QrcodeState::Idle)
+    }
+
+    /// 订阅状态转换，返回接收端；接收端被丢弃后自动取消订阅
+    pub fn subscribe(&self) -> Receiver<QrcodeTransition> {
+        self.transitions.subscribe()
+    }
+
+    /// 注册回调，在产生转换的线程上调用，回调里不要做耗时操作
+    pub fn on_transition(&self, callback: impl Fn(&QrcodeTransition) + Send + 'static) {
+        self.transitions.on_event(callback);
+    }
+
+    /// 输入一次观察到的状态
+    pub fn feed(&self, observed: QrcodeState, raw_code: Option<i32>) -> Option<QrcodeTransition> {
+        let transition = self
+            .machine
+            .lock()
+            .ok()?
+            .feed(observed, SystemTime::now(), raw_code)?;
+
+        self.transitions.publish(&transition);
+        Some(transition)
+    }
+}
+
+impl Default for QrcodeStateHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 全局状态，由状态回调hook驱动
+static QRCODE_STATE_HUB: LazyLock<QrcodeStateHub> = LazyLock::new(QrcodeStateHub::new);
+
+/// 获取全局二维码状态
+pub fn qrcode_state_hub() -> &'static QrcodeStateHub {
+    &QRCODE_STATE_HUB
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use QrcodeState::*;
+    use std::time::Duration;
+
+    // 按轮询顺序输入状态，返回每次转换的 (from, to)
+    fn replay(states: &[QrcodeState]) -> Vec<(QrcodeState, QrcodeState)> {
+        let start = SystemTime::UNIX_EPOCH;
+        QrcodeStateMachine::new()
+            .replay(
+                states
+                    .iter()
+                    .enumerate()
+                    .map(|(i, state)| (*state, start + Duration::from_secs(i as u64))),
+            )
+            .into_iter()
+            .map(|transition| (transition.from, transition.to))
+            .collect()
+    }
+
+    #[test]
+    fn parses_configured_state_names() {
+        for state in [Waiting, Scanned, Confirmed, Cancelled, Expired] {
+            assert_eq!(state.to_string().parse::<QrcodeState>(), Ok(state));
+        }
+        assert!("idle".parse::<QrcodeState>().is_err());
+        assert!("Waiting".parse::<QrcodeState>().is_err());
+    }
+
+    #[test]
+    fn repeated_polls_produce_one_transition() {
+        let transitions = replay(&[Waiting, Waiting, Waiting, Scanned, Scanned, Confirmed]);
+        assert_eq!(
+            transitions,
+            [(Idle, Waiting), (Waiting, Scanned), (Scanned, Confirmed)]
+        );
+    }
+
+    #[test]
+    fn missed_intermediate_states_are_allowed() {
+        assert_eq!(
+            replay(&[Waiting, Confirmed]),
+            [(Idle, Waiting), (Waiting, Confirmed)]
+        );
+        assert_eq!(replay(&[Scanned]), [(Idle, Scanned)]);
+        assert_eq!(replay(&[Expired]), [(Idle, Expired)]);
+    }
+
+    #[test]
+    fn final_states_wait_for_a_new_qrcode() {
+        // 过期后的轮询结果和迟到的扫码都被忽略，刷新后回到等待
+        let transitions = replay(&[
+            Waiting, Expired, Scanned, Confirmed, Expired, Waiting, Scanned,
+        ]);
+        assert_eq!(
+            transitions,
+            [
+                (Idle, Waiting),
+                (Waiting, Expired),
+                (Expired, Waiting),
+                (Waiting, Scanned)
+            ]
+        );
+        // 扫码后取消，再刷新
+        let transitions = replay(&[Waiting, Scanned, Cancelled, Scanned, Waiting]);
+        assert_eq!(
+            transitions,
+            [
+                (Idle, Waiting),
+                (Waiting, Scanned),
+                (Scanned, Cancelled),
+                (Cancelled, Waiting)
+            ]
+        );
+    }
+
+    #[test]
+    fn scanned_cannot_go_back_to_idle_or_itself() {
+        let mut machine = QrcodeStateMachine::new();
+        let at = SystemTime::UNIX_EPOCH;
+        assert!(machine.feed(Scanned, at, Some(1)).is_some());
+        assert!(machine.feed(Idle, at, None).is_none());
+        assert!(machine.feed(Scanned, at, Some(1)).is_none());
+        assert_eq!(machine.state(), Scanned);
+    }
+
+    #[test]
+    fn hub_publishes_transitions_with_raw_codes() {
+        let hub = QrcodeStateHub::new();
+        let receiver = hub.subscribe();
+        assert!(hub.feed(Waiting, Some(0)).is_some());
+        assert!(hub.feed(Waiting, Some(0)).is_none());
+        assert!(hub.feed(Scanned, Some(1)).is_some());
+        assert_eq!(hub.state(), Scanned);
+
+        let received: Vec<(QrcodeState, Option<i32>)> = receiver
+            .try_iter()
+            .map(|transition| (transition.to, transition.raw_code))
+            .collect();
+        assert_eq!(received, [(Waiting, Some(0)), (Scanned, Some(1))]);
+    }
+}
@@ -0,0 +1,141 @@
+use super::state::{QrcodeState, qrcode_state_hub};
+use retour::RawDetour;
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU32, Ordering};
+use winapi::um::memoryapi::VirtualAlloc;
+use winapi::um::processthreadsapi::{FlushInstructionCache, GetCurrentProcess};
+use winapi::um::winnt::{MEM_COMMIT, MEM_RESERVE, PAGE_EXECUTE_READWRITE};
+
+/// 调试日志输出函数
+fn debug_log(message: &str) {
+    unsafe {
+        use std::ffi::OsStr;
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::debugapi::OutputDebugStringW;
+
+        let formatted_message = format!("[QRCODE_STATUS] {}\n", message);
+        let wide_message: Vec<u16> = OsStr::new(&formatted_message)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        OutputDebugStringW(wide_message.as_ptr());
+    }
+}
+
+// 状态轮询回调的原型
+type StatusCallbackFn = extern "thiscall" fn(*mut c_void, i32);
+
+struct StatusHook {
+    detour: RawDetour,
+    codes: BTreeMap<i32, QrcodeState>, // 状态码 -> 状态
+    stub: usize,                       // 入口桩地址，hook跳到这里而不是直接跳到hook函数
+}
+
+// 入口桩中计数器的偏移
+const STUB_COUNTER_OFFSET: usize = 0x20;
+
+// 生成hook的入口桩：进入时计数加一，调用hook函数，返回后计数减一再返回客户端
+//
+// 计数在hook函数返回到桩里之后才减，所以计数为0时没有线程还在DLL的代码中。
+// 桩分配在DLL之外并且不释放，卸载DLL时正在返回的线程仍然落在有效的代码上。
+fn build_entry_stub(proxy: usize) -> Result<usize, String> {
+    let stub = unsafe {
+        VirtualAlloc(
+            std::ptr::null_mut(),
+            0x40,
+            MEM_COMMIT | MEM_RESERVE,
+            PAGE_EXECUTE_READWRITE,
+        )
+    } as usize;
+    if stub == 0 {
+        return Err("分配状态回调入口桩失败".to_string());
+    }
+    let counter = (stub + STUB_COUNTER_OFFSET) as u32;
+    let call_next = stub + 16;
+    let rel = (proxy as u32).wrapping_sub(call_next as u32);
+
+    let mut code = Vec::with_capacity(26);
+    code.extend_from_slice(&[0xF0, 0xFF, 0x05]); // lock inc dword ptr [counter]
+    code.extend_from_slice(&counter.to_le_bytes());
+    code.extend_from_slice(&[0xFF, 0x74, 0x24, 0x04]); // push dword ptr [esp+4]，重新压入状态码
+    code.push(0xE8); // call proxy，ecx 中的 this 保持不变
+    code.extend_from_slice(&rel.to_le_bytes());
+    code.extend_from_slice(&[0xF0, 0xFF, 0x0D]); // lock dec dword ptr [counter]
+    code.extend_from_slice(&counter.to_le_bytes());
+    code.extend_from_slice(&[0xC2, 0x04, 0x00]); // ret 4
+    unsafe {
+        std::ptr::copy_nonoverlapping(code.as_ptr(), stub as *mut u8, code.len());
+        FlushInstructionCache(GetCurrentProcess(), stub as *const _, code.len());
+    }
+    Ok(stub)
+}
+
+static STATUS_HOOK: OnceLock<StatusHook> = OnceLock::new();
+
+// hook函数：把状态码交给状态机，然后调用原始函数
+extern "thiscall" fn status_callback_proxy(this: *mut c_void, code: i32) {
+    let Some(hook) = STATUS_HOOK.get() else {
+        return;
+    };
+
+    match hook.codes.get(&code) {
+        Some(state) => {
+            qrcode_state_hub().feed(*state, Some(code));
+        }
+        None => debug_log(&format!("未知的二维码状态码: {}", code)),
+    }
+
+    let original: StatusCallbackFn = unsafe { std::mem::transmute(hook.detour.trampoline()) };
+    original(this, code);
+}
+
+/// 在状态轮询回调上安装hook
+///
+/// `codes` 为配置中的状态码映射，状态名必须能解析为 `QrcodeState`。
+pub fn install_status_hook(addr: usize, codes: &BTreeMap<i32, String>) -> Result<(), String> {
+    if STATUS_HOOK.get().is_some() {
+        return Ok(());
+    }
+
+    let codes = codes
+        .iter()
+        .map(|(code, name)| Ok((*code, name.parse::<QrcodeState>()?)))
+        .collect::<Result<BTreeMap<_, _>, String>>()?;
+
+    let stub = build_entry_stub(status_callback_proxy as usize)?;
+    let detour = unsafe {
+        RawDetour::new(addr as *const (), stub as *const ())
+            .map_err(|e| format!("创建状态回调hook失败: {:?}", e))?
+    };
+    let hook = STATUS_HOOK.get_or_init(|| StatusHook {
+        detour,
+        codes,
+        stub,
+    });
+    unsafe { hook.detour.enable() }.map_err(|e| format!("启用状态回调hook失败: {:?}", e))?;
+
+    debug_log(&format!("状态回调hook已安装: 0x{:X}", addr));
+    Ok(())
+}
+
+/// 禁用状态回调hook，恢复原始函数开头；没有安装时什么也不做
+pub fn remove_status_hook() -> Result<(), String> {
+    let Some(hook) = STATUS_HOOK.get() else {
+        return Ok(());
+    };
+    if hook.detour.is_enabled() {
+        unsafe { hook.detour.disable() }.map_err(|e| format!("禁用状态回调hook失败: {:?}", e))?;
+        debug_log("状态回调hook已禁用");
+    }
+    Ok(())
+}
+
+/// 正在执行的状态回调hook个数，在 `remove_status_hook` 之后降到0才能卸载DLL
+pub fn status_calls_in_flight() -> u32 {
+    STATUS_HOOK.get().map_or(0, |hook| {
+        let counter = (hook.stub + STUB_COUNTER_OFFSET) as *const AtomicU32;
+        unsafe { &*counter }.load(Ordering::SeqCst)
+    })
+}
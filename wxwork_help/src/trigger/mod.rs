@@ -0,0 +1,391 @@
+use crate::qrcode::state::{QrcodeState, qrcode_state_hub};
+use serde::Deserialize;
+use std::sync::mpsc::Sender;
+use std::sync::{Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+/// 调试日志输出函数
+fn debug_log(message: &str) {
+    unsafe {
+        use std::ffi::OsStr;
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::debugapi::OutputDebugStringW;
+
+        let formatted_message = format!("[TRIGGER] {}\n", message);
+        let wide_message: Vec<u16> = OsStr::new(&formatted_message)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        OutputDebugStringW(wide_message.as_ptr());
+    }
+}
+
+// 等待外部事件时检查退出标志的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 刷新二维码的触发器配置
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TriggerConfig {
+    // 启动后延迟触发一次
+    Once {
+        #[serde(default)]
+        delay_secs: u64,
+    },
+    // 固定间隔触发
+    Interval {
+        interval_secs: u64,
+    },
+    // 二维码过期时触发；监听不到二维码状态时，配置了 fallback_interval_secs 就改为固定间隔触发
+    OnExpiry {
+        #[serde(default)]
+        fallback_interval_secs: Option<u64>,
+    },
+    // 外部进程 SetEvent 命名事件时触发
+    Command {
+        event_name: String,
+    },
+}
+
+impl TriggerConfig {
+    /// 触发器名称，用于日志
+    pub fn name(&self) -> &'static str {
+        match self {
+            TriggerConfig::Once { .. } => "once",
+            TriggerConfig::Interval { .. } => "interval",
+            TriggerConfig::OnExpiry { .. } => "on_expiry",
+            TriggerConfig::Command { .. } => "command",
+        }
+    }
+
+    /// 检查配置是否合法
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            TriggerConfig::Interval { interval_secs: 0 } => {
+                Err("interval 触发器的 interval_secs 必须大于0".to_string())
+            }
+            TriggerConfig::OnExpiry {
+                fallback_interval_secs: Some(0),
+            } => Err("on_expiry 触发器的 fallback_interval_secs 必须大于0".to_string()),
+            TriggerConfig::Command { event_name } if event_name.is_empty() => {
+                Err("command 触发器的 event_name 不能为空".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 一次触发
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriggerEvent {
+    pub source: &'static str, // 触发器名称
+    pub at: SystemTime,       // 触发时间
+}
+
+impl TriggerEvent {
+    fn now(source: &'static str) -> Self {
+        TriggerEvent {
+            source,
+            at: SystemTime::now(),
+        }
+    }
+}
+
+/// 退出信号，DLL 卸载时设置，所有触发器线程和工作线程据此退出
+pub struct Shutdown {
+    requested: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Shutdown {
+    pub const fn new() -> Self {
+        Shutdown {
+            requested: Mutex::new(false),
+            condvar: Condvar::new(),
+        }
+    }
+
+    /// 请求退出，不等待线程结束（DLL_PROCESS_DETACH 持有加载器锁，不能等待线程）
+    pub fn request(&self) {
+        if let Ok(mut requested) = self.requested.lock() {
+            *requested = true;
+        }
+        self.condvar.notify_all();
+    }
+
+    /// 是否已请求退出
+    pub fn is_requested(&self) -> bool {
+        self.requested.lock().map(|r| *r).unwrap_or(true)
+    }
+
+    /// 最多等待 `timeout`，期间请求退出则提前返回，返回是否已请求退出
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let Ok(requested) = self.requested.lock() else {
+            return true;
+        };
+        self.condvar
+            .wait_timeout_while(requested, timeout, |requested| !*requested)
+            .map(|(requested, _)| *requested)
+            .unwrap_or(true)
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+static SHUTDOWN: Shutdown = Shutdown::new();
+
+/// 全局退出信号
+pub fn shutdown() -> &'static Shutdown {
+    &SHUTDOWN
+}
+
+// 每隔 `interval` 发送一次，直到退出
+fn spawn_interval(
+    interval: Duration,
+    source: &'static str,
+    sender: Sender<TriggerEvent>,
+    shutdown: &'static Shutdown,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        while !shutdown.wait_timeout(interval) {
+            if sender.send(TriggerEvent::now(source)).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// 启动所有触发器，触发时向 `sender` 发送事件
+///
+/// 返回触发器线程句柄，退出时要等这些线程结束。二维码过期触发器在 `status_watched`
+/// 时不占用线程，由状态回调直接发送；监听不到状态时按 `fallback_interval_secs` 定时触发，
+/// 没有配置则不会触发。
+pub fn start_triggers(
+    triggers: &[TriggerConfig],
+    status_watched: bool,
+    sender: Sender<TriggerEvent>,
+) -> Result<Vec<JoinHandle<()>>, String> {
+    start_triggers_with(triggers, status_watched, sender, shutdown())
+}
+
+// 同 `start_triggers`，退出信号由调用方指定，测试时不影响全局信号
+fn start_triggers_with(
+    triggers: &[TriggerConfig],
+    status_watched: bool,
+    sender: Sender<TriggerEvent>,
+    shutdown: &'static Shutdown,
+) -> Result<Vec<JoinHandle<()>>, String> {
+    // 先检查全部配置，避免启动一部分线程后出错，留下没人等待的线程
+    for trigger in triggers {
+        trigger.validate()?;
+    }
+    let mut handles = Vec::new();
+    for trigger in triggers {
+        let sender = sender.clone();
+        let source = trigger.name();
+        match trigger.clone() {
+            TriggerConfig::Once { delay_secs } => {
+                handles.push(thread::spawn(move || {
+                    if !shutdown.wait_timeout(Duration::from_secs(delay_secs)) {
+                        let _ = sender.send(TriggerEvent::now(source));
+                    }
+                }));
+            }
+            TriggerConfig::Interval { interval_secs } => {
+                handles.push(spawn_interval(
+                    Duration::from_secs(interval_secs),
+                    source,
+                    sender,
+                    shutdown,
+                ));
+            }
+            TriggerConfig::OnExpiry { .. } if status_watched => {
+                qrcode_state_hub().on_transition(move |transition| {
+                    if transition.to == QrcodeState::Expired && !shutdown.is_requested() {
+                        let _ = sender.send(TriggerEvent::now(source));
+                    }
+                });
+            }
+            TriggerConfig::OnExpiry {
+                fallback_interval_secs: Some(interval_secs),
+            } => {
+                debug_log(&format!(
+                    "未监听到二维码状态，on_expiry 改为每 {} 秒触发",
+                    interval_secs
+                ));
+                handles.push(spawn_interval(
+                    Duration::from_secs(interval_secs),
+                    source,
+                    sender,
+                    shutdown,
+                ));
+            }
+            TriggerConfig::OnExpiry {
+                fallback_interval_secs: None,
+            } => {
+                debug_log(
+                    "未监听到二维码状态，on_expiry 触发器不会触发（可配置 fallback_interval_secs）",
+                );
+                continue;
+            }
+            TriggerConfig::Command { event_name } => {
+                let event = command::create_event(&event_name)?;
+                handles.push(thread::spawn(move || {
+                    command::wait_event(event, shutdown, || {
+                        sender.send(TriggerEvent::now(source)).is_ok()
+                    });
+                }));
+            }
+        }
+        debug_log(&format!("触发器已启动: {:?}", trigger));
+    }
+    Ok(handles)
+}
+
+// 外部命令：命名事件
+mod command {
+    use super::{POLL_INTERVAL, Shutdown};
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::synchapi::{CreateEventW, WaitForSingleObject};
+    use winapi::um::winbase::WAIT_OBJECT_0;
+    use winapi::um::winnt::HANDLE;
+
+    // 创建自动复位的命名事件，返回句柄值
+    pub fn create_event(name: &str) -> Result<usize, String> {
+        let wide_name: Vec<u16> = OsStr::new(name)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        let handle = unsafe { CreateEventW(std::ptr::null_mut(), 0, 0, wide_name.as_ptr()) };
+        if handle.is_null() {
+            return Err(format!("创建命名事件 {} 失败", name));
+        }
+        Ok(handle as usize)
+    }
+
+    // 等待事件直到退出，`on_signal` 返回 false 时提前结束
+    pub fn wait_event(event: usize, shutdown: &Shutdown, mut on_signal: impl FnMut() -> bool) {
+        let handle = event as HANDLE;
+        while !shutdown.is_requested() {
+            let result = unsafe { WaitForSingleObject(handle, POLL_INTERVAL.as_millis() as u32) };
+            if result == WAIT_OBJECT_0 && !on_signal() {
+                break;
+            }
+        }
+        unsafe {
+            CloseHandle(handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    // 每个测试用自己的退出信号，不影响全局信号和并行的其他测试
+    fn test_shutdown() -> &'static Shutdown {
+        Box::leak(Box::new(Shutdown::new()))
+    }
+
+    // 在 `timeout` 内等待所有线程结束并回收，超时返回 false
+    fn join_within(handles: Vec<JoinHandle<()>>, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while handles.iter().any(|handle| !handle.is_finished()) {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        true
+    }
+
+    #[test]
+    fn shutdown_joins_every_trigger_thread() {
+        let shutdown = test_shutdown();
+        let event_name = format!("wxwork_help_trigger_test_{}", std::process::id());
+        let triggers = [
+            TriggerConfig::Once { delay_secs: 3600 },
+            TriggerConfig::Interval {
+                interval_secs: 3600,
+            },
+            TriggerConfig::OnExpiry {
+                fallback_interval_secs: Some(3600),
+            },
+            TriggerConfig::Command { event_name },
+        ];
+        let (sender, receiver) = mpsc::channel();
+        let handles = start_triggers_with(&triggers, false, sender, shutdown).unwrap();
+        assert_eq!(handles.len(), triggers.len());
+
+        shutdown.request();
+        // 命名事件按 POLL_INTERVAL 检查退出信号，其余线程立即被唤醒
+        assert!(join_within(handles, POLL_INTERVAL * 4));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn on_expiry_falls_back_to_interval_without_status() {
+        let shutdown = test_shutdown();
+        let triggers = [TriggerConfig::OnExpiry {
+            fallback_interval_secs: Some(1),
+        }];
+        let (sender, receiver) = mpsc::channel();
+        let handles = start_triggers_with(&triggers, false, sender, shutdown).unwrap();
+        assert_eq!(handles.len(), 1);
+
+        let event = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event.source, "on_expiry");
+
+        shutdown.request();
+        assert!(join_within(handles, Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn on_expiry_without_fallback_starts_nothing() {
+        let triggers = [TriggerConfig::OnExpiry {
+            fallback_interval_secs: None,
+        }];
+        let (sender, receiver) = mpsc::channel();
+        let handles = start_triggers_with(&triggers, false, sender, test_shutdown()).unwrap();
+        assert!(handles.is_empty());
+        // 没有线程持有发送端，通道已断开
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_millis(100)),
+            Err(mpsc::RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn once_fires_a_single_time() {
+        let triggers = [TriggerConfig::Once { delay_secs: 0 }];
+        let (sender, receiver) = mpsc::channel();
+        let handles = start_triggers_with(&triggers, false, sender, test_shutdown()).unwrap();
+        assert_eq!(receiver.recv().unwrap().source, "once");
+        assert!(join_within(handles, Duration::from_secs(2)));
+        assert_eq!(receiver.recv(), Err(mpsc::RecvError));
+    }
+
+    #[test]
+    fn rejects_invalid_trigger_before_starting() {
+        let triggers = [
+            TriggerConfig::Once { delay_secs: 3600 },
+            TriggerConfig::Interval { interval_secs: 0 },
+        ];
+        let (sender, receiver) = mpsc::channel();
+        assert!(start_triggers_with(&triggers, false, sender, test_shutdown()).is_err());
+        // 出错前没有启动任何线程
+        assert_eq!(receiver.recv(), Err(mpsc::RecvError));
+    }
+}
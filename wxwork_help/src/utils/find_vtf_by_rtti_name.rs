@@ -65,6 +65,7 @@ pub fn search_vtf_by_rtti(
     }
 }
 
+#[allow(dead_code)]
 pub fn search_object_by_rtti(
     module_name: &str,
     rtti_name: &str,
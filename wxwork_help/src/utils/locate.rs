@@ -0,0 +1,95 @@
+use super::find_vtf_by_rtti_name::search_vtf_by_rtti;
+use std::ffi::CString;
+use std::{mem, slice};
+use winapi::um::libloaderapi::GetModuleHandleA;
+use winapi::um::winnt::{
+    IMAGE_DOS_HEADER, IMAGE_DOS_SIGNATURE, IMAGE_NT_HEADERS, IMAGE_NT_SIGNATURE,
+    IMAGE_SCN_MEM_EXECUTE, IMAGE_SECTION_HEADER,
+};
+use wxwork_profile::{FnDescriptor, Locator, Profile, Signature};
+
+/// 获取模块基址
+pub fn module_base(module_name: &str) -> Result<usize, String> {
+    let name = CString::new(module_name).map_err(|_| format!("模块名称无效: {}", module_name))?;
+    let handle = unsafe { GetModuleHandleA(name.as_ptr()) };
+    if handle.is_null() {
+        return Err(format!("模块 {} 未加载", module_name));
+    }
+    Ok(handle as usize)
+}
+
+/// 按描述中的定位方式计算函数地址
+pub fn resolve_function(profile: &Profile, desc: &FnDescriptor) -> Result<usize, String> {
+    resolve_locator(profile, &desc.name, &desc.locator)
+}
+
+/// 按函数名称计算函数地址，不要求配置中写了调用方式
+pub fn resolve_function_by_name(profile: &Profile, name: &str) -> Result<usize, String> {
+    resolve_locator(profile, name, &profile.locator(name)?)
+}
+
+fn resolve_locator(profile: &Profile, name: &str, locator: &Locator) -> Result<usize, String> {
+    let base = module_base(&profile.module_name)?;
+    match locator {
+        Locator::Offset(offset) => Ok(base + offset),
+        Locator::Signature(signature) => unsafe { find_signature_in_module(base, signature) }
+            .map_err(|e| format!("函数 {}: {}", name, e)),
+        Locator::VtableSlot(slot_name) => {
+            let slot = profile.vtable_slot(slot_name)?;
+            let rtti = profile.rtti(&slot.rtti)?;
+            let vtable = search_vtf_by_rtti(
+                &profile.module_name,
+                &rtti.name,
+                rtti.offset_vtf_in_complete_class,
+                rtti.offset_constructor,
+            )
+            .ok_or_else(|| format!("未找到 {} 的虚函数表", rtti.name))?;
+            Ok(unsafe { *(vtable as *const usize).add(slot.index) })
+        }
+    }
+}
+
+/// 在已加载模块的可执行节中搜索特征码，要求恰好匹配一次
+unsafe fn find_signature_in_module(base: usize, signature: &Signature) -> Result<usize, String> {
+    unsafe {
+        let base_addr = base as *const u8;
+
+        // 解析dos头
+        let dos_header = base_addr as *const IMAGE_DOS_HEADER;
+        if (*dos_header).e_magic != IMAGE_DOS_SIGNATURE {
+            return Err("DOS头签名验证失败".to_string());
+        }
+
+        // 解析pe头
+        let nt_headers = base_addr.add((*dos_header).e_lfanew as usize) as *const IMAGE_NT_HEADERS;
+        if (*nt_headers).Signature != IMAGE_NT_SIGNATURE {
+            return Err("PE头签名验证失败".to_string());
+        }
+
+        // 遍历可执行节
+        let section_header = (nt_headers as *const u8).add(mem::size_of::<IMAGE_NT_HEADERS>())
+            as *const IMAGE_SECTION_HEADER;
+        let mut matches = Vec::new();
+        for i in 0..(*nt_headers).FileHeader.NumberOfSections as usize {
+            let section = &*section_header.add(i);
+            if section.Characteristics & IMAGE_SCN_MEM_EXECUTE == 0 {
+                continue;
+            }
+            let section_start = base_addr.add(section.VirtualAddress as usize);
+            let section_size = *section.Misc.VirtualSize() as usize;
+            let section_data = slice::from_raw_parts(section_start, section_size);
+            matches.extend(
+                signature
+                    .find_all(section_data)
+                    .into_iter()
+                    .map(|offset| section_start as usize + offset),
+            );
+        }
+
+        match matches.as_slice() {
+            [] => Err("特征码没有匹配".to_string()),
+            [addr] => Ok(*addr),
+            _ => Err(format!("特征码匹配了 {} 次", matches.len())),
+        }
+    }
+}
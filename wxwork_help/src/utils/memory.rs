@@ -0,0 +1,231 @@
+use super::msvc_string::MsvcString;
+use std::ffi::c_void;
+use std::mem;
+use winapi::um::memoryapi::VirtualQuery;
+use winapi::um::winnt::{
+    MEM_COMMIT, MEM_PRIVATE, MEMORY_BASIC_INFORMATION, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE,
+    PAGE_EXECUTE_WRITECOPY, PAGE_GUARD, PAGE_NOACCESS, PAGE_READONLY, PAGE_READWRITE,
+    PAGE_WRITECOPY,
+};
+use wxwork_profile::{FieldEntry, FieldKind};
+
+// 读取的字符串超过这个长度就认为字段位置不对
+const MAX_FIELD_STRING_LEN: usize = 64 * 1024;
+
+/// 检查一段内存是否已提交且可读（逐个内存区域检查）
+pub fn is_readable(addr: usize, len: usize) -> bool {
+    if addr == 0 {
+        return false;
+    }
+    let Some(end) = addr.checked_add(len) else {
+        return false;
+    };
+
+    let mut current = addr;
+    while current < end {
+        let mut mbi: MEMORY_BASIC_INFORMATION = unsafe { mem::zeroed() };
+        let queried = unsafe {
+            VirtualQuery(
+                current as *const _,
+                &mut mbi,
+                mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        };
+        if queried == 0 || mbi.State != MEM_COMMIT {
+            return false;
+        }
+        let protect = mbi.Protect;
+        if protect & (PAGE_GUARD | PAGE_NOACCESS) != 0
+            || protect
+                & (PAGE_READONLY
+                    | PAGE_READWRITE
+                    | PAGE_WRITECOPY
+                    | PAGE_EXECUTE_READ
+                    | PAGE_EXECUTE_READWRITE
+                    | PAGE_EXECUTE_WRITECOPY)
+                == 0
+        {
+            return false;
+        }
+        current = mbi.BaseAddress as usize + mbi.RegionSize;
+    }
+    true
+}
+
+/// 检查一段内存是否位于已提交的私有可写内存中（堆上的对象都在这样的内存里）
+pub fn is_private_writable(addr: usize, len: usize) -> bool {
+    if addr == 0 {
+        return false;
+    }
+    let Some(end) = addr.checked_add(len) else {
+        return false;
+    };
+
+    let mut current = addr;
+    while current < end {
+        let mut mbi: MEMORY_BASIC_INFORMATION = unsafe { mem::zeroed() };
+        let queried = unsafe {
+            VirtualQuery(
+                current as *const _,
+                &mut mbi,
+                mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        };
+        if queried == 0
+            || mbi.State != MEM_COMMIT
+            || mbi.Type != MEM_PRIVATE
+            || mbi.Protect & (PAGE_GUARD | PAGE_NOACCESS) != 0
+            || mbi.Protect & (PAGE_READWRITE | PAGE_EXECUTE_READWRITE) == 0
+        {
+            return false;
+        }
+        current = mbi.BaseAddress as usize + mbi.RegionSize;
+    }
+    true
+}
+
+/// 检查地址是否位于已提交的可执行内存中
+pub fn is_executable(addr: usize) -> bool {
+    if addr == 0 {
+        return false;
+    }
+    let mut mbi: MEMORY_BASIC_INFORMATION = unsafe { mem::zeroed() };
+    let queried = unsafe {
+        VirtualQuery(
+            addr as *const _,
+            &mut mbi,
+            mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+        )
+    };
+    queried != 0
+        && mbi.State == MEM_COMMIT
+        && mbi.Protect & (PAGE_GUARD | PAGE_NOACCESS) == 0
+        && mbi.Protect & (PAGE_EXECUTE_READ | PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY) != 0
+}
+
+/// 检查后读取一个值
+pub fn read_value<T: Copy>(addr: usize) -> Result<T, String> {
+    if !is_readable(addr, mem::size_of::<T>()) {
+        return Err(format!("地址 0x{:X} 不可读", addr));
+    }
+    Ok(unsafe { std::ptr::read_unaligned(addr as *const T) })
+}
+
+/// 检查后读取 MSVC 字符串的内容
+pub fn read_msvc_string<T: Copy>(addr: usize) -> Result<Vec<T>, String> {
+    let string: MsvcString<T> = read_value(addr)?;
+    let (data, len, capacity) = string.raw_parts();
+    if len > capacity || len > MAX_FIELD_STRING_LEN {
+        return Err(format!(
+            "地址 0x{:X} 处不像是字符串: 长度 {}, 容量 {}",
+            addr, len, capacity
+        ));
+    }
+    // 短字符串的数据在结构体内部，要从原地址读取
+    let data = if capacity > MsvcString::<T>::SSO_CAPACITY {
+        data as usize
+    } else {
+        addr
+    };
+    if !is_readable(data, len * mem::size_of::<T>()) {
+        return Err(format!("字符串数据 0x{:X} 不可读", data));
+    }
+    Ok(unsafe { std::slice::from_raw_parts(data as *const T, len) }.to_vec())
+}
+
+/// 从对象出发沿指针链读取字符串字段
+pub fn read_string_field(object: *const c_void, field: &FieldEntry) -> Result<String, String> {
+    let Some((last, chain)) = field.offsets.split_last() else {
+        return Err("字段没有偏移".to_string());
+    };
+
+    // 地址加偏移溢出时说明指针链已经走错，不能回绕到低地址继续读
+    let add = |base: usize, offset: usize| {
+        base.checked_add(offset)
+            .ok_or_else(|| format!("地址 0x{:X} 加偏移 0x{:X} 溢出", base, offset))
+    };
+    let mut current = object as usize;
+    for offset in chain {
+        current = read_value::<usize>(add(current, *offset)?)?;
+        if current == 0 {
+            return Err(format!("指针链在偏移 0x{:X} 处为空", offset));
+        }
+    }
+
+    let addr = add(current, *last)?;
+    match field.kind {
+        FieldKind::String => {
+            let bytes = read_msvc_string::<u8>(addr)?;
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        FieldKind::WString => {
+            let utf16 = read_msvc_string::<u16>(addr)?;
+            Ok(String::from_utf16_lossy(&utf16))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::msvc_string::OwnedMsvcString;
+
+    #[repr(C)]
+    struct Inner {
+        padding: [usize; 3],
+        text: MsvcString<u16>,
+    }
+
+    #[repr(C)]
+    struct Frame {
+        vtable: usize,
+        inner: *const Inner,
+    }
+
+    fn field(offsets: &[usize]) -> FieldEntry {
+        FieldEntry {
+            object: "qrcode_frame".to_string(),
+            offsets: offsets.to_vec(),
+            kind: FieldKind::WString,
+        }
+    }
+
+    #[test]
+    fn follows_pointer_chain() {
+        let url = "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=0123456789abcdef";
+        let utf16: Vec<u16> = url.encode_utf16().collect();
+        let mut owned = OwnedMsvcString::from_slice(&utf16);
+        let inner = Inner {
+            padding: [0; 3],
+            text: unsafe { *owned.as_mut_ptr() },
+        };
+        let frame = Frame {
+            vtable: 0,
+            inner: &inner,
+        };
+        let object = &frame as *const Frame as *const c_void;
+        let offsets = [mem::size_of::<usize>(), 3 * mem::size_of::<usize>()];
+        assert_eq!(read_string_field(object, &field(&offsets)).unwrap(), url);
+    }
+
+    #[test]
+    fn rejects_overflowing_offsets() {
+        let object = (usize::MAX - 4) as *const c_void;
+        let error = read_string_field(object, &field(&[0x10, 0])).unwrap_err();
+        assert!(error.contains("溢出"), "{}", error);
+
+        let error = read_string_field(object, &field(&[0x10])).unwrap_err();
+        assert!(error.contains("溢出"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_null_links() {
+        let frame = Frame {
+            vtable: 0,
+            inner: std::ptr::null(),
+        };
+        let object = &frame as *const Frame as *const c_void;
+        let error = read_string_field(object, &field(&[mem::size_of::<usize>(), 0])).unwrap_err();
+        assert!(error.contains("为空"), "{}", error);
+    }
+}
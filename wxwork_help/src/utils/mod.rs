@@ -1,2 +1,8 @@
 // src/utils/mod.rs
-pub mod find_vtf_by_rtti_name;
\ No newline at end of file
+pub mod find_vtf_by_rtti_name;
+pub mod locate;
+pub mod memory;
+pub mod module_guard;
+pub mod msvc_string;
+pub mod native_call;
+pub mod object_cache;
\ No newline at end of file
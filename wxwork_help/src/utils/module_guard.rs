@@ -0,0 +1,84 @@
+use super::locate::{module_base, resolve_function_by_name};
+use super::memory::is_readable;
+use winapi::um::winnt::{
+    IMAGE_DIRECTORY_ENTRY_BASERELOC, IMAGE_DOS_HEADER, IMAGE_DOS_SIGNATURE, IMAGE_NT_HEADERS,
+    IMAGE_NT_SIGNATURE,
+};
+use wxwork_profile::{Profile, Relocations};
+
+// 内存中的PE头信息
+struct ImageInfo {
+    time_date_stamp: u32,
+    size_of_image: u32,
+    relocation_directory: (u32, u32), // 重定位目录 (RVA, 大小)
+}
+
+/// 检查已加载的模块与配置中的指纹一致，返回模块基址
+///
+/// 比对时间戳、镜像大小以及每个函数开头字节的哈希。配置里没有指纹时同样拒绝，
+/// 避免把偏移加到其他版本的模块上去调用。模块开启了 ASLR 时函数中的绝对地址
+/// 已被加载器修正，按内存中的重定位表屏蔽后再计算哈希，与离线生成的指纹一致。
+pub fn check_module(profile: &Profile) -> Result<usize, String> {
+    let base = module_base(&profile.module_name)?;
+    let info = unsafe { read_image_info(base) }?;
+    let size_of_image = info.size_of_image as usize;
+    let relocations = read_relocations(base, &info)?;
+
+    profile.check_fingerprint(
+        info.time_date_stamp,
+        info.size_of_image,
+        &relocations,
+        |name, len| {
+            let addr = resolve_function_by_name(profile, name)?;
+            // 只读取模块镜像范围内的内存
+            if addr < base || addr.saturating_add(len) > base + size_of_image {
+                return Err(format!("函数 {} 地址 0x{:X} 不在模块范围内", name, addr));
+            }
+            if !is_readable(addr, len) {
+                return Err(format!("函数 {} 地址 0x{:X} 不可读", name, addr));
+            }
+            let bytes = unsafe { std::slice::from_raw_parts(addr as *const u8, len) }.to_vec();
+            Ok(((addr - base) as u32, bytes))
+        },
+    )?;
+
+    Ok(base)
+}
+
+// 读取内存中的重定位表（exe 的 .reloc 节加载后不会被丢弃）
+fn read_relocations(base: usize, info: &ImageInfo) -> Result<Relocations, String> {
+    let (rva, size) = info.relocation_directory;
+    if rva == 0 || size == 0 {
+        return Ok(Relocations::default());
+    }
+    if rva as usize + size as usize > info.size_of_image as usize {
+        return Err(format!("重定位目录 0x{:X} 超出镜像范围", rva));
+    }
+    let addr = base + rva as usize;
+    if !is_readable(addr, size as usize) {
+        return Err(format!("重定位目录 0x{:X} 不可读", addr));
+    }
+    let table = unsafe { std::slice::from_raw_parts(addr as *const u8, size as usize) };
+    Relocations::parse(table)
+}
+
+// 从内存中的PE头读取时间戳、镜像大小和重定位目录
+unsafe fn read_image_info(base: usize) -> Result<ImageInfo, String> {
+    unsafe {
+        let dos_header = base as *const IMAGE_DOS_HEADER;
+        if (*dos_header).e_magic != IMAGE_DOS_SIGNATURE {
+            return Err("DOS头签名验证失败".to_string());
+        }
+        let nt_headers = (base + (*dos_header).e_lfanew as usize) as *const IMAGE_NT_HEADERS;
+        if (*nt_headers).Signature != IMAGE_NT_SIGNATURE {
+            return Err("PE头签名验证失败".to_string());
+        }
+        let optional = &(*nt_headers).OptionalHeader;
+        let relocation = optional.DataDirectory[IMAGE_DIRECTORY_ENTRY_BASERELOC as usize];
+        Ok(ImageInfo {
+            time_date_stamp: (*nt_headers).FileHeader.TimeDateStamp,
+            size_of_image: optional.SizeOfImage,
+            relocation_directory: (relocation.VirtualAddress, relocation.Size),
+        })
+    }
+}
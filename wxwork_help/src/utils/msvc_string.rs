@@ -0,0 +1,117 @@
+use std::ffi::c_void;
+use std::marker::PhantomData;
+
+// MSVC std::basic_string 的内存布局（x86）
+// 长度不超过 SSO 容量时数据直接放在16字节的缓冲区里，否则缓冲区里存的是堆指针
+#[repr(C)]
+#[derive(Clone, Copy)]
+union MsvcStringBuf {
+    local: [u8; 16],  // 短字符串直接存储在这里
+    ptr: *mut c_void, // 长字符串存储在堆上
+}
+
+/// MSVC std::basic_string<T>，T 为 u8 时是 std::string，为 u16 时是 std::wstring
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MsvcString<T> {
+    buf: MsvcStringBuf,
+    size: usize,     // 存储的数据长度
+    capacity: usize, // 存储数据的容量
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> MsvcString<T> {
+    /// 缓冲区能直接存放的最大长度（需要留一个位置给结尾的0）
+    pub const SSO_CAPACITY: usize = 16 / std::mem::size_of::<T>() - 1;
+
+    fn empty() -> Self {
+        MsvcString {
+            buf: MsvcStringBuf { local: [0; 16] },
+            size: 0,
+            capacity: Self::SSO_CAPACITY,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 拆出 (数据指针, 长度, 容量)，短字符串时数据指针无意义
+    pub fn raw_parts(&self) -> (*const T, usize, usize) {
+        let ptr = if self.capacity > Self::SSO_CAPACITY {
+            unsafe { self.buf.ptr as *const T }
+        } else {
+            std::ptr::null()
+        };
+        (ptr, self.size, self.capacity)
+    }
+
+    /// 读取字符串内容
+    ///
+    /// # Safety
+    /// `self` 必须是一个有效的 MSVC 字符串对象
+    pub unsafe fn as_slice(&self) -> &[T] {
+        unsafe {
+            if self.capacity > Self::SSO_CAPACITY {
+                std::slice::from_raw_parts(self.buf.ptr as *const T, self.size)
+            } else {
+                std::slice::from_raw_parts(self.buf.local.as_ptr() as *const T, self.size)
+            }
+        }
+    }
+}
+
+/// 由我们分配、传给目标函数使用的 MSVC 字符串
+///
+/// 对象本身放在 Box 里，保证地址在调用期间不变；长字符串的数据放在 `heap` 中。
+/// 作为输出参数时目标函数可能用它自己的分配器扩容，那块内存我们不能也不会释放。
+pub struct OwnedMsvcString<T> {
+    raw: Box<MsvcString<T>>,
+    _heap: Vec<T>,
+}
+
+impl<T: Copy + Default> OwnedMsvcString<T> {
+    /// 创建空字符串，一般用作输出参数
+    pub fn new() -> Self {
+        OwnedMsvcString {
+            raw: Box::new(MsvcString::empty()),
+            _heap: Vec::new(),
+        }
+    }
+
+    /// 用给定内容创建字符串，一般用作输入参数
+    pub fn from_slice(data: &[T]) -> Self {
+        let mut raw = Box::new(MsvcString::<T>::empty());
+        let mut heap = Vec::new();
+        raw.size = data.len();
+
+        if data.len() <= MsvcString::<T>::SSO_CAPACITY {
+            unsafe {
+                let local = raw.buf.local.as_mut_ptr() as *mut T;
+                std::ptr::copy_nonoverlapping(data.as_ptr(), local, data.len());
+            }
+        } else {
+            // 结尾补0，和 c_str() 的行为一致
+            heap.reserve_exact(data.len() + 1);
+            heap.extend_from_slice(data);
+            heap.push(T::default());
+            raw.buf.ptr = heap.as_mut_ptr() as *mut c_void;
+            raw.capacity = data.len();
+        }
+
+        OwnedMsvcString { raw, _heap: heap }
+    }
+
+    /// 对象地址，作为参数传给目标函数
+    pub fn as_mut_ptr(&mut self) -> *mut MsvcString<T> {
+        &mut *self.raw
+    }
+
+    /// 读取当前内容（目标函数写入后）
+    pub fn to_vec(&self) -> Vec<T> {
+        unsafe { self.raw.as_slice().to_vec() }
+    }
+}
+
+impl<T: Copy + Default> Default for OwnedMsvcString<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
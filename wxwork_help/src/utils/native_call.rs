@@ -0,0 +1,375 @@
+use super::memory::{is_executable, is_readable};
+use super::msvc_string::OwnedMsvcString;
+use std::ffi::c_void;
+use wxwork_profile::{ArgKind, CallConv, FnDescriptor, RetKind};
+
+/// 调用参数，和描述中的 `ArgKind` 一一对应
+#[allow(dead_code)]
+pub enum ArgValue<'a> {
+    This(*mut c_void),
+    Pointer(*mut c_void),
+    I32(i32),
+    Str(&'a str),            // 转成 const std::string&
+    StrOut(&'a mut String),  // 传入空 std::string*，调用后写回
+    WStr(&'a str),           // 转成 const std::wstring&
+    WStrOut(&'a mut String), // 传入空 std::wstring*，调用后写回
+}
+
+impl ArgValue<'_> {
+    fn kind(&self) -> ArgKind {
+        match self {
+            ArgValue::This(_) => ArgKind::This,
+            ArgValue::Pointer(_) => ArgKind::Pointer,
+            ArgValue::I32(_) => ArgKind::I32,
+            ArgValue::Str(_) => ArgKind::StringIn,
+            ArgValue::StrOut(_) => ArgKind::StringOut,
+            ArgValue::WStr(_) => ArgKind::WStringIn,
+            ArgValue::WStrOut(_) => ArgKind::WStringOut,
+        }
+    }
+}
+
+/// 返回值，由描述中的 `RetKind` 决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetValue {
+    Void,
+    I32(i32),
+    Pointer(*mut c_void),
+    Bool(bool),
+}
+
+// 调用期间需要保持存活的字符串对象
+enum Marshaled {
+    None,
+    Str(OwnedMsvcString<u8>),
+    WStr(OwnedMsvcString<u16>),
+}
+
+/// 按描述调用目标函数
+///
+/// 参数按 `desc.args` 检查类型后转换成机器字，字符串参数会构造成 MSVC 的
+/// std::string / std::wstring；输出字符串在调用结束后写回到对应的 `String` 中。
+///
+/// 调用前检查函数地址位于可执行内存、this 指向可读内存。目标函数内部的访问违例
+/// 是 SEH 异常，Rust 捕获不到，会直接结束进程，所以这些检查不能省。
+///
+/// # Safety
+/// `addr` 必须是与 `desc` 描述一致的函数地址，指针参数必须满足目标函数的要求。
+pub unsafe fn invoke(
+    desc: &FnDescriptor,
+    addr: usize,
+    args: &mut [ArgValue],
+) -> Result<RetValue, String> {
+    if addr == 0 {
+        return Err(format!("函数 {} 地址为空", desc.name));
+    }
+    if !is_executable(addr) {
+        return Err(format!(
+            "函数 {} 的地址 0x{:X} 不是可执行内存",
+            desc.name, addr
+        ));
+    }
+    if args.len() != desc.args.len() {
+        return Err(format!(
+            "函数 {} 需要 {} 个参数，实际传入 {} 个",
+            desc.name,
+            desc.args.len(),
+            args.len()
+        ));
+    }
+
+    // 参数转换为机器字
+    let mut words = Vec::with_capacity(args.len());
+    let mut marshaled = Vec::with_capacity(args.len());
+    for (i, (arg, kind)) in args.iter().zip(&desc.args).enumerate() {
+        if arg.kind() != *kind {
+            return Err(format!(
+                "函数 {} 第 {} 个参数类型不匹配: 需要 {:?}, 实际 {:?}",
+                desc.name,
+                i,
+                kind,
+                arg.kind()
+            ));
+        }
+        let mut owned = match arg {
+            ArgValue::This(ptr) => {
+                if !is_readable(*ptr as usize, std::mem::size_of::<usize>()) {
+                    return Err(format!("函数 {} 的 this 指针 {:p} 不可读", desc.name, ptr));
+                }
+                words.push(*ptr as usize);
+                Marshaled::None
+            }
+            ArgValue::Pointer(ptr) => {
+                words.push(*ptr as usize);
+                Marshaled::None
+            }
+            ArgValue::I32(value) => {
+                words.push(*value as u32 as usize);
+                Marshaled::None
+            }
+            ArgValue::Str(s) => Marshaled::Str(OwnedMsvcString::from_slice(s.as_bytes())),
+            ArgValue::StrOut(_) => Marshaled::Str(OwnedMsvcString::new()),
+            ArgValue::WStr(s) => {
+                let utf16: Vec<u16> = s.encode_utf16().collect();
+                Marshaled::WStr(OwnedMsvcString::from_slice(&utf16))
+            }
+            ArgValue::WStrOut(_) => Marshaled::WStr(OwnedMsvcString::new()),
+        };
+        match &mut owned {
+            Marshaled::None => {}
+            Marshaled::Str(s) => words.push(s.as_mut_ptr() as usize),
+            Marshaled::WStr(s) => words.push(s.as_mut_ptr() as usize),
+        }
+        marshaled.push(owned);
+    }
+
+    // 调用目标函数
+    let raw = unsafe { call_raw(desc.convention, addr, &words) }?;
+
+    // 写回输出字符串
+    for (arg, owned) in args.iter_mut().zip(&marshaled) {
+        match (arg, owned) {
+            (ArgValue::StrOut(out), Marshaled::Str(s)) => {
+                **out = String::from_utf8_lossy(&s.to_vec()).into_owned();
+            }
+            (ArgValue::WStrOut(out), Marshaled::WStr(s)) => {
+                **out = String::from_utf16_lossy(&s.to_vec());
+            }
+            _ => {}
+        }
+    }
+
+    Ok(match desc.ret {
+        RetKind::Void => RetValue::Void,
+        RetKind::I32 => RetValue::I32(raw as u32 as i32),
+        RetKind::Pointer => RetValue::Pointer(raw as *mut c_void),
+        RetKind::Bool => RetValue::Bool(raw & 0xFF != 0),
+    })
+}
+
+// 按参数个数把地址转换成对应的函数指针并调用，返回 eax
+macro_rules! call_with {
+    ($abi:literal, $addr:expr, $words:expr; $($i:literal)*) => {{
+        let f: extern $abi fn($(call_with!(@word $i)),*) -> usize = std::mem::transmute($addr);
+        f($($words[$i]),*)
+    }};
+    (@word $i:literal) => { usize };
+}
+
+macro_rules! dispatch {
+    ($abi:literal, $addr:expr, $words:expr) => {
+        match $words.len() {
+            0 => call_with!($abi, $addr, $words;),
+            1 => call_with!($abi, $addr, $words; 0),
+            2 => call_with!($abi, $addr, $words; 0 1),
+            3 => call_with!($abi, $addr, $words; 0 1 2),
+            4 => call_with!($abi, $addr, $words; 0 1 2 3),
+            5 => call_with!($abi, $addr, $words; 0 1 2 3 4),
+            6 => call_with!($abi, $addr, $words; 0 1 2 3 4 5),
+            7 => call_with!($abi, $addr, $words; 0 1 2 3 4 5 6),
+            8 => call_with!($abi, $addr, $words; 0 1 2 3 4 5 6 7),
+            n => return Err(format!("不支持 {} 个参数的调用", n)),
+        }
+    };
+}
+
+unsafe fn call_raw(convention: CallConv, addr: usize, words: &[usize]) -> Result<usize, String> {
+    unsafe {
+        Ok(match convention {
+            CallConv::Thiscall => dispatch!("thiscall", addr, words),
+            CallConv::Stdcall => dispatch!("stdcall", addr, words),
+            CallConv::Cdecl => dispatch!("cdecl", addr, words),
+        })
+    }
+}
+
+// 调用约定只在 x86 上有区别，测试用本地的桩函数代替客户端函数
+#[cfg(all(test, target_arch = "x86"))]
+mod tests {
+    use super::*;
+    use crate::utils::msvc_string::MsvcString;
+    use wxwork_profile::Locator;
+
+    fn descriptor(convention: CallConv, args: &[ArgKind], ret: RetKind) -> FnDescriptor {
+        FnDescriptor {
+            name: "stub".to_string(),
+            locator: Locator::Offset(0),
+            convention,
+            args: args.to_vec(),
+            ret,
+        }
+    }
+
+    extern "stdcall" fn stdcall_sub(a: i32, b: i32) -> i32 {
+        a - b
+    }
+
+    extern "cdecl" fn cdecl_sum(a: i32, b: i32, c: i32) -> i32 {
+        a + b + c
+    }
+
+    extern "thiscall" fn thiscall_field(this: *const i32, add: i32) -> i32 {
+        unsafe { *this + add }
+    }
+
+    extern "stdcall" fn stdcall_is_positive(value: i32) -> usize {
+        // 高位的垃圾数据不影响 bool 返回值
+        0xFFFF_FF00 | (value > 0) as usize
+    }
+
+    extern "cdecl" fn cdecl_string_len(s: *const MsvcString<u8>) -> i32 {
+        unsafe { (*s).as_slice().len() as i32 }
+    }
+
+    extern "cdecl" fn cdecl_wstring_first(s: *const MsvcString<u16>) -> i32 {
+        unsafe { (*s).as_slice().first().map_or(-1, |&c| c as i32) }
+    }
+
+    // 按 MSVC 的布局写入短字符串：16字节缓冲区、长度、容量
+    extern "stdcall" fn stdcall_write_wstring(out: *mut MsvcString<u16>) {
+        unsafe {
+            let text: Vec<u16> = "完成".encode_utf16().collect();
+            let raw = out as *mut u8;
+            std::ptr::copy_nonoverlapping(text.as_ptr(), raw as *mut u16, text.len());
+            *(raw.add(16) as *mut usize) = text.len();
+        }
+    }
+
+    #[test]
+    fn calls_each_convention() {
+        let desc = descriptor(
+            CallConv::Stdcall,
+            &[ArgKind::I32, ArgKind::I32],
+            RetKind::I32,
+        );
+        let result = unsafe {
+            invoke(
+                &desc,
+                stdcall_sub as usize,
+                &mut [ArgValue::I32(10), ArgValue::I32(3)],
+            )
+        };
+        assert_eq!(result, Ok(RetValue::I32(7)));
+
+        let desc = descriptor(
+            CallConv::Cdecl,
+            &[ArgKind::I32, ArgKind::I32, ArgKind::I32],
+            RetKind::I32,
+        );
+        let result = unsafe {
+            invoke(
+                &desc,
+                cdecl_sum as usize,
+                &mut [ArgValue::I32(1), ArgValue::I32(-2), ArgValue::I32(40)],
+            )
+        };
+        assert_eq!(result, Ok(RetValue::I32(39)));
+
+        let mut field = 5i32;
+        let desc = descriptor(
+            CallConv::Thiscall,
+            &[ArgKind::This, ArgKind::I32],
+            RetKind::I32,
+        );
+        let this = &mut field as *mut i32 as *mut c_void;
+        let result = unsafe {
+            invoke(
+                &desc,
+                thiscall_field as usize,
+                &mut [ArgValue::This(this), ArgValue::I32(2)],
+            )
+        };
+        assert_eq!(result, Ok(RetValue::I32(7)));
+    }
+
+    #[test]
+    fn bool_return_reads_al_only() {
+        let desc = descriptor(CallConv::Stdcall, &[ArgKind::I32], RetKind::Bool);
+        let addr = stdcall_is_positive as usize;
+        let result = unsafe { invoke(&desc, addr, &mut [ArgValue::I32(3)]) };
+        assert_eq!(result, Ok(RetValue::Bool(true)));
+        let result = unsafe { invoke(&desc, addr, &mut [ArgValue::I32(-3)]) };
+        assert_eq!(result, Ok(RetValue::Bool(false)));
+    }
+
+    #[test]
+    fn marshals_input_strings() {
+        let desc = descriptor(CallConv::Cdecl, &[ArgKind::StringIn], RetKind::I32);
+        let addr = cdecl_string_len as usize;
+        // 短字符串在内联缓冲区，长字符串在堆上
+        for text in ["short", "a string longer than sixteen bytes"] {
+            let result = unsafe { invoke(&desc, addr, &mut [ArgValue::Str(text)]) };
+            assert_eq!(result, Ok(RetValue::I32(text.len() as i32)));
+        }
+
+        let desc = descriptor(CallConv::Cdecl, &[ArgKind::WStringIn], RetKind::I32);
+        let result = unsafe {
+            invoke(
+                &desc,
+                cdecl_wstring_first as usize,
+                &mut [ArgValue::WStr("企业微信")],
+            )
+        };
+        assert_eq!(result, Ok(RetValue::I32('企' as i32)));
+    }
+
+    #[test]
+    fn writes_back_output_strings() {
+        let desc = descriptor(CallConv::Stdcall, &[ArgKind::WStringOut], RetKind::Void);
+        let mut out = String::from("旧内容");
+        let result = unsafe {
+            invoke(
+                &desc,
+                stdcall_write_wstring as usize,
+                &mut [ArgValue::WStrOut(&mut out)],
+            )
+        };
+        assert_eq!(result, Ok(RetValue::Void));
+        assert_eq!(out, "完成");
+    }
+
+    #[test]
+    fn rejects_mismatched_arguments() {
+        let desc = descriptor(
+            CallConv::Stdcall,
+            &[ArgKind::I32, ArgKind::I32],
+            RetKind::I32,
+        );
+        let addr = stdcall_sub as usize;
+        let result = unsafe { invoke(&desc, addr, &mut [ArgValue::I32(1)]) };
+        assert!(result.unwrap_err().contains("需要 2 个参数"));
+
+        let result = unsafe { invoke(&desc, addr, &mut [ArgValue::I32(1), ArgValue::Str("x")]) };
+        assert!(result.unwrap_err().contains("类型不匹配"));
+    }
+
+    #[test]
+    fn rejects_invalid_addresses() {
+        let desc = descriptor(
+            CallConv::Stdcall,
+            &[ArgKind::I32, ArgKind::I32],
+            RetKind::I32,
+        );
+        let args = || [ArgValue::I32(1), ArgValue::I32(2)];
+        assert!(unsafe { invoke(&desc, 0, &mut args()) }.is_err());
+
+        // 栈上的数据不可执行，不能当成函数调用
+        let data = [0xC3u8; 16];
+        let result = unsafe { invoke(&desc, data.as_ptr() as usize, &mut args()) };
+        assert!(result.unwrap_err().contains("不是可执行内存"));
+
+        let desc = descriptor(
+            CallConv::Thiscall,
+            &[ArgKind::This, ArgKind::I32],
+            RetKind::I32,
+        );
+        let result = unsafe {
+            invoke(
+                &desc,
+                thiscall_field as usize,
+                &mut [ArgValue::This(std::ptr::null_mut()), ArgValue::I32(0)],
+            )
+        };
+        assert!(result.unwrap_err().contains("this 指针"));
+    }
+}
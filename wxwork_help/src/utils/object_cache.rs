@@ -0,0 +1,297 @@
+use super::find_vtf_by_rtti_name::{find_objects_in_all_memory_by_vtable, search_vtf_by_rtti};
+use super::memory::{is_executable, is_private_writable, is_readable, read_value};
+use std::ffi::c_void;
+use std::mem;
+use std::sync::Mutex;
+use wxwork_profile::RttiEntry;
+
+/// 调试日志输出函数
+fn debug_log(message: &str) {
+    unsafe {
+        use std::ffi::OsStr;
+        use std::os::windows::ffi::OsStrExt;
+        use winapi::um::debugapi::OutputDebugStringW;
+
+        let formatted_message = format!("[OBJECT_CACHE] {}\n", message);
+        let wide_message: Vec<u16> = OsStr::new(&formatted_message)
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect();
+        OutputDebugStringW(wide_message.as_ptr());
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Cached {
+    vtable: usize, // 虚函数表地址，模块不变时不会变化
+    object: usize, // 对象地址，窗口关闭或退出登录后会失效
+}
+
+/// 按 RTTI 找到的对象缓存
+///
+/// 每次取用前校验缓存的对象：内存仍然是已提交的私有可写内存（扫描时只找这样的内存），
+/// 对象开头仍指向同一个虚函数表，且虚函数表的第一项指向可执行代码。
+/// 校验失败时重新扫描内存；扫描期间持有锁，多个线程同时取用只会扫描一次。
+/// 释放后尚未被覆盖的内存仍可能通过校验，调用失败时应调用 `invalidate`。
+pub struct ObjectCache {
+    cached: Mutex<Option<Cached>>,
+}
+
+impl ObjectCache {
+    pub const fn new() -> Self {
+        ObjectCache {
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// 获取对象，缓存失效时重新扫描
+    pub fn get(&self, module_name: &str, rtti: &RttiEntry) -> Result<*mut c_void, String> {
+        self.get_with(
+            &rtti.name,
+            || {
+                search_vtf_by_rtti(
+                    module_name,
+                    &rtti.name,
+                    rtti.offset_vtf_in_complete_class,
+                    rtti.offset_constructor,
+                )
+                .map(|vtable| vtable as usize)
+                .ok_or_else(|| format!("未找到 {} 的虚函数表", rtti.name))
+            },
+            |vtable| {
+                find_objects_in_all_memory_by_vtable(vtable as *const c_void)
+                    .into_iter()
+                    .map(|object| object as usize)
+                    .collect()
+            },
+        )
+    }
+
+    // 同 `get`，查找虚函数表和扫描对象的方式由调用方提供
+    fn get_with(
+        &self,
+        name: &str,
+        find_vtable: impl FnOnce() -> Result<usize, String>,
+        find_objects: impl FnOnce(usize) -> Vec<usize>,
+    ) -> Result<*mut c_void, String> {
+        let mut cached = self
+            .cached
+            .lock()
+            .map_err(|_| "对象缓存锁已损坏".to_string())?;
+
+        // 校验缓存
+        let vtable = match *cached {
+            Some(entry) if is_valid_object(entry.object, entry.vtable) => {
+                return Ok(entry.object as *mut c_void);
+            }
+            Some(entry) => {
+                debug_log(&format!(
+                    "缓存的对象 0x{:X} 已失效，重新扫描 {}",
+                    entry.object, name
+                ));
+                entry.vtable
+            }
+            None => find_vtable()?,
+        };
+        *cached = None;
+
+        // 重新扫描，取第一个通过校验的对象
+        let object = find_objects(vtable)
+            .into_iter()
+            .find(|object| is_valid_object(*object, vtable))
+            .ok_or_else(|| format!("未找到 {} 的对象实例", name))?;
+
+        debug_log(&format!("找到对象 {}: 0x{:X}", name, object));
+        *cached = Some(Cached { vtable, object });
+        Ok(object as *mut c_void)
+    }
+
+    /// 清空缓存，下次取用时重新查找虚函数表和对象
+    pub fn invalidate(&self) {
+        if let Ok(mut cached) = self.cached.lock() {
+            if let Some(entry) = cached.take() {
+                debug_log(&format!("清空对象缓存: 0x{:X}", entry.object));
+            }
+        }
+    }
+}
+
+impl Default for ObjectCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 对象在私有可写内存中，开头的虚函数表指针没有变化，且虚函数表第一项指向代码
+fn is_valid_object(object: usize, vtable: usize) -> bool {
+    is_private_writable(object, mem::size_of::<usize>())
+        && read_value::<usize>(object).is_ok_and(|actual| actual == vtable)
+        && is_readable(vtable, mem::size_of::<usize>())
+        && read_value::<usize>(vtable).is_ok_and(is_executable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use winapi::um::memoryapi::{VirtualAlloc, VirtualFree};
+    use winapi::um::winnt::{MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_READWRITE};
+
+    extern "C" fn method() {}
+
+    // 虚函数表：第一项指向可执行代码
+    fn fake_vtable() -> Box<[usize; 2]> {
+        Box::new([method as usize, method as usize])
+    }
+
+    // 单独分配一页作为对象，测试中可以释放
+    fn alloc_object(vtable: usize) -> usize {
+        let object = unsafe {
+            VirtualAlloc(
+                std::ptr::null_mut(),
+                4096,
+                MEM_COMMIT | MEM_RESERVE,
+                PAGE_READWRITE,
+            )
+        } as usize;
+        assert_ne!(object, 0);
+        unsafe { *(object as *mut usize) = vtable };
+        object
+    }
+
+    fn free_object(object: usize) {
+        assert_ne!(unsafe { VirtualFree(object as *mut _, 0, MEM_RELEASE) }, 0);
+    }
+
+    // 按给定的对象列表扫描，记录扫描次数
+    fn get(
+        cache: &ObjectCache,
+        vtable: usize,
+        objects: &[usize],
+        scans: &Cell<usize>,
+    ) -> Result<usize, String> {
+        cache
+            .get_with(
+                "Frame",
+                || Ok(vtable),
+                |scanned| {
+                    assert_eq!(scanned, vtable);
+                    scans.set(scans.get() + 1);
+                    objects.to_vec()
+                },
+            )
+            .map(|object| object as usize)
+    }
+
+    #[test]
+    fn validates_object() {
+        let vtable = fake_vtable();
+        let vtable_addr = vtable.as_ptr() as usize;
+        let mut object = [vtable_addr, 0];
+        let addr = object.as_mut_ptr() as usize;
+        let set_vtable = |vtable: usize| unsafe { *(addr as *mut usize) = vtable };
+        assert!(is_valid_object(addr, vtable_addr));
+
+        // 虚函数表指针被改写
+        set_vtable(vtable_addr + mem::size_of::<usize>() * 2);
+        assert!(!is_valid_object(addr, vtable_addr));
+
+        // 虚函数表第一项不指向代码
+        let data_table = Box::new([addr, 0]);
+        set_vtable(data_table.as_ptr() as usize);
+        assert!(!is_valid_object(addr, data_table.as_ptr() as usize));
+
+        // 代码段不可写，不会是堆上的对象
+        assert!(!is_valid_object(method as usize, vtable_addr));
+        assert!(!is_valid_object(0, vtable_addr));
+    }
+
+    #[test]
+    fn reuses_valid_entry() {
+        let vtable = fake_vtable();
+        let object = [vtable.as_ptr() as usize];
+        let addr = object.as_ptr() as usize;
+        let cache = ObjectCache::new();
+        let scans = Cell::new(0);
+
+        assert_eq!(
+            get(&cache, vtable.as_ptr() as usize, &[addr], &scans),
+            Ok(addr)
+        );
+        assert_eq!(
+            get(&cache, vtable.as_ptr() as usize, &[addr], &scans),
+            Ok(addr)
+        );
+        assert_eq!(scans.get(), 1);
+    }
+
+    #[test]
+    fn rescans_when_vtable_changes() {
+        let vtable = fake_vtable();
+        let vtable_addr = vtable.as_ptr() as usize;
+        let mut first = [vtable_addr];
+        let second = [vtable_addr];
+        let (first_addr, second_addr) = (first.as_mut_ptr() as usize, second.as_ptr() as usize);
+        let cache = ObjectCache::new();
+        let scans = Cell::new(0);
+
+        assert_eq!(
+            get(&cache, vtable_addr, &[first_addr], &scans),
+            Ok(first_addr)
+        );
+        // 对象析构后虚函数表指针被改写，缓存失效，扫描到的旧对象也不再通过校验
+        unsafe { *(first_addr as *mut usize) = 0 };
+        let objects = [first_addr, second_addr];
+        assert_eq!(get(&cache, vtable_addr, &objects, &scans), Ok(second_addr));
+        assert_eq!(scans.get(), 2);
+    }
+
+    #[test]
+    fn rescans_when_object_is_freed() {
+        let vtable = fake_vtable();
+        let vtable_addr = vtable.as_ptr() as usize;
+        let object = alloc_object(vtable_addr);
+        let cache = ObjectCache::new();
+        let scans = Cell::new(0);
+
+        assert_eq!(get(&cache, vtable_addr, &[object], &scans), Ok(object));
+        free_object(object);
+        let error = get(&cache, vtable_addr, &[object], &scans).unwrap_err();
+        assert!(error.contains("对象实例"), "{}", error);
+        assert_eq!(scans.get(), 2);
+
+        // 找不到对象时不保留失效的缓存
+        let replacement = alloc_object(vtable_addr);
+        assert_eq!(
+            get(&cache, vtable_addr, &[replacement], &scans),
+            Ok(replacement)
+        );
+        free_object(replacement);
+    }
+
+    #[test]
+    fn invalidate_looks_up_vtable_again() {
+        let vtable = fake_vtable();
+        let object = [vtable.as_ptr() as usize];
+        let addr = object.as_ptr() as usize;
+        let cache = ObjectCache::new();
+        let scans = Cell::new(0);
+        assert_eq!(
+            get(&cache, vtable.as_ptr() as usize, &[addr], &scans),
+            Ok(addr)
+        );
+
+        cache.invalidate();
+        let lookups = Cell::new(0);
+        let result = cache.get_with(
+            "Frame",
+            || {
+                lookups.set(lookups.get() + 1);
+                Err("未找到 Frame 的虚函数表".to_string())
+            },
+            |_| unreachable!(),
+        );
+        assert!(result.is_err());
+        assert_eq!(lookups.get(), 1);
+    }
+}
@@ -1,9 +1,20 @@
 use super::wxwork_base::{WxWorkBase, WxWorkConfig};
-use crate::utils::find_vtf_by_rtti_name::{
-    search_object_by_rtti
-};
-use std::ffi::{CString, c_void};
-use winapi::um::libloaderapi::GetModuleHandleA;
+use crate::dispatch::Dispatcher;
+use crate::qrcode::content::{QrcodeContent, QrcodeSource};
+use crate::qrcode::status_hook::install_status_hook;
+use crate::utils::locate::resolve_function;
+use crate::utils::memory::read_string_field;
+use crate::utils::module_guard::check_module;
+use crate::utils::native_call::{ArgValue, invoke};
+use crate::utils::object_cache::ObjectCache;
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use std::time::SystemTime;
+use wxwork_profile::{ArgKind, CallConv, Profile};
+
+// 版本配置，函数偏移和RTTI名称都从这里读取
+const PROFILE_TOML: &str =
+    include_str!("../../../wxwork_profile/profiles/wxwork_4_1_38_6006.toml");
 
 /// 调试日志输出函数
 fn debug_log(message: &str) {
@@ -23,6 +34,9 @@ fn debug_log(message: &str) {
 
 pub struct WxWork4_1_38_6006 {
     config: WxWorkConfig,
+    profile: Option<Profile>,
+    objects: BTreeMap<String, ObjectCache>, // 配置中每个RTTI类型的对象缓存
+    dispatcher: Option<Dispatcher>,         // 客户端调用在窗口线程上执行
 }
 
 impl WxWork4_1_38_6006 {
@@ -32,23 +46,89 @@ impl WxWork4_1_38_6006 {
                 version: "4.1.38.6006".to_string(),
                 refresh_qrcode_addr: 0,
                 module_name: "WxWork.exe".to_string(),
+                module_base: 0,
             },
+            profile: None,
+            objects: BTreeMap::new(),
+            dispatcher: None,
+        }
+    }
+
+    // 客户端调用的派发器，没有设置时不调用，避免在工作线程上操作界面对象
+    fn dispatcher(&self) -> Result<&Dispatcher, String> {
+        self.dispatcher
+            .as_ref()
+            .ok_or_else(|| "没有设置派发器".to_string())
+    }
+
+    // 获取配置并重新校验模块，防止模块被替换或重新加载
+    fn checked_profile(&self) -> Result<&Profile, String> {
+        let profile = self
+            .profile
+            .as_ref()
+            .ok_or_else(|| "尚未初始化".to_string())?;
+        let module_base = check_module(profile)?;
+        if module_base != self.config.module_base {
+            self.invalidate_objects();
+            return Err(format!(
+                "模块基址已变化: 0x{:X} -> 0x{:X}，需要重新初始化",
+                self.config.module_base, module_base
+            ));
+        }
+        Ok(profile)
+    }
+
+    // 按 `rtti` 表中的键获取对象，缓存失效时自动重新扫描
+    fn object(&self, profile: &Profile, key: &str) -> Result<*mut c_void, String> {
+        let rtti = profile.rtti(key)?;
+        let cache = self
+            .objects
+            .get(key)
+            .ok_or_else(|| format!("RTTI类型 {} 没有对象缓存", key))?;
+        cache
+            .get(&self.config.module_name, rtti)
+            .inspect_err(|e| debug_log(&format!("错误：{}", e)))
+    }
+
+    // 清空某个对象的缓存
+    fn invalidate_object(&self, key: &str) {
+        if let Some(cache) = self.objects.get(key) {
+            cache.invalidate();
+        }
+    }
+
+    fn invalidate_objects(&self) {
+        for cache in self.objects.values() {
+            cache.invalidate();
         }
     }
 }
 
 impl WxWorkBase for WxWork4_1_38_6006 {
     fn init(&mut self) -> Result<(), String> {
-        let module_base = unsafe {
-            GetModuleHandleA(
-                CString::new(self.config.module_name.as_str())
-                    .unwrap()
-                    .as_ptr(),
-            )
-        } as usize;
+        // 加载版本配置
+        let profile = Profile::from_toml_str(PROFILE_TOML)?;
+        if profile.version != self.config.version {
+            return Err(format!(
+                "版本配置不匹配: 期望 {}, 实际 {}",
+                self.config.version, profile.version
+            ));
+        }
+        self.config.module_name = profile.module_name.clone();
+
+        // 确认加载的模块就是这个版本
+        self.config.module_base = check_module(&profile)?;
+        debug_log(&format!("模块指纹校验通过，基址: 0x{:X}", self.config.module_base));
 
         // 刷新二维码函数地址
-        self.config.refresh_qrcode_addr = module_base + (0x34239A0 as usize);
+        let refresh_qrcode = profile.descriptor("refresh_qrcode")?;
+        self.config.refresh_qrcode_addr = resolve_function(&profile, &refresh_qrcode)?;
+        self.objects = profile
+            .rtti
+            .keys()
+            .map(|key| (key.clone(), ObjectCache::new()))
+            .collect();
+        self.profile = Some(profile);
 
         // 日志输出
         debug_log(&format!("初始化 WxWork 版本: {}", self.config.version));
@@ -65,55 +145,126 @@ impl WxWorkBase for WxWork4_1_38_6006 {
     fn refresh_qrcode(&mut self) -> Result<(), String> {
         debug_log("开始刷新二维码");
 
-        // 定义一个静态的Qrcode对象指针
-        static mut QRCODE_OBJ_PTR: *mut c_void = std::ptr::null_mut();
-
-        // 检测指针是否已经初始化
-        let qrcode_obj_ptr = unsafe {
-            if QRCODE_OBJ_PTR.is_null() {
-                let qrcode_obj = search_object_by_rtti(&self.config.module_name, ".?AVQrcodeFrame@ui@wework@@", 0, 0);
-                if qrcode_obj.is_none() {
-                    debug_log("错误：未找到二维码框架对象");
-                    return Err("未找到二维码框架对象".to_string());
-                }
-                QRCODE_OBJ_PTR = qrcode_obj.unwrap();
-                debug_log(&format!("找到二维码对象地址: {:p}", QRCODE_OBJ_PTR as *const c_void));
-            }
-            QRCODE_OBJ_PTR
-        };
-
-        // 检测二维码对象指针
-        if qrcode_obj_ptr.is_null() {
-            debug_log("错误：二维码对象指针无效");
-            return Err("二维码对象指针无效".to_string());
-        }
+        let profile = self.checked_profile()?;
+        let qrcode_obj_ptr = self.object(profile, "qrcode_frame")?;
 
         debug_log(&format!("使用二维码对象地址: {:p}", qrcode_obj_ptr));
 
-        // 定义刷新二维码函数指针
-        type FnReflashQrcode = extern "thiscall" fn(*mut c_void);
-
-        // 转换二维码刷新函数指针
-        let refresh_qrcode_fn: FnReflashQrcode = unsafe {
-            std::mem::transmute::<usize, FnReflashQrcode>(self.config.refresh_qrcode_addr)
-        };
+        // 刷新二维码函数描述
+        let refresh_qrcode = profile.descriptor("refresh_qrcode")?;
 
         debug_log(&format!("准备调用刷新函数，地址: 0x{:X}", self.config.refresh_qrcode_addr));
 
-        // 调用刷新二维码
-        let result = std::panic::catch_unwind(|| {
-            refresh_qrcode_fn(qrcode_obj_ptr);
-        });
+        // 在窗口线程上调用刷新二维码
+        let addr = self.config.refresh_qrcode_addr;
+        let object = qrcode_obj_ptr as usize;
+        let result = self
+            .dispatcher()?
+            .run(move || unsafe {
+                invoke(
+                    &refresh_qrcode,
+                    addr,
+                    &mut [ArgValue::This(object as *mut c_void)],
+                )
+                .map(|_| ())
+            })
+            .and_then(|result| result);
 
         match result {
             Ok(_) => {
                 debug_log("二维码刷新成功");
                 Ok(())
             }
-            Err(_) => {
-                debug_log("二维码刷新时发生异常");
-                Err("调用刷新函数时发生异常".to_string())
+            Err(e) => {
+                debug_log(&format!("二维码刷新失败: {}", e));
+                // 对象可能已经释放但内存还没被覆盖，下次重新扫描
+                self.invalidate_object("qrcode_frame");
+                Err(e)
             }
         }
     }
+
+    // 读取当前二维码内容
+    fn get_qrcode_content(&mut self) -> Result<QrcodeContent, String> {
+        debug_log("开始读取二维码内容");
+
+        let profile = self.checked_profile()?;
+        let field = profile.field("qrcode_content")?.clone();
+        // 字段从哪个对象出发由配置决定
+        let object_key = field.object.clone();
+        let object = self.object(profile, &object_key)? as usize;
+
+        // 窗口线程可能正在修改字符串，在窗口线程上读取
+        let content = self
+            .dispatcher()?
+            .run(move || read_string_field(object as *const c_void, &field))
+            .and_then(|result| result)
+            .map_err(|e| {
+                debug_log(&format!("读取二维码内容失败: {}", e));
+                // 指针链读不通时对象可能已经失效，下次重新扫描
+                self.invalidate_object(&object_key);
+                format!("读取二维码内容失败: {}", e)
+            })?;
+        if content.is_empty() {
+            return Err("二维码内容为空".to_string());
+        }
+
+        debug_log(&format!("二维码内容: {}", content));
+        Ok(QrcodeContent {
+            content,
+            read_at: SystemTime::now(),
+            source: QrcodeSource::Memory,
+        })
+    }
+
+    // 二维码内容字段还没有定位时配置中没有 qrcode_content
+    fn supports_qrcode_content(&self) -> bool {
+        self.profile
+            .as_ref()
+            .is_some_and(|profile| profile.fields.contains_key("qrcode_content"))
+    }
+
+    // 在状态轮询回调上挂hook，驱动二维码状态机
+    fn watch_qrcode_status(&mut self) -> Result<(), String> {
+        let profile = self.checked_profile()?;
+        let status = profile
+            .qrcode_status
+            .as_ref()
+            .ok_or_else(|| "配置中没有 qrcode_status".to_string())?;
+
+        // hook函数的原型是固定的，配置必须与之一致
+        let callback = profile.descriptor(&status.callback)?;
+        if callback.convention != CallConv::Thiscall
+            || callback.args != [ArgKind::This, ArgKind::I32]
+        {
+            return Err(format!(
+                "状态回调 {} 的原型必须是 thiscall fn(this, i32)",
+                status.callback
+            ));
+        }
+
+        let addr = resolve_function(profile, &callback)?;
+        let codes = status.parse_codes()?;
+        install_status_hook(addr, &codes)?;
+
+        debug_log(&format!("开始监听二维码状态，回调地址: 0x{:X}", addr));
+        Ok(())
+    }
+
+    // 状态回调地址和状态码还没有定位时配置中没有 qrcode_status
+    fn supports_qrcode_status(&self) -> bool {
+        self.profile
+            .as_ref()
+            .is_some_and(|profile| profile.qrcode_status.is_some())
+    }
+
+    // 清空缓存的对象
+    fn invalidate_cache(&mut self) {
+        self.invalidate_objects();
+    }
+
+    // 设置客户端调用的派发器
+    fn set_dispatcher(&mut self, dispatcher: Dispatcher) {
+        self.dispatcher = Some(dispatcher);
+    }
 }
@@ -1,9 +1,23 @@
 
 
+use crate::dispatch::Dispatcher;
+use crate::qrcode::content::QrcodeContent;
+
 // 定义版本接口
 pub trait WxWorkBase {
     fn init(&mut self) -> Result<(), String>;
     fn refresh_qrcode(&mut self) -> Result<(), String>;
+    fn get_qrcode_content(&mut self) -> Result<QrcodeContent, String>;
+    // 版本配置中是否有二维码内容字段，没有时不从内存读取二维码内容
+    fn supports_qrcode_content(&self) -> bool;
+    // 监听二维码状态，状态变化通过 qrcode::state::qrcode_state_hub 发布
+    fn watch_qrcode_status(&mut self) -> Result<(), String>;
+    // 版本配置中是否有二维码状态回调，没有时不监听二维码状态
+    fn supports_qrcode_status(&self) -> bool;
+    // 清空缓存的客户端对象，下次使用时重新查找
+    fn invalidate_cache(&mut self);
+    // 设置客户端调用的派发器，设置之前不调用客户端函数
+    fn set_dispatcher(&mut self, dispatcher: Dispatcher);
 }
 
 // 基础配置结构体，存放共同的成员变量
@@ -12,4 +26,5 @@ pub struct WxWorkConfig {
     pub version: String,                // 版本信息
     pub refresh_qrcode_addr: usize,     // 刷新二维码函数地址
     pub module_name: String,            // 模块名称
+    pub module_base: usize,             // 初始化时校验过的模块基址
 }
@@ -0,0 +1,187 @@
+// 解析抓取到的客户端日志，可在 Linux 上检查解析结果
+//
+// 用法: parse_log [--json] [--check] [--jsonl <目录>] [--filter <规则文件>] <日志文件>
+// 默认逐行输出解析后的字段，带 --json 时每行输出一个 JSON 记录
+// 带 --jsonl 时同时按 hook 中的方式写入目录下按日期轮转的 JSONL 文件
+// 带 --filter 时按 TOML 规则文件（default 和 [[rules]]，同 wxwork_tools.toml 的 [log.filter]）过滤，
+// 只输出保留的记录，最后输出每条规则的计数
+// 每行按 hook 中的方式识别编码（UTF-8、GBK、UTF-16，都不是时转义）
+// 带 --check 时校验每行解析后再按客户端格式输出与原文一致、按识别的编码能还原出原始字节，
+// 用于回归检查样例日志
+// 全部通过时返回0，有行校验失败返回1，参数错误返回2
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+use wxwork_log::{
+    FilterConfig, LogFilter, LogPipeline, LogRecord, SinkConfig, Verdict, decode_bytes, encode_text,
+};
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    let check = args.iter().any(|arg| arg == "--check");
+    args.retain(|arg| arg != "--json" && arg != "--check");
+    let jsonl_dir = take_option(&mut args, "--jsonl").map(PathBuf::from);
+    let filter_file = take_option(&mut args, "--filter").map(PathBuf::from);
+    if args.len() != 2 {
+        eprintln!(
+            "用法: {} [--json] [--check] [--jsonl <目录>] [--filter <规则文件>] <日志文件>",
+            args.first().map_or("parse_log", String::as_str)
+        );
+        return ExitCode::from(2);
+    }
+
+    let pipeline = match jsonl_dir.map(start_pipeline).transpose() {
+        Ok(pipeline) => pipeline,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let filter = match filter_file.as_deref().map(load_filter).transpose() {
+        Ok(filter) => filter.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let bytes = match std::fs::read(&args[1]) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("❌ 读取 {} 失败: {}", args[1], e);
+            return ExitCode::from(2);
+        }
+    };
+    let mut structured = 0;
+    let mut unstructured = 0;
+    let mut mismatched = 0;
+    // 样例日志可能混有 GBK、UTF-16 或二进制内容，逐行按 hook 中的方式解码
+    let lines = bytes
+        .split(|byte| *byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+    for (index, raw) in lines.enumerate() {
+        if raw.is_empty() {
+            continue;
+        }
+        let decoded = decode_bytes(raw);
+        let record = LogRecord {
+            encoding: decoded.encoding,
+            ..LogRecord::parse(&decoded.text)
+        };
+        if record.is_structured() {
+            structured += 1;
+        } else {
+            unstructured += 1;
+        }
+
+        // 解析后按原格式输出与解码的文本一致，按解码时的编码还原出原始字节
+        let raw = raw.strip_prefix(&[0xFF, 0xFE]).unwrap_or(raw);
+        if check
+            && (record.to_string() != decoded.text
+                || encode_text(&decoded.text, decoded.encoding) != raw)
+        {
+            mismatched += 1;
+            eprintln!(
+                "❌ 第 {} 行 ({}) 输出与原文不一致",
+                index + 1,
+                decoded.encoding
+            );
+            eprintln!("   原文: {}", decoded.text);
+            eprintln!("   输出: {}", record);
+        }
+
+        if filter.check(&record, Instant::now()) == Verdict::Drop {
+            continue;
+        }
+        if json {
+            println!("{}", record.to_json());
+        } else {
+            print_record(index + 1, &record);
+        }
+        if let Some(pipeline) = &pipeline {
+            // 离线转换不能丢记录，队列满时等一会再提交
+            while !pipeline.submit(record.clone()) {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+    }
+    if let Some(pipeline) = pipeline {
+        pipeline.shutdown();
+    }
+    if filter_file.is_some() {
+        for stats in filter.stats() {
+            eprintln!("{}", stats);
+        }
+    }
+
+    eprintln!(
+        "共 {} 行: {} 行结构化, {} 行按纯文本处理",
+        structured + unstructured,
+        structured,
+        unstructured
+    );
+    if mismatched > 0 {
+        eprintln!("❌ {} 行校验失败", mismatched);
+        return ExitCode::from(1);
+    }
+    ExitCode::SUCCESS
+}
+
+// 取出 `<选项> <值>`，缺少值时清空参数以输出用法
+fn take_option(args: &mut Vec<String>, option: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == option)?;
+    args.remove(index);
+    if index < args.len() {
+        Some(args.remove(index))
+    } else {
+        args.clear();
+        None
+    }
+}
+
+// 读取过滤规则文件
+fn load_filter(path: &Path) -> Result<LogFilter, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("读取规则文件 {} 失败: {}", path.display(), e))?;
+    let config: FilterConfig =
+        toml::from_str(&text).map_err(|e| format!("解析规则文件失败: {}", e))?;
+    LogFilter::new(&config)
+}
+
+// 启动写 JSONL 文件的后台线程
+fn start_pipeline(dir: PathBuf) -> Result<LogPipeline, String> {
+    let config = SinkConfig::Jsonl {
+        dir,
+        prefix: "client_log".to_string(),
+        max_bytes: 64 * 1024 * 1024,
+        daily: true,
+    };
+    let (sink, _) = config.build()?;
+    LogPipeline::start(vec![sink], 1024, |e| eprintln!("❌ {}", e))
+}
+
+// 输出一条记录的字段
+fn print_record(line_number: usize, record: &LogRecord) {
+    let (Some(level), Some(timestamp)) = (record.level, record.timestamp) else {
+        println!("{:>5} -  {}", line_number, record.message);
+        return;
+    };
+    let location = record
+        .location
+        .as_ref()
+        .map(|location| format!("{}:{} {}", location.file, location.line, location.function))
+        .unwrap_or_default();
+    println!(
+        "{:>5} {}  {}  {}/{}{}  [{}]  {}  {}",
+        line_number,
+        level,
+        timestamp,
+        record.process_id.unwrap_or_default(),
+        record.thread_id.unwrap_or_default(),
+        if record.main_thread { "*" } else { "" },
+        record.tag.as_deref().unwrap_or_default(),
+        location,
+        record.message
+    );
+}
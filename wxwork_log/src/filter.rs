@@ -0,0 +1,533 @@
+use crate::record::{LogLevel, LogRecord};
+use regex::Regex;
+use serde::Deserialize;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// 规则匹配后的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    #[default]
+    Include,
+    Exclude,
+}
+
+/// 一条过滤规则的配置，指定的条件全部满足才算匹配
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct FilterRuleConfig {
+    pub name: String,
+    #[serde(default)]
+    pub action: FilterAction,
+    pub min_level: Option<LogLevel>, // 级别不低于
+    pub max_level: Option<LogLevel>, // 级别不高于
+    #[serde(default)]
+    pub tags: Vec<String>, // 标签是其中之一
+    pub pattern: Option<String>,     // 消息匹配的正则表达式
+    pub rate_limit_per_sec: Option<u32>, // 每秒最多保留的条数，只用于 include
+    pub sample_every: Option<u32>,   // 每 N 条保留一条，只用于 include
+}
+
+/// 过滤配置：按顺序匹配规则，第一条匹配的规则决定去留，都不匹配时按 `default` 处理
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct FilterConfig {
+    #[serde(default)]
+    pub default: FilterAction,
+    #[serde(default)]
+    pub rules: Vec<FilterRuleConfig>,
+}
+
+/// 过滤结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Keep,
+    Drop,
+}
+
+/// 一条规则的计数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleStats {
+    pub name: String,
+    pub matched: u64,      // 匹配的条数
+    pub dropped: u64,      // 因为这条规则丢弃的条数（排除、限速、采样）
+    pub rate_limited: u64, // 其中因为限速丢弃的
+    pub sampled_out: u64,  // 其中因为采样丢弃的
+}
+
+impl fmt::Display for RuleStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<20} 匹配 {:>8}  丢弃 {:>8} (限速 {}, 采样 {})",
+            self.name, self.matched, self.dropped, self.rate_limited, self.sampled_out
+        )
+    }
+}
+
+// 令牌桶，容量等于每秒的条数
+struct RateLimiter {
+    per_sec: u32,
+    tokens: f64,
+    last: Option<Instant>,
+}
+
+impl RateLimiter {
+    fn allow(&mut self, now: Instant) -> bool {
+        let per_sec = self.per_sec as f64;
+        let elapsed = self
+            .last
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * per_sec).min(per_sec);
+        self.last = Some(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+struct FilterRule {
+    config: FilterRuleConfig,
+    pattern: Option<Regex>,
+    rate_limiter: Option<Mutex<RateLimiter>>,
+    matched: AtomicU64,
+    rate_limited: AtomicU64,
+    sampled_out: AtomicU64,
+    excluded: AtomicU64,
+}
+
+impl FilterRule {
+    fn new(config: FilterRuleConfig) -> Result<Self, String> {
+        let invalid = |reason: String| format!("过滤规则 {}: {}", config.name, reason);
+        let pattern = config
+            .pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| invalid(format!("正则表达式无效: {}", e)))?;
+        if config
+            .min_level
+            .zip(config.max_level)
+            .is_some_and(|(min, max)| min > max)
+        {
+            return Err(invalid("min_level 高于 max_level".to_string()));
+        }
+        if config.action == FilterAction::Exclude
+            && (config.rate_limit_per_sec.is_some() || config.sample_every.is_some())
+        {
+            return Err(invalid("exclude 规则不能设置限速或采样".to_string()));
+        }
+        if config.rate_limit_per_sec == Some(0) || config.sample_every == Some(0) {
+            return Err(invalid("限速和采样不能为0".to_string()));
+        }
+
+        let rate_limiter = config.rate_limit_per_sec.map(|per_sec| {
+            Mutex::new(RateLimiter {
+                per_sec,
+                tokens: per_sec as f64,
+                last: None,
+            })
+        });
+        Ok(FilterRule {
+            config,
+            pattern,
+            rate_limiter,
+            matched: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+            sampled_out: AtomicU64::new(0),
+            excluded: AtomicU64::new(0),
+        })
+    }
+
+    fn matches(&self, record: &LogRecord) -> bool {
+        let config = &self.config;
+        if config.min_level.is_some() || config.max_level.is_some() {
+            let Some(level) = record.level else {
+                return false;
+            };
+            if config.min_level.is_some_and(|min| level < min)
+                || config.max_level.is_some_and(|max| level > max)
+            {
+                return false;
+            }
+        }
+        if !config.tags.is_empty()
+            && !record
+                .tag
+                .as_ref()
+                .is_some_and(|tag| config.tags.contains(tag))
+        {
+            return false;
+        }
+        self.pattern
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(&record.message))
+    }
+
+    // 匹配后决定去留，先采样再限速，被采样丢弃的不占用限速额度
+    fn apply(&self, now: Instant) -> Verdict {
+        let matched = self.matched.fetch_add(1, Ordering::Relaxed);
+        if self.config.action == FilterAction::Exclude {
+            self.excluded.fetch_add(1, Ordering::Relaxed);
+            return Verdict::Drop;
+        }
+        // 每 N 条中保留第一条
+        let sampled_out = self
+            .config
+            .sample_every
+            .is_some_and(|every| matched % u64::from(every) != 0);
+        if sampled_out {
+            self.sampled_out.fetch_add(1, Ordering::Relaxed);
+            return Verdict::Drop;
+        }
+        if let Some(rate_limiter) = &self.rate_limiter {
+            let allowed = rate_limiter
+                .lock()
+                .map_or(true, |mut rate_limiter| rate_limiter.allow(now));
+            if !allowed {
+                self.rate_limited.fetch_add(1, Ordering::Relaxed);
+                return Verdict::Drop;
+            }
+        }
+        Verdict::Keep
+    }
+
+    fn stats(&self) -> RuleStats {
+        let rate_limited = self.rate_limited.load(Ordering::Relaxed);
+        let sampled_out = self.sampled_out.load(Ordering::Relaxed);
+        RuleStats {
+            name: self.config.name.clone(),
+            matched: self.matched.load(Ordering::Relaxed),
+            dropped: self.excluded.load(Ordering::Relaxed) + rate_limited + sampled_out,
+            rate_limited,
+            sampled_out,
+        }
+    }
+}
+
+/// 日志过滤器
+///
+/// 可以在多个线程上同时调用 `check`，计数用原子变量，只有限速规则需要短暂加锁。
+/// 重新加载配置时创建新的过滤器替换旧的，计数从零开始。
+pub struct LogFilter {
+    default: FilterAction,
+    rules: Vec<FilterRule>,
+    unmatched: AtomicU64,
+    default_dropped: AtomicU64,
+}
+
+impl LogFilter {
+    /// 按配置创建，规则有误时返回错误
+    pub fn new(config: &FilterConfig) -> Result<Self, String> {
+        let mut names = Vec::new();
+        for rule in &config.rules {
+            if rule.name.is_empty() {
+                return Err("过滤规则缺少名称".to_string());
+            }
+            if names.contains(&rule.name.as_str()) {
+                return Err(format!("过滤规则名称重复: {}", rule.name));
+            }
+            names.push(rule.name.as_str());
+        }
+        let rules = config
+            .rules
+            .iter()
+            .cloned()
+            .map(FilterRule::new)
+            .collect::<Result<_, _>>()?;
+        Ok(LogFilter {
+            default: config.default,
+            rules,
+            unmatched: AtomicU64::new(0),
+            default_dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// 不过滤任何日志
+    pub fn pass_all() -> Self {
+        LogFilter {
+            default: FilterAction::Include,
+            rules: Vec::new(),
+            unmatched: AtomicU64::new(0),
+            default_dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// 判断一条记录是否保留
+    pub fn check(&self, record: &LogRecord, now: Instant) -> Verdict {
+        if let Some(rule) = self.rules.iter().find(|rule| rule.matches(record)) {
+            return rule.apply(now);
+        }
+        self.unmatched.fetch_add(1, Ordering::Relaxed);
+        match self.default {
+            FilterAction::Include => Verdict::Keep,
+            FilterAction::Exclude => {
+                self.default_dropped.fetch_add(1, Ordering::Relaxed);
+                Verdict::Drop
+            }
+        }
+    }
+
+    /// 每条规则的计数，最后一项是没有匹配任何规则的记录
+    pub fn stats(&self) -> Vec<RuleStats> {
+        let mut stats: Vec<RuleStats> = self.rules.iter().map(FilterRule::stats).collect();
+        stats.push(RuleStats {
+            name: "(default)".to_string(),
+            matched: self.unmatched.load(Ordering::Relaxed),
+            dropped: self.default_dropped.load(Ordering::Relaxed),
+            rate_limited: 0,
+            sampled_out: 0,
+        });
+        stats
+    }
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self::pass_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(level: &str, tag: &str, message: &str) -> LogRecord {
+        LogRecord::parse(&format!(
+            "[{}][2024-05-01 +8.0 10:11:12.345][7824, 7828*][{}][a.cpp, f, 1][{}",
+            level, tag, message
+        ))
+    }
+
+    fn rule(name: &str) -> FilterRuleConfig {
+        FilterRuleConfig {
+            name: name.to_string(),
+            ..FilterRuleConfig::default()
+        }
+    }
+
+    fn filter(default: FilterAction, rules: Vec<FilterRuleConfig>) -> LogFilter {
+        LogFilter::new(&FilterConfig { default, rules }).unwrap()
+    }
+
+    fn stats_of<'a>(stats: &'a [RuleStats], name: &str) -> &'a RuleStats {
+        stats.iter().find(|stats| stats.name == name).unwrap()
+    }
+
+    #[test]
+    fn parses_toml_config() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+            default = "exclude"
+
+            [[rules]]
+            name = "errors"
+            min_level = "warn"
+
+            [[rules]]
+            name = "noisy"
+            action = "exclude"
+            tags = ["network", "db"]
+            pattern = "^heartbeat"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.default, FilterAction::Exclude);
+        assert_eq!(config.rules[0].min_level, Some(LogLevel::Warn));
+        assert_eq!(config.rules[0].action, FilterAction::Include);
+        assert_eq!(config.rules[1].tags, ["network", "db"]);
+        assert!(LogFilter::new(&config).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let invalid = [
+            vec![rule("")],
+            vec![rule("a"), rule("a")],
+            vec![FilterRuleConfig {
+                pattern: Some("(".to_string()),
+                ..rule("a")
+            }],
+            vec![FilterRuleConfig {
+                min_level: Some(LogLevel::Error),
+                max_level: Some(LogLevel::Info),
+                ..rule("a")
+            }],
+            vec![FilterRuleConfig {
+                action: FilterAction::Exclude,
+                sample_every: Some(2),
+                ..rule("a")
+            }],
+            vec![FilterRuleConfig {
+                rate_limit_per_sec: Some(0),
+                ..rule("a")
+            }],
+            vec![FilterRuleConfig {
+                sample_every: Some(0),
+                ..rule("a")
+            }],
+        ];
+        for rules in invalid {
+            let config = FilterConfig {
+                default: FilterAction::Include,
+                rules: rules.clone(),
+            };
+            assert!(LogFilter::new(&config).is_err(), "{:?}", rules);
+        }
+    }
+
+    #[test]
+    fn matches_level_tag_and_pattern() {
+        let filter = filter(
+            FilterAction::Exclude,
+            vec![FilterRuleConfig {
+                min_level: Some(LogLevel::Info),
+                max_level: Some(LogLevel::Error),
+                tags: vec!["network".to_string()],
+                pattern: Some(r"cost=\d+".to_string()),
+                ..rule("slow_network")
+            }],
+        );
+        let now = Instant::now();
+        let keep = |record: &LogRecord| filter.check(record, now) == Verdict::Keep;
+        assert!(keep(&record("W", "network", "cost=598")));
+        assert!(!keep(&record("D", "network", "cost=598")));
+        assert!(!keep(&record("F", "network", "cost=598")));
+        assert!(!keep(&record("W", "db", "cost=598")));
+        assert!(!keep(&record("W", "", "cost=598")));
+        assert!(!keep(&record("W", "network", "cost=slow")));
+        // 没有级别的记录不匹配带级别条件的规则
+        assert!(!keep(&LogRecord::parse("network cost=598")));
+
+        let stats = filter.stats();
+        assert_eq!(stats_of(&stats, "slow_network").matched, 1);
+        assert_eq!(stats_of(&stats, "(default)").matched, 6);
+        assert_eq!(stats_of(&stats, "(default)").dropped, 6);
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let filter = filter(
+            FilterAction::Include,
+            vec![
+                FilterRuleConfig {
+                    min_level: Some(LogLevel::Error),
+                    ..rule("errors")
+                },
+                FilterRuleConfig {
+                    action: FilterAction::Exclude,
+                    tags: vec!["network".to_string()],
+                    ..rule("no_network")
+                },
+            ],
+        );
+        let now = Instant::now();
+        assert_eq!(
+            filter.check(&record("E", "network", "a"), now),
+            Verdict::Keep
+        );
+        assert_eq!(
+            filter.check(&record("I", "network", "b"), now),
+            Verdict::Drop
+        );
+        assert_eq!(filter.check(&record("I", "ui", "c"), now), Verdict::Keep);
+
+        let stats = filter.stats();
+        assert_eq!(stats.len(), 3);
+        assert_eq!((stats[0].matched, stats[0].dropped), (1, 0));
+        assert_eq!((stats[1].matched, stats[1].dropped), (1, 1));
+        assert_eq!((stats[2].matched, stats[2].dropped), (1, 0));
+    }
+
+    #[test]
+    fn samples_first_of_every_n() {
+        let filter = filter(
+            FilterAction::Include,
+            vec![FilterRuleConfig {
+                sample_every: Some(3),
+                ..rule("sampled")
+            }],
+        );
+        let now = Instant::now();
+        let verdicts: Vec<Verdict> = (0..7)
+            .map(|index| filter.check(&record("I", "ui", &index.to_string()), now))
+            .collect();
+        use Verdict::{Drop, Keep};
+        assert_eq!(verdicts, [Keep, Drop, Drop, Keep, Drop, Drop, Keep]);
+        let stats = &filter.stats()[0];
+        assert_eq!(
+            (
+                stats.matched,
+                stats.dropped,
+                stats.sampled_out,
+                stats.rate_limited
+            ),
+            (7, 4, 4, 0)
+        );
+    }
+
+    #[test]
+    fn rate_limit_refills_over_time() {
+        let filter = filter(
+            FilterAction::Include,
+            vec![FilterRuleConfig {
+                rate_limit_per_sec: Some(2),
+                ..rule("limited")
+            }],
+        );
+        let start = Instant::now();
+        let check = |at: Duration| filter.check(&record("I", "ui", "a"), start + at);
+        assert_eq!(check(Duration::ZERO), Verdict::Keep);
+        assert_eq!(check(Duration::ZERO), Verdict::Keep);
+        assert_eq!(check(Duration::from_millis(100)), Verdict::Drop);
+        // 半秒补充一条
+        assert_eq!(check(Duration::from_millis(600)), Verdict::Keep);
+        assert_eq!(check(Duration::from_millis(600)), Verdict::Drop);
+        // 空闲很久也只补满容量
+        assert_eq!(check(Duration::from_secs(60)), Verdict::Keep);
+        assert_eq!(check(Duration::from_secs(60)), Verdict::Keep);
+        assert_eq!(check(Duration::from_secs(60)), Verdict::Drop);
+
+        let stats = &filter.stats()[0];
+        assert_eq!(
+            (stats.matched, stats.rate_limited, stats.dropped),
+            (8, 3, 3)
+        );
+    }
+
+    #[test]
+    fn sampled_out_records_do_not_use_rate_limit() {
+        let filter = filter(
+            FilterAction::Include,
+            vec![FilterRuleConfig {
+                sample_every: Some(2),
+                rate_limit_per_sec: Some(1),
+                ..rule("both")
+            }],
+        );
+        let now = Instant::now();
+        let verdicts: Vec<Verdict> = (0..4)
+            .map(|_| filter.check(&record("I", "ui", "a"), now))
+            .collect();
+        assert_eq!(
+            verdicts,
+            [Verdict::Keep, Verdict::Drop, Verdict::Drop, Verdict::Drop]
+        );
+        let stats = &filter.stats()[0];
+        assert_eq!((stats.sampled_out, stats.rate_limited), (2, 1));
+    }
+
+    #[test]
+    fn pass_all_keeps_everything() {
+        let filter = LogFilter::pass_all();
+        assert_eq!(
+            filter.check(&LogRecord::parse("anything"), Instant::now()),
+            Verdict::Keep
+        );
+        assert_eq!(filter.stats()[0].matched, 1);
+    }
+}
@@ -0,0 +1,10 @@
+// 企业微信客户端日志（WriteLog）的解码、解析、过滤和输出，不依赖 Windows，可在 Linux 上检查
+pub mod filter;
+pub mod record;
+pub mod sink;
+pub mod text;
+
+pub use filter::{FilterAction, FilterConfig, FilterRuleConfig, LogFilter, RuleStats, Verdict};
+pub use record::{LogLevel, LogRecord, LogTimestamp, SourceLocation};
+pub use sink::{LogPipeline, LogSink, PipelineStats, RingBuffer, RingQuery, SinkConfig};
+pub use text::{DecodedText, TextEncoding, decode_bytes, decode_utf16, encode_text};
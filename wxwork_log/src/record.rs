@@ -0,0 +1,552 @@
+use crate::text::{TextEncoding, decode_bytes};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// 日志级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Verbose,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl LogLevel {
+    /// 日志行中的单字母缩写
+    pub fn letter(self) -> char {
+        match self {
+            LogLevel::Verbose => 'V',
+            LogLevel::Debug => 'D',
+            LogLevel::Info => 'I',
+            LogLevel::Warn => 'W',
+            LogLevel::Error => 'E',
+            LogLevel::Fatal => 'F',
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    /// 接受单字母缩写和完整名称，不区分大小写
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "v" | "verbose" => Ok(LogLevel::Verbose),
+            "d" | "debug" => Ok(LogLevel::Debug),
+            "i" | "info" => Ok(LogLevel::Info),
+            "w" | "warn" | "warning" => Ok(LogLevel::Warn),
+            "e" | "error" => Ok(LogLevel::Error),
+            "f" | "fatal" => Ok(LogLevel::Fatal),
+            _ => Err(format!("未知的日志级别: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
+
+/// 日志时间，客户端写的是本地时间加时区
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct LogTimestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub millisecond: u16,
+    pub utc_offset_minutes: Option<i16>, // 时区偏移，行中没有时为 None
+}
+
+impl LogTimestamp {
+    /// 解析 `2024-05-01 +8.0 10:11:12.345`，时区部分可以省略
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("时间格式不正确: {}", text);
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        let (date, zone, time) = match parts.as_slice() {
+            [date, zone, time] => (*date, Some(*zone), *time),
+            [date, time] => (*date, None, *time),
+            _ => return Err(invalid()),
+        };
+
+        let mut date_parts = date.split('-');
+        let year = parse_fixed(date_parts.next(), 4).ok_or_else(invalid)?;
+        let month = parse_fixed(date_parts.next(), 2).ok_or_else(invalid)?;
+        let day = parse_fixed(date_parts.next(), 2).ok_or_else(invalid)?;
+        if date_parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let (clock, millisecond) = time.split_once('.').unwrap_or((time, "000"));
+        let mut clock_parts = clock.split(':');
+        let hour = parse_fixed(clock_parts.next(), 2).ok_or_else(invalid)?;
+        let minute = parse_fixed(clock_parts.next(), 2).ok_or_else(invalid)?;
+        let second = parse_fixed(clock_parts.next(), 2).ok_or_else(invalid)?;
+        let millisecond = parse_fixed(Some(millisecond), 3).ok_or_else(invalid)?;
+        if clock_parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        if !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            return Err(invalid());
+        }
+
+        let utc_offset_minutes = match zone {
+            Some(zone) => Some(parse_zone(zone).ok_or_else(invalid)?),
+            None => None,
+        };
+
+        Ok(LogTimestamp {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: hour as u8,
+            minute: minute as u8,
+            second: second as u8,
+            millisecond: millisecond as u16,
+            utc_offset_minutes,
+        })
+    }
+}
+
+impl fmt::Display for LogTimestamp {
+    /// 输出为客户端的格式，解析后再输出结果不变
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} ", self.year, self.month, self.day)?;
+        if let Some(offset) = self.utc_offset_minutes {
+            let sign = if offset < 0 { '-' } else { '+' };
+            let offset = offset.unsigned_abs();
+            let (hours, minutes) = (offset / 60, offset % 60);
+            // 一位小数表示不了的偏移（如 +5:45）写成 时:分，解析时两种都接受
+            if minutes % 6 == 0 {
+                write!(f, "{}{}.{} ", sign, hours, minutes / 6)?;
+            } else {
+                write!(f, "{}{}:{:02} ", sign, hours, minutes)?;
+            }
+        }
+        write!(
+            f,
+            "{:02}:{:02}:{:02}.{:03}",
+            self.hour, self.minute, self.second, self.millisecond
+        )
+    }
+}
+
+/// 写日志的源码位置
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub file: String,
+    pub function: String,
+    pub line: u32,
+}
+
+/// 一行解析后的客户端日志
+///
+/// 客户端日志行的格式为
+/// `[I][2024-05-01 +8.0 10:11:12.345][1234, 5678*][tag][file.cpp, Function, 42][消息`，
+/// 线程ID后的 `*` 表示主线程，消息没有右括号，可以包含任何字符。
+/// 不符合这个格式的行不会丢弃：整行作为消息，其余字段为空。
+/// `encoding` 记录原始字节按什么编码解码，UTF-8 时 JSON 中省略。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub level: Option<LogLevel>,
+    pub timestamp: Option<LogTimestamp>,
+    pub process_id: Option<u32>,
+    pub thread_id: Option<u32>,
+    pub main_thread: bool,
+    pub tag: Option<String>,
+    pub location: Option<SourceLocation>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "TextEncoding::is_utf8")]
+    pub encoding: TextEncoding,
+}
+
+impl LogRecord {
+    /// 解析一行日志，不认识的格式整行作为消息
+    pub fn parse(line: &str) -> Self {
+        let line = line.trim_end_matches(['\r', '\n']);
+        Self::parse_structured(line).unwrap_or_else(|_| LogRecord::unstructured(line))
+    }
+
+    /// 解码客户端写入的原始字节并逐行解析，跳过空行
+    ///
+    /// 一次写入的多行内容中，不以级别字段（如 `[I]`）开头的行是上一条记录的后续行
+    /// （比如调用栈），并入上一条记录的消息，用 `\n` 连接；第一行就是后续行时单独作为一条记录。
+    /// 非 UTF-8 的内容按 `text::decode_bytes` 识别编码，识别不了的字节转义保留，不会丢行。
+    pub fn parse_bytes(bytes: &[u8]) -> Vec<Self> {
+        let decoded = decode_bytes(bytes);
+        let mut records: Vec<Self> = Vec::new();
+        for line in decoded.text.lines().filter(|line| !line.is_empty()) {
+            if let Some(previous) = records.last_mut().filter(|_| !starts_record(line)) {
+                previous.message.push('\n');
+                previous.message.push_str(line.trim_end_matches('\r'));
+                continue;
+            }
+            records.push(LogRecord {
+                encoding: decoded.encoding,
+                ..Self::parse(line)
+            });
+        }
+        records
+    }
+
+    /// 按客户端格式解析一行日志，格式不对时返回原因
+    pub fn parse_structured(line: &str) -> Result<Self, String> {
+        let mut rest = line;
+        let mut field = |name: &str| -> Result<&str, String> {
+            let inner = rest
+                .strip_prefix('[')
+                .ok_or_else(|| format!("缺少{}字段", name))?;
+            let end = inner
+                .find(']')
+                .ok_or_else(|| format!("{}字段没有结束", name))?;
+            rest = &inner[end + 1..];
+            Ok(&inner[..end])
+        };
+
+        let level = field("级别")?.parse::<LogLevel>()?;
+        let timestamp = LogTimestamp::parse(field("时间")?)?;
+        let (process_id, thread_id, main_thread) = parse_ids(field("线程")?)?;
+        let tag = field("标签")?.to_string();
+        let location = parse_location(field("位置")?)?;
+        let message = rest
+            .strip_prefix('[')
+            .ok_or_else(|| "缺少消息字段".to_string())?;
+
+        Ok(LogRecord {
+            level: Some(level),
+            timestamp: Some(timestamp),
+            process_id: Some(process_id),
+            thread_id: Some(thread_id),
+            main_thread,
+            tag: (!tag.is_empty()).then_some(tag),
+            location,
+            message: message.to_string(),
+            encoding: TextEncoding::Utf8,
+        })
+    }
+
+    /// 只有消息的记录
+    pub fn unstructured(line: &str) -> Self {
+        LogRecord {
+            level: None,
+            timestamp: None,
+            process_id: None,
+            thread_id: None,
+            main_thread: false,
+            tag: None,
+            location: None,
+            message: line.to_string(),
+            encoding: TextEncoding::Utf8,
+        }
+    }
+
+    /// 是否按客户端格式解析成功
+    pub fn is_structured(&self) -> bool {
+        self.level.is_some()
+    }
+
+    /// 输出为一行 JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for LogRecord {
+    /// 结构化的记录按客户端格式输出，否则只输出消息
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (Some(level), Some(timestamp), Some(process_id), Some(thread_id)) =
+            (self.level, self.timestamp, self.process_id, self.thread_id)
+        else {
+            return write!(f, "{}", self.message);
+        };
+        write!(
+            f,
+            "[{}][{}][{}, {}{}][{}]",
+            level,
+            timestamp,
+            process_id,
+            thread_id,
+            if self.main_thread { "*" } else { "" },
+            self.tag.as_deref().unwrap_or_default()
+        )?;
+        match &self.location {
+            Some(location) => write!(
+                f,
+                "[{}, {}, {}]",
+                location.file, location.function, location.line
+            )?,
+            None => write!(f, "[, , 0]")?,
+        }
+        write!(f, "[{}", self.message)
+    }
+}
+
+// 是否以级别字段开头，是的话是一条新记录的第一行（即使其余字段格式不对）
+fn starts_record(line: &str) -> bool {
+    line.strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .is_some_and(|(level, _)| level.len() <= 7 && level.parse::<LogLevel>().is_ok())
+}
+
+// 解析固定位数的十进制数
+fn parse_fixed(text: Option<&str>, digits: usize) -> Option<u32> {
+    text.filter(|text| text.len() == digits)
+        .and_then(|text| parse_number(Some(text), digits))
+}
+
+// 解析最多 max_digits 位的十进制数
+fn parse_number(text: Option<&str>, max_digits: usize) -> Option<u32> {
+    let text = text?;
+    if text.is_empty() || text.len() > max_digits || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+// 时区写成小时数，例如 +8.0、-3.5；也接受 时:分，例如 +5:45
+fn parse_zone(zone: &str) -> Option<i16> {
+    let (negative, hours) = match zone.as_bytes().first()? {
+        b'+' => (false, &zone[1..]),
+        b'-' => (true, &zone[1..]),
+        _ => return None,
+    };
+    let minutes = match hours.split_once(':') {
+        Some((whole, minutes)) => {
+            let minutes = parse_fixed(Some(minutes), 2).filter(|minutes| *minutes < 60)?;
+            parse_number(Some(whole), 2)? as i16 * 60 + minutes as i16
+        }
+        None => {
+            let (whole, fraction) = hours.split_once('.').unwrap_or((hours, "0"));
+            let whole = parse_number(Some(whole), 2)? as i16;
+            let fraction = parse_number(Some(fraction), 1)? as i16;
+            whole * 60 + fraction * 6
+        }
+    };
+    if minutes > 14 * 60 {
+        return None;
+    }
+    Some(if negative { -minutes } else { minutes })
+}
+
+// 解析 `1234, 5678*`
+fn parse_ids(text: &str) -> Result<(u32, u32, bool), String> {
+    let invalid = || format!("线程字段格式不正确: {}", text);
+    let (process_id, thread_id) = text.split_once(',').ok_or_else(invalid)?;
+    let thread_id = thread_id.trim();
+    let (thread_id, main_thread) = match thread_id.strip_suffix('*') {
+        Some(thread_id) => (thread_id, true),
+        None => (thread_id, false),
+    };
+    let process_id = process_id.trim().parse().map_err(|_| invalid())?;
+    let thread_id = thread_id.parse().map_err(|_| invalid())?;
+    Ok((process_id, thread_id, main_thread))
+}
+
+// 解析 `file.cpp, Function, 42`，全部为空（`, , 0`）时返回 None
+fn parse_location(text: &str) -> Result<Option<SourceLocation>, String> {
+    let invalid = || format!("位置字段格式不正确: {}", text);
+    // 函数名可能带逗号（模板参数），文件名取第一段，行号取最后一段
+    let (file, rest) = text.split_once(',').ok_or_else(invalid)?;
+    let (function, line) = rest.rsplit_once(',').ok_or_else(invalid)?;
+    let file = file.trim();
+    let function = function.trim();
+    let line = line.trim().parse::<u32>().map_err(|_| invalid())?;
+    if file.is_empty() && function.is_empty() && line == 0 {
+        return Ok(None);
+    }
+    Ok(Some(SourceLocation {
+        file: file.to_string(),
+        function: function.to_string(),
+        line,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::TextEncoding;
+
+    // corpus 中的样例日志是按客户端的格式构造的，不是抓取的真实日志
+    fn corpus(name: &str) -> Vec<u8> {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("corpus")
+            .join(name);
+        std::fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn parses_structured_line() {
+        let record = LogRecord::parse(
+            "[I][2024-05-01 +8.0 10:11:12.345][7824, 7828*][login][LoginWindow.cpp, LoginWindow::OnQrcodeRefresh, 218][刷新登录二维码\r\n",
+        );
+        assert_eq!(record.level, Some(LogLevel::Info));
+        assert_eq!(
+            record.timestamp,
+            Some(LogTimestamp {
+                year: 2024,
+                month: 5,
+                day: 1,
+                hour: 10,
+                minute: 11,
+                second: 12,
+                millisecond: 345,
+                utc_offset_minutes: Some(480),
+            })
+        );
+        assert_eq!(record.process_id, Some(7824));
+        assert_eq!(record.thread_id, Some(7828));
+        assert!(record.main_thread);
+        assert_eq!(record.tag.as_deref(), Some("login"));
+        assert_eq!(
+            record.location,
+            Some(SourceLocation {
+                file: "LoginWindow.cpp".to_string(),
+                function: "LoginWindow::OnQrcodeRefresh".to_string(),
+                line: 218,
+            })
+        );
+        assert_eq!(record.message, "刷新登录二维码");
+    }
+
+    #[test]
+    fn parses_timestamp_forms() {
+        let west = LogTimestamp::parse("2024-05-01 -3.5 23:59:59.999").unwrap();
+        assert_eq!(west.utc_offset_minutes, Some(-210));
+        assert_eq!(west.to_string(), "2024-05-01 -3.5 23:59:59.999");
+        let nepal = LogTimestamp::parse("2024-05-01 +5:45 10:11:12.000").unwrap();
+        assert_eq!(nepal.utc_offset_minutes, Some(345));
+        assert_eq!(nepal.to_string(), "2024-05-01 +5:45 10:11:12.000");
+        let local = LogTimestamp::parse("2024-05-01 10:11:16").unwrap();
+        assert_eq!(local.utc_offset_minutes, None);
+        assert_eq!(local.millisecond, 0);
+        for invalid in [
+            "2024-05-01 +5:60 10:11:12",
+            "2024-05-01 +5:5 10:11:12",
+            "2024-05-01 +8.0 10:11:1",
+            "2024-13-01 10:11:12.000",
+            "2024-05-01 +15.0 10:11:12.000",
+            "24-05-01 10:11:12.000",
+        ] {
+            assert!(LogTimestamp::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn every_zone_round_trips() {
+        let mut timestamp = LogTimestamp::parse("2024-05-01 +0.0 10:11:12.345").unwrap();
+        for offset in -14 * 60..=14 * 60 {
+            timestamp.utc_offset_minutes = Some(offset);
+            let text = timestamp.to_string();
+            assert_eq!(LogTimestamp::parse(&text), Ok(timestamp), "{}", text);
+        }
+    }
+
+    #[test]
+    fn keeps_malformed_lines_as_messages() {
+        let line = "[I][2024-05-01 +8.0 10:11:1][7824, 7828*][bad][a.cpp, f, 1][时间不完整";
+        let record = LogRecord::parse(line);
+        assert!(!record.is_structured());
+        assert_eq!(record.message, line);
+        assert_eq!(record.to_string(), line);
+        assert!(LogRecord::parse_structured(line).is_err());
+    }
+
+    #[test]
+    fn attaches_continuation_lines() {
+        let bytes = b"[F][2024-05-01 10:11:16.000][7824, 7828*][crash][CrashReport.cpp, CrashReport::Dump, 64][unhandled exception\r\n  at WxWork.exe+0x33D158\r\n[x] not a level\r\n\r\n[I][2024-05-01 10:11:17.000][7824, 7828*][][, , 0][next";
+        let records = LogRecord::parse_bytes(bytes);
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].message,
+            "unhandled exception\n  at WxWork.exe+0x33D158\n[x] not a level"
+        );
+        assert_eq!(records[0].level, Some(LogLevel::Fatal));
+        assert_eq!(records[1].message, "next");
+        assert_eq!(records[1].location, None);
+    }
+
+    #[test]
+    fn leading_continuation_is_its_own_record() {
+        let records = LogRecord::parse_bytes(
+            b"  orphan\n[W][2024-05-01 10:11:17.000][1, 2][net][a.cpp, f, 1][slow",
+        );
+        assert_eq!(records.len(), 2);
+        assert!(!records[0].is_structured());
+        assert_eq!(records[0].message, "  orphan");
+        assert_eq!(records[1].level, Some(LogLevel::Warn));
+    }
+
+    #[test]
+    fn parses_sample_corpus() {
+        let bytes = corpus("write_log_sample.log");
+        let records = LogRecord::parse_bytes(&bytes);
+        let structured = records
+            .iter()
+            .filter(|record| record.is_structured())
+            .count();
+        assert_eq!((records.len(), structured), (8, 7));
+
+        // 消息中的方括号不影响解析，空标签和空位置为 None
+        assert_eq!(records[2].message, "response slow: cost=[598ms], retry=0");
+        assert_eq!(records[3].tag, None);
+        assert_eq!(records[3].location, None);
+        assert_eq!(records[3].message, "");
+        let location = records[4].location.as_ref().unwrap();
+        assert_eq!(location.function, "Holder<int, bool>::Load");
+        assert_eq!(records[6].timestamp.unwrap().utc_offset_minutes, None);
+        assert_eq!(
+            records[6].message,
+            "unhandled exception 0xC0000005\n  at WxWork.exe+0x33D158"
+        );
+        assert!(!records[7].is_structured());
+
+        // 按客户端格式输出后与原文一致
+        let text = String::from_utf8(bytes).unwrap();
+        let rendered: Vec<String> = records.iter().map(LogRecord::to_string).collect();
+        assert_eq!(rendered.join("\n"), text.trim_end().replace("\r\n", "\n"));
+    }
+
+    #[test]
+    fn parses_mixed_corpus_line_by_line() {
+        // 混合编码的样例每行编码不同，按 parse_log 中的方式逐行解码
+        let bytes = corpus("write_log_mixed.log");
+        let records: Vec<LogRecord> = bytes
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .flat_map(LogRecord::parse_bytes)
+            .collect();
+        assert_eq!(records.len(), 5);
+        assert!(records.iter().all(LogRecord::is_structured));
+        assert_eq!(records[1].encoding, TextEncoding::Gbk);
+        assert_eq!(records[2].message, "utf-16 without bom");
+        assert!(
+            matches!(records[3].encoding, TextEncoding::Utf16Le),
+            "{:?}",
+            records[3].encoding
+        );
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let records = LogRecord::parse_bytes(&corpus("write_log_sample.log"));
+        for record in records {
+            let json = record.to_json();
+            assert!(!json.contains("encoding"), "{}", json);
+            let back: LogRecord = serde_json::from_str(&json).unwrap();
+            assert_eq!(back, record);
+        }
+    }
+}
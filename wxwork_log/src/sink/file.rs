@@ -0,0 +1,318 @@
+use super::LogSink;
+use crate::record::LogRecord;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 按大小和日期轮转的 JSONL 文件
+///
+/// 文件名为 `<前缀>_<日期>_<序号>.jsonl`，日期取记录中的时间（客户端本地时间），
+/// 没有时间的记录沿用上一条的日期。超过大小上限时序号加一；
+/// 重新打开时追加到当天最后一个未写满的文件。
+pub struct RotatingFileSink {
+    name: String,
+    dir: PathBuf,
+    prefix: String,
+    max_bytes: u64,
+    daily: bool,
+    day: Option<u32>, // 当前文件的日期，YYYYMMDD
+    index: u32,       // 当天的文件序号
+    written: u64,     // 当前文件已有的字节数
+    file: Option<BufWriter<File>>,
+}
+
+impl RotatingFileSink {
+    pub fn new(dir: &Path, prefix: &str, max_bytes: u64, daily: bool) -> Result<Self, String> {
+        if max_bytes == 0 {
+            return Err("日志文件大小上限不能为0".to_string());
+        }
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("创建日志目录 {} 失败: {}", dir.display(), e))?;
+        Ok(RotatingFileSink {
+            name: format!("jsonl:{}", dir.display()),
+            dir: dir.to_path_buf(),
+            prefix: prefix.to_string(),
+            max_bytes,
+            daily,
+            day: None,
+            index: 0,
+            written: 0,
+            file: None,
+        })
+    }
+
+    /// 当前写入的文件
+    pub fn current_path(&self) -> Option<PathBuf> {
+        self.file
+            .as_ref()
+            .map(|_| self.path_for(self.day, self.index))
+    }
+
+    fn path_for(&self, day: Option<u32>, index: u32) -> PathBuf {
+        match day.filter(|_| self.daily) {
+            Some(day) => self
+                .dir
+                .join(format!("{}_{}_{}.jsonl", self.prefix, day, index)),
+            None => self.dir.join(format!("{}_{}.jsonl", self.prefix, index)),
+        }
+    }
+
+    // 打开指定日期第一个未写满的文件
+    fn open(&mut self, day: u32) -> Result<(), String> {
+        self.close()?;
+        let mut index = 0;
+        loop {
+            let size = std::fs::metadata(self.path_for(Some(day), index))
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+            if size < self.max_bytes {
+                self.written = size;
+                break;
+            }
+            index += 1;
+        }
+        self.day = Some(day);
+        self.index = index;
+        self.open_current()
+    }
+
+    // 写满后换下一个序号
+    fn rotate(&mut self) -> Result<(), String> {
+        self.close()?;
+        self.index += 1;
+        self.written = 0;
+        self.open_current()
+    }
+
+    fn open_current(&mut self) -> Result<(), String> {
+        let path = self.path_for(self.day, self.index);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("打开日志文件 {} 失败: {}", path.display(), e))?;
+        self.file = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        match self.file.take() {
+            Some(mut file) => file.flush().map_err(|e| format!("写日志文件失败: {}", e)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl LogSink for RotatingFileSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn write(&mut self, record: &LogRecord) -> Result<(), String> {
+        let day = record
+            .timestamp
+            .map(|t| t.year as u32 * 10000 + t.month as u32 * 100 + t.day as u32)
+            .or(self.day)
+            .unwrap_or_else(today_utc);
+        if self.file.is_none() || (self.daily && self.day != Some(day)) {
+            self.open(day)?;
+        }
+
+        let mut line = record.to_json();
+        line.push('\n');
+        if self.written > 0 && self.written + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let file = self.file.as_mut().ok_or("日志文件未打开")?;
+        file.write_all(line.as_bytes())
+            .map_err(|e| format!("写日志文件失败: {}", e))?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        match self.file.as_mut() {
+            Some(file) => file.flush().map_err(|e| format!("写日志文件失败: {}", e)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for RotatingFileSink {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+// 当前 UTC 日期，YYYYMMDD
+fn today_utc() -> u32 {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86400)
+        .unwrap_or(0) as i64;
+
+    // 从 1970-01-01 起的天数换算公历日期
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year * 10000 + month * 100 + day) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // 目录中的文件名和行数，按文件名排序
+    fn files(dir: &Path) -> Vec<(String, usize)> {
+        let mut files: Vec<(String, usize)> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+                (
+                    path.file_name().unwrap().to_string_lossy().into_owned(),
+                    lines,
+                )
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn record(day: u8, message: &str) -> LogRecord {
+        LogRecord::parse(&format!(
+            "[I][2024-05-{:02} +8.0 10:11:12.345][7824, 7828*][ui][a.cpp, f, 1][{}",
+            day, message
+        ))
+    }
+
+    fn line_len(record: &LogRecord) -> u64 {
+        record.to_json().len() as u64 + 1
+    }
+
+    #[test]
+    fn rotates_by_size() {
+        let temp = TempDir::new().unwrap();
+        // 目录不存在时由输出创建
+        let dir = temp.path().join("logs");
+        let first = record(1, "0");
+        // 每个文件正好放两条
+        let mut sink =
+            RotatingFileSink::new(&dir, "client_log", line_len(&first) * 2, true).unwrap();
+        for index in 0..5 {
+            sink.write(&record(1, &index.to_string())).unwrap();
+        }
+        assert_eq!(
+            sink.current_path(),
+            Some(dir.join("client_log_20240501_2.jsonl"))
+        );
+        sink.flush().unwrap();
+        assert_eq!(
+            files(&dir),
+            [
+                ("client_log_20240501_0.jsonl".to_string(), 2),
+                ("client_log_20240501_1.jsonl".to_string(), 2),
+                ("client_log_20240501_2.jsonl".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn oversized_record_still_written() {
+        let dir = TempDir::new().unwrap();
+        let mut sink = RotatingFileSink::new(dir.path(), "client_log", 8, true).unwrap();
+        sink.write(&record(1, "longer than the limit")).unwrap();
+        sink.write(&record(1, "again")).unwrap();
+        drop(sink);
+        assert_eq!(
+            files(dir.path()),
+            [
+                ("client_log_20240501_0.jsonl".to_string(), 1),
+                ("client_log_20240501_1.jsonl".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_by_record_date() {
+        let dir = TempDir::new().unwrap();
+        let mut sink = RotatingFileSink::new(dir.path(), "client_log", 1 << 20, true).unwrap();
+        sink.write(&record(1, "a")).unwrap();
+        // 没有时间的记录沿用上一条的日期
+        sink.write(&LogRecord::parse("continuation without header"))
+            .unwrap();
+        sink.write(&record(2, "b")).unwrap();
+        drop(sink);
+        assert_eq!(
+            files(dir.path()),
+            [
+                ("client_log_20240501_0.jsonl".to_string(), 2),
+                ("client_log_20240502_0.jsonl".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn without_daily_uses_one_sequence() {
+        let dir = TempDir::new().unwrap();
+        let mut sink = RotatingFileSink::new(dir.path(), "app", 1 << 20, false).unwrap();
+        sink.write(&record(1, "a")).unwrap();
+        sink.write(&record(2, "b")).unwrap();
+        drop(sink);
+        assert_eq!(files(dir.path()), [("app_0.jsonl".to_string(), 2)]);
+    }
+
+    #[test]
+    fn reopen_appends_to_last_file() {
+        let dir = TempDir::new().unwrap();
+        let max_bytes = line_len(&record(1, "0")) * 2;
+        let mut sink = RotatingFileSink::new(dir.path(), "client_log", max_bytes, true).unwrap();
+        for index in 0..3 {
+            sink.write(&record(1, &index.to_string())).unwrap();
+        }
+        drop(sink);
+
+        // 第一个文件已满，追加到第二个文件
+        let mut sink = RotatingFileSink::new(dir.path(), "client_log", max_bytes, true).unwrap();
+        sink.write(&record(1, "3")).unwrap();
+        sink.write(&record(1, "4")).unwrap();
+        drop(sink);
+        assert_eq!(
+            files(dir.path()),
+            [
+                ("client_log_20240501_0.jsonl".to_string(), 2),
+                ("client_log_20240501_1.jsonl".to_string(), 2),
+                ("client_log_20240501_2.jsonl".to_string(), 1),
+            ]
+        );
+        let text = std::fs::read_to_string(dir.path().join("client_log_20240501_1.jsonl")).unwrap();
+        let records: Vec<LogRecord> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records, [record(1, "2"), record(1, "3")]);
+    }
+
+    #[test]
+    fn rejects_zero_limit() {
+        let dir = TempDir::new().unwrap();
+        assert!(RotatingFileSink::new(dir.path(), "client_log", 0, true).is_err());
+    }
+
+    #[test]
+    fn today_is_a_valid_date() {
+        let today = today_utc();
+        assert!(today > 20240000, "{}", today);
+        assert!((1..=12).contains(&(today / 100 % 100)));
+        assert!((1..=31).contains(&(today % 100)));
+    }
+}
@@ -0,0 +1,89 @@
+// 日志输出：轮转的 JSONL 文件、内存环形缓冲、本机 TCP 推送，以及把它们串起来的后台写线程
+pub mod file;
+pub mod pipeline;
+pub mod ring;
+pub mod tcp;
+
+use crate::record::LogRecord;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+pub use file::RotatingFileSink;
+pub use pipeline::{LogPipeline, PipelineStats};
+pub use ring::{RingBuffer, RingQuery};
+pub use tcp::TcpSink;
+
+/// 日志输出目标，在后台写线程上调用
+pub trait LogSink: Send {
+    /// 名称，用于错误信息
+    fn name(&self) -> &str;
+
+    /// 写入一条记录
+    fn write(&mut self, record: &LogRecord) -> Result<(), String>;
+
+    /// 把缓冲的内容写出，队列空闲时调用
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// 一个输出目标的配置
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SinkConfig {
+    /// 按大小和日期轮转的 JSONL 文件
+    Jsonl {
+        dir: PathBuf,
+        #[serde(default = "default_prefix")]
+        prefix: String, // 文件名前缀
+        #[serde(default = "default_max_bytes")]
+        max_bytes: u64, // 单个文件的最大字节数
+        #[serde(default = "default_daily")]
+        daily: bool, // 按日期分文件
+    },
+    /// 内存环形缓冲，可以在控制台查询
+    Ring {
+        #[serde(default = "default_ring_capacity")]
+        capacity: usize,
+    },
+    /// 监听本机端口，把记录按 JSONL 推送给所有连接的客户端
+    Tcp { port: u16 },
+}
+
+fn default_prefix() -> String {
+    "client_log".to_string()
+}
+
+fn default_max_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_daily() -> bool {
+    true
+}
+
+fn default_ring_capacity() -> usize {
+    10000
+}
+
+impl SinkConfig {
+    /// 创建输出目标，环形缓冲返回可供查询的句柄
+    pub fn build(&self) -> Result<(Box<dyn LogSink>, Option<RingBuffer>), String> {
+        match self {
+            SinkConfig::Jsonl {
+                dir,
+                prefix,
+                max_bytes,
+                daily,
+            } => {
+                let sink = RotatingFileSink::new(dir, prefix, *max_bytes, *daily)?;
+                Ok((Box::new(sink), None))
+            }
+            SinkConfig::Ring { capacity } => {
+                let ring = RingBuffer::new(*capacity);
+                Ok((Box::new(ring.clone()), Some(ring)))
+            }
+            SinkConfig::Tcp { port } => Ok((Box::new(TcpSink::bind(*port)?), None)),
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use super::LogSink;
+use crate::record::LogRecord;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// 队列空闲多久后刷新缓冲
+const FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 队列计数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    pub submitted: u64,    // 进入队列的条数
+    pub dropped: u64,      // 队列满时丢弃的条数
+    pub write_errors: u64, // 输出目标写失败的次数
+}
+
+#[derive(Default)]
+struct Counters {
+    submitted: AtomicU64,
+    dropped: AtomicU64,
+    write_errors: AtomicU64,
+}
+
+/// 把记录交给后台写线程依次写入所有输出目标
+///
+/// `submit` 只把记录放进有界队列，不做任何 I/O，也不会等待：队列满时丢弃并计数，
+/// 保证被 hook 的线程不会因为磁盘或网络变慢而卡住。
+/// 输出目标写失败时调用 `on_error`，同一个目标连续失败只报告第一次。
+pub struct LogPipeline {
+    sender: Option<SyncSender<LogRecord>>,
+    counters: Arc<Counters>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl LogPipeline {
+    pub fn start(
+        sinks: Vec<Box<dyn LogSink>>,
+        capacity: usize,
+        on_error: impl Fn(String) + Send + 'static,
+    ) -> Result<Self, String> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(capacity.max(1));
+        let counters = Arc::new(Counters::default());
+        let worker_counters = counters.clone();
+        let worker = std::thread::Builder::new()
+            .name("log_pipeline".to_string())
+            .spawn(move || run_worker(receiver, sinks, &worker_counters, on_error))
+            .map_err(|e| format!("启动日志写线程失败: {}", e))?;
+        Ok(LogPipeline {
+            sender: Some(sender),
+            counters,
+            worker: Some(worker),
+        })
+    }
+
+    /// 提交一条记录，队列满或写线程已退出时丢弃并返回 false
+    pub fn submit(&self, record: LogRecord) -> bool {
+        let Some(sender) = &self.sender else {
+            return false;
+        };
+        match sender.try_send(record) {
+            Ok(()) => {
+                self.counters.submitted.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// 当前计数
+    pub fn stats(&self) -> PipelineStats {
+        PipelineStats {
+            submitted: self.counters.submitted.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            write_errors: self.counters.write_errors.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 写完队列中剩余的记录后结束写线程
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    /// 关闭队列但不等待写线程，写线程写完剩余的记录后自行退出；用于不能等待的地方（如 DllMain）
+    pub fn close(mut self) {
+        self.sender = None;
+        // 丢弃 JoinHandle 不会等待线程
+        self.worker = None;
+    }
+
+    fn stop(&mut self) {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for LogPipeline {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run_worker(
+    receiver: Receiver<LogRecord>,
+    mut sinks: Vec<Box<dyn LogSink>>,
+    counters: &Counters,
+    on_error: impl Fn(String),
+) {
+    // 每个目标是否处于失败状态，恢复后再失败会重新报告
+    let mut failing = vec![false; sinks.len()];
+    let mut report = |index: usize, sink: &dyn LogSink, result: Result<(), String>| match result {
+        Ok(()) => failing[index] = false,
+        Err(e) => {
+            counters.write_errors.fetch_add(1, Ordering::Relaxed);
+            if !failing[index] {
+                failing[index] = true;
+                on_error(format!("{}: {}", sink.name(), e));
+            }
+        }
+    };
+
+    loop {
+        match receiver.recv_timeout(FLUSH_INTERVAL) {
+            Ok(record) => {
+                for (index, sink) in sinks.iter_mut().enumerate() {
+                    let result = sink.write(&record);
+                    report(index, sink.as_ref(), result);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                for (index, sink) in sinks.iter_mut().enumerate() {
+                    let result = sink.flush();
+                    report(index, sink.as_ref(), result);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    for (index, sink) in sinks.iter_mut().enumerate() {
+        let result = sink.flush();
+        report(index, sink.as_ref(), result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    // 把写入的消息放进共享的列表，`fail` 为 true 时写入失败
+    struct MemorySink {
+        messages: Arc<Mutex<Vec<String>>>,
+        fail: bool,
+    }
+
+    impl LogSink for MemorySink {
+        fn name(&self) -> &str {
+            "memory"
+        }
+
+        fn write(&mut self, record: &LogRecord) -> Result<(), String> {
+            if self.fail {
+                return Err("disk full".to_string());
+            }
+            self.messages.lock().unwrap().push(record.message.clone());
+            Ok(())
+        }
+    }
+
+    fn start(fail: bool, capacity: usize) -> (LogPipeline, Arc<Mutex<Vec<String>>>) {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let sink = MemorySink {
+            messages: messages.clone(),
+            fail,
+        };
+        let pipeline = LogPipeline::start(vec![Box::new(sink)], capacity, |_| {}).unwrap();
+        (pipeline, messages)
+    }
+
+    #[test]
+    fn shutdown_writes_queued_records() {
+        let (pipeline, messages) = start(false, 64);
+        for index in 0..10 {
+            assert!(pipeline.submit(LogRecord::parse(&index.to_string())));
+        }
+        assert_eq!(pipeline.stats().submitted, 10);
+        pipeline.shutdown();
+        assert_eq!(messages.lock().unwrap().len(), 10);
+    }
+
+    #[test]
+    fn close_does_not_wait() {
+        let (pipeline, messages) = start(false, 64);
+        pipeline.submit(LogRecord::parse("a"));
+        pipeline.close();
+        // 写线程关闭后自行写完剩余的记录
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while messages.lock().unwrap().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*messages.lock().unwrap(), ["a"]);
+    }
+
+    #[test]
+    fn reports_first_failure_only() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let reported = errors.clone();
+        let sink = MemorySink {
+            messages: Arc::new(Mutex::new(Vec::new())),
+            fail: true,
+        };
+        let pipeline = LogPipeline::start(vec![Box::new(sink)], 64, move |e| {
+            reported.lock().unwrap().push(e)
+        })
+        .unwrap();
+        for _ in 0..3 {
+            pipeline.submit(LogRecord::parse("a"));
+        }
+        let stats_before = pipeline.stats();
+        pipeline.shutdown();
+        assert_eq!(stats_before.submitted, 3);
+        assert_eq!(*errors.lock().unwrap(), ["memory: disk full"]);
+    }
+}
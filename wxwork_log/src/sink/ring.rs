@@ -0,0 +1,230 @@
+use super::LogSink;
+use crate::record::{LogLevel, LogRecord};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// 查询条件，都不指定时返回最近的记录
+#[derive(Debug, Clone, Default)]
+pub struct RingQuery {
+    pub min_level: Option<LogLevel>, // 最低级别，没有级别的记录不匹配
+    pub tag: Option<String>,         // 标签完全一致
+    pub text: Option<String>,        // 消息中包含的文本
+    pub limit: Option<usize>,        // 最多返回的条数，取最近的
+}
+
+impl RingQuery {
+    fn matches(&self, record: &LogRecord) -> bool {
+        let below_level = self
+            .min_level
+            .is_some_and(|min_level| record.level.is_none_or(|level| level < min_level));
+        let other_tag = self
+            .tag
+            .as_ref()
+            .is_some_and(|tag| record.tag.as_ref() != Some(tag));
+        if below_level || other_tag {
+            return false;
+        }
+        match &self.text {
+            Some(text) => record.message.contains(text.as_str()),
+            None => true,
+        }
+    }
+}
+
+struct RingInner {
+    records: VecDeque<LogRecord>,
+    capacity: usize,
+    total: u64, // 写入过的总条数
+}
+
+/// 内存中最近的日志记录，满了丢弃最早的
+///
+/// 克隆得到的是同一个缓冲的句柄：一个交给后台写线程写入，其余用于查询。
+#[derive(Clone)]
+pub struct RingBuffer {
+    inner: Arc<Mutex<RingInner>>,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RingBuffer {
+            inner: Arc::new(Mutex::new(RingInner {
+                records: VecDeque::with_capacity(capacity.min(1024)),
+                capacity,
+                total: 0,
+            })),
+        }
+    }
+
+    /// 写入一条记录
+    pub fn push(&self, record: LogRecord) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        if inner.capacity == 0 {
+            return;
+        }
+        if inner.records.len() == inner.capacity {
+            inner.records.pop_front();
+        }
+        inner.records.push_back(record);
+        inner.total += 1;
+    }
+
+    /// 按条件查询，结果按时间先后排列
+    pub fn query(&self, query: &RingQuery) -> Vec<LogRecord> {
+        let Ok(inner) = self.inner.lock() else {
+            return Vec::new();
+        };
+        let limit = query.limit.unwrap_or(usize::MAX);
+        let mut found: Vec<LogRecord> = inner
+            .records
+            .iter()
+            .rev()
+            .filter(|record| query.matches(record))
+            .take(limit)
+            .cloned()
+            .collect();
+        found.reverse();
+        found
+    }
+
+    /// 缓冲中的条数
+    pub fn len(&self) -> usize {
+        self.inner.lock().map_or(0, |inner| inner.records.len())
+    }
+
+    /// 缓冲是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 写入过的总条数，包括已被丢弃的
+    pub fn total(&self) -> u64 {
+        self.inner.lock().map_or(0, |inner| inner.total)
+    }
+}
+
+impl LogSink for RingBuffer {
+    fn name(&self) -> &str {
+        "ring"
+    }
+
+    fn write(&mut self, record: &LogRecord) -> Result<(), String> {
+        self.push(record.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(level: &str, tag: &str, message: &str) -> LogRecord {
+        LogRecord::parse(&format!(
+            "[{}][2024-05-01 +8.0 10:11:12.345][7824, 7828*][{}][a.cpp, f, 1][{}",
+            level, tag, message
+        ))
+    }
+
+    fn messages(records: &[LogRecord]) -> Vec<&str> {
+        records
+            .iter()
+            .map(|record| record.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn drops_oldest_when_full() {
+        let ring = RingBuffer::new(3);
+        for index in 0..5 {
+            ring.push(record("I", "ui", &index.to_string()));
+        }
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.total(), 5);
+        let all = ring.query(&RingQuery::default());
+        assert_eq!(messages(&all), ["2", "3", "4"]);
+    }
+
+    #[test]
+    fn zero_capacity_keeps_nothing() {
+        let ring = RingBuffer::new(0);
+        ring.push(record("I", "ui", "a"));
+        assert!(ring.is_empty());
+        assert_eq!(ring.total(), 0);
+    }
+
+    #[test]
+    fn filters_by_level_tag_and_text() {
+        let ring = RingBuffer::new(16);
+        ring.push(record("D", "net", "request begin"));
+        ring.push(record("W", "net", "response slow"));
+        ring.push(record("E", "ui", "load failed"));
+        ring.push(LogRecord::parse("没有级别的一行 slow"));
+        ring.push(record("F", "net", "crash"));
+
+        let query = RingQuery {
+            min_level: Some(LogLevel::Warn),
+            ..RingQuery::default()
+        };
+        assert_eq!(
+            messages(&ring.query(&query)),
+            ["response slow", "load failed", "crash"]
+        );
+
+        let query = RingQuery {
+            tag: Some("net".to_string()),
+            ..RingQuery::default()
+        };
+        assert_eq!(
+            messages(&ring.query(&query)),
+            ["request begin", "response slow", "crash"]
+        );
+
+        let query = RingQuery {
+            text: Some("slow".to_string()),
+            ..RingQuery::default()
+        };
+        assert_eq!(
+            messages(&ring.query(&query)),
+            ["response slow", "没有级别的一行 slow"]
+        );
+
+        let query = RingQuery {
+            min_level: Some(LogLevel::Warn),
+            tag: Some("net".to_string()),
+            text: Some("slow".to_string()),
+            limit: None,
+        };
+        assert_eq!(messages(&ring.query(&query)), ["response slow"]);
+    }
+
+    #[test]
+    fn limit_keeps_most_recent_in_order() {
+        let ring = RingBuffer::new(16);
+        for index in 0..6 {
+            let level = if index % 2 == 0 { "I" } else { "E" };
+            ring.push(record(level, "ui", &index.to_string()));
+        }
+        let query = RingQuery {
+            min_level: Some(LogLevel::Error),
+            limit: Some(2),
+            ..RingQuery::default()
+        };
+        assert_eq!(messages(&ring.query(&query)), ["3", "5"]);
+        let query = RingQuery {
+            limit: Some(0),
+            ..RingQuery::default()
+        };
+        assert!(ring.query(&query).is_empty());
+    }
+
+    #[test]
+    fn clones_share_the_buffer() {
+        let ring = RingBuffer::new(4);
+        let mut sink = ring.clone();
+        sink.write(&record("I", "ui", "a")).unwrap();
+        assert_eq!(ring.len(), 1);
+        assert_eq!(sink.name(), "ring");
+    }
+}
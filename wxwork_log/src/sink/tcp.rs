@@ -0,0 +1,137 @@
+use super::LogSink;
+use crate::record::LogRecord;
+use std::io::{ErrorKind, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::time::Duration;
+
+// 单个客户端的写超时，读得慢的客户端会被断开，不拖住其他输出
+const WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// 监听本机端口，把每条记录按 JSONL 推送给所有连接的客户端
+///
+/// 只绑定 127.0.0.1。连接后只收到之后的记录；写失败的客户端直接断开。
+/// 用 `nc 127.0.0.1 <端口>` 即可查看。
+pub struct TcpSink {
+    name: String,
+    listener: TcpListener,
+    clients: Vec<TcpStream>,
+}
+
+impl TcpSink {
+    pub fn bind(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .map_err(|e| format!("监听 127.0.0.1:{} 失败: {}", port, e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("设置监听端口失败: {}", e))?;
+        let port = listener.local_addr().map_or(port, |addr| addr.port());
+        Ok(TcpSink {
+            name: format!("tcp:{}", port),
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// 实际监听的端口，配置为0时由系统分配
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |addr| addr.port())
+    }
+
+    // 接收新连接
+    fn accept(&mut self) -> Result<(), String> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    // 监听端口是非阻塞的，连接需要改回阻塞并设置超时
+                    if stream.set_nonblocking(false).is_ok()
+                        && stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok()
+                    {
+                        self.clients.push(stream);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(format!("接收连接失败: {}", e)),
+            }
+        }
+    }
+}
+
+impl LogSink for TcpSink {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn write(&mut self, record: &LogRecord) -> Result<(), String> {
+        self.accept()?;
+        if self.clients.is_empty() {
+            return Ok(());
+        }
+        let mut line = record.to_json();
+        line.push('\n');
+        self.clients
+            .retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    fn record(message: &str) -> LogRecord {
+        LogRecord::parse(&format!(
+            "[I][2024-05-01 +8.0 10:11:12.345][7824, 7828*][ui][a.cpp, f, 1][{}",
+            message
+        ))
+    }
+
+    fn connect(sink: &TcpSink) -> BufReader<TcpStream> {
+        let stream = TcpStream::connect((Ipv4Addr::LOCALHOST, sink.port())).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        BufReader::new(stream)
+    }
+
+    // 读一行并解析为记录
+    fn receive(client: &mut BufReader<TcpStream>) -> LogRecord {
+        let mut line = String::new();
+        client.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn writes_without_clients() {
+        let mut sink = TcpSink::bind(0).unwrap();
+        assert_ne!(sink.port(), 0);
+        assert_eq!(sink.name(), format!("tcp:{}", sink.port()));
+        sink.write(&record("nobody")).unwrap();
+        assert!(sink.clients.is_empty());
+    }
+
+    #[test]
+    fn reconnects_after_peer_closes() {
+        let mut sink = TcpSink::bind(0).unwrap();
+        let mut first = connect(&sink);
+        sink.write(&record("a")).unwrap();
+        assert_eq!(receive(&mut first), record("a"));
+
+        // 对端关闭后，写失败的连接被断开，不影响后续写入
+        drop(first);
+        for index in 0..100 {
+            sink.write(&record(&index.to_string())).unwrap();
+            if sink.clients.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(sink.clients.is_empty());
+
+        // 重新连接后只收到之后的记录
+        let mut second = connect(&sink);
+        sink.write(&record("b")).unwrap();
+        assert_eq!(receive(&mut second), record("b"));
+        assert_eq!(sink.clients.len(), 1);
+    }
+}
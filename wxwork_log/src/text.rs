@@ -0,0 +1,336 @@
+use encoding_rs::GBK;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 解码客户端字符串时实际使用的编码
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TextEncoding {
+    #[default]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "gbk")]
+    Gbk, // GBK/ANSI（简体中文系统的代码页 936）
+    #[serde(rename = "utf-16le")]
+    Utf16Le, // UTF-16，包括放在窄字符串里的 UTF-16
+    #[serde(rename = "escaped")]
+    Escaped, // 无法识别，可打印的部分原样保留，其余转义
+}
+
+impl TextEncoding {
+    pub fn is_utf8(&self) -> bool {
+        *self == TextEncoding::Utf8
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Gbk => "gbk",
+            TextEncoding::Utf16Le => "utf-16le",
+            TextEncoding::Escaped => "escaped",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 解码结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedText {
+    pub text: String,
+    pub encoding: TextEncoding,
+}
+
+/// 解码窄字符串，任何输入都能得到文本
+///
+/// 依次尝试 UTF-8、窄字符串里的 UTF-16（带 BOM，或者大部分高字节为0）、GBK，
+/// 解码出的文本除制表和换行外不能有控制字符；GBK 中有几个码位解码后编码回去不一样
+/// （如 `A2 E3` 和 `80` 都解码为 €），编码回去与原文不一致时不按 GBK 解码。
+/// 都不是时按 `Escaped` 输出：合法的 UTF-8 片段原样保留，其余字节写成 `\xNN`，
+/// 反斜杠写成 `\\`，转义后的文本可以还原出原始字节，不会丢失内容。
+pub fn decode_bytes(bytes: &[u8]) -> DecodedText {
+    // 纯 ASCII 的 UTF-16 也是合法的 UTF-8（每隔一个字节是0），要排除控制字符
+    if let Some(text) = std::str::from_utf8(bytes)
+        .ok()
+        .filter(|text| is_plain_text(text))
+    {
+        return DecodedText {
+            text: text.to_string(),
+            encoding: TextEncoding::Utf8,
+        };
+    }
+    if let Some(text) = decode_utf16_in_bytes(bytes) {
+        return DecodedText {
+            text,
+            encoding: TextEncoding::Utf16Le,
+        };
+    }
+    if let Some(text) = GBK
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .filter(|text| is_plain_text(text) && GBK.encode(text).0 == bytes)
+    {
+        return DecodedText {
+            text: text.into_owned(),
+            encoding: TextEncoding::Gbk,
+        };
+    }
+    DecodedText {
+        text: escape_bytes(bytes),
+        encoding: TextEncoding::Escaped,
+    }
+}
+
+/// 解码宽字符串，只有不成对的代理项写成 `\u{XXXX}`，其余字符（包括反斜杠）原样保留
+///
+/// 宽字符串多是路径，一个坏的代理项不能让整个路径里的反斜杠都变样。
+/// 编码为 `Escaped` 时文本中有转义，不能用 `encode_text` 还原。
+pub fn decode_utf16(units: &[u16]) -> DecodedText {
+    if let Ok(text) = String::from_utf16(units) {
+        return DecodedText {
+            text,
+            encoding: TextEncoding::Utf16Le,
+        };
+    }
+    let mut text = String::with_capacity(units.len());
+    for unit in char::decode_utf16(units.iter().copied()) {
+        match unit {
+            Ok(c) => text.push(c),
+            Err(e) => text.push_str(&format!("\\u{{{:04X}}}", e.unpaired_surrogate())),
+        }
+    }
+    DecodedText {
+        text,
+        encoding: TextEncoding::Escaped,
+    }
+}
+
+/// 按解码时的编码还原原始字节（窄字符串里 UTF-16 的 BOM 不保留）
+pub fn encode_text(text: &str, encoding: TextEncoding) -> Vec<u8> {
+    match encoding {
+        TextEncoding::Utf8 => text.as_bytes().to_vec(),
+        TextEncoding::Gbk => GBK.encode(text).0.into_owned(),
+        TextEncoding::Utf16Le => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+        TextEncoding::Escaped => unescape_bytes(text),
+    }
+}
+
+/// 还原 `Escaped` 文本中的字节，没有转义的部分按 UTF-8 输出
+pub fn unescape_bytes(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&first, tail)) = rest.split_first() {
+        if first == b'\\' {
+            if let Some(tail) = tail.strip_prefix(b"\\") {
+                bytes.push(b'\\');
+                rest = tail;
+                continue;
+            }
+            if let Some(value) = tail
+                .strip_prefix(b"x")
+                .and_then(|hex| hex.get(..2))
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                bytes.push(value);
+                rest = &tail[3..];
+                continue;
+            }
+        }
+        bytes.push(first);
+        rest = tail;
+    }
+    bytes
+}
+
+// 按 UTF-16LE 解码窄字符串：有 BOM，或者长度为偶数、至少一半字符的高字节为0且全是可打印文本
+fn decode_utf16_in_bytes(bytes: &[u8]) -> Option<String> {
+    let (bytes, has_bom) = match bytes.strip_prefix(&[0xFF, 0xFE]) {
+        Some(rest) => (rest, true),
+        None => (bytes, false),
+    };
+    if bytes.len() % 2 != 0 || bytes.is_empty() {
+        return None;
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    if !has_bom {
+        let narrow = units.iter().filter(|unit| **unit < 0x100).count();
+        if narrow * 2 < units.len() {
+            return None;
+        }
+    }
+    String::from_utf16(&units)
+        .ok()
+        .filter(|text| is_plain_text(text))
+}
+
+// 除了制表、换行以外没有控制字符
+fn is_plain_text(text: &str) -> bool {
+    text.chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\r' | '\n'))
+}
+
+// 合法的 UTF-8 片段原样保留，其余字节转义
+fn escape_bytes(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len() * 2);
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => text.push_str("\\\\"),
+                c if c.is_control() && !matches!(c, '\t' | '\r' | '\n') => {
+                    // 控制字符也转义，避免日志里出现终端控制序列
+                    let mut buffer = [0u8; 4];
+                    for byte in c.encode_utf8(&mut buffer).bytes() {
+                        text.push_str(&format!("\\x{:02X}", byte));
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            text.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 解码后按解码时的编码还原，应该得到原始字节（BOM 除外）
+    fn assert_round_trip(bytes: &[u8]) -> TextEncoding {
+        let decoded = decode_bytes(bytes);
+        let expected = match decoded.encoding {
+            TextEncoding::Utf16Le => bytes.strip_prefix(&[0xFF, 0xFE]).unwrap_or(bytes),
+            _ => bytes,
+        };
+        assert_eq!(
+            encode_text(&decoded.text, decoded.encoding),
+            expected,
+            "{:02X?} -> {:?}",
+            bytes,
+            decoded
+        );
+        decoded.encoding
+    }
+
+    #[test]
+    fn detects_encodings() {
+        assert_eq!(
+            assert_round_trip("登录 ok\t\r\n".as_bytes()),
+            TextEncoding::Utf8
+        );
+        let gbk = GBK.encode("读取配置 GBK 编码").0;
+        assert_eq!(assert_round_trip(&gbk), TextEncoding::Gbk);
+        let utf16: Vec<u8> = "wide 字符串"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(assert_round_trip(&utf16), TextEncoding::Utf16Le);
+        assert_eq!(decode_bytes(&utf16).text, "wide 字符串");
+        let with_bom = [&[0xFF, 0xFE][..], &utf16].concat();
+        assert_eq!(assert_round_trip(&with_bom), TextEncoding::Utf16Le);
+        assert_eq!(decode_bytes(&with_bom).text, "wide 字符串");
+        assert_eq!(assert_round_trip(b""), TextEncoding::Utf8);
+    }
+
+    #[test]
+    fn escapes_binary_and_control_bytes() {
+        let bytes = b"body=\x08\x96\x01\xFF\xFE\x00\x1B[0m path=a\\b";
+        let decoded = decode_bytes(bytes);
+        assert_eq!(decoded.encoding, TextEncoding::Escaped);
+        assert_eq!(
+            decoded.text,
+            "body=\\x08\\x96\\x01\\xFF\\xFE\\x00\\x1B[0m path=a\\\\b"
+        );
+        assert!(!decoded.text.chars().any(char::is_control));
+        assert_eq!(unescape_bytes(&decoded.text), bytes);
+    }
+
+    #[test]
+    fn unescape_keeps_incomplete_escapes() {
+        assert_eq!(unescape_bytes("\\x4"), b"\\x4");
+        assert_eq!(unescape_bytes("\\xZZ"), b"\\xZZ");
+        assert_eq!(unescape_bytes("\\"), b"\\");
+        assert_eq!(unescape_bytes("a\\x41\\\\x41"), b"aA\\x41");
+    }
+
+    #[test]
+    fn every_short_input_round_trips() {
+        for first in 0..=u8::MAX {
+            assert_round_trip(&[first]);
+            for second in 0..=u8::MAX {
+                assert_round_trip(&[first, second]);
+                assert_round_trip(&[b'\\', first, second]);
+            }
+        }
+    }
+
+    #[test]
+    fn longer_inputs_round_trip() {
+        // 固定种子的伪随机输入，偏向反斜杠、ASCII 和 GBK/UTF-16 常见的字节
+        let mut state: u32 = 0x2545_F491;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        for _ in 0..5000 {
+            let len = (next() % 48) as usize;
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| match next() % 6 {
+                    0 => b'\\',
+                    1 => 0,
+                    2 => b'a' + (next() % 26) as u8,
+                    3 => 0x81 + (next() % 0x7E) as u8,
+                    _ => next() as u8,
+                })
+                .collect();
+            assert_round_trip(&bytes);
+        }
+    }
+
+    #[test]
+    fn decodes_wide_strings() {
+        let units: Vec<u16> = "a\\b".encode_utf16().collect();
+        assert_eq!(decode_utf16(&units).text, "a\\b");
+        assert_eq!(decode_utf16(&units).encoding, TextEncoding::Utf16Le);
+        let broken = [0x61, 0xD800, 0x5C];
+        let decoded = decode_utf16(&broken);
+        assert_eq!(decoded.encoding, TextEncoding::Escaped);
+        assert_eq!(decoded.text, "a\\u{D800}\\");
+
+        // 路径中的反斜杠不受坏代理项影响
+        let mut path: Vec<u16> = "C:\\skin\\login.xml".encode_utf16().collect();
+        path.push(0xDC00);
+        let decoded = decode_utf16(&path);
+        assert_eq!(decoded.encoding, TextEncoding::Escaped);
+        assert_eq!(decoded.text, "C:\\skin\\login.xml\\u{DC00}");
+    }
+
+    #[test]
+    fn mixed_corpus_round_trips() {
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus/write_log_mixed.log");
+        let bytes = std::fs::read(path).unwrap();
+        let encodings: Vec<TextEncoding> = bytes
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(assert_round_trip)
+            .collect();
+        assert_eq!(
+            encodings,
+            [
+                TextEncoding::Utf8,
+                TextEncoding::Gbk,
+                TextEncoding::Utf16Le,
+                TextEncoding::Utf16Le,
+                TextEncoding::Escaped,
+            ]
+        );
+    }
+}
@@ -0,0 +1,69 @@
+// 离线校验版本配置
+//
+// 用法: verify_profile [--fingerprint] <WxWork.exe> <配置.toml>
+// 全部通过时返回0，有检查失败返回1，参数或文件错误返回2
+// 带 --fingerprint 时不做校验，只输出可以粘贴进配置的模块指纹
+use std::path::Path;
+use std::process::ExitCode;
+use wxwork_profile::{Fingerprint, PeImage, Profile, verify_profile};
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().collect();
+    let print_fingerprint = args.iter().any(|arg| arg == "--fingerprint");
+    args.retain(|arg| arg != "--fingerprint");
+    if args.len() != 3 {
+        eprintln!("用法: {} [--fingerprint] <PE文件> <配置文件>", args[0]);
+        return ExitCode::from(2);
+    }
+
+    let pe = match PeImage::load(Path::new(&args[1])) {
+        Ok(pe) => pe,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let profile = match Profile::load(Path::new(&args[2])) {
+        Ok(profile) => profile,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    if print_fingerprint {
+        return match Fingerprint::generate(&pe, &profile) {
+            Ok(fingerprint) => {
+                print!("{}", fingerprint.to_toml());
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("❌ 生成模块指纹失败: {}", e);
+                ExitCode::from(1)
+            }
+        };
+    }
+
+    println!("PE文件: {}", args[1]);
+    println!(
+        "  {}，基址 0x{:X}，时间戳 0x{:08X}，镜像大小 0x{:X}",
+        if pe.is_64 { "PE32+" } else { "PE32" },
+        pe.image_base,
+        pe.time_date_stamp,
+        pe.size_of_image
+    );
+    println!("配置: {} ({})", profile.version, profile.module_name);
+    if profile.fingerprint.is_none() {
+        println!("  ⚠️ 配置中没有模块指纹，wxwork_help 会拒绝调用任何函数");
+    }
+    println!();
+
+    let report = verify_profile(&pe, &profile);
+    println!("{}", report);
+
+    if report.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
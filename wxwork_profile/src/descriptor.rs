@@ -0,0 +1,74 @@
+use crate::profile::Signature;
+use serde::Deserialize;
+
+/// 调用约定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CallConv {
+    Thiscall, // this 放在 ecx，其余参数压栈，被调用方清栈
+    Stdcall,  // 参数压栈，被调用方清栈
+    Cdecl,    // 参数压栈，调用方清栈
+}
+
+/// 参数类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArgKind {
+    This,       // 对象指针，只能是 thiscall 的第一个参数
+    Pointer,    // 任意指针
+    I32,        // 32位整数
+    StringIn,   // const std::string&
+    StringOut,  // std::string*，由被调用方写入
+    WStringIn,  // const std::wstring&
+    WStringOut, // std::wstring*，由被调用方写入
+}
+
+/// 返回值类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetKind {
+    #[default]
+    Void,
+    I32,
+    Pointer,
+    Bool, // 只看 al
+}
+
+/// 函数的定位方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Locator {
+    Offset(usize),        // 相对模块基址的偏移
+    Signature(Signature), // 在可执行节中搜索特征码
+    VtableSlot(String),   // `vtable_slots` 表中的键
+}
+
+/// 目标函数的完整描述：怎么找到它，以及怎么调用它
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FnDescriptor {
+    pub name: String,
+    pub locator: Locator,
+    pub convention: CallConv,
+    pub args: Vec<ArgKind>,
+    pub ret: RetKind,
+}
+
+impl FnDescriptor {
+    /// 检查参数列表和调用约定是否自洽
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, arg) in self.args.iter().enumerate() {
+            if *arg == ArgKind::This && (i != 0 || self.convention != CallConv::Thiscall) {
+                return Err(format!(
+                    "函数 {} 的 this 参数只能是 thiscall 的第一个参数",
+                    self.name
+                ));
+            }
+        }
+        if self.convention == CallConv::Thiscall && self.args.first() != Some(&ArgKind::This) {
+            return Err(format!(
+                "thiscall 函数 {} 的第一个参数必须是 this",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+use crate::pe::{PeImage, Relocations};
+use crate::profile::Profile;
+use crate::verify::function_rva;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// 默认对每个函数开头多少字节计算哈希
+pub const DEFAULT_HASH_LEN: usize = 16;
+
+fn default_hash_len() -> usize {
+    DEFAULT_HASH_LEN
+}
+
+/// 模块指纹，用来确认当前加载的模块就是配置对应的那个版本
+///
+/// 可以用 `verify_profile --fingerprint` 从PE文件生成。函数开头的字节中被基址重定位
+/// 修改的位置在哈希前清零，模块没有加载到默认基址时哈希也一致。
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fingerprint {
+    pub time_date_stamp: u32, // PE文件头中的链接时间戳
+    pub size_of_image: u32,   // 可选头中的镜像大小
+    #[serde(default = "default_hash_len")]
+    pub hash_len: usize, // 每个函数参与哈希的字节数
+    #[serde(default)]
+    pub functions: BTreeMap<String, String>, // 函数名 -> 开头字节的哈希
+}
+
+/// 屏蔽重定位位置后计算函数开头字节的哈希
+pub fn hash_function(relocations: &Relocations, rva: u32, bytes: &[u8]) -> String {
+    let mut masked = bytes.to_vec();
+    relocations.mask(rva, &mut masked);
+    hash_bytes(&masked)
+}
+
+/// 计算字节的 FNV-1a 64 位哈希，输出16位十六进制
+pub fn hash_bytes(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+impl Fingerprint {
+    /// 与实际模块比对
+    ///
+    /// `read_function` 根据函数名返回函数的 RVA 和开头的 `hash_len` 个字节，
+    /// 离线时读PE文件，运行时读进程内存；`relocations` 是同一模块的重定位表。
+    pub fn check(
+        &self,
+        time_date_stamp: u32,
+        size_of_image: u32,
+        relocations: &Relocations,
+        mut read_function: impl FnMut(&str, usize) -> Result<(u32, Vec<u8>), String>,
+    ) -> Result<(), String> {
+        if time_date_stamp != self.time_date_stamp {
+            return Err(format!(
+                "模块时间戳不匹配: 期望 0x{:08X}, 实际 0x{:08X}",
+                self.time_date_stamp, time_date_stamp
+            ));
+        }
+        if size_of_image != self.size_of_image {
+            return Err(format!(
+                "模块镜像大小不匹配: 期望 0x{:X}, 实际 0x{:X}",
+                self.size_of_image, size_of_image
+            ));
+        }
+        for (name, expected) in &self.functions {
+            let (rva, bytes) = read_function(name, self.hash_len)?;
+            let actual = hash_function(relocations, rva, &bytes);
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(format!(
+                    "函数 {} 开头字节哈希不匹配: 期望 {}, 实际 {}",
+                    name, expected, actual
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// 从PE文件为配置中的所有函数生成指纹
+    pub fn generate(pe: &PeImage, profile: &Profile) -> Result<Self, String> {
+        let relocations = pe.relocations()?;
+        let mut functions = BTreeMap::new();
+        for name in profile.functions.keys() {
+            let rva = function_rva(pe, profile, name)?;
+            let bytes = pe
+                .read_at_rva(rva, DEFAULT_HASH_LEN)
+                .ok_or_else(|| format!("函数 {} 在 0x{:X} 处没有足够数据", name, rva))?;
+            functions.insert(name.clone(), hash_function(&relocations, rva, bytes));
+        }
+        Ok(Fingerprint {
+            time_date_stamp: pe.time_date_stamp,
+            size_of_image: pe.size_of_image,
+            hash_len: DEFAULT_HASH_LEN,
+            functions,
+        })
+    }
+
+    /// 输出为可以直接粘贴进配置文件的 TOML 片段
+    pub fn to_toml(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "[fingerprint]");
+        let _ = writeln!(text, "time_date_stamp = 0x{:08X}", self.time_date_stamp);
+        let _ = writeln!(text, "size_of_image = 0x{:X}", self.size_of_image);
+        let _ = writeln!(text, "hash_len = {}", self.hash_len);
+        let _ = writeln!(text);
+        let _ = writeln!(text, "[fingerprint.functions]");
+        for (name, hash) in &self.functions {
+            let _ = writeln!(text, "{} = \"{}\"", name, hash);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE_BASE: u32 = 0x0040_0000;
+    const TEXT_RVA: u32 = 0x1000;
+    const RELOC_RVA: u32 = 0x2000;
+
+    // 最小的 PE32 文件：.text 中的函数在偏移2处引用了一个绝对地址，.reloc 记录了这个位置
+    fn sample_pe() -> Vec<u8> {
+        let mut data = vec![0u8; 0x600];
+        let put_u16 = |data: &mut Vec<u8>, at: usize, value: u16| {
+            data[at..at + 2].copy_from_slice(&value.to_le_bytes())
+        };
+        let put_u32 = |data: &mut Vec<u8>, at: usize, value: u32| {
+            data[at..at + 4].copy_from_slice(&value.to_le_bytes())
+        };
+        put_u16(&mut data, 0, 0x5A4D);
+        put_u32(&mut data, 0x3C, 0x40);
+        put_u32(&mut data, 0x40, 0x4550);
+        let file_header = 0x44;
+        put_u16(&mut data, file_header, 0x14C);
+        put_u16(&mut data, file_header + 2, 2);
+        put_u32(&mut data, file_header + 4, 0x5F00_0000);
+        put_u16(&mut data, file_header + 16, 0xE0);
+        let optional = file_header + 20;
+        put_u16(&mut data, optional, 0x10B);
+        put_u32(&mut data, optional + 28, IMAGE_BASE);
+        put_u32(&mut data, optional + 56, 0x3000);
+        put_u32(&mut data, optional + 96 + 5 * 8, RELOC_RVA);
+        put_u32(&mut data, optional + 96 + 5 * 8 + 4, 12);
+
+        let sections = optional + 0xE0;
+        for (i, (name, rva, raw)) in [
+            (&b".text"[..], TEXT_RVA, 0x200),
+            (&b".reloc"[..], RELOC_RVA, 0x400),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let header = sections + i * 40;
+            data[header..header + name.len()].copy_from_slice(name);
+            put_u32(&mut data, header + 8, 0x200);
+            put_u32(&mut data, header + 12, *rva);
+            put_u32(&mut data, header + 16, 0x200);
+            put_u32(&mut data, header + 20, *raw);
+            put_u32(
+                &mut data,
+                header + 36,
+                if i == 0 { 0x6000_0020 } else { 0x4200_0040 },
+            );
+        }
+
+        // push ebp; mov eax, [IMAGE_BASE + 0x1100]; ...
+        let code = [
+            0x55, 0xA1, 0, 0, 0, 0, 0x8B, 0xEC, 0x83, 0xEC, 0x10, 0x5D, 0xC3, 0xCC,
+        ];
+        data[0x200..0x200 + code.len()].copy_from_slice(&code);
+        put_u32(&mut data, 0x202, IMAGE_BASE + 0x1100);
+        // 一个重定位块：HIGHLOW 0x1002，加一项对齐填充
+        put_u32(&mut data, 0x400, TEXT_RVA);
+        put_u32(&mut data, 0x404, 12);
+        put_u16(&mut data, 0x408, 0x3002);
+        data
+    }
+
+    fn sample_profile() -> Profile {
+        Profile::from_toml_str(
+            "version = \"test\"\nmodule_name = \"Test.exe\"\n[functions.entry]\noffset = 0x1000\n",
+        )
+        .unwrap()
+    }
+
+    // 模拟加载到 `base`：按重定位表修正后读取函数开头
+    fn load_at(pe: &PeImage, base: u32) -> impl Fn(&str, usize) -> Result<(u32, Vec<u8>), String> {
+        let mut code = pe.read_at_rva(TEXT_RVA, 0x20).unwrap().to_vec();
+        let value = u32::from_le_bytes(code[2..6].try_into().unwrap());
+        code[2..6].copy_from_slice(&(value - IMAGE_BASE + base).to_le_bytes());
+        move |_, len| Ok((TEXT_RVA, code[..len].to_vec()))
+    }
+
+    #[test]
+    fn fingerprint_matches_relocated_module() {
+        let pe = PeImage::parse(sample_pe()).unwrap();
+        let relocations = pe.relocations().unwrap();
+        assert_eq!(relocations.len(), 1);
+        let fingerprint = Fingerprint::generate(&pe, &sample_profile()).unwrap();
+
+        for base in [IMAGE_BASE, 0x0120_0000, 0x7700_0000] {
+            fingerprint
+                .check(
+                    pe.time_date_stamp,
+                    pe.size_of_image,
+                    &relocations,
+                    load_at(&pe, base),
+                )
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn fingerprint_detects_changed_code() {
+        let pe = PeImage::parse(sample_pe()).unwrap();
+        let relocations = pe.relocations().unwrap();
+        let fingerprint = Fingerprint::generate(&pe, &sample_profile()).unwrap();
+
+        let patched = |_: &str, len: usize| {
+            let mut code = pe.read_at_rva(TEXT_RVA, len).unwrap().to_vec();
+            code[0] = 0xE9; // 被 hook 改成了 jmp
+            Ok((TEXT_RVA, code))
+        };
+        let error = fingerprint
+            .check(pe.time_date_stamp, pe.size_of_image, &relocations, patched)
+            .unwrap_err();
+        assert!(error.contains("哈希不匹配"), "{}", error);
+
+        let error = fingerprint
+            .check(
+                pe.time_date_stamp + 1,
+                pe.size_of_image,
+                &relocations,
+                load_at(&pe, 0),
+            )
+            .unwrap_err();
+        assert!(error.contains("时间戳"), "{}", error);
+    }
+
+    #[test]
+    fn profile_without_fingerprint_is_refused() {
+        let pe = PeImage::parse(sample_pe()).unwrap();
+        let relocations = pe.relocations().unwrap();
+        let error = sample_profile()
+            .check_fingerprint(
+                pe.time_date_stamp,
+                pe.size_of_image,
+                &relocations,
+                load_at(&pe, IMAGE_BASE),
+            )
+            .unwrap_err();
+        assert!(error.contains("缺少模块指纹"), "{}", error);
+    }
+
+    #[test]
+    fn profile_with_matching_fingerprint_is_accepted() {
+        let pe = PeImage::parse(sample_pe()).unwrap();
+        let relocations = pe.relocations().unwrap();
+        let mut profile = sample_profile();
+        profile.fingerprint = Some(Fingerprint::generate(&pe, &profile).unwrap());
+        profile
+            .check_fingerprint(
+                pe.time_date_stamp,
+                pe.size_of_image,
+                &relocations,
+                load_at(&pe, 0x0120_0000),
+            )
+            .unwrap();
+
+        let error = profile
+            .check_fingerprint(
+                pe.time_date_stamp,
+                pe.size_of_image + 0x1000,
+                &relocations,
+                load_at(&pe, IMAGE_BASE),
+            )
+            .unwrap_err();
+        assert!(error.contains("不匹配，拒绝调用"), "{}", error);
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let pe = PeImage::parse(sample_pe()).unwrap();
+        let fingerprint = Fingerprint::generate(&pe, &sample_profile()).unwrap();
+        let text = format!(
+            "version = \"test\"\nmodule_name = \"Test.exe\"\n[functions.entry]\noffset = 0x1000\n\n{}",
+            fingerprint.to_toml()
+        );
+        let profile = Profile::from_toml_str(&text).unwrap();
+        let parsed = profile.fingerprint.unwrap();
+        assert_eq!(parsed.time_date_stamp, fingerprint.time_date_stamp);
+        assert_eq!(parsed.functions, fingerprint.functions);
+    }
+}
@@ -0,0 +1,18 @@
+// 企业微信版本配置（偏移、RTTI、虚表槽位、特征码）以及离线校验
+pub mod descriptor;
+pub mod fingerprint;
+pub mod pe;
+pub mod profile;
+pub mod verify;
+
+// 重新导出主要类型
+pub use descriptor::{ArgKind, CallConv, FnDescriptor, Locator, RetKind};
+pub use fingerprint::{Fingerprint, hash_bytes, hash_function};
+pub use pe::{PeImage, PeSection, Relocations};
+pub use profile::{
+    FieldEntry, FieldKind, FunctionEntry, Profile, QrcodeStatusEntry, RttiEntry, Signature,
+    VtableSlotEntry,
+};
+pub use verify::{
+    CheckItem, VerifyReport, find_signature, function_rva, verify_profile, vtable_slot_rva,
+};
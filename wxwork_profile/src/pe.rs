@@ -0,0 +1,369 @@
+// 离线解析 PE 文件，只读取校验配置需要的字段
+
+const IMAGE_DOS_SIGNATURE: u16 = 0x5A4D; // MZ
+const IMAGE_NT_SIGNATURE: u32 = 0x0000_4550; // PE\0\0
+const IMAGE_NT_OPTIONAL_HDR32_MAGIC: u16 = 0x10B;
+const IMAGE_NT_OPTIONAL_HDR64_MAGIC: u16 = 0x20B;
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+const IMAGE_DIRECTORY_ENTRY_EXCEPTION: usize = 3;
+const IMAGE_DIRECTORY_ENTRY_BASERELOC: usize = 5;
+const IMAGE_REL_BASED_HIGHLOW: u16 = 3;
+const IMAGE_REL_BASED_DIR64: u16 = 10;
+
+/// 节信息
+#[derive(Debug, Clone)]
+pub struct PeSection {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+    pub characteristics: u32,
+}
+
+impl PeSection {
+    /// 是否为可执行节
+    pub fn is_executable(&self) -> bool {
+        self.characteristics & IMAGE_SCN_MEM_EXECUTE != 0
+    }
+
+    /// RVA 是否落在该节内
+    pub fn contains_rva(&self, rva: u32) -> bool {
+        let size = self.virtual_size.max(self.raw_size);
+        rva >= self.virtual_address && rva - self.virtual_address < size
+    }
+}
+
+/// 从文件读入（或从进程内存复制）的 PE 镜像
+pub struct PeImage {
+    data: Vec<u8>,
+    pub is_64: bool,             // 是否为 PE32+
+    pub image_base: u64,         // 默认加载基址
+    pub time_date_stamp: u32,    // 链接时间戳
+    pub size_of_image: u32,      // 镜像大小
+    pub sections: Vec<PeSection>, // 节表
+    exception_directory: (u32, u32), // 异常目录 (RVA, 大小)，只有 x64 才有
+    relocation_directory: (u32, u32), // 重定位目录 (RVA, 大小)
+}
+
+impl PeImage {
+    /// 从文件读取并解析
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let data = std::fs::read(path)
+            .map_err(|e| format!("读取PE文件失败: {} - {}", path.display(), e))?;
+        Self::parse(data)
+    }
+
+    /// 解析PE数据
+    pub fn parse(data: Vec<u8>) -> Result<Self, String> {
+        // 解析dos头
+        if read_u16(&data, 0) != Some(IMAGE_DOS_SIGNATURE) {
+            return Err("DOS头签名验证失败".to_string());
+        }
+        let e_lfanew = read_u32(&data, 0x3C).ok_or("DOS头不完整")? as usize;
+
+        // 解析pe头
+        if read_u32(&data, e_lfanew) != Some(IMAGE_NT_SIGNATURE) {
+            return Err("PE头签名验证失败".to_string());
+        }
+        let file_header = e_lfanew + 4;
+        let section_count = read_u16(&data, file_header + 2).ok_or("文件头不完整")?;
+        let time_date_stamp = read_u32(&data, file_header + 4).ok_or("文件头不完整")?;
+        let optional_size = read_u16(&data, file_header + 16).ok_or("文件头不完整")? as usize;
+
+        // 解析可选头
+        let optional_header = file_header + 20;
+        let magic = read_u16(&data, optional_header).ok_or("可选头不完整")?;
+        let is_64 = match magic {
+            IMAGE_NT_OPTIONAL_HDR32_MAGIC => false,
+            IMAGE_NT_OPTIONAL_HDR64_MAGIC => true,
+            _ => return Err(format!("未知的可选头类型: 0x{:X}", magic)),
+        };
+        let image_base = if is_64 {
+            read_u64(&data, optional_header + 24)
+        } else {
+            read_u32(&data, optional_header + 28).map(u64::from)
+        }
+        .ok_or("可选头不完整")?;
+        let size_of_image = read_u32(&data, optional_header + 56).ok_or("可选头不完整")?;
+        let data_directory = optional_header + if is_64 { 112 } else { 96 };
+        let directory = |index: usize| {
+            let entry = data_directory + index * 8;
+            if entry + 8 <= optional_header + optional_size {
+                (
+                    read_u32(&data, entry).unwrap_or(0),
+                    read_u32(&data, entry + 4).unwrap_or(0),
+                )
+            } else {
+                (0, 0)
+            }
+        };
+        let exception_directory = directory(IMAGE_DIRECTORY_ENTRY_EXCEPTION);
+        let relocation_directory = directory(IMAGE_DIRECTORY_ENTRY_BASERELOC);
+
+        // 获取节表
+        let section_table = optional_header + optional_size;
+        let mut sections = Vec::with_capacity(section_count as usize);
+        for i in 0..section_count as usize {
+            let header = section_table + i * 40;
+            let raw_name = data.get(header..header + 8).ok_or("节表不完整")?;
+            let name = String::from_utf8_lossy(raw_name)
+                .trim_end_matches('\0')
+                .to_string();
+            sections.push(PeSection {
+                name,
+                virtual_size: read_u32(&data, header + 8).ok_or("节表不完整")?,
+                virtual_address: read_u32(&data, header + 12).ok_or("节表不完整")?,
+                raw_size: read_u32(&data, header + 16).ok_or("节表不完整")?,
+                raw_offset: read_u32(&data, header + 20).ok_or("节表不完整")?,
+                characteristics: read_u32(&data, header + 36).ok_or("节表不完整")?,
+            });
+        }
+
+        Ok(PeImage {
+            data,
+            is_64,
+            image_base,
+            time_date_stamp,
+            size_of_image,
+            sections,
+            exception_directory,
+            relocation_directory,
+        })
+    }
+
+    /// 解析从进程内存复制出来的已加载镜像
+    ///
+    /// 已加载镜像按 RVA 排列，节的原始数据就在 RVA 处；加载器会把实际基址
+    /// 写回可选头，所以 `image_base` 是实际加载基址。
+    pub fn parse_mapped(data: Vec<u8>) -> Result<Self, String> {
+        let mut pe = Self::parse(data)?;
+        let len = pe.data.len() as u32;
+        for section in &mut pe.sections {
+            section.raw_offset = section.virtual_address;
+            section.raw_size = section
+                .virtual_size
+                .min(len.saturating_sub(section.virtual_address));
+        }
+        Ok(pe)
+    }
+
+    /// 指针大小
+    pub fn pointer_size(&self) -> usize {
+        if self.is_64 { 8 } else { 4 }
+    }
+
+    /// 按名称查找节
+    pub fn sections_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a PeSection> {
+        self.sections.iter().filter(move |s| s.name == name)
+    }
+
+    /// 查找包含 RVA 的节
+    pub fn section_by_rva(&self, rva: u32) -> Option<&PeSection> {
+        self.sections.iter().find(|s| s.contains_rva(rva))
+    }
+
+    /// 节在文件中的原始数据
+    pub fn section_data(&self, section: &PeSection) -> &[u8] {
+        let start = (section.raw_offset as usize).min(self.data.len());
+        let end = (start + section.raw_size as usize).min(self.data.len());
+        &self.data[start..end]
+    }
+
+    /// 读取从 RVA 开始的最多 `len` 个字节（不跨节）
+    pub fn read_at_rva(&self, rva: u32, len: usize) -> Option<&[u8]> {
+        let section = self.section_by_rva(rva)?;
+        let data = self.section_data(section);
+        let start = (rva - section.virtual_address) as usize;
+        let end = start.checked_add(len)?;
+        data.get(start..end)
+    }
+
+    /// 读取 RVA 处的指针（按镜像位数）
+    pub fn read_pointer_at_rva(&self, rva: u32) -> Option<u64> {
+        let bytes = self.read_at_rva(rva, self.pointer_size())?;
+        Some(if self.is_64 {
+            u64::from_le_bytes(bytes.try_into().ok()?)
+        } else {
+            u32::from_le_bytes(bytes.try_into().ok()?) as u64
+        })
+    }
+
+    /// 虚拟地址转 RVA（按默认基址）
+    pub fn va_to_rva(&self, va: u64) -> Option<u32> {
+        va.checked_sub(self.image_base)
+            .and_then(|rva| u32::try_from(rva).ok())
+    }
+
+    /// 重定位目录的 (RVA, 大小)
+    pub fn relocation_directory(&self) -> (u32, u32) {
+        self.relocation_directory
+    }
+
+    /// 解析重定位表，没有重定位目录时为空
+    pub fn relocations(&self) -> Result<Relocations, String> {
+        let (rva, size) = self.relocation_directory;
+        if rva == 0 || size == 0 {
+            return Ok(Relocations::default());
+        }
+        let table = self
+            .read_at_rva(rva, size as usize)
+            .ok_or_else(|| format!("重定位目录 0x{:X} 超出节的范围", rva))?;
+        Relocations::parse(table)
+    }
+
+    /// 异常目录中记录的所有函数起始 RVA（仅 x64），已排序
+    pub fn runtime_function_starts(&self) -> Vec<u32> {
+        let (rva, size) = self.exception_directory;
+        if !self.is_64 || rva == 0 || size == 0 {
+            return Vec::new();
+        }
+        let mut starts: Vec<u32> = self
+            .read_at_rva(rva, size as usize)
+            .map(|table| {
+                table
+                    .chunks_exact(12)
+                    .map(|entry| u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]))
+                    .collect()
+            })
+            .unwrap_or_default();
+        starts.sort_unstable();
+        starts
+    }
+}
+
+/// 基址重定位表：加载器在这些位置写入按实际基址修正后的地址
+///
+/// 文件中的字节按默认基址，进程中的字节按实际基址，两边比较代码字节前要先屏蔽这些位置。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Relocations {
+    fixups: Vec<(u32, u32)>, // (RVA, 字节数)，按 RVA 排序
+}
+
+impl Relocations {
+    /// 解析重定位目录的原始数据（由若干个“页RVA、块大小、类型和页内偏移数组”组成的块）
+    pub fn parse(table: &[u8]) -> Result<Self, String> {
+        let mut fixups = Vec::new();
+        let mut offset = 0;
+        while offset + 8 <= table.len() {
+            let page_rva = read_u32(table, offset).unwrap_or(0);
+            let block_size = read_u32(table, offset + 4).unwrap_or(0) as usize;
+            if block_size == 0 {
+                // 有的链接器在末尾补0
+                break;
+            }
+            if block_size < 8 || offset + block_size > table.len() {
+                return Err(format!(
+                    "重定位块 0x{:X} 的大小 {} 无效",
+                    offset, block_size
+                ));
+            }
+            for entry in table[offset + 8..offset + block_size].chunks_exact(2) {
+                let entry = u16::from_le_bytes([entry[0], entry[1]]);
+                let size = match entry >> 12 {
+                    IMAGE_REL_BASED_HIGHLOW => 4,
+                    IMAGE_REL_BASED_DIR64 => 8,
+                    _ => continue, // ABSOLUTE 是对齐用的填充，其他类型 x86/x64 不会出现
+                };
+                fixups.push((page_rva + (entry & 0x0FFF) as u32, size));
+            }
+            offset += block_size;
+        }
+        fixups.sort_unstable();
+        Ok(Relocations { fixups })
+    }
+
+    /// 重定位项个数
+    pub fn len(&self) -> usize {
+        self.fixups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fixups.is_empty()
+    }
+
+    /// 把从 `rva` 开始的 `bytes` 中被重定位的字节清零
+    pub fn mask(&self, rva: u32, bytes: &mut [u8]) {
+        let start = rva as u64;
+        let end = start + bytes.len() as u64;
+        // 从可能覆盖 start 的最后一项开始（重定位项最长8字节）
+        let first = self
+            .fixups
+            .partition_point(|(fixup, _)| (*fixup as u64) + 8 <= start);
+        for &(fixup, size) in &self.fixups[first..] {
+            let fixup_start = fixup as u64;
+            if fixup_start >= end {
+                break;
+            }
+            let from = fixup_start.max(start);
+            let to = (fixup_start + size as u64).min(end);
+            if from < to {
+                bytes[(from - start) as usize..(to - start) as usize].fill(0);
+            }
+        }
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 一个重定位块：页RVA、块大小、若干 (类型 << 12 | 页内偏移)
+    fn block(page_rva: u32, entries: &[u16]) -> Vec<u8> {
+        let mut data = page_rva.to_le_bytes().to_vec();
+        data.extend_from_slice(&((8 + entries.len() * 2) as u32).to_le_bytes());
+        for entry in entries {
+            data.extend_from_slice(&entry.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn parses_relocation_blocks() {
+        let mut table = block(0x1000, &[0x3010, 0x3004, 0x0000]);
+        table.extend(block(0x3000, &[0xA008]));
+        table.extend([0; 8]); // 末尾的填充
+        let relocations = Relocations::parse(&table).unwrap();
+        assert_eq!(
+            relocations.fixups,
+            vec![(0x1004, 4), (0x1010, 4), (0x3008, 8)]
+        );
+
+        let mut truncated = block(0x1000, &[0x3010]);
+        truncated[4] = 0x40;
+        assert!(Relocations::parse(&truncated).is_err());
+    }
+
+    #[test]
+    fn masks_only_relocated_bytes() {
+        let relocations = Relocations::parse(&block(0x1000, &[0x3002, 0x300E])).unwrap();
+        let mut bytes = [0xAAu8; 16];
+        relocations.mask(0x1000, &mut bytes);
+        let mut expected = [0xAAu8; 16];
+        expected[2..6].fill(0);
+        expected[14..16].fill(0); // 重定位项跨过了结尾，只清零范围内的部分
+        assert_eq!(bytes, expected);
+
+        // 从重定位项中间开始
+        let mut bytes = [0xAAu8; 4];
+        relocations.mask(0x1004, &mut bytes);
+        assert_eq!(bytes, [0, 0, 0xAA, 0xAA]);
+
+        // 范围外的重定位项不影响
+        let mut bytes = [0xAAu8; 4];
+        relocations.mask(0x1006, &mut bytes);
+        assert_eq!(bytes, [0xAA; 4]);
+    }
+}
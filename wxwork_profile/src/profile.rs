@@ -0,0 +1,328 @@
+use crate::descriptor::{ArgKind, CallConv, FnDescriptor, Locator, RetKind};
+use crate::fingerprint::Fingerprint;
+use crate::pe::Relocations;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// 某个企业微信版本的配置
+///
+/// 描述目标模块中各个函数的偏移/特征码、RTTI 类型名以及虚表槽位，
+/// 由 TOML 文件加载，例如 `profiles/wxwork_4_1_38_6006.toml`。
+#[derive(Debug, Clone, Deserialize)]
+pub struct Profile {
+    pub version: String,     // 版本信息
+    pub module_name: String, // 模块名称
+    #[serde(default)]
+    pub functions: BTreeMap<String, FunctionEntry>, // 函数
+    #[serde(default)]
+    pub rtti: BTreeMap<String, RttiEntry>, // RTTI 类型
+    #[serde(default)]
+    pub vtable_slots: BTreeMap<String, VtableSlotEntry>, // 虚表槽位
+    #[serde(default)]
+    pub fields: BTreeMap<String, FieldEntry>, // 对象字段
+    pub qrcode_status: Option<QrcodeStatusEntry>, // 二维码状态轮询回调
+    pub fingerprint: Option<Fingerprint>, // 模块指纹，调用任何函数前都要先比对
+}
+
+/// 函数定位和调用信息
+///
+/// 偏移、特征码、虚表槽位至少要有一个，同时存在时按这个顺序使用；
+/// 要通过 `invoke` 调用的函数还需要写 `convention`、`args`、`ret`。
+#[derive(Debug, Clone, Deserialize)]
+pub struct FunctionEntry {
+    pub offset: Option<usize>,        // 相对模块基址的偏移
+    pub signature: Option<String>,    // 特征码，例如 "55 8B EC ?? 83"
+    pub vtable_slot: Option<String>,  // `vtable_slots` 表中的键
+    pub convention: Option<CallConv>, // 调用约定
+    #[serde(default)]
+    pub args: Vec<ArgKind>, // 参数类型
+    #[serde(default)]
+    pub ret: RetKind, // 返回值类型
+}
+
+/// RTTI 类型信息，参数含义与 `search_vtf_by_rtti` 一致
+#[derive(Debug, Clone, Deserialize)]
+pub struct RttiEntry {
+    pub name: String, // RTTI类型名称，例如 ".?AVQrcodeFrame@ui@wework@@"
+    #[serde(default)]
+    pub offset_vtf_in_complete_class: u32, // 虚函数表在完整类中的偏移
+    #[serde(default)]
+    pub offset_constructor: u32, // 构造函数偏移
+}
+
+/// 虚表槽位：某个 RTTI 类型虚函数表中的第几项
+#[derive(Debug, Clone, Deserialize)]
+pub struct VtableSlotEntry {
+    pub rtti: String, // 对应 `rtti` 表中的键
+    pub index: usize, // 槽位序号，从0开始
+}
+
+/// 对象字段：从某个 RTTI 类型的对象出发，沿指针链找到的数据
+///
+/// `offsets` 中除最后一项外，每一项都表示“加上偏移后读取指针”，
+/// 最后一项是字段相对于最终对象的偏移。例如 `[0x10, 0x24]` 表示
+/// `*(obj + 0x10) + 0x24` 处的字段。
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldEntry {
+    pub object: String,      // 对应 `rtti` 表中的键
+    pub offsets: Vec<usize>, // 指针链偏移
+    pub kind: FieldKind,     // 字段类型
+}
+
+/// 字段类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    String,  // MSVC std::string
+    WString, // MSVC std::wstring
+}
+
+/// 二维码状态轮询回调
+///
+/// 客户端每次轮询到登录状态都会调用 `callback`，`codes` 把回调收到的状态码
+/// 映射为状态名（waiting / scanned / confirmed / cancelled / expired）。
+#[derive(Debug, Clone, Deserialize)]
+pub struct QrcodeStatusEntry {
+    pub callback: String, // 对应 `functions` 表中的键
+    #[serde(default)]
+    pub codes: BTreeMap<String, String>, // 状态码 -> 状态名
+}
+
+impl QrcodeStatusEntry {
+    /// 解析状态码映射
+    pub fn parse_codes(&self) -> Result<BTreeMap<i32, String>, String> {
+        self.codes
+            .iter()
+            .map(|(code, state)| {
+                code.parse::<i32>()
+                    .map(|code| (code, state.clone()))
+                    .map_err(|_| format!("二维码状态码无效: {}", code))
+            })
+            .collect()
+    }
+}
+
+impl Profile {
+    /// 从 TOML 文本解析配置
+    pub fn from_toml_str(text: &str) -> Result<Self, String> {
+        let profile: Profile =
+            toml::from_str(text).map_err(|e| format!("解析配置失败: {}", e))?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// 从文件加载配置
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("读取配置文件失败: {} - {}", path.display(), e))?;
+        Self::from_toml_str(&text)
+    }
+
+    // 检查配置内部的一致性
+    fn validate(&self) -> Result<(), String> {
+        for (name, function) in &self.functions {
+            if function.offset.is_none()
+                && function.signature.is_none()
+                && function.vtable_slot.is_none()
+            {
+                return Err(format!("函数 {} 没有偏移、特征码或虚表槽位", name));
+            }
+            if let Some(signature) = &function.signature {
+                Signature::parse(signature).map_err(|e| format!("函数 {} 的{}", name, e))?;
+            }
+            if let Some(slot) = function
+                .vtable_slot
+                .as_ref()
+                .filter(|slot| !self.vtable_slots.contains_key(*slot))
+            {
+                return Err(format!("函数 {} 引用了不存在的虚表槽位: {}", name, slot));
+            }
+            if function.convention.is_some() {
+                self.descriptor(name)?.validate()?;
+            }
+        }
+        if let Some(name) = self.fingerprint.iter().find_map(|fingerprint| {
+            fingerprint
+                .functions
+                .keys()
+                .find(|name| !self.functions.contains_key(*name))
+        }) {
+            return Err(format!("模块指纹引用了不存在的函数: {}", name));
+        }
+        for (name, field) in &self.fields {
+            if !self.rtti.contains_key(&field.object) {
+                return Err(format!(
+                    "字段 {} 引用了不存在的RTTI: {}",
+                    name, field.object
+                ));
+            }
+            if field.offsets.is_empty() {
+                return Err(format!("字段 {} 没有偏移", name));
+            }
+        }
+        if let Some(status) = &self.qrcode_status {
+            if !self.functions.contains_key(&status.callback) {
+                return Err(format!(
+                    "二维码状态回调引用了不存在的函数: {}",
+                    status.callback
+                ));
+            }
+            status.parse_codes()?;
+        }
+        for (name, slot) in &self.vtable_slots {
+            if !self.rtti.contains_key(&slot.rtti) {
+                return Err(format!("虚表槽位 {} 引用了不存在的RTTI: {}", name, slot.rtti));
+            }
+        }
+        Ok(())
+    }
+
+    /// 按名称获取函数
+    pub fn function(&self, name: &str) -> Result<&FunctionEntry, String> {
+        self.functions
+            .get(name)
+            .ok_or_else(|| format!("配置 {} 中没有函数 {}", self.version, name))
+    }
+
+    /// 按名称获取函数偏移
+    pub fn function_offset(&self, name: &str) -> Result<usize, String> {
+        self.function(name)?
+            .offset
+            .ok_or_else(|| format!("配置 {} 中函数 {} 没有偏移", self.version, name))
+    }
+
+    /// 按名称获取函数的定位方式（偏移 -> 特征码 -> 虚表槽位）
+    pub fn locator(&self, name: &str) -> Result<Locator, String> {
+        let function = self.function(name)?;
+        if let Some(offset) = function.offset {
+            Ok(Locator::Offset(offset))
+        } else if let Some(signature) = &function.signature {
+            Ok(Locator::Signature(Signature::parse(signature)?))
+        } else if let Some(slot) = &function.vtable_slot {
+            Ok(Locator::VtableSlot(slot.clone()))
+        } else {
+            Err(format!("函数 {} 没有定位方式", name))
+        }
+    }
+
+    /// 按名称生成函数描述，供 `invoke` 使用
+    pub fn descriptor(&self, name: &str) -> Result<FnDescriptor, String> {
+        let function = self.function(name)?;
+        let convention = function
+            .convention
+            .ok_or_else(|| format!("配置 {} 中函数 {} 没有调用约定", self.version, name))?;
+        let locator = self.locator(name)?;
+        Ok(FnDescriptor {
+            name: name.to_string(),
+            locator,
+            convention,
+            args: function.args.clone(),
+            ret: function.ret,
+        })
+    }
+
+    /// 按名称获取虚表槽位
+    pub fn vtable_slot(&self, name: &str) -> Result<&VtableSlotEntry, String> {
+        self.vtable_slots
+            .get(name)
+            .ok_or_else(|| format!("配置 {} 中没有虚表槽位 {}", self.version, name))
+    }
+
+    /// 按名称获取字段
+    pub fn field(&self, name: &str) -> Result<&FieldEntry, String> {
+        self.fields
+            .get(name)
+            .ok_or_else(|| format!("配置 {} 中没有字段 {}", self.version, name))
+    }
+
+    /// 与实际模块比对模块指纹，配置中没有指纹时同样拒绝
+    ///
+    /// 参数与 `Fingerprint::check` 相同，离线时来自PE文件，运行时来自进程内存。
+    pub fn check_fingerprint(
+        &self,
+        time_date_stamp: u32,
+        size_of_image: u32,
+        relocations: &Relocations,
+        read_function: impl FnMut(&str, usize) -> Result<(u32, Vec<u8>), String>,
+    ) -> Result<(), String> {
+        let fingerprint = self.fingerprint.as_ref().ok_or_else(|| {
+            format!(
+                "配置 {} 缺少模块指纹，拒绝调用（可用 verify_profile --fingerprint 生成）",
+                self.version
+            )
+        })?;
+        fingerprint
+            .check(time_date_stamp, size_of_image, relocations, read_function)
+            .map_err(|e| {
+                format!(
+                    "模块 {} 与配置 {} 不匹配，拒绝调用: {}",
+                    self.module_name, self.version, e
+                )
+            })
+    }
+
+    /// 按名称获取RTTI类型
+    pub fn rtti(&self, name: &str) -> Result<&RttiEntry, String> {
+        self.rtti
+            .get(name)
+            .ok_or_else(|| format!("配置 {} 中没有RTTI类型 {}", self.version, name))
+    }
+}
+
+/// 特征码，`None` 表示通配字节（`??`）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature(pub Vec<Option<u8>>);
+
+impl Signature {
+    /// 解析形如 "55 8B EC ?? 83" 的特征码
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bytes = Vec::new();
+        for token in text.split_whitespace() {
+            if token == "??" || token == "?" {
+                bytes.push(None);
+            } else {
+                let byte = u8::from_str_radix(token, 16)
+                    .map_err(|_| format!("特征码包含无效字节: {}", token))?;
+                bytes.push(Some(byte));
+            }
+        }
+        if bytes.is_empty() {
+            return Err("特征码为空".to_string());
+        }
+        if bytes.iter().all(|b| b.is_none()) {
+            return Err("特征码不能全部是通配字节".to_string());
+        }
+        Ok(Signature(bytes))
+    }
+
+    /// 特征码长度
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// 特征码是否为空
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 检查数据开头是否匹配特征码
+    pub fn matches_at(&self, data: &[u8]) -> bool {
+        data.len() >= self.0.len()
+            && self
+                .0
+                .iter()
+                .zip(data)
+                .all(|(pattern, byte)| pattern.is_none_or(|p| p == *byte))
+    }
+
+    /// 在数据中搜索所有匹配位置
+    pub fn find_all(&self, data: &[u8]) -> Vec<usize> {
+        if data.len() < self.0.len() {
+            return Vec::new();
+        }
+        (0..=data.len() - self.0.len())
+            .filter(|&i| self.matches_at(&data[i..]))
+            .collect()
+    }
+}
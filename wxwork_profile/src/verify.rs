@@ -0,0 +1,594 @@
+use crate::fingerprint::Fingerprint;
+use crate::pe::PeImage;
+use crate::profile::{FunctionEntry, Profile, RttiEntry, Signature};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// 单项检查结果
+#[derive(Debug, Clone)]
+pub struct CheckItem {
+    pub category: &'static str, // 检查类别：函数偏移、特征码、RTTI、虚表槽位
+    pub name: String,           // 配置中的名称
+    pub passed: bool,           // 是否通过
+    pub detail: String,         // 说明
+}
+
+/// 校验报告
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub items: Vec<CheckItem>,
+}
+
+impl VerifyReport {
+    fn push(&mut self, category: &'static str, name: &str, result: Result<String, String>) {
+        let (passed, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        self.items.push(CheckItem {
+            category,
+            name: name.to_string(),
+            passed,
+            detail,
+        });
+    }
+
+    /// 失败项数量
+    pub fn failure_count(&self) -> usize {
+        self.items.iter().filter(|item| !item.passed).count()
+    }
+
+    /// 是否全部通过
+    pub fn is_ok(&self) -> bool {
+        self.failure_count() == 0
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            writeln!(
+                f,
+                "[{}] {} {}: {}",
+                if item.passed { "通过" } else { "失败" },
+                item.category,
+                item.name,
+                item.detail
+            )?;
+        }
+        write!(
+            f,
+            "共 {} 项检查，{} 项通过，{} 项失败",
+            self.items.len(),
+            self.items.len() - self.failure_count(),
+            self.failure_count()
+        )
+    }
+}
+
+/// 用PE文件离线校验配置
+///
+/// * 每个函数偏移都落在可执行节中的函数起始位置
+/// * 每个特征码在可执行节中恰好匹配一次
+/// * 每个RTTI名称都能解析到虚函数表
+/// * 每个虚表槽位序号都在虚函数表范围内，槽位指向函数起始位置
+/// * 配置了模块指纹时，指纹与PE文件一致
+pub fn verify_profile(pe: &PeImage, profile: &Profile) -> VerifyReport {
+    let mut report = VerifyReport::default();
+
+    if let Some(fingerprint) = &profile.fingerprint {
+        report.push(
+            "模块指纹",
+            &profile.module_name,
+            check_fingerprint(pe, profile, fingerprint),
+        );
+    }
+    let function_starts = pe.runtime_function_starts();
+
+    for (name, function) in &profile.functions {
+        if let Some(offset) = function.offset {
+            report.push(
+                "函数偏移",
+                name,
+                check_function_start(pe, &function_starts, offset),
+            );
+        }
+        if function.signature.is_some() {
+            report.push("特征码", name, check_signature(pe, function));
+        }
+    }
+
+    // RTTI 解析结果给虚表槽位检查复用
+    let mut vtables = BTreeMap::new();
+    for (name, rtti) in &profile.rtti {
+        let result = resolve_vtable(pe, rtti);
+        if let Ok((vtable_rva, slot_count)) = result {
+            vtables.insert(name.as_str(), (vtable_rva, slot_count));
+        }
+        report.push(
+            "RTTI",
+            name,
+            result.map(|(vtable_rva, slot_count)| {
+                format!(
+                    "{} -> 虚函数表 RVA 0x{:X}，共 {} 个槽位",
+                    rtti.name, vtable_rva, slot_count
+                )
+            }),
+        );
+    }
+
+    for (name, slot) in &profile.vtable_slots {
+        let result = match vtables.get(slot.rtti.as_str()) {
+            None => Err(format!("RTTI {} 未解析，无法检查槽位", slot.rtti)),
+            Some(&(_, slot_count)) if slot.index >= slot_count => Err(format!(
+                "槽位 {} 超出范围，虚函数表只有 {} 个槽位",
+                slot.index, slot_count
+            )),
+            Some(&(vtable_rva, _)) => {
+                let entry_rva = vtable_rva + (slot.index * pe.pointer_size()) as u32;
+                pe.read_pointer_at_rva(entry_rva)
+                    .and_then(|va| pe.va_to_rva(va))
+                    .ok_or_else(|| format!("无法读取槽位 {}", slot.index))
+                    .and_then(|target| {
+                        // 槽位指向的函数与配置的偏移一样按函数起始位置检查
+                        check_function_start(pe, &function_starts, target as usize)
+                            .map(|detail| format!("槽位 {} -> 函数 {}", slot.index, detail))
+                    })
+            }
+        };
+        report.push("虚表槽位", name, result);
+    }
+
+    report
+}
+
+/// 检查偏移是否为可执行节中的函数起始位置
+///
+/// x64 镜像直接查异常目录；x86 镜像没有这张表，只能看前一个字节是否为
+/// 对齐填充（int3 / nop）或返回指令。
+fn check_function_start(
+    pe: &PeImage,
+    function_starts: &[u32],
+    offset: usize,
+) -> Result<String, String> {
+    let rva = u32::try_from(offset).map_err(|_| format!("偏移 0x{:X} 超出镜像范围", offset))?;
+    let section = pe
+        .section_by_rva(rva)
+        .ok_or_else(|| format!("偏移 0x{:X} 不在任何节中", rva))?;
+    if !section.is_executable() {
+        return Err(format!(
+            "偏移 0x{:X} 位于不可执行的节 {} 中",
+            rva, section.name
+        ));
+    }
+
+    if !function_starts.is_empty() {
+        return if function_starts.binary_search(&rva).is_ok() {
+            Ok(format!(
+                "0x{:X} 位于 {}，异常目录中有记录",
+                rva, section.name
+            ))
+        } else {
+            Err(format!("0x{:X} 不是异常目录中记录的函数起始位置", rva))
+        };
+    }
+
+    if rva == section.virtual_address {
+        return Ok(format!("0x{:X} 位于 {} 起始处", rva, section.name));
+    }
+    let first = pe
+        .read_at_rva(rva, 1)
+        .ok_or_else(|| format!("偏移 0x{:X} 没有文件数据", rva))?[0];
+    if first == 0xCC || first == 0x00 {
+        return Err(format!("0x{:X} 处是填充字节 0x{:02X}", rva, first));
+    }
+    let previous = pe.read_at_rva(rva - 1, 1).map(|b| b[0]);
+    let ret_imm16 = rva >= 3 && pe.read_at_rva(rva - 3, 1).map(|b| b[0]) == Some(0xC2);
+    match previous {
+        Some(0xCC) | Some(0x90) | Some(0xC3) => Ok(format!(
+            "0x{:X} 位于 {}，前一字节为 0x{:02X}",
+            rva,
+            section.name,
+            previous.unwrap_or_default()
+        )),
+        _ if ret_imm16 => Ok(format!("0x{:X} 位于 {}，紧跟 ret n", rva, section.name)),
+        _ => Err(format!(
+            "0x{:X} 前面不是填充或返回指令，可能在函数中间",
+            rva
+        )),
+    }
+}
+
+/// 检查模块指纹：时间戳、镜像大小以及每个函数开头字节的哈希
+fn check_fingerprint(
+    pe: &PeImage,
+    profile: &Profile,
+    fingerprint: &Fingerprint,
+) -> Result<String, String> {
+    let relocations = pe.relocations()?;
+    fingerprint.check(
+        pe.time_date_stamp,
+        pe.size_of_image,
+        &relocations,
+        |name, len| {
+            let rva = function_rva(pe, profile, name)?;
+            pe.read_at_rva(rva, len)
+                .map(|bytes| (rva, bytes.to_vec()))
+                .ok_or_else(|| format!("函数 {} 在 0x{:X} 处没有 {} 字节数据", name, rva, len))
+        },
+    )?;
+    Ok(format!(
+        "时间戳 0x{:08X}，镜像大小 0x{:X}，{} 个函数哈希一致（模块共 {} 个重定位项）",
+        pe.time_date_stamp,
+        pe.size_of_image,
+        fingerprint.functions.len(),
+        relocations.len()
+    ))
+}
+
+/// 按配置中的定位方式计算函数 RVA（偏移 -> 特征码 -> 虚表槽位）
+pub fn function_rva(pe: &PeImage, profile: &Profile, name: &str) -> Result<u32, String> {
+    let function = profile.function(name)?;
+    if let Some(offset) = function.offset {
+        return u32::try_from(offset).map_err(|_| format!("偏移 0x{:X} 超出镜像范围", offset));
+    }
+    if let Some(text) = &function.signature {
+        return match find_signature(pe, &Signature::parse(text)?).as_slice() {
+            [rva] => Ok(*rva),
+            matches => Err(format!("函数 {} 的特征码匹配了 {} 次", name, matches.len())),
+        };
+    }
+    if let Some(slot_name) = &function.vtable_slot {
+        let slot = profile.vtable_slot(slot_name)?;
+        return vtable_slot_rva(pe, profile.rtti(&slot.rtti)?, slot.index);
+    }
+    Err(format!("函数 {} 没有定位方式", name))
+}
+
+/// 通过RTTI找到虚函数表，返回第 `index` 个槽位指向的函数 RVA
+pub fn vtable_slot_rva(pe: &PeImage, rtti: &RttiEntry, index: usize) -> Result<u32, String> {
+    let (vtable_rva, slot_count) = resolve_vtable(pe, rtti)?;
+    if index >= slot_count {
+        return Err(format!("槽位 {} 超出范围", index));
+    }
+    pe.read_pointer_at_rva(vtable_rva + (index * pe.pointer_size()) as u32)
+        .and_then(|va| pe.va_to_rva(va))
+        .ok_or_else(|| format!("无法读取槽位 {}", index))
+}
+
+/// 在所有可执行节中搜索特征码，返回匹配的 RVA
+pub fn find_signature(pe: &PeImage, signature: &Signature) -> Vec<u32> {
+    let mut matches = Vec::new();
+    for section in pe.sections.iter().filter(|s| s.is_executable()) {
+        let data = pe.section_data(section);
+        matches.extend(
+            signature
+                .find_all(data)
+                .into_iter()
+                .map(|i| section.virtual_address + i as u32),
+        );
+    }
+    matches
+}
+
+/// 检查特征码在可执行节中恰好匹配一次
+fn check_signature(pe: &PeImage, function: &FunctionEntry) -> Result<String, String> {
+    let text = function.signature.as_deref().unwrap_or_default();
+    let signature = Signature::parse(text)?;
+    let matches = find_signature(pe, &signature);
+
+    match matches.as_slice() {
+        [] => Err("特征码没有匹配".to_string()),
+        [rva] => match function.offset {
+            Some(offset) if offset != *rva as usize => Err(format!(
+                "特征码匹配在 0x{:X}，与偏移 0x{:X} 不一致",
+                rva, offset
+            )),
+            _ => Ok(format!("唯一匹配于 0x{:X}", rva)),
+        },
+        _ => Err(format!(
+            "特征码匹配了 {} 次: {}",
+            matches.len(),
+            matches
+                .iter()
+                .take(8)
+                .map(|rva| format!("0x{:X}", rva))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// 通过RTTI名称找到虚函数表，返回 (虚函数表RVA, 槽位数量)
+///
+/// 步骤与运行时的 `find_vtable_by_rtti_name` 相同：
+/// .data 中找 TypeDescriptor -> .rdata 中找 Complete Object Locator -> 找引用它的虚函数表。
+fn resolve_vtable(pe: &PeImage, rtti: &RttiEntry) -> Result<(u32, usize), String> {
+    let ptr_size = pe.pointer_size();
+
+    // 第一步：在.data节中搜索TypeDescriptor
+    let mut name_bytes = rtti.name.as_bytes().to_vec();
+    name_bytes.push(0);
+    let name_rva = pe
+        .sections_named(".data")
+        .find_map(|section| {
+            find_bytes(pe.section_data(section), &name_bytes, 1)
+                .map(|i| section.virtual_address + i as u32)
+        })
+        .ok_or_else(|| format!("未找到RTTI名称 {}", rtti.name))?;
+    // TypeDescriptor 开头是虚函数表指针和保留字段，名称在它们后面
+    let type_desc_rva = name_rva
+        .checked_sub((ptr_size * 2) as u32)
+        .ok_or_else(|| format!("RTTI名称 {} 前面放不下 TypeDescriptor", rtti.name))?;
+
+    // 第二步：在.rdata节中搜索RTTI Complete Object Locator
+    // x86 中 TypeDescriptor 是绝对地址，x64 中是 RVA 且 signature 为 1
+    let mut col = Vec::with_capacity(16);
+    col.extend_from_slice(&(if pe.is_64 { 1u32 } else { 0u32 }).to_le_bytes());
+    col.extend_from_slice(&rtti.offset_vtf_in_complete_class.to_le_bytes());
+    col.extend_from_slice(&rtti.offset_constructor.to_le_bytes());
+    let type_desc_ref = if pe.is_64 {
+        type_desc_rva
+    } else {
+        (pe.image_base + type_desc_rva as u64) as u32
+    };
+    col.extend_from_slice(&type_desc_ref.to_le_bytes());
+    let col_rva = pe
+        .sections_named(".rdata")
+        .find_map(|section| {
+            find_bytes(pe.section_data(section), &col, 4)
+                .map(|i| section.virtual_address + i as u32)
+        })
+        .ok_or_else(|| format!("未找到 {} 的 Complete Object Locator", rtti.name))?;
+
+    // 第三步：搜索指向 Complete Object Locator 的指针，虚函数表紧跟其后
+    let col_va = pe.image_base + col_rva as u64;
+    let col_va_bytes = col_va.to_le_bytes();
+    let col_ref_rva = pe
+        .sections_named(".rdata")
+        .find_map(|section| {
+            find_bytes(
+                pe.section_data(section),
+                &col_va_bytes[..ptr_size],
+                ptr_size,
+            )
+            .map(|i| section.virtual_address + i as u32)
+        })
+        .ok_or_else(|| format!("未找到 {} 的虚函数表", rtti.name))?;
+    let vtable_rva = col_ref_rva + ptr_size as u32;
+
+    // 统计槽位：连续指向可执行节的指针
+    let mut slot_count = 0;
+    while let Some(target) = pe
+        .read_pointer_at_rva(vtable_rva + (slot_count * ptr_size) as u32)
+        .and_then(|va| pe.va_to_rva(va))
+    {
+        match pe.section_by_rva(target) {
+            Some(section) if section.is_executable() => slot_count += 1,
+            _ => break,
+        }
+    }
+    if slot_count == 0 {
+        return Err(format!(
+            "{} 的虚函数表 0x{:X} 中没有有效槽位",
+            rtti.name, vtable_rva
+        ));
+    }
+
+    Ok((vtable_rva, slot_count))
+}
+
+// 按对齐搜索字节序列
+fn find_bytes(data: &[u8], needle: &[u8], align: usize) -> Option<usize> {
+    if data.len() < needle.len() {
+        return None;
+    }
+    (0..=data.len() - needle.len())
+        .step_by(align)
+        .find(|&i| &data[i..i + needle.len()] == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE_BASE: u32 = 0x0040_0000;
+    const CODE: u32 = 0x6000_0020;
+    const RDATA: u32 = 0x4000_0040;
+    const DATA: u32 = 0xC000_0040;
+
+    // 最小的 PE32 文件，每个节 0x200 字节，按给出的 (名称, RVA, 属性, 内容) 依次排列
+    fn build_pe(sections: &[(&str, u32, u32, Vec<u8>)]) -> PeImage {
+        let mut data = vec![0u8; 0x200 + sections.len() * 0x200];
+        let put_u16 = |data: &mut Vec<u8>, at: usize, value: u16| {
+            data[at..at + 2].copy_from_slice(&value.to_le_bytes())
+        };
+        let put_u32 = |data: &mut Vec<u8>, at: usize, value: u32| {
+            data[at..at + 4].copy_from_slice(&value.to_le_bytes())
+        };
+        put_u16(&mut data, 0, 0x5A4D);
+        put_u32(&mut data, 0x3C, 0x40);
+        put_u32(&mut data, 0x40, 0x4550);
+        let file_header = 0x44;
+        put_u16(&mut data, file_header, 0x14C);
+        put_u16(&mut data, file_header + 2, sections.len() as u16);
+        put_u16(&mut data, file_header + 16, 0xE0);
+        let optional = file_header + 20;
+        put_u16(&mut data, optional, 0x10B);
+        put_u32(&mut data, optional + 28, IMAGE_BASE);
+        put_u32(&mut data, optional + 56, 0x4000);
+
+        for (i, (name, rva, characteristics, content)) in sections.iter().enumerate() {
+            let header = optional + 0xE0 + i * 40;
+            let raw = 0x200 + i * 0x200;
+            data[header..header + name.len()].copy_from_slice(name.as_bytes());
+            put_u32(&mut data, header + 8, 0x200);
+            put_u32(&mut data, header + 12, *rva);
+            put_u32(&mut data, header + 16, 0x200);
+            put_u32(&mut data, header + 20, raw as u32);
+            put_u32(&mut data, header + 36, *characteristics);
+            data[raw..raw + content.len()].copy_from_slice(content);
+        }
+        PeImage::parse(data).unwrap()
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    // .text 中有三个函数：0x1000、0x1010、0x1020，.rdata 中是 Frame 的
+    // Complete Object Locator 和虚函数表，虚函数表第3项指向函数中间
+    fn sample_pe() -> PeImage {
+        let mut text = vec![0xCC; 0x30];
+        text[0x00..0x05].copy_from_slice(&[0x55, 0x8B, 0xEC, 0x5D, 0xC3]);
+        text[0x10..0x17].copy_from_slice(&[0x55, 0x8B, 0xEC, 0x33, 0xC0, 0x5D, 0xC3]);
+        text[0x20..0x24].copy_from_slice(&[0x56, 0x8B, 0xF1, 0xC3]);
+
+        let va = |rva: u32| IMAGE_BASE + rva;
+        let mut rdata = words(&[0, 0, 0, va(0x3000), 0]);
+        rdata.resize(0x20, 0);
+        rdata.extend(words(&[va(0x2000), va(0x1000), va(0x1010), va(0x1012), 0]));
+
+        let mut data = words(&[0, 0]);
+        data.extend_from_slice(b".?AVFrame@@\0");
+
+        build_pe(&[
+            (".text", 0x1000, CODE, text),
+            (".rdata", 0x2000, RDATA, rdata),
+            (".data", 0x3000, DATA, data),
+        ])
+    }
+
+    fn sample_profile() -> Profile {
+        Profile::from_toml_str(
+            r#"
+            version = "test"
+            module_name = "Test.exe"
+
+            [functions.start]
+            offset = 0x1000
+
+            [functions.second]
+            signature = "55 8B EC 33 C0"
+
+            [functions.wrong_offset]
+            offset = 0x1000
+            signature = "55 8B EC 33 C0"
+
+            [functions.middle]
+            offset = 0x1012
+
+            [functions.data]
+            offset = 0x2000
+
+            [rtti.frame]
+            name = ".?AVFrame@@"
+
+            [rtti.missing]
+            name = ".?AVMissing@@"
+
+            [vtable_slots.first]
+            rtti = "frame"
+            index = 1
+
+            [vtable_slots.inside]
+            rtti = "frame"
+            index = 2
+
+            [vtable_slots.outside]
+            rtti = "frame"
+            index = 3
+
+            [vtable_slots.orphan]
+            rtti = "missing"
+            index = 0
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn item<'a>(report: &'a VerifyReport, category: &str, name: &str) -> &'a CheckItem {
+        report
+            .items
+            .iter()
+            .find(|item| item.category == category && item.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn checks_function_offsets() {
+        let report = verify_profile(&sample_pe(), &sample_profile());
+        assert!(item(&report, "函数偏移", "start").passed);
+        let middle = item(&report, "函数偏移", "middle");
+        assert!(!middle.passed);
+        assert!(middle.detail.contains("函数中间"), "{}", middle.detail);
+        let data = item(&report, "函数偏移", "data");
+        assert!(!data.passed);
+        assert!(data.detail.contains("不可执行"), "{}", data.detail);
+    }
+
+    #[test]
+    fn checks_signatures() {
+        let pe = sample_pe();
+        let report = verify_profile(&pe, &sample_profile());
+        assert!(item(&report, "特征码", "second").passed);
+        let wrong = item(&report, "特征码", "wrong_offset");
+        assert!(!wrong.passed);
+        assert!(wrong.detail.contains("不一致"), "{}", wrong.detail);
+        assert_eq!(function_rva(&pe, &sample_profile(), "second"), Ok(0x1010));
+    }
+
+    #[test]
+    fn checks_rtti_and_vtable_slots() {
+        let pe = sample_pe();
+        let profile = sample_profile();
+        let report = verify_profile(&pe, &profile);
+
+        let frame = item(&report, "RTTI", "frame");
+        assert!(frame.passed);
+        assert!(
+            frame.detail.contains("0x2024，共 3 个槽位"),
+            "{}",
+            frame.detail
+        );
+        let missing = item(&report, "RTTI", "missing");
+        assert!(!missing.passed);
+        assert!(
+            missing.detail.contains("未找到RTTI名称"),
+            "{}",
+            missing.detail
+        );
+
+        assert!(item(&report, "虚表槽位", "first").passed);
+        // 槽位指向函数中间
+        assert!(!item(&report, "虚表槽位", "inside").passed);
+        // 第4项是0，不在 .text 中，虚函数表只有3个槽位
+        let outside = item(&report, "虚表槽位", "outside");
+        assert!(!outside.passed);
+        assert!(outside.detail.contains("超出范围"), "{}", outside.detail);
+        assert!(!item(&report, "虚表槽位", "orphan").passed);
+
+        assert_eq!(
+            vtable_slot_rva(&pe, profile.rtti("frame").unwrap(), 1),
+            Ok(0x1010)
+        );
+        assert!(vtable_slot_rva(&pe, profile.rtti("frame").unwrap(), 3).is_err());
+        assert_eq!(report.failure_count(), 7);
+    }
+
+    #[test]
+    fn rejects_type_descriptor_before_image_start() {
+        // 名称在 RVA 0 处，前面放不下 TypeDescriptor 的两个指针
+        let pe = build_pe(&[(".data", 0, DATA, b".?AVFrame@@\0".to_vec())]);
+        let rtti = RttiEntry {
+            name: ".?AVFrame@@".to_string(),
+            offset_vtf_in_complete_class: 0,
+            offset_constructor: 0,
+        };
+        let error = vtable_slot_rva(&pe, &rtti, 0).unwrap_err();
+        assert!(error.contains("TypeDescriptor"), "{}", error);
+    }
+}
@@ -0,0 +1,77 @@
+// 解码客户端缓存的二维码图片，可在 Linux 上用样例图片检查解码结果
+//
+// 用法: decode_qrcode [--json] <图片或目录>
+// 传入目录时只处理其中 UUID 命名的 JPG，相同内容只输出一次
+// 带 --json 时把内容解析为登录二维码并输出 JSON
+// 全部成功时返回0，有图片无法解码或内容不是登录二维码返回1，参数错误返回2
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+use wxwork_qrcode::{ImageWatcher, LoginQrPayload, decode_image_file};
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    args.retain(|arg| arg != "--json");
+    if args.len() != 2 {
+        eprintln!("用法: {} [--json] <图片或目录>", args[0]);
+        return ExitCode::from(2);
+    }
+
+    let path = Path::new(&args[1]);
+    let mut ok = true;
+    if path.is_dir() {
+        // 离线解码不需要等待写入完成
+        let mut watcher = ImageWatcher::new(path).with_settle_time(Duration::ZERO);
+        let result = watcher.poll(SystemTime::now());
+        for decoded in &result.decoded {
+            print!("{}\t", decoded.path.display());
+            ok &= print_content(&decoded.content, json);
+        }
+        for error in &result.errors {
+            eprintln!("❌ {}", error);
+        }
+        ok &= result.errors.is_empty();
+    } else {
+        match decode_image_file(path) {
+            Ok(contents) if contents.is_empty() => {
+                eprintln!("❌ 图片中没有二维码");
+                ok = false;
+            }
+            Ok(contents) => {
+                for content in contents {
+                    ok &= print_content(&content, json);
+                }
+            }
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                ok = false;
+            }
+        }
+    }
+
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+// 输出二维码内容，返回内容是否有效
+fn print_content(content: &str, json: bool) -> bool {
+    if !json {
+        println!("{}", content);
+        return true;
+    }
+    match LoginQrPayload::parse(content) {
+        Ok(payload) => {
+            println!("{}", payload.to_json());
+            true
+        }
+        Err(e) => {
+            println!("{}", content);
+            eprintln!("❌ {}", e);
+            false
+        }
+    }
+}
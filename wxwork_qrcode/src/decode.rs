@@ -0,0 +1,201 @@
+use std::path::Path;
+
+/// 判断文件名是否为客户端缓存的二维码图片
+///
+/// 客户端把二维码保存为 UUID 命名的 JPG，如
+/// `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx.jpg`，扩展名不区分大小写。
+pub fn is_uuid_image_name(file_name: &str) -> bool {
+    let Some((stem, ext)) = file_name.rsplit_once('.') else {
+        return false;
+    };
+    if !ext.eq_ignore_ascii_case("jpg") && !ext.eq_ignore_ascii_case("jpeg") {
+        return false;
+    }
+
+    // 8-4-4-4-12 个十六进制字符
+    let groups: Vec<&str> = stem.split('-').collect();
+    let lengths = [8, 4, 4, 4, 12];
+    groups.len() == lengths.len()
+        && groups
+            .iter()
+            .zip(lengths)
+            .all(|(group, len)| group.len() == len && group.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// 解码图片中的所有二维码，返回文本内容
+///
+/// 一张图片可能有多个二维码，只要有一个解码成功就返回解码出的内容，其余的忽略；
+/// 图片中没有二维码时返回空列表；找到了二维码但全部解码失败、或者图片本身无法解析时返回错误。
+pub fn decode_image_bytes(data: &[u8]) -> Result<Vec<String>, String> {
+    let image = image::load_from_memory(data).map_err(|e| format!("解析图片失败: {}", e))?;
+    let mut prepared = rqrr::PreparedImage::prepare(image.to_luma8());
+
+    let mut contents = Vec::new();
+    let mut first_error = None;
+    for grid in prepared.detect_grids() {
+        match grid.decode() {
+            Ok((_, content)) => contents.push(content),
+            Err(e) => {
+                first_error.get_or_insert_with(|| format!("解码二维码失败: {}", e));
+            }
+        }
+    }
+    match first_error {
+        Some(e) if contents.is_empty() => Err(e),
+        _ => Ok(contents),
+    }
+}
+
+/// 读取并解码图片文件
+pub fn decode_image_file(path: &Path) -> Result<Vec<String>, String> {
+    let data =
+        std::fs::read(path).map_err(|e| format!("读取图片 {} 失败: {}", path.display(), e))?;
+    decode_image_bytes(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, ImageFormat, Luma};
+    use qrcode::{Color, EcLevel, QrCode};
+    use std::io::Cursor;
+
+    const FIRST: &str = "https://example.com/qrcode?key=0123456789ABCDEF";
+    const SECOND: &str = "https://example.com/qrcode?key=FEDCBA9876543210";
+
+    // 生成二维码图片：每个模块 scale 像素，四周留4个模块的空白；
+    // corrupt 时翻转中间的一大块数据模块，定位图案完好但纠错无法恢复
+    fn render(text: &str, scale: u32, corrupt: bool) -> GrayImage {
+        let code = QrCode::with_error_correction_level(text, EcLevel::L).unwrap();
+        let width = code.width() as u32;
+        let mut colors = code.to_colors();
+        if corrupt {
+            for y in width / 2 - 4..width / 2 + 5 {
+                for x in 9..width - 9 {
+                    let index = (y * width + x) as usize;
+                    colors[index] = match colors[index] {
+                        Color::Dark => Color::Light,
+                        Color::Light => Color::Dark,
+                    };
+                }
+            }
+        }
+        let quiet = 4;
+        let size = (width + 2 * quiet) * scale;
+        GrayImage::from_fn(size, size, |x, y| {
+            let (column, row) = (x / scale, y / scale);
+            if column < quiet || row < quiet || column >= width + quiet || row >= width + quiet {
+                return Luma([255]);
+            }
+            let index = ((row - quiet) * width + (column - quiet)) as usize;
+            match colors[index] {
+                Color::Dark => Luma([0]),
+                Color::Light => Luma([255]),
+            }
+        })
+    }
+
+    // 左右拼接两张图片
+    fn side_by_side(left: &GrayImage, right: &GrayImage) -> GrayImage {
+        let height = left.height().max(right.height());
+        let mut image = GrayImage::from_pixel(left.width() + right.width(), height, Luma([255]));
+        image::imageops::overlay(&mut image, left, 0, 0);
+        image::imageops::overlay(&mut image, right, left.width() as i64, 0);
+        image
+    }
+
+    fn encode(image: &GrayImage, format: ImageFormat) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        image.write_to(&mut data, format).unwrap();
+        data.into_inner()
+    }
+
+    fn corpus(name: &str) -> Vec<u8> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("corpus/images")
+            .join(name);
+        std::fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn recognizes_uuid_image_names() {
+        assert!(is_uuid_image_name(
+            "0c5e2f7a-3b9d-4e21-8f6a-92d4b1c7e305.jpg"
+        ));
+        assert!(is_uuid_image_name(
+            "0C5E2F7A-3B9D-4E21-8F6A-92D4B1C7E305.JPEG"
+        ));
+        assert!(!is_uuid_image_name(
+            "0c5e2f7a-3b9d-4e21-8f6a-92d4b1c7e305.png"
+        ));
+        assert!(!is_uuid_image_name("0c5e2f7a-3b9d-4e21-8f6a.jpg"));
+        assert!(!is_uuid_image_name(
+            "0c5e2f7g-3b9d-4e21-8f6a-92d4b1c7e305.jpg"
+        ));
+        assert!(!is_uuid_image_name("avatar_cache.jpg"));
+    }
+
+    #[test]
+    fn decodes_png_and_jpeg() {
+        let image = render(FIRST, 6, false);
+        for format in [ImageFormat::Png, ImageFormat::Jpeg] {
+            let decoded = decode_image_bytes(&encode(&image, format)).unwrap();
+            assert_eq!(decoded, vec![FIRST.to_string()], "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn decodes_every_code_in_image() {
+        let image = side_by_side(&render(FIRST, 6, false), &render(SECOND, 6, false));
+        let mut decoded = decode_image_bytes(&encode(&image, ImageFormat::Png)).unwrap();
+        decoded.sort();
+        assert_eq!(decoded, vec![FIRST.to_string(), SECOND.to_string()]);
+    }
+
+    #[test]
+    fn keeps_decoded_codes_when_another_fails() {
+        let image = side_by_side(&render(FIRST, 6, false), &render(SECOND, 6, true));
+        let decoded = decode_image_bytes(&encode(&image, ImageFormat::Png)).unwrap();
+        assert_eq!(decoded, vec![FIRST.to_string()]);
+    }
+
+    #[test]
+    fn reports_error_when_no_code_decodes() {
+        let image = render(FIRST, 6, true);
+        let error = decode_image_bytes(&encode(&image, ImageFormat::Png)).unwrap_err();
+        assert!(error.starts_with("解码二维码失败"), "{}", error);
+    }
+
+    #[test]
+    fn empty_image_has_no_codes() {
+        let image = GrayImage::from_pixel(200, 200, Luma([255]));
+        assert_eq!(
+            decode_image_bytes(&encode(&image, ImageFormat::Png)).unwrap(),
+            Vec::<String>::new()
+        );
+        assert!(decode_image_bytes(b"not an image").is_err());
+    }
+
+    #[test]
+    fn decodes_corpus_images() {
+        let single = decode_image_bytes(&corpus("0c5e2f7a-3b9d-4e21-8f6a-92d4b1c7e305.jpg"));
+        assert_eq!(
+            single.unwrap(),
+            vec![
+                "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=5D0B7E1A9C3F42E8B6A1D4C7E9F02B13&wx=1"
+                    .to_string()
+            ]
+        );
+        // 一个完好、一个损坏的二维码
+        let partial = decode_image_bytes(&corpus("7e41a9d2-6c08-4b5f-a3e7-1d92f0c4b868.jpg"));
+        assert_eq!(
+            partial.unwrap(),
+            vec![
+                "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=A1B2C3D4E5F60718293A4B5C6D7E8F90&wx=1"
+                    .to_string()
+            ]
+        );
+        let blank = decode_image_bytes(&corpus("b3f80c16-2a5e-47d9-9c41-e6a07d35f2b9.jpg"));
+        assert!(blank.unwrap().is_empty());
+    }
+}
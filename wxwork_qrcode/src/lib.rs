@@ -0,0 +1,7 @@
+pub mod decode;
+pub mod payload;
+pub mod watch;
+
+pub use decode::{decode_image_bytes, decode_image_file, is_uuid_image_name};
+pub use payload::{LOGIN_HOST, LOGIN_PATH, LoginQrPayload};
+pub use watch::{DecodedQrcode, ImageWatcher};
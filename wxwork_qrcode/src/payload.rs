@@ -0,0 +1,285 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// 推测的登录二维码域名
+///
+/// 没有用客户端真实的二维码核实过，不同版本或部署也可能不同，所以解析时不强制要求，
+/// 用 [`LoginQrPayload::is_known_location`] 判断。
+pub const LOGIN_HOST: &str = "wx.work.weixin.qq.com";
+
+/// 推测的登录二维码路径，同 [`LOGIN_HOST`] 没有核实过
+pub const LOGIN_PATH: &str = "/cgi-bin/crtx_auth";
+
+/// 登录二维码中的内容
+///
+/// 二维码是 URL + 密钥参数，形如
+/// `https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=<十六进制>&wx=1`。
+/// 域名和路径原样保留；渲染时按规范形式输出：`key` 在前，其余参数按名称排序，参数值做百分号编码。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoginQrPayload {
+    pub host: String,                     // 域名（小写）
+    pub path: String,                     // 路径
+    pub key: String,                      // 临时密钥
+    pub params: BTreeMap<String, String>, // 除 key 以外的参数（已解码）
+}
+
+impl LoginQrPayload {
+    /// 解析并校验二维码文本：必须是 https 链接，带一个十六进制的 key 参数
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let rest = text
+            .strip_prefix("https://")
+            .ok_or_else(|| format!("二维码内容不是 https 链接: {}", text))?;
+
+        // 去掉片段，拆分域名、路径和参数
+        let rest = rest.split_once('#').map_or(rest, |(rest, _)| rest);
+        let (location, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let (host, path) = location
+            .find('/')
+            .map_or((location, "/"), |i| (&location[..i], &location[i..]));
+        validate_host(host)?;
+
+        let mut key = None;
+        let mut params = BTreeMap::new();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let name = percent_decode(name)?;
+            let value = percent_decode(value)?;
+            if name == "key" {
+                if key.replace(value).is_some() {
+                    return Err("二维码中有多个 key 参数".to_string());
+                }
+            } else if params.insert(name.clone(), value).is_some() {
+                return Err(format!("二维码中有重复的参数: {}", name));
+            }
+        }
+
+        let key = key.ok_or_else(|| "二维码中没有 key 参数".to_string())?;
+        validate_key(&key)?;
+        Ok(LoginQrPayload {
+            host: host.to_ascii_lowercase(),
+            path: path.to_string(),
+            key,
+            params,
+        })
+    }
+
+    /// 域名和路径是否为推测的登录二维码位置（[`LOGIN_HOST`]、[`LOGIN_PATH`]）
+    pub fn is_known_location(&self) -> bool {
+        self.host == LOGIN_HOST && self.path == LOGIN_PATH
+    }
+
+    /// 渲染为规范形式的 URL
+    pub fn to_url(&self) -> String {
+        let mut url = format!(
+            "https://{}{}?key={}",
+            self.host,
+            self.path,
+            percent_encode(&self.key)
+        );
+        for (name, value) in &self.params {
+            url.push('&');
+            url.push_str(&percent_encode(name));
+            url.push('=');
+            url.push_str(&percent_encode(value));
+        }
+        url
+    }
+
+    /// 输出为 JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// 从 JSON 读取，读取后同样做校验
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let payload: LoginQrPayload =
+            serde_json::from_str(json).map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        validate_host(&payload.host)?;
+        if !payload.path.starts_with('/') {
+            return Err(format!("二维码路径格式不正确: {}", payload.path));
+        }
+        validate_key(&payload.key)?;
+        Ok(payload)
+    }
+}
+
+impl FromStr for LoginQrPayload {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for LoginQrPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_url())
+    }
+}
+
+// 域名只能是字母、数字、`-` 和 `.`，不带端口和用户信息
+fn validate_host(host: &str) -> Result<(), String> {
+    if host.is_empty()
+        || !host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.'))
+    {
+        return Err(format!("二维码域名格式不正确: {}", host));
+    }
+    Ok(())
+}
+
+// 密钥是服务端生成的十六进制串
+fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("二维码 key 为空".to_string());
+    }
+    if !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(format!("二维码 key 格式不正确: {}", key));
+    }
+    Ok(())
+}
+
+// 百分号解码，`+` 按空格处理
+fn percent_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                // from_str_radix 接受 "+1" 这样的符号，要先确认两位都是十六进制数字
+                let hex = text
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("参数中有错误的百分号编码: {}", text))?;
+                decoded.push(hex);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| format!("参数解码后不是 UTF-8: {}", text))
+}
+
+// 百分号编码，只保留 RFC 3986 中的非保留字符
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 没有抓到客户端真实的二维码内容，以下 URL 都是按推测的格式构造的
+    const LOGIN_URL: &str =
+        "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=5D0B7E1A9C3F42E8B6A1D4C7E9F02B13&wx=1";
+
+    #[test]
+    fn parses_login_url() {
+        let payload = LoginQrPayload::parse(LOGIN_URL).unwrap();
+        assert_eq!(payload.host, LOGIN_HOST);
+        assert_eq!(payload.path, LOGIN_PATH);
+        assert_eq!(payload.key, "5D0B7E1A9C3F42E8B6A1D4C7E9F02B13");
+        assert_eq!(
+            payload.params,
+            BTreeMap::from([("wx".to_string(), "1".to_string())])
+        );
+        assert!(payload.is_known_location());
+        assert_eq!(payload.to_url(), LOGIN_URL);
+        assert_eq!(LOGIN_URL.parse::<LoginQrPayload>().unwrap(), payload);
+    }
+
+    #[test]
+    fn renders_canonical_url() {
+        let text =
+            " https://WX.work.weixin.qq.com/cgi-bin/crtx_auth?wx=1&from=a+b%2Fc&key=abc123#top\n";
+        let payload = LoginQrPayload::parse(text).unwrap();
+        assert_eq!(payload.params["from"], "a b/c");
+        let url = payload.to_url();
+        assert_eq!(
+            url,
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=abc123&from=a%20b%2Fc&wx=1"
+        );
+        // 规范形式再解析一次不变
+        assert_eq!(LoginQrPayload::parse(&url).unwrap(), payload);
+        assert_eq!(payload.to_string(), url);
+    }
+
+    #[test]
+    fn keeps_other_locations() {
+        let payload =
+            LoginQrPayload::parse("https://work.weixin.qq.com/wework_admin/login?key=0A1B")
+                .unwrap();
+        assert_eq!(payload.host, "work.weixin.qq.com");
+        assert_eq!(payload.path, "/wework_admin/login");
+        assert!(!payload.is_known_location());
+        assert_eq!(
+            payload.to_url(),
+            "https://work.weixin.qq.com/wework_admin/login?key=0A1B"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_content() {
+        let cases = [
+            "http://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=0A1B",
+            "https:///cgi-bin/crtx_auth?key=0A1B",
+            "https://user@wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=0A1B",
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?wx=1",
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=",
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=XYZ",
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=0A&key=1B",
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=0A&wx=1&wx=2",
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=0A&wx=%G1",
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=0A&wx=%FF",
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=0A&wx=%+1",
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=0A&wx=%-1",
+            "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=0A&wx=%1",
+        ];
+        for text in cases {
+            assert!(LoginQrPayload::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn decodes_only_hex_escapes() {
+        assert_eq!(percent_decode("a%2Bb+c%7e").unwrap(), "a+b c~");
+        for text in ["%+1", "%-1", "% 1", "%1", "%", "%é1"] {
+            assert!(percent_decode(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let payload = LoginQrPayload::parse(LOGIN_URL).unwrap();
+        let json = payload.to_json();
+        assert_eq!(LoginQrPayload::from_json(&json).unwrap(), payload);
+
+        let mut bad = payload.clone();
+        bad.key = "not hex".to_string();
+        assert!(LoginQrPayload::from_json(&bad.to_json()).is_err());
+        let mut bad = payload;
+        bad.path = "cgi-bin".to_string();
+        assert!(LoginQrPayload::from_json(&bad.to_json()).is_err());
+    }
+}
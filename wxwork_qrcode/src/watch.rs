@@ -0,0 +1,267 @@
+use crate::decode::{decode_image_file, is_uuid_image_name};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// 默认等待文件写入完成的时间
+pub const DEFAULT_SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// 默认记住最近多少个二维码内容用于去重
+pub const DEFAULT_RECENT_CAPACITY: usize = 64;
+
+/// 从图片中解码出的二维码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedQrcode {
+    pub content: String,      // 二维码文本
+    pub path: PathBuf,        // 图片路径
+    pub modified: SystemTime, // 图片修改时间
+}
+
+/// 一次扫描的结果
+#[derive(Debug, Default)]
+pub struct PollResult {
+    pub decoded: Vec<DecodedQrcode>, // 新出现的二维码，按图片修改时间排序
+    pub errors: Vec<String>,         // 无法解码的图片
+}
+
+/// 轮询目录中客户端缓存的二维码图片
+///
+/// 只处理 UUID 命名的 JPG；同一文件只在修改后重新解码，内容与最近出现过的
+/// 二维码相同时不再输出。目录不存在时视为空目录，等待客户端创建。
+#[derive(Debug)]
+pub struct ImageWatcher {
+    dir: PathBuf,
+    settle_time: Duration,
+    recent_capacity: usize,
+    files: HashMap<PathBuf, (SystemTime, u64)>, // 已处理的文件 -> (修改时间, 大小)
+    recent: VecDeque<String>,                   // 最近输出的内容
+}
+
+impl ImageWatcher {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ImageWatcher {
+            dir: dir.into(),
+            settle_time: DEFAULT_SETTLE_TIME,
+            recent_capacity: DEFAULT_RECENT_CAPACITY,
+            files: HashMap::new(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// 文件最后修改后至少经过多久才解码，避免读到写了一半的图片
+    pub fn with_settle_time(mut self, settle_time: Duration) -> Self {
+        self.settle_time = settle_time;
+        self
+    }
+
+    /// 去重时记住的最近内容个数
+    pub fn with_recent_capacity(mut self, capacity: usize) -> Self {
+        self.recent_capacity = capacity.max(1);
+        self
+    }
+
+    /// 监控的目录
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 把目录中已有的图片标记为已处理，之后只输出新出现的二维码
+    pub fn skip_existing(&mut self) -> Result<usize, String> {
+        let entries = self.list_images()?;
+        let count = entries.len();
+        for (path, modified, len) in entries {
+            self.files.insert(path, (modified, len));
+        }
+        Ok(count)
+    }
+
+    /// 扫描一次目录，`now` 用于判断文件是否写入完成
+    pub fn poll(&mut self, now: SystemTime) -> PollResult {
+        let mut result = PollResult::default();
+        let mut entries = match self.list_images() {
+            Ok(entries) => entries,
+            Err(e) => {
+                result.errors.push(e);
+                return result;
+            }
+        };
+        entries.sort_by_key(|(_, modified, _)| *modified);
+
+        // 客户端会删除旧图片，不再存在的文件不用再记住
+        self.files
+            .retain(|path, _| entries.iter().any(|(entry, _, _)| entry == path));
+
+        for (path, modified, len) in entries {
+            if self.files.get(&path) == Some(&(modified, len)) {
+                continue;
+            }
+            // 还在写入，下次再处理
+            let age = now.duration_since(modified).unwrap_or(Duration::ZERO);
+            if age < self.settle_time {
+                continue;
+            }
+            self.files.insert(path.clone(), (modified, len));
+
+            match decode_image_file(&path) {
+                Ok(contents) => {
+                    for content in contents {
+                        if self.remember(&content) {
+                            result.decoded.push(DecodedQrcode {
+                                content,
+                                path: path.clone(),
+                                modified,
+                            });
+                        }
+                    }
+                }
+                Err(e) => result.errors.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+        result
+    }
+
+    // 记录内容，返回是否为新内容
+    fn remember(&mut self, content: &str) -> bool {
+        if self.recent.iter().any(|recent| recent == content) {
+            return false;
+        }
+        if self.recent.len() >= self.recent_capacity {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(content.to_string());
+        true
+    }
+
+    // 列出目录中的二维码图片
+    fn list_images(&self) -> Result<Vec<(PathBuf, SystemTime, u64)>, String> {
+        let read_dir = match std::fs::read_dir(&self.dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("读取目录 {} 失败: {}", self.dir.display(), e)),
+        };
+
+        let mut images = Vec::new();
+        for entry in read_dir.flatten() {
+            let name = entry.file_name();
+            if !name.to_str().is_some_and(is_uuid_image_name) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            images.push((entry.path(), modified, metadata.len()));
+        }
+        Ok(images)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SINGLE: &str = "0c5e2f7a-3b9d-4e21-8f6a-92d4b1c7e305.jpg";
+    const PARTIAL: &str = "7e41a9d2-6c08-4b5f-a3e7-1d92f0c4b868.jpg";
+    const BLANK: &str = "b3f80c16-2a5e-47d9-9c41-e6a07d35f2b9.jpg";
+    const SINGLE_CONTENT: &str =
+        "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=5D0B7E1A9C3F42E8B6A1D4C7E9F02B13&wx=1";
+    const PARTIAL_CONTENT: &str =
+        "https://wx.work.weixin.qq.com/cgi-bin/crtx_auth?key=A1B2C3D4E5F60718293A4B5C6D7E8F90&wx=1";
+
+    use tempfile::TempDir;
+
+    // 把样例图片复制到目录中，返回修改时间
+    fn copy(dir: &TempDir, corpus_name: &str, name: &str) -> SystemTime {
+        let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("corpus/images")
+            .join(corpus_name);
+        let path = dir.path().join(name);
+        std::fs::copy(source, &path).unwrap();
+        std::fs::metadata(path).unwrap().modified().unwrap()
+    }
+
+    fn contents(result: &PollResult) -> Vec<&str> {
+        result
+            .decoded
+            .iter()
+            .map(|decoded| decoded.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn reports_each_code_once() {
+        let dir = TempDir::new().unwrap();
+        let mut watcher = ImageWatcher::new(dir.path()).with_settle_time(Duration::ZERO);
+        copy(&dir, SINGLE, SINGLE);
+        copy(&dir, SINGLE, "avatar_cache.jpg");
+
+        let result = watcher.poll(SystemTime::now());
+        assert_eq!(contents(&result), vec![SINGLE_CONTENT]);
+        assert_eq!(result.decoded[0].path, dir.path().join(SINGLE));
+        assert!(result.errors.is_empty());
+        assert!(watcher.poll(SystemTime::now()).decoded.is_empty());
+
+        // 同样的内容换了文件名不再输出，新内容照常输出
+        copy(&dir, SINGLE, "11111111-2222-3333-4444-555555555555.jpg");
+        copy(&dir, PARTIAL, PARTIAL);
+        copy(&dir, BLANK, BLANK);
+        let result = watcher.poll(SystemTime::now());
+        assert_eq!(contents(&result), vec![PARTIAL_CONTENT]);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+    }
+
+    #[test]
+    fn waits_until_file_settles() {
+        let dir = TempDir::new().unwrap();
+        let mut watcher = ImageWatcher::new(dir.path()).with_settle_time(Duration::from_secs(1));
+        let modified = copy(&dir, SINGLE, SINGLE);
+
+        assert!(watcher.poll(modified).decoded.is_empty());
+        let result = watcher.poll(modified + Duration::from_secs(2));
+        assert_eq!(contents(&result), vec![SINGLE_CONTENT]);
+    }
+
+    #[test]
+    fn skips_existing_images() {
+        let dir = TempDir::new().unwrap();
+        copy(&dir, SINGLE, SINGLE);
+        let mut watcher = ImageWatcher::new(dir.path()).with_settle_time(Duration::ZERO);
+        assert_eq!(watcher.skip_existing().unwrap(), 1);
+        assert!(watcher.poll(SystemTime::now()).decoded.is_empty());
+
+        copy(&dir, PARTIAL, PARTIAL);
+        assert_eq!(
+            contents(&watcher.poll(SystemTime::now())),
+            vec![PARTIAL_CONTENT]
+        );
+    }
+
+    #[test]
+    fn missing_directory_is_empty() {
+        let dir = TempDir::new().unwrap();
+        let mut watcher = ImageWatcher::new(dir.path().join("not_created"));
+        let result = watcher.poll(SystemTime::now());
+        assert!(result.decoded.is_empty());
+        assert!(result.errors.is_empty());
+    }
+
+    #[test]
+    fn forgets_deleted_files() {
+        let dir = TempDir::new().unwrap();
+        let mut watcher = ImageWatcher::new(dir.path()).with_settle_time(Duration::ZERO);
+        copy(&dir, SINGLE, SINGLE);
+        copy(&dir, PARTIAL, PARTIAL);
+        assert_eq!(watcher.poll(SystemTime::now()).decoded.len(), 2);
+        assert_eq!(watcher.files.len(), 2);
+
+        std::fs::remove_file(dir.path().join(SINGLE)).unwrap();
+        assert!(watcher.poll(SystemTime::now()).decoded.is_empty());
+        assert_eq!(
+            watcher.files.keys().collect::<Vec<_>>(),
+            vec![&dir.path().join(PARTIAL)]
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use crate::hooks::{XmlDumpConfig, XmlOverrideConfig};
+use crate::log_capture::LogConfig;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use wxwork_trace::TraceSpec;
+
+/// 配置文件名，放在 DLL 同目录下
+pub const CONFIG_FILE_NAME: &str = "wxwork_tools.toml";
+
+/// 指定配置文件路径的环境变量，优先于 DLL 同目录下的配置
+pub const CONFIG_ENV: &str = "WXWORK_TOOLS_CONFIG";
+
+/// 分析工具的运行配置
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ToolsConfig {
+    pub client_version: Option<String>, // 客户端版本，默认读取主程序的文件版本
+    #[serde(default)]
+    pub log: LogConfig, // 抓取的客户端日志输出到哪里
+    #[serde(default)]
+    pub xml_dump: XmlDumpConfig, // LoadXML 抓到的XML保存到哪里
+    #[serde(default)]
+    pub xml_override: XmlOverrideConfig, // 用替换目录中的文件替换 LoadXML 返回的内容
+    #[serde(default)]
+    pub trace: Vec<TraceSpec>, // 跟踪调用的函数
+}
+
+impl ToolsConfig {
+    /// 从 TOML 文本解析
+    pub fn from_toml_str(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| format!("解析配置失败: {}", e))
+    }
+
+    /// 从文件加载
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("读取配置 {} 失败: {}", path.display(), e))?;
+        Self::from_toml_str(&text)
+    }
+
+    /// 确定配置文件路径：环境变量优先，其次是 DLL 所在目录
+    pub fn locate(module_dir: Option<&Path>) -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        module_dir
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .filter(|path| path.is_file())
+    }
+}
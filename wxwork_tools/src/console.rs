@@ -0,0 +1,224 @@
+// 控制台命令：运行时列出、安装、启用和禁用hook
+use crate::config::ToolsConfig;
+use crate::hook::hook_registry;
+use crate::hooks;
+use crate::log_capture;
+use std::io::BufRead;
+use std::path::PathBuf;
+use wxwork_log::RingQuery;
+use wxwork_trace::TraceSpec;
+
+const HELP: &str = "命令: list | install <名称> | enable <名称> | disable <名称> | logs [条数] [level=W] [tag=标签] [文本] | filters [reload] | overrides [on|off|list|reload] | traces [名称] | traces add <名称> <模块!导出名|模块+0x偏移> <cdecl|stdcall|thiscall> [参数类型...] [-> 返回类型] | traces remove <名称> | traces reset [名称] | traces export <文件.csv|文件.json> | eject | help";
+
+/// 从控制台读取命令直到输入结束
+pub fn run() {
+    println!("{}", HELP);
+    let stdin = std::io::stdin();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        if command == "logs" {
+            if let Err(e) = print_logs(words) {
+                println!("❌ {}", e);
+            }
+            continue;
+        }
+        if command == "filters" {
+            if let Err(e) = filters(words.next()) {
+                println!("❌ {}", e);
+            }
+            continue;
+        }
+        if command == "traces" {
+            if let Err(e) = traces(words) {
+                println!("❌ {}", e);
+            }
+            continue;
+        }
+        if command == "overrides" {
+            if let Err(e) = overrides(words.next()) {
+                println!("❌ {}", e);
+            }
+            continue;
+        }
+        let name = words.next();
+
+        let registry = hook_registry();
+        let result = match (command, name) {
+            ("list", _) => {
+                print_hooks();
+                Ok(())
+            }
+            ("install", Some(name)) => registry.install(name),
+            ("enable", Some(name)) => registry.enable(name),
+            ("disable", Some(name)) => registry.disable(name),
+            // 成功时线程随DLL一起结束，不会返回
+            ("eject", _) => crate::eject(),
+            _ => {
+                println!("{}", HELP);
+                Ok(())
+            }
+        };
+        match result {
+            Ok(()) if command != "list" && name.is_some() => {
+                println!("{} {}: 成功", command, name.unwrap_or_default());
+            }
+            Ok(()) => {}
+            Err(e) => println!("❌ {}", e),
+        }
+    }
+}
+
+// 输出hook列表
+fn print_hooks() {
+    for info in hook_registry().list() {
+        let target = info
+            .target
+            .map(|addr| format!("0x{:08X}", addr))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<16} {:<12} {:<10} {}",
+            info.name,
+            info.status.to_string(),
+            target,
+            info.locator
+        );
+    }
+}
+
+// 按条件输出内存中最近的日志
+fn print_logs<'a>(args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let ring = log_capture::ring().ok_or("没有配置 ring 日志输出")?;
+    let mut query = RingQuery {
+        limit: Some(20),
+        ..RingQuery::default()
+    };
+    for arg in args {
+        if let Ok(limit) = arg.parse::<usize>() {
+            query.limit = Some(limit);
+        } else if let Some(level) = arg.strip_prefix("level=") {
+            query.min_level = Some(level.parse()?);
+        } else if let Some(tag) = arg.strip_prefix("tag=") {
+            query.tag = Some(tag.to_string());
+        } else {
+            query.text = Some(arg.to_string());
+        }
+    }
+
+    for record in ring.query(&query) {
+        println!("{}", record);
+    }
+    if let Some(stats) = log_capture::stats() {
+        println!(
+            "缓冲 {} 条, 共收到 {} 条, 队列丢弃 {} 条, 写失败 {} 次",
+            ring.len(),
+            stats.submitted,
+            stats.dropped,
+            stats.write_errors
+        );
+    }
+    Ok(())
+}
+
+// 输出过滤规则计数，带 reload 时先从配置文件重新加载规则
+fn filters(action: Option<&str>) -> Result<(), String> {
+    match action {
+        None => {}
+        Some("reload") => {
+            let path = crate::config_path().ok_or("没有找到配置文件")?;
+            let config = ToolsConfig::load(&path)?;
+            log_capture::set_filter(&config.log.filter)?;
+            println!("已从 {} 重新加载过滤规则", path.display());
+        }
+        Some(action) => return Err(format!("未知的参数: {}", action)),
+    }
+    for stats in log_capture::filter_stats() {
+        println!("{}", stats);
+    }
+    Ok(())
+}
+
+// XML替换：切换启用状态、清空缓存，输出替换目录中的文件和替换次数
+fn overrides(action: Option<&str>) -> Result<(), String> {
+    match action {
+        None | Some("list") => {}
+        Some("on") => hooks::set_override_enabled(true)?,
+        Some("off") => hooks::set_override_enabled(false)?,
+        Some("reload") => hooks::with_overrides(|store| store.reload()),
+        Some(action) => return Err(format!("未知的参数: {}", action)),
+    }
+    hooks::with_overrides(|store| {
+        println!(
+            "替换{}，目录 {}",
+            if hooks::override_enabled() {
+                "已启用"
+            } else {
+                "未启用"
+            },
+            store.root().display()
+        );
+        for path in store.files()? {
+            let hits = store.hits().get(&path).copied().unwrap_or(0);
+            println!("{:>6}  {}", hits, path);
+        }
+        Ok(())
+    })
+}
+
+// 函数调用跟踪：汇总表、单个跟踪的详情、添加、清空统计和导出
+fn traces<'a>(mut args: impl Iterator<Item = &'a str>) -> Result<(), String> {
+    let report = || hooks::trace_report(crate::current_client_version());
+    match args.next() {
+        None => print!("{}", report().summary_table()),
+        Some("add") => {
+            let name = hooks::add_trace(TraceSpec::parse_command(args)?)?;
+            println!(
+                "跟踪 {} 已添加，disable {} 停止跟踪，traces remove {} 移除",
+                name, name, name
+            );
+        }
+        Some("remove") => {
+            let name = args.next().ok_or("缺少跟踪名称")?;
+            hooks::remove_trace(name)?;
+            println!("跟踪 {} 已移除", name);
+        }
+        Some("reset") => {
+            hooks::reset_traces(args.next())?;
+            println!("统计已清空");
+        }
+        Some("export") => {
+            let path = PathBuf::from(args.next().ok_or("缺少导出文件名")?);
+            // 相对路径相对于 DLL 所在目录
+            let path = match crate::module_dir() {
+                Some(dir) if path.is_relative() => dir.join(path),
+                _ => path,
+            };
+            let is_csv = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+            let text = if is_csv {
+                report().to_csv()
+            } else {
+                report().to_json()?
+            };
+            std::fs::write(&path, text)
+                .map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+            println!("已导出到 {}", path.display());
+        }
+        Some(name) => {
+            let report = report();
+            let trace = report
+                .traces
+                .iter()
+                .find(|trace| trace.name == name)
+                .ok_or_else(|| format!("没有名为 {} 的跟踪", name))?;
+            print!("{}", trace);
+        }
+    }
+    Ok(())
+}
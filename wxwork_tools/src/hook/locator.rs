@@ -0,0 +1,146 @@
+use std::ffi::CString;
+use std::fmt;
+use windows::Win32::Foundation::HMODULE;
+use windows::Win32::System::Diagnostics::Debug::ReadProcessMemory;
+use windows::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
+use windows::Win32::System::Threading::GetCurrentProcess;
+use windows::core::{HSTRING, PCSTR};
+use wxwork_profile::{PeImage, RttiEntry, Signature, find_signature, vtable_slot_rva};
+
+/// hook目标的定位方式
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub enum HookLocator {
+    // 导出函数
+    Export {
+        module: &'static str,
+        symbol: &'static str,
+    },
+    // 模块基址 + 偏移
+    Offset {
+        module: &'static str,
+        offset: usize,
+    },
+    // 可执行节中唯一匹配的特征码，如 "55 8B EC ?? 83"
+    Signature {
+        module: &'static str,
+        pattern: &'static str,
+    },
+    // 按RTTI名称找到虚函数表，取第 index 个槽位
+    RttiSlot {
+        module: &'static str,
+        rtti_name: &'static str,
+        offset_vtf_in_complete_class: u32,
+        offset_constructor: u32,
+        index: usize,
+    },
+}
+
+impl HookLocator {
+    /// 目标所在模块
+    pub fn module(&self) -> &'static str {
+        match self {
+            HookLocator::Export { module, .. }
+            | HookLocator::Offset { module, .. }
+            | HookLocator::Signature { module, .. }
+            | HookLocator::RttiSlot { module, .. } => module,
+        }
+    }
+
+    /// 计算目标函数地址
+    pub fn resolve(&self) -> Result<usize, String> {
+        let module = load_module(self.module())?;
+        let base = module.0 as usize;
+        match self {
+            HookLocator::Export { symbol, .. } => {
+                let symbol = CString::new(*symbol).map_err(|_| "导出名称无效".to_string())?;
+                unsafe { GetProcAddress(module, PCSTR(symbol.as_ptr() as *const u8)) }
+                    .map(|addr| addr as usize)
+                    .ok_or_else(|| format!("{} 中没有导出 {:?}", self.module(), symbol))
+            }
+            HookLocator::Offset { offset, .. } => Ok(base + offset),
+            HookLocator::Signature { pattern, .. } => {
+                let signature = Signature::parse(pattern)?;
+                let image = snapshot_module(base)?;
+                match find_signature(&image, &signature).as_slice() {
+                    [rva] => Ok(base + *rva as usize),
+                    matches => Err(format!("特征码匹配了 {} 次", matches.len())),
+                }
+            }
+            HookLocator::RttiSlot {
+                rtti_name,
+                offset_vtf_in_complete_class,
+                offset_constructor,
+                index,
+                ..
+            } => {
+                let rtti = RttiEntry {
+                    name: rtti_name.to_string(),
+                    offset_vtf_in_complete_class: *offset_vtf_in_complete_class,
+                    offset_constructor: *offset_constructor,
+                };
+                let image = snapshot_module(base)?;
+                vtable_slot_rva(&image, &rtti, *index).map(|rva| base + rva as usize)
+            }
+        }
+    }
+}
+
+impl fmt::Display for HookLocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookLocator::Export { module, symbol } => write!(f, "{}!{}", module, symbol),
+            HookLocator::Offset { module, offset } => write!(f, "{}+0x{:X}", module, offset),
+            HookLocator::Signature { module, pattern } => {
+                write!(f, "{} 特征码 [{}]", module, pattern)
+            }
+            HookLocator::RttiSlot {
+                module,
+                rtti_name,
+                index,
+                ..
+            } => write!(f, "{} {} 槽位 {}", module, rtti_name, index),
+        }
+    }
+}
+
+// 获取模块句柄，模块还没加载时加载它
+fn load_module(name: &str) -> Result<HMODULE, String> {
+    unsafe { LoadLibraryW(&HSTRING::from(name)) }
+        .map_err(|e| format!("加载模块 {} 失败: {:?}", name, e))
+}
+
+// 按页复制已加载的模块镜像，不可读的页填0
+fn snapshot_module(base: usize) -> Result<PeImage, String> {
+    const PAGE_SIZE: usize = 0x1000;
+
+    // 先读头部拿到镜像大小
+    let header = read_memory(base, PAGE_SIZE).ok_or("模块头不可读")?;
+    let size_of_image = PeImage::parse(header)?.size_of_image as usize;
+
+    let mut data = vec![0u8; size_of_image];
+    for offset in (0..size_of_image).step_by(PAGE_SIZE) {
+        let len = PAGE_SIZE.min(size_of_image - offset);
+        if let Some(page) = read_memory(base + offset, len) {
+            data[offset..offset + len].copy_from_slice(&page);
+        }
+    }
+    PeImage::parse_mapped(data)
+}
+
+// 读取本进程内存，不可读时返回 None 而不是崩溃
+fn read_memory(addr: usize, len: usize) -> Option<Vec<u8>> {
+    let mut buffer = vec![0u8; len];
+    let mut read = 0;
+    unsafe {
+        ReadProcessMemory(
+            GetCurrentProcess(),
+            addr as *const _,
+            buffer.as_mut_ptr() as *mut _,
+            len,
+            Some(&mut read),
+        )
+    }
+    .ok()?;
+    (read == len).then_some(buffer)
+}
@@ -0,0 +1,10 @@
+// hook基础设施：声明、定位、安装、启用和禁用
+pub mod locator;
+pub mod registry;
+pub mod stub;
+pub mod trampoline;
+
+pub use locator::HookLocator;
+pub use registry::{HookDef, hook_registry};
+pub use stub::StubAbi;
+pub use trampoline::Trampoline;
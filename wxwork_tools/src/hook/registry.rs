@@ -0,0 +1,659 @@
+use super::locator::HookLocator;
+use super::stub::{EntryStub, StubAbi};
+use super::trampoline::{Trampoline, TrampolineSlot, fn_addr};
+use crate::debug_log;
+use retour::RawDetour;
+use std::fmt;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 等待调用返回时的检查间隔
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// 一个hook的声明：名称、目标定位方式、hook函数和保存原始函数的单元
+pub struct HookDef {
+    pub name: &'static str,
+    pub locator: HookLocator,
+    abi: StubAbi,
+    detour: usize,
+    trampoline: &'static dyn TrampolineSlot,
+}
+
+impl HookDef {
+    /// 声明hook，`detour` 与 `trampoline` 必须是同一个函数类型 `F`，`abi` 与 `F` 一致
+    pub fn new<F: Copy + 'static>(
+        name: &'static str,
+        locator: HookLocator,
+        abi: StubAbi,
+        detour: F,
+        trampoline: &'static Trampoline<F>,
+    ) -> Self {
+        HookDef {
+            name,
+            locator,
+            abi,
+            detour: fn_addr(detour),
+            trampoline,
+        }
+    }
+}
+
+/// hook的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookStatus {
+    Registered,     // 已声明，还没安装
+    Disabled,       // 已安装但未启用
+    Enabled,        // 已启用
+    Failed(String), // 安装或启用失败
+}
+
+impl fmt::Display for HookStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookStatus::Registered => write!(f, "未安装"),
+            HookStatus::Disabled => write!(f, "已禁用"),
+            HookStatus::Enabled => write!(f, "已启用"),
+            HookStatus::Failed(e) => write!(f, "失败: {}", e),
+        }
+    }
+}
+
+/// hook信息，用于列出
+#[derive(Debug, Clone)]
+pub struct HookInfo {
+    pub name: &'static str,
+    pub locator: String,
+    pub target: Option<usize>,
+    pub status: HookStatus,
+}
+
+struct HookEntry {
+    def: HookDef,
+    target: Option<usize>,
+    stub: Option<EntryStub>, // detour 跳到这里，由它计数后调用hook函数
+    detour: Option<RawDetour>,
+    status: HookStatus,
+}
+
+/// hook注册表
+pub struct HookRegistry {
+    entries: Mutex<Vec<HookEntry>>,
+}
+
+impl HookRegistry {
+    pub const fn new() -> Self {
+        HookRegistry {
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 注册hook，名称不能重复
+    pub fn register(&self, def: HookDef) -> Result<(), String> {
+        let mut entries = self.lock()?;
+        if entries.iter().any(|entry| entry.def.name == def.name) {
+            return Err(format!("hook {} 已注册", def.name));
+        }
+        entries.push(HookEntry {
+            def,
+            target: None,
+            stub: None,
+            detour: None,
+            status: HookStatus::Registered,
+        });
+        Ok(())
+    }
+
+    /// 定位目标函数并创建hook，安装后处于禁用状态
+    pub fn install(&self, name: &str) -> Result<(), String> {
+        self.with_entry(name, install_entry)
+    }
+
+    /// 启用hook，没安装时先安装
+    pub fn enable(&self, name: &str) -> Result<(), String> {
+        self.with_entry(name, |entry| {
+            if entry.detour.is_none() {
+                install_entry(entry)?;
+            }
+            let detour = entry.detour.as_ref().ok_or("hook未安装")?;
+            // 卸载失败后重新启用时桩已经关闭
+            if let Some(stub) = entry.stub.as_ref() {
+                stub.open();
+            }
+            if let Err(e) = unsafe { detour.enable() } {
+                let message = format!("启用失败: {:?}", e);
+                entry.status = HookStatus::Failed(message.clone());
+                return Err(message);
+            }
+            entry.status = HookStatus::Enabled;
+            debug_log(&format!("hook {} 已启用", entry.def.name));
+            Ok(())
+        })
+    }
+
+    /// 禁用hook，恢复原始函数
+    pub fn disable(&self, name: &str) -> Result<(), String> {
+        self.with_entry(name, |entry| {
+            let Some(detour) = entry.detour.as_ref() else {
+                return Err("hook未安装".to_string());
+            };
+            unsafe { detour.disable() }.map_err(|e| format!("禁用失败: {:?}", e))?;
+            entry.status = HookStatus::Disabled;
+            debug_log(&format!("hook {} 已禁用", entry.def.name));
+            Ok(())
+        })
+    }
+
+    /// 卸载所有hook
+    ///
+    /// 先关闭所有入口桩并禁用hook：之后进入桩的线程直接转到原始函数，不再进入DLL。
+    /// 然后等待桩中的调用计数归零，计数在hook函数返回到桩之后才减，归零时没有线程
+    /// 还在DLL的代码中。超时说明仍有线程在hook函数里，此时保留注册表并返回错误，
+    /// 调用方不能卸载DLL。成功后释放注册表，桩和跳板不释放（可能还有线程在上面），
+    /// 重新注入时重新注册。
+    pub fn teardown(&self, timeout: Duration) -> Result<(), String> {
+        let mut entries = self.lock()?;
+        for entry in entries.iter_mut() {
+            close_entry(entry);
+        }
+        wait_for_calls(&entries, timeout)?;
+        for entry in entries.drain(..) {
+            release_entry(entry);
+        }
+        Ok(())
+    }
+
+    /// 卸载并移除一个hook，之后可以用同一个名称重新注册
+    ///
+    /// 与 `teardown` 相同，先关闭入口桩并禁用hook，等待桩中的调用返回；
+    /// 超时时保留这个hook（处于禁用状态）并返回错误。
+    pub fn remove(&self, name: &str, timeout: Duration) -> Result<(), String> {
+        let mut entries = self.lock()?;
+        let index = entries
+            .iter()
+            .position(|entry| entry.def.name == name)
+            .ok_or_else(|| format!("没有名为 {} 的hook", name))?;
+        close_entry(&mut entries[index]);
+        wait_for_calls(&entries[index..=index], timeout)?;
+        release_entry(entries.remove(index));
+        Ok(())
+    }
+
+    /// 是否还有注册的hook
+    pub fn is_empty(&self) -> bool {
+        self.lock().map_or(true, |entries| entries.is_empty())
+    }
+
+    /// 列出所有hook
+    pub fn list(&self) -> Vec<HookInfo> {
+        let Ok(entries) = self.lock() else {
+            return Vec::new();
+        };
+        entries
+            .iter()
+            .map(|entry| HookInfo {
+                name: entry.def.name,
+                locator: entry.def.locator.to_string(),
+                target: entry.target,
+                status: entry.status.clone(),
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Vec<HookEntry>>, String> {
+        self.entries
+            .lock()
+            .map_err(|_| "hook注册表锁已损坏".to_string())
+    }
+
+    fn with_entry<R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut HookEntry) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let mut entries = self.lock()?;
+        let entry = entries
+            .iter_mut()
+            .find(|entry| entry.def.name == name)
+            .ok_or_else(|| format!("没有名为 {} 的hook", name))?;
+        f(entry).map_err(|e| format!("hook {}: {}", name, e))
+    }
+}
+
+impl Default for HookRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 关闭入口桩并禁用hook，之后进入桩的线程直接转到原始函数
+fn close_entry(entry: &mut HookEntry) {
+    if let Some(stub) = entry.stub.as_ref() {
+        stub.close();
+    }
+    if let Some(detour) = entry.detour.as_ref().filter(|d| d.is_enabled()) {
+        match unsafe { detour.disable() } {
+            Ok(()) => entry.status = HookStatus::Disabled,
+            Err(e) => debug_log(&format!("禁用hook {} 失败: {:?}", entry.def.name, e)),
+        }
+    }
+}
+
+// 等待桩中的调用计数归零
+fn wait_for_calls(entries: &[HookEntry], timeout: Duration) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let busy: Vec<String> = entries
+            .iter()
+            .filter_map(|entry| {
+                let calls = entry.stub.as_ref()?.active_calls();
+                (calls > 0).then(|| format!("{}({})", entry.def.name, calls))
+            })
+            .collect();
+        if busy.is_empty() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("等待hook调用返回超时: {}", busy.join(", ")));
+        }
+        thread::sleep(DRAIN_POLL_INTERVAL);
+    }
+}
+
+// 清空跳板单元，桩和跳板不释放（可能还有线程在上面）
+fn release_entry(mut entry: HookEntry) {
+    entry.def.trampoline.clear();
+    // 关闭的桩会转到跳板上，跳板不能随 detour 释放
+    if let Some(detour) = entry.detour.take() {
+        std::mem::forget(detour);
+    }
+    debug_log(&format!("hook {} 已卸载", entry.def.name));
+}
+
+// 定位目标，生成入口桩并创建detour
+fn install_entry(entry: &mut HookEntry) -> Result<(), String> {
+    if entry.detour.is_some() {
+        return Ok(());
+    }
+    if entry.stub.is_none() {
+        entry.stub = Some(
+            EntryStub::build(entry.def.abi, entry.def.detour)
+                .inspect_err(|e| entry.status = HookStatus::Failed(e.clone()))?,
+        );
+    }
+    let stub = entry.stub.as_ref().ok_or("入口桩不存在")?;
+    let result = entry.def.locator.resolve().and_then(|target| {
+        entry.target = Some(target);
+        unsafe { RawDetour::new(target as *const (), stub.entry() as *const ()) }
+            .map_err(|e| format!("创建hook失败: {:?}", e))
+    });
+    let result = result.and_then(|detour| {
+        let original = detour.trampoline() as *const () as usize;
+        stub.set_original(original);
+        entry.def.trampoline.set(original).map(|()| detour)
+    });
+    match result {
+        Ok(detour) => {
+            debug_log(&format!(
+                "hook {} 已安装: {} -> 0x{:X}",
+                entry.def.name,
+                entry.def.locator,
+                entry.target.unwrap_or_default()
+            ));
+            entry.detour = Some(detour);
+            entry.status = HookStatus::Disabled;
+            Ok(())
+        }
+        Err(e) => {
+            entry.status = HookStatus::Failed(e.clone());
+            Err(e)
+        }
+    }
+}
+
+static HOOK_REGISTRY: HookRegistry = HookRegistry::new();
+
+/// 全局hook注册表
+pub fn hook_registry() -> &'static HookRegistry {
+    &HOOK_REGISTRY
+}
+
+#[cfg(all(test, target_arch = "x86"))]
+pub(crate) mod tests {
+    use super::*;
+    use std::hint::black_box;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::core::PCWSTR;
+
+    pub(crate) type Target = extern "stdcall" fn(u32) -> u32;
+
+    /// 让hook函数停在里面，直到放行，用来模拟卸载时还没返回的调用
+    pub(crate) struct Gate {
+        entered: AtomicBool,
+        released: AtomicBool,
+    }
+
+    impl Gate {
+        pub(crate) const fn new() -> Self {
+            Gate {
+                entered: AtomicBool::new(false),
+                released: AtomicBool::new(false),
+            }
+        }
+
+        /// 在hook函数中调用，放行前不返回
+        pub(crate) fn hold(&self) {
+            self.entered.store(true, Ordering::SeqCst);
+            while !self.released.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        /// 等待有线程进入hook函数
+        pub(crate) fn wait_entered(&self) {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !self.entered.load(Ordering::SeqCst) {
+                assert!(Instant::now() < deadline, "hook函数没有被调用");
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        pub(crate) fn release(&self) {
+            self.released.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// 定位测试程序自己的函数：模块用测试程序的完整路径，偏移相对于主模块基址
+    pub(crate) fn local_target(target: Target) -> HookLocator {
+        let path = std::env::current_exe().unwrap();
+        let module: &'static str = Box::leak(path.to_string_lossy().into_owned().into_boxed_str());
+        let base = unsafe { GetModuleHandleW(PCWSTR::null()) }.unwrap().0 as usize;
+        HookLocator::Offset {
+            module,
+            offset: target as usize - base,
+        }
+    }
+
+    // 找不到目标的定位方式
+    fn missing_target() -> HookLocator {
+        HookLocator::Export {
+            module: "kernel32.dll",
+            symbol: "wxwork_tools_missing_export",
+        }
+    }
+
+    fn status(registry: &HookRegistry, name: &str) -> HookStatus {
+        registry
+            .list()
+            .into_iter()
+            .find(|info| info.name == name)
+            .map(|info| info.status)
+            .unwrap()
+    }
+
+    /// 经过函数指针调用，避免被内联
+    pub(crate) fn call(target: Target, value: u32) -> u32 {
+        black_box(target)(value)
+    }
+
+    // 每个测试hook自己的目标函数，函数体不同，避免被链接器合并
+    #[inline(never)]
+    extern "stdcall" fn toggle_target(value: u32) -> u32 {
+        black_box(value).wrapping_mul(3).wrapping_add(1)
+    }
+
+    static TOGGLE: Trampoline<Target> = Trampoline::new();
+
+    extern "stdcall" fn toggle_detour(value: u32) -> u32 {
+        let call = TOGGLE.enter();
+        call.original().map_or(0, |original| original(value) + 1000)
+    }
+
+    #[inline(never)]
+    extern "stdcall" fn enable_target(value: u32) -> u32 {
+        black_box(value).wrapping_mul(5).wrapping_add(2)
+    }
+
+    static ENABLE: Trampoline<Target> = Trampoline::new();
+
+    extern "stdcall" fn enable_detour(value: u32) -> u32 {
+        let call = ENABLE.enter();
+        call.original().map_or(0, |original| original(value) + 1000)
+    }
+
+    static UNUSED: Trampoline<Target> = Trampoline::new();
+
+    extern "stdcall" fn unused_detour(value: u32) -> u32 {
+        let call = UNUSED.enter();
+        call.original().map_or(value, |original| original(value))
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let registry = HookRegistry::new();
+        let def = || {
+            HookDef::new(
+                "duplicate",
+                missing_target(),
+                StubAbi::Stdcall(1),
+                unused_detour as Target,
+                &UNUSED,
+            )
+        };
+        registry.register(def()).unwrap();
+        let error = registry.register(def()).unwrap_err();
+        assert!(error.contains("已注册"), "{}", error);
+        assert_eq!(registry.list().len(), 1);
+        assert_eq!(status(&registry, "duplicate"), HookStatus::Registered);
+    }
+
+    #[test]
+    fn reports_unknown_names() {
+        let registry = HookRegistry::new();
+        let results = [
+            registry.install("missing"),
+            registry.enable("missing"),
+            registry.disable("missing"),
+            registry.remove("missing", Duration::ZERO),
+        ];
+        for result in results {
+            assert_eq!(result.unwrap_err(), "没有名为 missing 的hook");
+        }
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn records_locator_failure() {
+        let registry = HookRegistry::new();
+        registry
+            .register(HookDef::new(
+                "unresolved",
+                missing_target(),
+                StubAbi::Stdcall(1),
+                unused_detour as Target,
+                &UNUSED,
+            ))
+            .unwrap();
+
+        // 启用时先安装，找不到目标时记录失败，不写入跳板
+        assert!(registry.enable("unresolved").is_err());
+        assert!(matches!(
+            status(&registry, "unresolved"),
+            HookStatus::Failed(_)
+        ));
+        assert_eq!(registry.list()[0].target, None);
+        let error = registry.disable("unresolved").unwrap_err();
+        assert!(error.contains("hook未安装"), "{}", error);
+        assert!(UNUSED.enter().original().is_none());
+    }
+
+    #[test]
+    fn enable_installs_first() {
+        let registry = HookRegistry::new();
+        registry
+            .register(HookDef::new(
+                "enable",
+                local_target(enable_target),
+                StubAbi::Stdcall(1),
+                enable_detour as Target,
+                &ENABLE,
+            ))
+            .unwrap();
+        assert_eq!(call(enable_target, 1), 7);
+
+        registry.enable("enable").unwrap();
+        assert_eq!(status(&registry, "enable"), HookStatus::Enabled);
+        assert_eq!(registry.list()[0].target, Some(enable_target as usize));
+        assert_eq!(call(enable_target, 1), 1007);
+
+        registry.teardown(Duration::from_secs(1)).unwrap();
+        assert_eq!(call(enable_target, 1), 7);
+    }
+
+    #[test]
+    fn toggles_state() {
+        let registry = HookRegistry::new();
+        registry
+            .register(HookDef::new(
+                "toggle",
+                local_target(toggle_target),
+                StubAbi::Stdcall(1),
+                toggle_detour as Target,
+                &TOGGLE,
+            ))
+            .unwrap();
+        assert_eq!(status(&registry, "toggle"), HookStatus::Registered);
+
+        registry.install("toggle").unwrap();
+        assert_eq!(status(&registry, "toggle"), HookStatus::Disabled);
+        assert_eq!(call(toggle_target, 2), 7);
+        // 重复安装不重新创建hook
+        registry.install("toggle").unwrap();
+
+        for _ in 0..2 {
+            registry.enable("toggle").unwrap();
+            assert_eq!(status(&registry, "toggle"), HookStatus::Enabled);
+            assert_eq!(call(toggle_target, 2), 1007);
+
+            registry.disable("toggle").unwrap();
+            assert_eq!(status(&registry, "toggle"), HookStatus::Disabled);
+            assert_eq!(call(toggle_target, 2), 7);
+        }
+
+        registry.remove("toggle", Duration::from_secs(1)).unwrap();
+        assert!(registry.is_empty());
+    }
+
+    #[inline(never)]
+    extern "stdcall" fn remove_target(value: u32) -> u32 {
+        black_box(value).wrapping_mul(7).wrapping_add(3)
+    }
+
+    static REMOVE: Trampoline<Target> = Trampoline::new();
+    static REMOVE_GATE: Gate = Gate::new();
+
+    extern "stdcall" fn remove_detour(value: u32) -> u32 {
+        let call = REMOVE.enter();
+        REMOVE_GATE.hold();
+        call.original().map_or(0, |original| original(value) + 1000)
+    }
+
+    fn remove_def() -> HookDef {
+        HookDef::new(
+            "remove",
+            local_target(remove_target),
+            StubAbi::Stdcall(1),
+            remove_detour as Target,
+            &REMOVE,
+        )
+    }
+
+    #[test]
+    fn remove_waits_for_active_calls() {
+        let registry: &'static HookRegistry = Box::leak(Box::new(HookRegistry::new()));
+        registry.register(remove_def()).unwrap();
+        registry.enable("remove").unwrap();
+        let caller = thread::spawn(|| call(remove_target, 1));
+        REMOVE_GATE.wait_entered();
+
+        // 调用没返回时超时，hook保留并处于禁用状态
+        let error = registry
+            .remove("remove", Duration::from_millis(50))
+            .unwrap_err();
+        assert!(
+            error.contains("超时") && error.contains("remove(1)"),
+            "{}",
+            error
+        );
+        assert_eq!(status(registry, "remove"), HookStatus::Disabled);
+        // 新的调用直接进入原始函数
+        assert_eq!(call(remove_target, 1), 10);
+
+        REMOVE_GATE.release();
+        assert_eq!(caller.join().unwrap(), 1010);
+        registry.remove("remove", Duration::from_secs(1)).unwrap();
+        assert!(registry.is_empty());
+
+        // 移除后可以用同一个名称重新注册
+        registry.register(remove_def()).unwrap();
+        registry.enable("remove").unwrap();
+        assert_eq!(call(remove_target, 1), 1010);
+        registry.teardown(Duration::from_secs(1)).unwrap();
+        assert_eq!(call(remove_target, 1), 10);
+    }
+
+    #[inline(never)]
+    extern "stdcall" fn teardown_target(value: u32) -> u32 {
+        black_box(value).wrapping_mul(11).wrapping_add(4)
+    }
+
+    static TEARDOWN: Trampoline<Target> = Trampoline::new();
+    static TEARDOWN_GATE: Gate = Gate::new();
+
+    extern "stdcall" fn teardown_detour(value: u32) -> u32 {
+        let call = TEARDOWN.enter();
+        TEARDOWN_GATE.hold();
+        call.original().map_or(0, |original| original(value) + 1000)
+    }
+
+    #[test]
+    fn teardown_keeps_registry_until_calls_return() {
+        assert!(HookRegistry::new().teardown(Duration::ZERO).is_ok());
+
+        let registry: &'static HookRegistry = Box::leak(Box::new(HookRegistry::new()));
+        registry
+            .register(HookDef::new(
+                "teardown",
+                local_target(teardown_target),
+                StubAbi::Stdcall(1),
+                teardown_detour as Target,
+                &TEARDOWN,
+            ))
+            .unwrap();
+        registry
+            .register(HookDef::new(
+                "teardown_unresolved",
+                missing_target(),
+                StubAbi::Stdcall(1),
+                unused_detour as Target,
+                &UNUSED,
+            ))
+            .unwrap();
+        registry.enable("teardown").unwrap();
+        let caller = thread::spawn(|| call(teardown_target, 1));
+        TEARDOWN_GATE.wait_entered();
+
+        let error = registry.teardown(Duration::from_millis(50)).unwrap_err();
+        assert!(error.contains("teardown(1)"), "{}", error);
+        assert_eq!(registry.list().len(), 2);
+        assert_eq!(status(registry, "teardown"), HookStatus::Disabled);
+        assert_eq!(call(teardown_target, 1), 15);
+
+        TEARDOWN_GATE.release();
+        assert_eq!(caller.join().unwrap(), 1015);
+        registry.teardown(Duration::from_secs(1)).unwrap();
+        assert!(registry.is_empty());
+        assert!(TEARDOWN.enter().original().is_none());
+    }
+}
@@ -0,0 +1,228 @@
+// hook的入口桩：detour 跳到桩上，由桩计数后调用hook函数
+//
+// 计数在hook函数返回到桩里之后才减，计数为0时没有线程还在DLL的代码中；
+// 桩关闭后新进入的线程直接转到原始函数，不再进入DLL。
+// 桩分配在DLL之外并且不释放，卸载DLL后仍在桩里的线程落在有效的代码上。
+use std::sync::atomic::{AtomicU32, Ordering};
+use windows::Win32::System::Diagnostics::Debug::FlushInstructionCache;
+use windows::Win32::System::Memory::{
+    MEM_COMMIT, MEM_RESERVE, PAGE_EXECUTE_READWRITE, VirtualAlloc,
+};
+use windows::Win32::System::Threading::GetCurrentProcess;
+
+// 桩的数据区：调用计数、关闭标志、hook函数地址、原始函数跳板地址
+const COUNTER_OFFSET: usize = 0x00;
+const CLOSED_OFFSET: usize = 0x04;
+const PROXY_OFFSET: usize = 0x08;
+const ORIGINAL_OFFSET: usize = 0x0C;
+const CODE_OFFSET: usize = 0x10;
+const STUB_SIZE: usize = 0x100;
+
+// 重新压入参数用 [esp+disp8] 寻址，栈上参数最多31个
+const MAX_STACK_ARGS: u8 = 31;
+
+/// hook函数的调用约定和栈上参数个数，桩据此重新压入参数并清理栈
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StubAbi {
+    Cdecl(u8),    // 调用者清理栈
+    Stdcall(u8),  // 被调用者清理栈
+    Thiscall(u8), // this 在 ecx 中，栈上参数个数不含 this
+}
+
+impl StubAbi {
+    fn stack_args(self) -> u8 {
+        match self {
+            StubAbi::Cdecl(count) | StubAbi::Stdcall(count) | StubAbi::Thiscall(count) => count,
+        }
+    }
+}
+
+/// 一个hook的入口桩
+pub struct EntryStub {
+    base: usize,
+}
+
+impl EntryStub {
+    /// 分配并生成调用 `proxy` 的入口桩，原始函数的跳板地址在创建detour后用 `set_original` 写入
+    pub fn build(abi: StubAbi, proxy: usize) -> Result<Self, String> {
+        let args = abi.stack_args();
+        if args > MAX_STACK_ARGS {
+            return Err(format!("栈上参数最多 {} 个", MAX_STACK_ARGS));
+        }
+        let base = unsafe {
+            VirtualAlloc(
+                None,
+                STUB_SIZE,
+                MEM_COMMIT | MEM_RESERVE,
+                PAGE_EXECUTE_READWRITE,
+            )
+        } as usize;
+        if base == 0 {
+            return Err("分配hook入口桩失败".to_string());
+        }
+        let stub = EntryStub { base };
+        stub.field(PROXY_OFFSET)
+            .store(proxy as u32, Ordering::Release);
+
+        let code = stub_code(base as u32, abi);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                code.as_ptr(),
+                (base + CODE_OFFSET) as *mut u8,
+                code.len(),
+            );
+            let _ = FlushInstructionCache(
+                GetCurrentProcess(),
+                Some((base + CODE_OFFSET) as *const _),
+                code.len(),
+            );
+        }
+        Ok(stub)
+    }
+
+    /// detour 跳转的目标
+    pub fn entry(&self) -> usize {
+        self.base + CODE_OFFSET
+    }
+
+    /// 写入原始函数的跳板地址，桩关闭后转到这里
+    pub fn set_original(&self, addr: usize) {
+        self.field(ORIGINAL_OFFSET)
+            .store(addr as u32, Ordering::Release);
+    }
+
+    /// 关闭桩：之后进入的线程直接转到原始函数
+    pub fn close(&self) {
+        self.field(CLOSED_OFFSET).store(1, Ordering::SeqCst);
+    }
+
+    /// 重新打开桩，重新启用hook时调用
+    pub fn open(&self) {
+        self.field(CLOSED_OFFSET).store(0, Ordering::SeqCst);
+    }
+
+    /// 正在桩中执行的调用数，包括还没返回的hook函数
+    pub fn active_calls(&self) -> u32 {
+        self.field(COUNTER_OFFSET).load(Ordering::SeqCst)
+    }
+
+    fn field(&self, offset: usize) -> &AtomicU32 {
+        unsafe { &*((self.base + offset) as *const AtomicU32) }
+    }
+}
+
+// 生成桩的代码，`base` 为桩的地址
+//
+//     lock inc dword ptr [counter]
+//     cmp dword ptr [closed], 0
+//     je call_proxy
+//     lock dec dword ptr [counter]      ; 已关闭，转到原始函数
+//     jmp dword ptr [original]
+// call_proxy:
+//     push dword ptr [esp+4*n]          ; 重复n次，按原顺序重新压入参数
+//     call dword ptr [proxy]            ; ecx 中的 this 保持不变
+//     add esp, 4*n                      ; 只有 cdecl
+//     lock dec dword ptr [counter]      ; 不改变 edx:eax 中的返回值
+//     ret / ret 4*n
+fn stub_code(base: u32, abi: StubAbi) -> Vec<u8> {
+    let address = |offset: usize| (base + offset as u32).to_le_bytes();
+    let args = abi.stack_args();
+    let mut code = Vec::with_capacity(64 + 4 * args as usize);
+    code.extend_from_slice(&[0xF0, 0xFF, 0x05]);
+    code.extend_from_slice(&address(COUNTER_OFFSET));
+    code.extend_from_slice(&[0x83, 0x3D]);
+    code.extend_from_slice(&address(CLOSED_OFFSET));
+    code.push(0x00);
+    code.extend_from_slice(&[0x74, 13]);
+    code.extend_from_slice(&[0xF0, 0xFF, 0x0D]);
+    code.extend_from_slice(&address(COUNTER_OFFSET));
+    code.extend_from_slice(&[0xFF, 0x25]);
+    code.extend_from_slice(&address(ORIGINAL_OFFSET));
+
+    for _ in 0..args {
+        code.extend_from_slice(&[0xFF, 0x74, 0x24, 4 * args]);
+    }
+    code.extend_from_slice(&[0xFF, 0x15]);
+    code.extend_from_slice(&address(PROXY_OFFSET));
+    if matches!(abi, StubAbi::Cdecl(_)) && args > 0 {
+        code.extend_from_slice(&[0x83, 0xC4, 4 * args]);
+    }
+    code.extend_from_slice(&[0xF0, 0xFF, 0x0D]);
+    code.extend_from_slice(&address(COUNTER_OFFSET));
+    match abi {
+        StubAbi::Stdcall(1..) | StubAbi::Thiscall(1..) => {
+            code.push(0xC2);
+            code.extend_from_slice(&(4 * args as u16).to_le_bytes());
+        }
+        _ => code.push(0xC3),
+    }
+    code
+}
+
+#[cfg(all(test, target_arch = "x86"))]
+mod tests {
+    use super::*;
+    use std::ffi::c_void;
+    use std::sync::atomic::AtomicUsize;
+
+    static STUB_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    extern "stdcall" fn stdcall_proxy(a: u32, b: u32, c: u32) -> u64 {
+        STUB_CALLS.fetch_add(1, Ordering::SeqCst);
+        ((a as u64) << 32) | (b * 10 + c) as u64
+    }
+
+    extern "cdecl" fn cdecl_proxy(a: u32, b: u32) -> u32 {
+        STUB_CALLS.fetch_add(1, Ordering::SeqCst);
+        a - b
+    }
+
+    extern "thiscall" fn thiscall_proxy(this: *mut c_void, a: u32) -> u32 {
+        STUB_CALLS.fetch_add(1, Ordering::SeqCst);
+        this as u32 + a
+    }
+
+    extern "stdcall" fn stdcall_original(a: u32, b: u32, c: u32) -> u64 {
+        (a + b + c) as u64
+    }
+
+    #[test]
+    fn forwards_arguments_and_return_values() {
+        let stub = EntryStub::build(StubAbi::Stdcall(3), stdcall_proxy as usize).unwrap();
+        let f: extern "stdcall" fn(u32, u32, u32) -> u64 =
+            unsafe { std::mem::transmute(stub.entry()) };
+        assert_eq!(f(7, 2, 3), (7 << 32) | 23);
+        assert_eq!(stub.active_calls(), 0);
+
+        let stub = EntryStub::build(StubAbi::Cdecl(2), cdecl_proxy as usize).unwrap();
+        let f: extern "cdecl" fn(u32, u32) -> u32 = unsafe { std::mem::transmute(stub.entry()) };
+        assert_eq!(f(10, 4), 6);
+        assert_eq!(stub.active_calls(), 0);
+
+        let stub = EntryStub::build(StubAbi::Thiscall(1), thiscall_proxy as usize).unwrap();
+        let f: extern "thiscall" fn(*mut c_void, u32) -> u32 =
+            unsafe { std::mem::transmute(stub.entry()) };
+        assert_eq!(f(0x1000 as *mut c_void, 0x20), 0x1020);
+        assert_eq!(stub.active_calls(), 0);
+    }
+
+    #[test]
+    fn closed_stub_calls_original() {
+        let stub = EntryStub::build(StubAbi::Stdcall(3), stdcall_proxy as usize).unwrap();
+        stub.set_original(stdcall_original as usize);
+        let f: extern "stdcall" fn(u32, u32, u32) -> u64 =
+            unsafe { std::mem::transmute(stub.entry()) };
+        let before = STUB_CALLS.load(Ordering::SeqCst);
+        stub.close();
+        assert_eq!(f(1, 2, 3), 6);
+        assert_eq!(stub.active_calls(), 0);
+        stub.open();
+        assert_eq!(f(1, 2, 3), (1 << 32) | 23);
+        assert!(STUB_CALLS.load(Ordering::SeqCst) > before);
+    }
+
+    #[test]
+    fn rejects_too_many_arguments() {
+        assert!(EntryStub::build(StubAbi::Cdecl(32), cdecl_proxy as usize).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+thread_local! {
+    // 当前线程正在执行的hook，按跳板单元地址区分
+    static ENTERED: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 注册表写入跳板地址用的接口，与函数类型无关
+pub trait TrampolineSlot: Sync {
+    /// 写入跳板地址，只能写一次，卸载后才能重新写入
+    fn set(&self, addr: usize) -> Result<(), String>;
+    /// 卸载后清空跳板地址
+    fn clear(&self);
+}
+
+/// 保存原始函数跳板的静态单元，hook函数通过它调用原始函数
+///
+/// `F` 是被hook函数的函数指针类型，读写不加锁，调用原始函数时不持有任何锁。
+/// hook函数开头调用 `enter` 拿到 `ActiveCall`，原始函数内部再次触发同一个hook时
+/// `is_nested` 为真，hook函数应直接调用原始函数。卸载时等待的调用计数在入口桩中（见 `stub`）。
+pub struct Trampoline<F> {
+    addr: AtomicUsize,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Copy> Trampoline<F> {
+    pub const fn new() -> Self {
+        Trampoline {
+            addr: AtomicUsize::new(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// 进入hook函数，返回的守卫在hook函数返回时释放
+    pub fn enter(&self) -> ActiveCall<'_, F> {
+        let key = self as *const Self as usize;
+        // 线程退出、线程局部变量已销毁时按重入处理，只调用原始函数
+        let nested = ENTERED
+            .try_with(|entered| {
+                let mut entered = entered.borrow_mut();
+                if entered.contains(&key) {
+                    return true;
+                }
+                entered.push(key);
+                false
+            })
+            .unwrap_or(true);
+        ActiveCall {
+            trampoline: self,
+            nested,
+        }
+    }
+
+    /// 原始函数，hook还没安装或已经卸载时返回 None
+    fn original(&self) -> Option<F> {
+        let addr = self.addr.load(Ordering::Acquire);
+        if addr == 0 {
+            return None;
+        }
+        Some(unsafe { mem::transmute_copy::<usize, F>(&addr) })
+    }
+}
+
+impl<F: Copy> Default for Trampoline<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// PhantomData<F> 对函数指针类型没有影响，单元本身只保存地址
+unsafe impl<F> Sync for Trampoline<F> {}
+
+impl<F: Copy> TrampolineSlot for Trampoline<F> {
+    fn set(&self, addr: usize) -> Result<(), String> {
+        match self
+            .addr
+            .compare_exchange(0, addr, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => Ok(()),
+            Err(current) if current == addr => Ok(()),
+            Err(current) => Err(format!("跳板已指向 0x{:X}", current)),
+        }
+    }
+
+    fn clear(&self) {
+        self.addr.store(0, Ordering::Release);
+    }
+}
+
+/// 一次正在进行的hook调用
+pub struct ActiveCall<'a, F: Copy> {
+    trampoline: &'a Trampoline<F>,
+    nested: bool,
+}
+
+impl<F: Copy> ActiveCall<'_, F> {
+    /// 当前线程已经在这个hook函数里（原始函数又触发了hook）
+    pub fn is_nested(&self) -> bool {
+        self.nested
+    }
+
+    /// 原始函数
+    pub fn original(&self) -> Option<F> {
+        self.trampoline.original()
+    }
+}
+
+impl<F: Copy> Drop for ActiveCall<'_, F> {
+    fn drop(&mut self) {
+        if !self.nested {
+            let key = self.trampoline as *const Trampoline<F> as usize;
+            let _ = ENTERED.try_with(|entered| entered.borrow_mut().retain(|k| *k != key));
+        }
+    }
+}
+
+/// 函数指针转地址
+pub fn fn_addr<F: Copy>(f: F) -> usize {
+    assert_eq!(
+        mem::size_of::<F>(),
+        mem::size_of::<usize>(),
+        "F 必须是函数指针"
+    );
+    unsafe { mem::transmute_copy::<F, usize>(&f) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::thread;
+
+    type Target = extern "C" fn(u32) -> u32;
+
+    extern "C" fn original(value: u32) -> u32 {
+        value + 1
+    }
+
+    #[test]
+    fn nested_enter_is_reported() {
+        let trampoline = Trampoline::<Target>::new();
+        let other = Trampoline::<Target>::new();
+        let outer = trampoline.enter();
+        assert!(!outer.is_nested());
+        {
+            let inner = trampoline.enter();
+            assert!(inner.is_nested());
+            // 其他hook不受影响
+            assert!(!other.enter().is_nested());
+        }
+        // 内层释放后外层仍在执行
+        assert!(trampoline.enter().is_nested());
+        drop(outer);
+        assert!(!trampoline.enter().is_nested());
+    }
+
+    #[test]
+    fn drop_resets_after_unwind() {
+        let trampoline = Trampoline::<Target>::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _call = trampoline.enter();
+            panic!("hook函数出错");
+        }));
+        assert!(result.is_err());
+        assert!(!trampoline.enter().is_nested());
+    }
+
+    #[test]
+    fn other_threads_are_not_nested() {
+        static SHARED: Trampoline<Target> = Trampoline::new();
+        let _call = SHARED.enter();
+        let nested = thread::spawn(|| SHARED.enter().is_nested()).join().unwrap();
+        assert!(!nested);
+        assert!(SHARED.enter().is_nested());
+    }
+
+    #[test]
+    fn set_only_once_until_cleared() {
+        let trampoline = Trampoline::<Target>::new();
+        assert!(trampoline.enter().original().is_none());
+
+        let addr = fn_addr(original as Target);
+        trampoline.set(addr).unwrap();
+        // 同一个地址重复写入没有影响，不同地址拒绝
+        trampoline.set(addr).unwrap();
+        let error = trampoline.set(addr + 1).unwrap_err();
+        assert!(error.contains("跳板已指向"), "{}", error);
+        let call = trampoline.enter();
+        assert_eq!(call.original().map(|f| f(1)), Some(2));
+        drop(call);
+
+        trampoline.clear();
+        assert!(trampoline.enter().original().is_none());
+        trampoline.set(addr + 1).unwrap();
+    }
+}
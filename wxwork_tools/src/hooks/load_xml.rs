@@ -0,0 +1,348 @@
+use crate::debug_log;
+use crate::hook::{HookDef, HookLocator, StubAbi, Trampoline};
+use crate::utils;
+use serde::Deserialize;
+use std::ffi::c_void;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wxwork_ui::{Archive, DumpJob, DumpLayout, DumpWriter, OverrideStore};
+
+// 默认保存目录名
+const DEFAULT_DUMP_DIR: &str = "wxwork_ui_rs";
+
+// 默认存档目录名
+const DEFAULT_ARCHIVE_DIR: &str = "wxwork_ui_archive";
+
+// 默认替换目录名
+const DEFAULT_OVERRIDE_DIR: &str = "wxwork_ui_override";
+
+// 待保存的XML队列长度，启动时会连续加载几百个文件
+const DUMP_QUEUE_CAPACITY: usize = 1024;
+
+// 客户端使用的 C 运行库，VS2015 以后是 ucrtbase.dll
+const DEFAULT_CRT_MODULE: &str = "ucrtbase.dll";
+
+/// 抓到的XML的保存配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct XmlDumpConfig {
+    pub root: Option<PathBuf>, // 保存目录，相对路径相对于 DLL 所在目录，默认 wxwork_ui_rs
+    pub bom: bool,             // 文件开头写 UTF-8 BOM（C++ 版本的工具写 BOM）
+    pub archive: bool,         // 同时按版本去重保存到存档
+    pub archive_root: Option<PathBuf>, // 存档目录，相对路径相对于 DLL 所在目录，默认 wxwork_ui_archive
+}
+
+impl Default for XmlDumpConfig {
+    fn default() -> Self {
+        XmlDumpConfig {
+            root: None,
+            bom: false,
+            archive: true,
+            archive_root: None,
+        }
+    }
+}
+
+/// 替换客户端读取的XML的配置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct XmlOverrideConfig {
+    pub enabled: bool,        // 启动时启用替换，控制台 overrides on/off 可以随时切换
+    pub dir: Option<PathBuf>, // 替换目录，相对路径相对于 DLL 所在目录，默认 wxwork_ui_override
+    pub crt: String,          // 客户端释放字符串用的 C 运行库，替换的内容从它的堆上分配
+}
+
+impl Default for XmlOverrideConfig {
+    fn default() -> Self {
+        XmlOverrideConfig {
+            enabled: false,
+            dir: None,
+            crt: DEFAULT_CRT_MODULE.to_string(),
+        }
+    }
+}
+
+// 替换目录和客户端的 CRT，CRT 找不到时无法替换
+static OVERRIDES: OnceLock<OverrideStore> = OnceLock::new();
+static CRT_HEAP: OnceLock<Result<utils::CrtHeap, String>> = OnceLock::new();
+static OVERRIDE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// 保存XML的后台线程，保存目录和存档都由它写入；启动失败时不保存
+static DUMP_WRITER: RwLock<Option<DumpWriter>> = RwLock::new(None);
+
+/// 设置保存目录、格式和存档并启动保存线程，只在安装hook前调用
+pub fn configure_dump(config: &XmlDumpConfig, module_dir: Option<&Path>, client_version: &str) {
+    let resolve = |path: Option<&PathBuf>, default: &str| {
+        let path = path.cloned().unwrap_or_else(|| PathBuf::from(default));
+        match module_dir {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path,
+        }
+    };
+
+    let root = resolve(config.root.as_ref(), DEFAULT_DUMP_DIR);
+    debug_log(&format!("XML保存目录: {}", root.display()));
+    let layout = DumpLayout::new(root, config.bom);
+    let archive = config
+        .archive
+        .then(|| open_archive(&resolve(config.archive_root.as_ref(), DEFAULT_ARCHIVE_DIR)))
+        .flatten()
+        .map(|archive| (archive, client_version.to_string()));
+
+    let writer = match DumpWriter::start(layout, archive, DUMP_QUEUE_CAPACITY, |event| {
+        debug_log(&event)
+    }) {
+        Ok(writer) => writer,
+        Err(e) => {
+            debug_log(&format!("{}，不保存XML", e));
+            return;
+        }
+    };
+    if let Ok(mut current) = DUMP_WRITER.write() {
+        if let Some(previous) = current.replace(writer) {
+            previous.shutdown();
+        }
+    }
+}
+
+/// 保存完队列中的XML后停止保存线程，卸载DLL前调用
+pub fn stop_dump() {
+    let writer = DUMP_WRITER
+        .write()
+        .ok()
+        .and_then(|mut writer| writer.take());
+    if let Some(writer) = writer {
+        writer.shutdown();
+    }
+}
+
+/// 关闭保存队列但不等待保存线程，卸载DLL时在 DllMain 中调用，拿不到锁时什么也不做
+pub fn close_dump() {
+    let writer = DUMP_WRITER
+        .try_write()
+        .ok()
+        .and_then(|mut writer| writer.take());
+    if let Some(writer) = writer {
+        writer.close();
+    }
+}
+
+// 打开存档，失败时不存档
+fn open_archive(archive_root: &Path) -> Option<Archive> {
+    match Archive::open(archive_root) {
+        Ok(archive) => {
+            debug_log(&format!(
+                "XML存档目录: {}，已有 {} 项",
+                archive_root.display(),
+                archive.entries().len()
+            ));
+            if archive.skipped_lines() > 0 {
+                debug_log(&format!(
+                    "存档清单中有 {} 行无法解析，已跳过",
+                    archive.skipped_lines()
+                ));
+            }
+            Some(archive)
+        }
+        Err(e) => {
+            debug_log(&format!("打开XML存档失败，不存档: {}", e));
+            None
+        }
+    }
+}
+
+/// 设置替换目录，只在安装hook前生效一次
+pub fn configure_override(config: &XmlOverrideConfig, module_dir: Option<&Path>) {
+    let dir = config
+        .dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_OVERRIDE_DIR));
+    let dir = match module_dir {
+        Some(module_dir) if dir.is_relative() => module_dir.join(dir),
+        _ => dir,
+    };
+    debug_log(&format!(
+        "XML替换目录: {}（{}）",
+        dir.display(),
+        if config.enabled {
+            "启用"
+        } else {
+            "未启用"
+        }
+    ));
+    let _ = OVERRIDES.set(OverrideStore::new(dir));
+    let crt = if config.crt.is_empty() {
+        DEFAULT_CRT_MODULE
+    } else {
+        &config.crt
+    };
+    let _ = CRT_HEAP.set(utils::CrtHeap::open(crt));
+    OVERRIDE_ENABLED.store(config.enabled, Ordering::Release);
+}
+
+/// 启用或禁用替换
+pub fn set_override_enabled(enabled: bool) -> Result<(), String> {
+    if enabled {
+        crt_heap()?;
+    }
+    OVERRIDE_ENABLED.store(enabled, Ordering::Release);
+    Ok(())
+}
+
+/// 替换是否启用
+pub fn override_enabled() -> bool {
+    OVERRIDE_ENABLED.load(Ordering::Acquire)
+}
+
+/// 对替换目录执行操作，例如列出文件、查看替换次数、清空缓存
+pub fn with_overrides<T>(f: impl FnOnce(&OverrideStore) -> T) -> T {
+    f(overrides())
+}
+
+// 没有配置时使用当前目录下的 wxwork_ui_override
+fn overrides() -> &'static OverrideStore {
+    OVERRIDES.get_or_init(|| OverrideStore::new(DEFAULT_OVERRIDE_DIR))
+}
+
+fn crt_heap() -> Result<&'static utils::CrtHeap, String> {
+    CRT_HEAP
+        .get_or_init(|| utils::CrtHeap::open(DEFAULT_CRT_MODULE))
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+// 替换目录中有对应的文件时，把返回的字符串换成文件的内容
+unsafe fn override_xml(path: &str, result: *mut utils::WString) {
+    if !override_enabled() {
+        return;
+    }
+    // 检查和读取替换文件时不持有锁，不会阻塞其他线程的 LoadXML
+    let found = match overrides().lookup(path) {
+        Ok(Some(found)) => found,
+        Ok(None) => return,
+        Err(e) => {
+            debug_log(&format!("XML替换失败，使用原内容: {}: {}", path, e));
+            return;
+        }
+    };
+    if found.reloaded {
+        debug_log(&format!("XML替换文件已加载: {}", found.file.display()));
+    }
+    if let Some(warning) = &found.warning {
+        debug_log(&format!(
+            "XML替换文件格式有误，仍然替换: {}: {}",
+            path, warning
+        ));
+    }
+    let heap = match crt_heap() {
+        Ok(heap) => heap,
+        Err(e) => {
+            debug_log(&format!("XML替换失败，使用原内容: {}", e));
+            return;
+        }
+    };
+    match unsafe { (*result).assign_with(&found.content, heap) } {
+        Ok(()) => debug_log(&format!(
+            "XML已替换: {} <- {} ({} 字符)",
+            path,
+            found.file.display(),
+            found.content.len()
+        )),
+        Err(e) => debug_log(&format!("XML替换失败，使用原内容: {}", e)),
+    }
+}
+
+// 交给保存线程保存和存档，哈希和文件读写都不在被hook的线程上
+fn submit_dump(path: &str, content: String, flag: i32) {
+    let Ok(writer) = DUMP_WRITER.read() else {
+        return;
+    };
+    let Some(writer) = writer.as_ref() else {
+        return;
+    };
+    let seen_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64);
+    let job = DumpJob {
+        path: path.to_string(),
+        content,
+        flag,
+        seen_at,
+    };
+    if !writer.submit(job) {
+        debug_log(&format!(
+            "XML保存队列已满，丢弃: {}（共丢弃 {} 个）",
+            path,
+            writer.dropped()
+        ));
+    }
+}
+
+// 返回类型是std::wstring* DuiLib::CResManager::LoadXML(void*,wchar_t const *, int);
+type LoadXMLFn =
+    extern "cdecl" fn(_this: *const c_void, path: *const u16, flag: i32) -> *const utils::WString;
+
+static ORIGINAL_LOAD_XML: Trampoline<LoadXMLFn> = Trampoline::new();
+
+/// hook声明：duilib.dll 导出的 CResManager::LoadXML
+pub fn hook_def() -> HookDef {
+    HookDef::new(
+        "load_xml",
+        HookLocator::Export {
+            module: "duilib.dll",
+            symbol: "?LoadXML@CResManager@DuiLib@@SA?AV?$basic_string@_WU?$char_traits@_W@std@@V?$allocator@_W@2@@std@@PB_WH@Z",
+        },
+        StubAbi::Cdecl(3),
+        load_xml_proxy as LoadXMLFn,
+        &ORIGINAL_LOAD_XML,
+    )
+}
+
+// 我们的hook函数实现
+extern "cdecl" fn load_xml_proxy(
+    _this: *const c_void,
+    path: *const u16,
+    flag: i32,
+) -> *const utils::WString {
+    let call = ORIGINAL_LOAD_XML.enter();
+    if call.is_nested() {
+        // 原始函数里再次加载XML，直接放行
+        return call
+            .original()
+            .map_or(std::ptr::null(), |original| original(_this, path, flag));
+    }
+    // 打印被拦截的参数，路径读不了或者没有结尾时记录原因，不保存也不替换
+    let original_path = match unsafe { utils::read_wide_text(path, utils::PATH_LIMIT) } {
+        Ok(original_path) => {
+            debug_log(&format!(
+                "LoadXML被调用: 路径={}, 标志={}",
+                original_path, flag
+            ));
+            Some(original_path).filter(|original_path| !original_path.is_empty())
+        }
+        Err(e) => {
+            debug_log(&format!("LoadXML被调用: 路径无效（{}）, 标志={}", e, flag));
+            None
+        }
+    };
+
+    // 调用原始函数
+    let Some(original) = call.original() else {
+        return std::ptr::null();
+    };
+    let result = original(_this, path, flag);
+
+    // 保存返回的XML内容到文件
+    if let (false, Some(original_path)) = (result.is_null(), original_path) {
+        // 不成对的代理项转义保留，内容不会因为解码失败丢掉
+        let content = unsafe { &*result }.decode().text;
+
+        // 保存的是客户端原来的内容，替换在提交之后
+        submit_dump(&original_path, content, flag);
+
+        unsafe { override_xml(&original_path, result as *mut utils::WString) };
+    }
+    result
+}
@@ -0,0 +1,35 @@
+// 所有hook的声明，新增hook时在这里注册
+mod load_xml;
+mod trace;
+mod write_log;
+
+use crate::debug_log;
+use crate::hook::{HookDef, hook_registry};
+
+pub use load_xml::{
+    XmlDumpConfig, XmlOverrideConfig, close_dump, configure_dump, configure_override,
+    override_enabled, set_override_enabled, stop_dump, with_overrides,
+};
+pub use trace::{add_trace, register_traces, remove_trace, reset_traces, trace_report};
+
+// 启动时默认启用的hook
+const DEFAULT_ENABLED: &[&str] = &["write_log"];
+
+fn hook_defs() -> Vec<HookDef> {
+    vec![load_xml::hook_def(), write_log::hook_def()]
+}
+
+/// 注册所有hook，并启用默认的hook
+pub fn register_all() {
+    let registry = hook_registry();
+    for def in hook_defs() {
+        if let Err(e) = registry.register(def) {
+            debug_log(&e);
+        }
+    }
+    for name in DEFAULT_ENABLED {
+        if let Err(e) = registry.enable(name) {
+            debug_log(&e);
+        }
+    }
+}
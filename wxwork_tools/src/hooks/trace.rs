@@ -0,0 +1,464 @@
+// 通用的函数调用跟踪：按配置或控制台命令hook任意函数，记录调用次数、耗时分布、线程、参数和返回值
+//
+// 每个跟踪占用一个槽位，每种调用约定和参数个数有一组hook函数，按槽位编号区分统计，
+// 移除跟踪时卸载hook并释放槽位。
+// hook函数按 u64 返回，原样保留 edx:eax，返回 64 位整数的函数也不受影响；返回浮点数的函数不支持。
+use crate::debug_log;
+use crate::hook::{HookDef, HookLocator, StubAbi, hook_registry};
+use crate::utils;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use wxwork_trace::{
+    CallConv, TraceReport, TraceSpec, TraceStats, TraceSummary, TraceTarget, ValueKind,
+};
+
+// 最多同时跟踪的函数个数
+const MAX_TRACES: usize = 8;
+
+// 移除跟踪时等待正在进行的调用返回的时间
+const REMOVE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// 字符串参数最多读取的字符数，显示时再截短
+const STRING_READ_LIMIT: usize = 4096;
+const STRING_DISPLAY_CHARS: usize = 256;
+
+struct TraceSlot {
+    spec: TraceSpec,
+    target: TraceTarget,
+    stats: TraceStats,
+}
+
+static SLOTS: [Mutex<Option<TraceSlot>>; MAX_TRACES] = [const { Mutex::new(None) }; MAX_TRACES];
+
+// 为每种调用约定和参数个数生成一组hook函数，每个槽位一个
+macro_rules! trace_proxies {
+    ($($module:ident: $abi:literal, $convention:ident, ($($arg:ident),*);)*) => {
+        $(
+            mod $module {
+                use super::{MAX_TRACES, traced};
+                use crate::hook::Trampoline;
+
+                pub type Target = extern $abi fn($($arg: usize),*) -> u64;
+
+                pub static TRAMPOLINES: [Trampoline<Target>; MAX_TRACES] =
+                    [const { Trampoline::new() }; MAX_TRACES];
+
+                pub static PROXIES: [Target; MAX_TRACES] = [
+                    proxy::<0>, proxy::<1>, proxy::<2>, proxy::<3>,
+                    proxy::<4>, proxy::<5>, proxy::<6>, proxy::<7>,
+                ];
+
+                extern $abi fn proxy<const SLOT: usize>($($arg: usize),*) -> u64 {
+                    let call = TRAMPOLINES[SLOT].enter();
+                    let Some(original) = call.original() else {
+                        return 0;
+                    };
+                    if call.is_nested() {
+                        // 原始函数或者记录过程中再次调用，直接放行
+                        return original($($arg),*);
+                    }
+                    traced(SLOT, &[$($arg),*], || original($($arg),*))
+                }
+            }
+        )*
+
+        // 按调用约定和参数个数选择hook函数
+        fn proxy_def(
+            slot: usize,
+            name: &'static str,
+            locator: HookLocator,
+            convention: CallConv,
+            arity: usize,
+        ) -> Option<HookDef> {
+            $(
+                if convention == CallConv::$convention
+                    && arity == <[&str]>::len(&[$(stringify!($arg)),*])
+                {
+                    return Some(HookDef::new(
+                        name,
+                        locator,
+                        stub_abi(convention, arity),
+                        $module::PROXIES[slot],
+                        &$module::TRAMPOLINES[slot],
+                    ));
+                }
+            )*
+            None
+        }
+    };
+}
+
+// 按配置的参数个数转发，入口桩只重新压入这么多参数，原始函数读不到多余的栈；
+// 每种调用约定都有 0 到 MAX_ARGS 个参数的hook函数（thiscall 至少有 this）
+trace_proxies! {
+    cdecl_0: "cdecl", Cdecl, ();
+    cdecl_1: "cdecl", Cdecl, (a0);
+    cdecl_2: "cdecl", Cdecl, (a0, a1);
+    cdecl_3: "cdecl", Cdecl, (a0, a1, a2);
+    cdecl_4: "cdecl", Cdecl, (a0, a1, a2, a3);
+    cdecl_5: "cdecl", Cdecl, (a0, a1, a2, a3, a4);
+    cdecl_6: "cdecl", Cdecl, (a0, a1, a2, a3, a4, a5);
+    cdecl_7: "cdecl", Cdecl, (a0, a1, a2, a3, a4, a5, a6);
+    cdecl_8: "cdecl", Cdecl, (a0, a1, a2, a3, a4, a5, a6, a7);
+    stdcall_0: "stdcall", Stdcall, ();
+    stdcall_1: "stdcall", Stdcall, (a0);
+    stdcall_2: "stdcall", Stdcall, (a0, a1);
+    stdcall_3: "stdcall", Stdcall, (a0, a1, a2);
+    stdcall_4: "stdcall", Stdcall, (a0, a1, a2, a3);
+    stdcall_5: "stdcall", Stdcall, (a0, a1, a2, a3, a4);
+    stdcall_6: "stdcall", Stdcall, (a0, a1, a2, a3, a4, a5);
+    stdcall_7: "stdcall", Stdcall, (a0, a1, a2, a3, a4, a5, a6);
+    stdcall_8: "stdcall", Stdcall, (a0, a1, a2, a3, a4, a5, a6, a7);
+    thiscall_1: "thiscall", Thiscall, (this);
+    thiscall_2: "thiscall", Thiscall, (this, a0);
+    thiscall_3: "thiscall", Thiscall, (this, a0, a1);
+    thiscall_4: "thiscall", Thiscall, (this, a0, a1, a2);
+    thiscall_5: "thiscall", Thiscall, (this, a0, a1, a2, a3);
+    thiscall_6: "thiscall", Thiscall, (this, a0, a1, a2, a3, a4);
+    thiscall_7: "thiscall", Thiscall, (this, a0, a1, a2, a3, a4, a5);
+    thiscall_8: "thiscall", Thiscall, (this, a0, a1, a2, a3, a4, a5, a6);
+}
+
+// 入口桩按hook函数的调用约定转发，thiscall 的 this 在 ecx 中，不占栈
+fn stub_abi(convention: CallConv, arity: usize) -> StubAbi {
+    let arity = arity as u8;
+    match convention {
+        CallConv::Cdecl => StubAbi::Cdecl(arity),
+        CallConv::Stdcall => StubAbi::Stdcall(arity),
+        CallConv::Thiscall => StubAbi::Thiscall(arity.saturating_sub(1)),
+    }
+}
+
+// 调用原始函数并记录；参数在调用前显示（字符串参数可能在调用中被修改或释放）
+fn traced(slot: usize, args: &[usize], original: impl FnOnce() -> u64) -> u64 {
+    let kinds = match SLOTS[slot].lock() {
+        Ok(guard) => guard
+            .as_ref()
+            .map(|slot| (slot.spec.args.clone(), slot.spec.ret)),
+        Err(_) => None,
+    };
+    let Some((arg_kinds, ret_kind)) = kinds else {
+        return original();
+    };
+    let rendered: Vec<String> = args
+        .iter()
+        .zip(&arg_kinds)
+        .map(|(value, kind)| render_value(*kind, *value))
+        .collect();
+
+    let thread = unsafe { GetCurrentThreadId() };
+    let start = Instant::now();
+    let result = original();
+    let elapsed_ns = start.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+
+    let ret = render_value(ret_kind, result as usize);
+    if let Ok(mut guard) = SLOTS[slot].lock() {
+        if let Some(slot) = guard.as_mut() {
+            slot.stats.record(thread, rendered, ret, elapsed_ns);
+        }
+    }
+    result
+}
+
+// 按配置的类型显示参数或返回值，读不了的字符串显示地址和原因
+fn render_value(kind: ValueKind, value: usize) -> String {
+    if let Some(text) = kind.render_scalar(value as u32) {
+        return text;
+    }
+    let result = unsafe {
+        match kind {
+            ValueKind::Wstr => utils::read_wide_text(value as *const u16, STRING_READ_LIMIT),
+            ValueKind::Str => utils::read_ansi_text(value as *const u8, STRING_READ_LIMIT),
+            ValueKind::Wstring => utils::read_msvc_wstring(value as *const _, STRING_READ_LIMIT),
+            _ => utils::read_msvc_string(value as *const _, STRING_READ_LIMIT),
+        }
+    };
+    match result {
+        Ok(text) => match text.char_indices().nth(STRING_DISPLAY_CHARS) {
+            Some((end, _)) => format!("{:?}…", &text[..end]),
+            None => format!("{:?}", text),
+        },
+        Err(utils::CStrError::Null) => "NULL".to_string(),
+        Err(e) => format!("0x{:08X}（{}）", value, e),
+    }
+}
+
+/// 添加跟踪：注册hook，配置为启用时立即启用，返回hook名称
+///
+/// 任何一步失败时撤销已完成的步骤，槽位和hook注册表中都不留下这个跟踪。
+pub fn add_trace(spec: TraceSpec) -> Result<&'static str, String> {
+    let target = spec.validate()?;
+    let arity = spec.args.len();
+    let convention = spec.convention;
+    let enabled = spec.enabled;
+    let trace_name = spec.name.clone();
+    let described = format!("{} {}", target, spec.signature());
+    let locator_target = target.clone();
+    let stats = TraceStats::new(spec.recent);
+    let slot = reserve_slot(TraceSlot {
+        spec,
+        target,
+        stats,
+    })?;
+
+    // hook注册表使用静态字符串，注册失败时收回，注册成功后才真正泄漏；
+    // 跟踪只在启动和控制台命令时添加，泄漏的字符串很少
+    let mut leaked = Vec::new();
+    let name = leak(&trace_name, &mut leaked);
+    let locator = locator_for(&locator_target, &mut leaked);
+    let registered = proxy_def(slot, name, locator, convention, arity)
+        .ok_or_else(|| format!("不支持 {} 的 {} 个参数", convention, arity))
+        .and_then(|def| hook_registry().register(def));
+    if let Err(e) = registered {
+        // 注册表没有保存定义，这些字符串没有其他引用
+        for text in leaked {
+            unsafe { reclaim(text) };
+        }
+        release_slot(slot);
+        return Err(e);
+    }
+
+    if enabled {
+        if let Err(e) = hook_registry().enable(name) {
+            // 还没有启用，没有正在进行的调用，卸载后释放槽位
+            return Err(match hook_registry().remove(name, REMOVE_TIMEOUT) {
+                Ok(()) => {
+                    release_slot(slot);
+                    e
+                }
+                Err(remove_error) => format!("{}；撤销跟踪失败: {}", e, remove_error),
+            });
+        }
+    }
+    debug_log(&format!("跟踪 {} 已添加: {}", name, described));
+    Ok(name)
+}
+
+// 占用一个空槽位：在持有槽位的锁时写入，同时添加的跟踪不会拿到同一个槽位；
+// 移除跟踪后槽位可以重新使用
+fn reserve_slot(trace: TraceSlot) -> Result<usize, String> {
+    let name = trace.spec.name.clone();
+    let mut trace = Some(trace);
+    let mut reserved = None;
+    let scanned = SLOTS.iter().enumerate().try_for_each(|(index, slot)| {
+        let mut guard = slot.lock().map_err(|_| "跟踪槽位的锁已损坏".to_string())?;
+        match guard.as_ref() {
+            Some(used) if used.spec.name == name => Err(format!("跟踪 {} 已存在", name)),
+            None if reserved.is_none() => {
+                *guard = trace.take();
+                reserved = Some(index);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    });
+    match (scanned, reserved) {
+        (Ok(()), Some(slot)) => Ok(slot),
+        (Ok(()), None) => Err(format!("最多跟踪 {} 个函数", MAX_TRACES)),
+        (Err(e), reserved) => {
+            if let Some(slot) = reserved {
+                release_slot(slot);
+            }
+            Err(e)
+        }
+    }
+}
+
+fn release_slot(slot: usize) {
+    if let Ok(mut guard) = SLOTS[slot].lock() {
+        *guard = None;
+    }
+}
+
+/// 按配置添加跟踪，一个失败不影响其他的
+pub fn register_traces(specs: &[TraceSpec]) {
+    for spec in specs {
+        if let Err(e) = add_trace(spec.clone()) {
+            debug_log(&format!("添加跟踪失败: {}", e));
+        }
+    }
+}
+
+/// 当前的跟踪结果
+pub fn trace_report(client_version: &str) -> TraceReport {
+    let traces = SLOTS
+        .iter()
+        .filter_map(|slot| {
+            let guard = slot.lock().ok()?;
+            let slot = guard.as_ref()?;
+            Some(TraceSummary {
+                name: slot.spec.name.clone(),
+                target: slot.target.to_string(),
+                signature: slot.spec.signature(),
+                convention: slot.spec.convention,
+                stats: slot.stats.clone(),
+            })
+        })
+        .collect();
+    TraceReport {
+        client_version: client_version.to_string(),
+        traces,
+    }
+}
+
+/// 移除跟踪：卸载hook并释放槽位，有调用没返回时保留跟踪（hook已禁用）并返回错误
+pub fn remove_trace(name: &str) -> Result<(), String> {
+    let slot = SLOTS
+        .iter()
+        .position(|slot| {
+            slot.lock()
+                .is_ok_and(|guard| guard.as_ref().is_some_and(|used| used.spec.name == name))
+        })
+        .ok_or_else(|| format!("没有名为 {} 的跟踪", name))?;
+    // 先等hook调用返回再释放槽位，槽位重新使用时不会记到新的跟踪上
+    hook_registry().remove(name, REMOVE_TIMEOUT)?;
+    *SLOTS[slot]
+        .lock()
+        .map_err(|_| "跟踪槽位的锁已损坏".to_string())? = None;
+    debug_log(&format!("跟踪 {} 已移除", name));
+    Ok(())
+}
+
+/// 清空统计，不指定名称时清空所有跟踪
+pub fn reset_traces(name: Option<&str>) -> Result<(), String> {
+    let mut found = false;
+    for slot in &SLOTS {
+        let mut guard = slot.lock().map_err(|_| "跟踪槽位的锁已损坏".to_string())?;
+        if let Some(slot) = guard.as_mut() {
+            if name.is_none_or(|name| slot.spec.name == name) {
+                slot.stats.reset();
+                found = true;
+            }
+        }
+    }
+    match (found, name) {
+        (false, Some(name)) => Err(format!("没有名为 {} 的跟踪", name)),
+        _ => Ok(()),
+    }
+}
+
+// 转换为hook注册表的定位方式，泄漏的字符串记在 `leaked` 中
+fn locator_for(target: &TraceTarget, leaked: &mut Vec<&'static str>) -> HookLocator {
+    let mut leak = |text: &str| leak(text, leaked);
+    match target {
+        TraceTarget::Export { module, symbol } => HookLocator::Export {
+            module: leak(module),
+            symbol: leak(symbol),
+        },
+        TraceTarget::Offset { module, offset } => HookLocator::Offset {
+            module: leak(module),
+            offset: *offset,
+        },
+        TraceTarget::Signature { module, pattern } => HookLocator::Signature {
+            module: leak(module),
+            pattern: leak(pattern),
+        },
+    }
+}
+
+// 复制为静态字符串，记在 `leaked` 中，失败时用 [`reclaim`] 收回
+fn leak(text: &str, leaked: &mut Vec<&'static str>) -> &'static str {
+    let text: &'static str = Box::leak(text.to_string().into_boxed_str());
+    leaked.push(text);
+    text
+}
+
+// 收回 [`leak`] 泄漏的字符串
+//
+// # Safety
+// `text` 必须来自 [`leak`]，并且已经没有其他引用。
+unsafe fn reclaim(text: &'static str) {
+    drop(unsafe { Box::from_raw(text as *const str as *mut str) });
+}
+
+#[cfg(all(test, target_arch = "x86"))]
+mod tests {
+    use super::*;
+    use std::thread;
+    use wxwork_trace::MAX_ARGS;
+
+    fn spec(name: &str, target: &str, enabled: bool) -> TraceSpec {
+        let command = format!("{} {} stdcall -> uint", name, target);
+        TraceSpec {
+            enabled,
+            ..TraceSpec::parse_command(command.split_whitespace()).unwrap()
+        }
+    }
+
+    fn traced_names() -> Vec<String> {
+        trace_report("test")
+            .traces
+            .into_iter()
+            .map(|trace| trace.name)
+            .collect()
+    }
+
+    fn registered(name: &str) -> bool {
+        hook_registry().list().iter().any(|info| info.name == name)
+    }
+
+    #[test]
+    fn every_accepted_arity_has_proxy() {
+        let locator = || HookLocator::Offset {
+            module: "kernel32.dll",
+            offset: 0,
+        };
+        for convention in [CallConv::Cdecl, CallConv::Stdcall, CallConv::Thiscall] {
+            let min = usize::from(convention == CallConv::Thiscall);
+            for arity in min..=MAX_ARGS {
+                for slot in 0..MAX_TRACES {
+                    assert!(
+                        proxy_def(slot, "proxy", locator(), convention, arity).is_some(),
+                        "{} {}",
+                        convention,
+                        arity
+                    );
+                }
+            }
+            assert!(proxy_def(0, "proxy", locator(), convention, MAX_ARGS + 1).is_none());
+        }
+    }
+
+    // 槽位是全局的，添加和移除放在一个测试中
+    #[test]
+    fn add_and_remove_keep_slots_consistent() {
+        // 启用失败时撤销注册和槽位，同名的跟踪可以重新添加
+        let missing = spec("trace_missing", "kernel32.dll!WxworkMissingExport", true);
+        assert!(add_trace(missing).is_err());
+        assert!(traced_names().is_empty());
+        assert!(!registered("trace_missing"));
+
+        let tick = spec("trace_missing", "kernel32.dll!GetTickCount", false);
+        assert_eq!(add_trace(tick.clone()), Ok("trace_missing"));
+        assert!(registered("trace_missing"));
+        assert!(add_trace(tick).is_err());
+        assert_eq!(traced_names(), ["trace_missing"]);
+        remove_trace("trace_missing").unwrap();
+        assert!(!registered("trace_missing") && traced_names().is_empty());
+
+        // 同时添加时每个跟踪占用不同的槽位
+        let adders: Vec<_> = (0..MAX_TRACES)
+            .map(|index| {
+                thread::spawn(move || {
+                    add_trace(spec(
+                        &format!("trace_{}", index),
+                        "kernel32.dll!GetTickCount",
+                        false,
+                    ))
+                })
+            })
+            .collect();
+        for adder in adders {
+            adder.join().unwrap().unwrap();
+        }
+        assert_eq!(traced_names().len(), MAX_TRACES);
+        let extra = spec("trace_extra", "kernel32.dll!GetTickCount", false);
+        assert!(add_trace(extra).is_err());
+        assert!(!registered("trace_extra"));
+        for index in 0..MAX_TRACES {
+            remove_trace(&format!("trace_{}", index)).unwrap();
+        }
+        assert!(traced_names().is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::debug_log;
+use crate::hook::{HookDef, HookLocator, StubAbi, Trampoline};
+use crate::log_capture;
+use crate::utils;
+use std::ffi::c_void;
+use wxwork_log::LogRecord;
+
+// 日志
+type WriteLogFn = extern "cdecl" fn(_this: *const c_void, log: *const utils::String);
+
+static ORIGINAL_WRITE_LOG: Trampoline<WriteLogFn> = Trampoline::new();
+
+/// hook声明：WxWork.exe 中的写日志函数（4.1.38.6006）
+pub fn hook_def() -> HookDef {
+    HookDef::new(
+        "write_log",
+        HookLocator::Offset {
+            module: "WxWork.exe",
+            offset: 0x33D158,
+        },
+        StubAbi::Cdecl(2),
+        write_log_proxy as WriteLogFn,
+        &ORIGINAL_WRITE_LOG,
+    )
+}
+
+extern "cdecl" fn write_log_proxy(_this: *const c_void, log: *const utils::String) {
+    let call = ORIGINAL_WRITE_LOG.enter();
+    if call.is_nested() {
+        // 原始函数里又写了日志，直接放行
+        if let Some(original) = call.original() {
+            original(_this, log);
+        }
+        return;
+    }
+    unsafe {
+        // 打印被拦截的参数
+        if !log.is_null() {
+            // 非 UTF-8 的内容按识别出的编码解码，识别不了的字节转义，一行都不丢
+            // 一次可能写入多行，不以级别开头的后续行（如调用栈）并入上一条记录
+            for record in LogRecord::parse_bytes((*log).as_bytes()) {
+                log_capture::emit(record);
+            }
+        } else {
+            debug_log("日志=NULL");
+        }
+
+        // 调用原始函数
+        if let Some(original) = call.original() {
+            original(_this, log);
+        }
+    }
+}
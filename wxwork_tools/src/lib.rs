@@ -1,15 +1,18 @@
-use std::{ffi::*,  sync::Mutex, *};
+use std::{ffi::*, *};
 use windows::{
     Win32::{
         Foundation::*,
-        System::{Console::*, LibraryLoader::*, SystemServices::*,Diagnostics::Debug::*},
+        System::{Console::*, SystemServices::*,Diagnostics::Debug::*,LibraryLoader::*},
 
     },
     core::*,
 };
 
-use retour::GenericDetour;
-
+mod config;
+mod console;
+mod hook;
+mod hooks;
+mod log_capture;
 mod utils;
 
 fn debug_log(message: &str) {
@@ -20,217 +23,166 @@ fn debug_log(message: &str) {
     }
 }
 
-// 要hook的函数指针
-type LoadXMLFn =
-    extern "cdecl" fn(_this: *const c_void, path: *const u16, flag: i32) -> *const utils::WString;
-
-// 日志
-type WriteLogFn = extern "cdecl" fn(_this: *const c_void, log: *const utils::String);
+// DllMain 收到的模块句柄，卸载自身时使用
+static MODULE: sync::atomic::AtomicUsize = sync::atomic::AtomicUsize::new(0);
 
-// 保存原始函数的静态变量
-static ORIGINAL_LOAD_XML: Mutex<Option<GenericDetour<LoadXMLFn>>> = Mutex::new(None);
-static ORIGINAL_WRITE_LOG: Mutex<Option<GenericDetour<WriteLogFn>>> = Mutex::new(None);
+// 主动卸载时等待hook调用返回的时间
+const EJECT_TIMEOUT: time::Duration = time::Duration::from_secs(5);
 
-// 我们的hook函数实现
-extern "cdecl" fn load_xml_proxy(
-    _this: *const c_void,
-    path: *const u16,
-    flag: i32,
-) -> *const utils::WString {
+/// 卸载所有hook，停止后台线程，释放工作线程持有的模块引用，成功时不返回
+///
+/// 只能在工作线程（控制台线程）上调用：FreeLibraryAndExitThread 释放引用后直接结束线程，
+/// 不会返回到已经被卸载的代码。有hook调用没返回时放弃卸载，hook保持禁用。
+/// 注入器 LoadLibrary 的引用由注入器自己 FreeLibrary，两个引用都释放后DLL才卸载。
+pub(crate) fn eject() -> result::Result<(), String> {
+    prepare_eject(hook::hook_registry(), EJECT_TIMEOUT)?;
+    debug_log("所有hook已卸载，后台线程已停止，释放模块引用");
     unsafe {
-        // 打印被拦截的参数
-        if !path.is_null() {
-            let path_slice = std::slice::from_raw_parts(path, 1024); // 假设最大长度
-            let mut len = 0;
-            for i in 0..1024 {
-                if path_slice[i] == 0 {
-                    len = i;
-                    break;
-                }
-            }
-
-            if len > 0 {
-                let path_u16 = &path_slice[0..len];
-                if let Ok(path_str) = String::from_utf16(path_u16) {
-                    debug_log(&format!("LoadXML被调用: 路径={}, 标志={}", path_str, flag));
-                }
-            }
-        } else {
-            debug_log(&format!("LoadXML被调用: 路径=NULL, 标志={}", flag));
-        }
-
-        // 调用原始函数
-        if let Ok(guard) = ORIGINAL_LOAD_XML.lock() {
-            if let Some(original) = guard.as_ref() {
-                let result = original.call(_this, path, flag);
-
-                // 保存返回的XML内容到文件
-                if !result.is_null() && !path.is_null() {
-                    let wstring = &*result;
-                    if let Ok(content) = wstring.to_string() {
-                        // 获取path参数作为文件路径
-                        let path_slice = std::slice::from_raw_parts(path, 1024);
-                        let mut len = 0;
-                        for i in 0..1024 {
-                            if path_slice[i] == 0 {
-                                len = i;
-                                break;
-                            }
-                        }
-
-                        if len > 0 {
-                            let path_u16 = &path_slice[0..len];
-                            if let Ok(original_path) = String::from_utf16(path_u16) {
-                                // 在路径前追加wxwork_ui_rs目录
-                                let file_path = format!("wxwork_ui_rs/{}", original_path);
-
-                                // 创建目录（如果不存在）
-                                if let Some(parent) = std::path::Path::new(&file_path).parent() {
-                                    let _ = std::fs::create_dir_all(parent);
-                                }
-
-                                // 保存XML内容到文件
-                                if let Err(e) = std::fs::write(&file_path, &content) {
-                                    debug_log(&format!("保存XML文件失败: {} - {}", file_path, e));
-                                } else {
-                                    debug_log(&format!("XML内容已保存到: {}", file_path));
-                                }
-                            }
-                        }
-                    }
-                }
-
-                return result;
-            }
-        }
-
-        std::ptr::null()
+        let _ = FreeConsole();
+        FreeLibraryAndExitThread(
+            HMODULE(MODULE.load(sync::atomic::Ordering::Acquire) as *mut c_void),
+            0,
+        );
     }
 }
 
-extern "cdecl" fn write_log_proxy(_this: *const c_void, log: *const utils::String) {
-    unsafe {
-        // 打印被拦截的参数
-        if !log.is_null() {
-            let wstring = &*log;
-            if let Ok(content) = wstring.to_string() {
-                debug_log(&format!("{}", content));
-            }
-        } else {
-            debug_log("日志=NULL");
-        }
+// 卸载hook后停止后台线程；有hook调用没返回时什么也不停止，返回错误
+fn prepare_eject(
+    registry: &hook::registry::HookRegistry,
+    timeout: time::Duration,
+) -> result::Result<(), String> {
+    registry.teardown(timeout)?;
+    // 等保存线程和写线程写完队列中的XML和日志并退出
+    hooks::stop_dump();
+    log_capture::stop();
+    Ok(())
+}
 
-        // 调用原始函数
-        if let Ok(guard) = ORIGINAL_WRITE_LOG.lock() {
-            if let Some(original) = guard.as_ref() {
-                original.call(_this, log);
-            }
-        }
+// 为工作线程增加一次模块引用，外部 FreeLibrary 只减少注入器的引用，
+// DLL不会在hook和后台线程还在运行时被卸载；工作线程退出时用 FreeLibraryAndExitThread 释放
+fn add_module_reference() -> bool {
+    let mut module = HMODULE::default();
+    unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+            PCWSTR(add_module_reference as *const u16),
+            &mut module,
+        )
     }
+    .is_ok()
 }
 
-fn hook_load_xml() {
-    // 准备hook指定模块的导出函数，模块属于duilib.dll,导出函数是"?LoadXML@CResManager@DuiLib@@SA?AV?$basic_string@_WU?$char_traits@_W@std@@V?$allocator@_W@2@@std@@PB_WH@Z",
-    //返回类型是std::wstring* DuiLib::CResManager::LoadXML(void*,wchar_t const *, int);
-    // 1. 获取duilib.dll的句柄
-    let h_dll = match unsafe { LoadLibraryW(w!("duilib.dll")) } {
-        Ok(h) => h,
-        Err(e) => {
-            debug_log(&format!("LoadLibraryW failed: {:?}", e));
-            return;
-        }
-    };
+// DLL 所在目录
+pub(crate) fn module_dir() -> Option<path::PathBuf> {
+    let module = HMODULE(MODULE.load(sync::atomic::Ordering::Acquire) as *mut c_void);
+    let mut buffer = [0u16; 1024];
+    let len = unsafe { GetModuleFileNameW(Some(module), &mut buffer) } as usize;
+    if len == 0 || len >= buffer.len() {
+        return None;
+    }
+    let path = path::PathBuf::from(String::from_utf16_lossy(&buffer[..len]));
+    path.parent().map(|dir| dir.to_path_buf())
+}
 
-    // 2. 获取导出函数地址
-    let addr = match unsafe {
-        GetProcAddress(
-            h_dll,
-            s!(
-                "?LoadXML@CResManager@DuiLib@@SA?AV?$basic_string@_WU?$char_traits@_W@std@@V?$allocator@_W@2@@std@@PB_WH@Z"
-            ),
-        )
-    } {
-        Some(addr) => addr,
-        None => {
-            debug_log("GetProcAddress failed");
-            return;
-        }
+// 客户端版本，取自主程序的文件版本信息，例如 4.1.38.6006
+fn detect_client_version() -> Option<String> {
+    use windows::Win32::Storage::FileSystem::{
+        GetFileVersionInfoSizeW, GetFileVersionInfoW, VS_FIXEDFILEINFO, VerQueryValueW,
     };
 
-    // 3. 将地址转换为函数指针
-    let original_fn: LoadXMLFn = unsafe { std::mem::transmute(addr) };
-
-    // 4. 创建hook
+    let mut buffer = [0u16; 1024];
+    let len = unsafe { GetModuleFileNameW(None, &mut buffer) } as usize;
+    if len == 0 || len >= buffer.len() {
+        return None;
+    }
+    let exe = PCWSTR(buffer.as_ptr());
     unsafe {
-        match GenericDetour::<LoadXMLFn>::new(original_fn, load_xml_proxy) {
-            Ok(detour) => {
-                // 启用hook
-                if let Err(e) = detour.enable() {
-                    debug_log(&format!("Failed to enable hook: {:?}", e));
-                    return;
-                }
-
-                // 保存原始函数
-                if let Ok(mut guard) = ORIGINAL_LOAD_XML.lock() {
-                    *guard = Some(detour);
-                }
-                debug_log("Successfully hooked LoadXML function");
-            }
-            Err(e) => {
-                debug_log(&format!("Failed to create hook: {:?}", e));
-            }
+        let size = GetFileVersionInfoSizeW(exe, None);
+        if size == 0 {
+            return None;
         }
+        let mut data = vec![0u8; size as usize];
+        GetFileVersionInfoW(exe, None, size, data.as_mut_ptr() as *mut c_void).ok()?;
+        let mut info: *mut c_void = ptr::null_mut();
+        let mut info_len = 0u32;
+        let found = VerQueryValueW(
+            data.as_ptr() as *const c_void,
+            w!("\\"),
+            &mut info,
+            &mut info_len,
+        );
+        if !found.as_bool()
+            || info.is_null()
+            || (info_len as usize) < mem::size_of::<VS_FIXEDFILEINFO>()
+        {
+            return None;
+        }
+        let info = &*(info as *const VS_FIXEDFILEINFO);
+        Some(format!(
+            "{}.{}.{}.{}",
+            info.dwFileVersionMS >> 16,
+            info.dwFileVersionMS & 0xFFFF,
+            info.dwFileVersionLS >> 16,
+            info.dwFileVersionLS & 0xFFFF
+        ))
     }
 }
 
-fn hook_write_log() {
-    // 1. 获取duilib.dll的句柄
-    let h_dll = match unsafe { LoadLibraryW(w!("WxWork.exe")) } {
-        Ok(h) => h,
-        Err(e) => {
-            debug_log(&format!("LoadLibraryW failed: {:?}", e));
-            return;
-        }
-    };
+// 启动时确定的客户端版本，导出结果时使用
+static CLIENT_VERSION: sync::OnceLock<String> = sync::OnceLock::new();
 
-    // 2. 获取导出函数地址
-    let addr = { h_dll.0 as usize + 0x33D158 };
+// 当前客户端版本，启动完成前为 unknown
+pub(crate) fn current_client_version() -> &'static str {
+    CLIENT_VERSION.get().map_or("unknown", String::as_str)
+}
 
-    // 3. 将地址转换为函数指针
-    let original_fn: WriteLogFn = unsafe { std::mem::transmute(addr) };
+// 客户端版本：配置优先，读不到版本信息时为 unknown
+fn client_version(config: &config::ToolsConfig) -> String {
+    if let Some(version) = &config.client_version {
+        return version.clone();
+    }
+    detect_client_version().unwrap_or_else(|| "unknown".to_string())
+}
 
-    // 4. 创建hook
-    unsafe {
-        match GenericDetour::<WriteLogFn>::new(original_fn, write_log_proxy) {
-            Ok(detour) => {
-                // 启用hook
-                if let Err(e) = detour.enable() {
-                    debug_log(&format!("Failed to enable hook: {:?}", e));
-                    return;
-                }
+// 配置文件路径，没有时为 None
+pub(crate) fn config_path() -> Option<path::PathBuf> {
+    config::ToolsConfig::locate(module_dir().as_deref())
+}
 
-                // 保存原始函数
-                if let Ok(mut guard) = ORIGINAL_WRITE_LOG.lock() {
-                    *guard = Some(detour);
-                }
-                debug_log("Successfully hooked WriteLog function");
-            }
-            Err(e) => {
-                debug_log(&format!("Failed to create hook: {:?}", e));
-            }
-        }
-    }
+// 读取配置，没有配置文件或配置有误时使用默认配置
+fn load_config() -> config::ToolsConfig {
+    let Some(path) = config_path() else {
+        debug_log("未找到配置文件，使用默认配置");
+        return config::ToolsConfig::default();
+    };
+    debug_log(&format!("加载配置: {}", path.display()));
+    config::ToolsConfig::load(&path).unwrap_or_else(|e| {
+        debug_log(&format!("{}，使用默认配置", e));
+        config::ToolsConfig::default()
+    })
 }
 
 fn worker_thread() {
-    //hook_load_xml();
-    hook_write_log();
+    let config = load_config();
+    if let Err(e) = log_capture::start(&config.log) {
+        debug_log(&format!("启动日志输出失败: {}", e));
+    }
+    let client_version = client_version(&config);
+    debug_log(&format!("客户端版本: {}", client_version));
+    hooks::configure_dump(&config.xml_dump, module_dir().as_deref(), &client_version);
+    hooks::configure_override(&config.xml_override, module_dir().as_deref());
+    let _ = CLIENT_VERSION.set(client_version);
+    hooks::register_all();
+    hooks::register_traces(&config.trace);
+    console::run();
 }
 
 #[unsafe(no_mangle)] // 防止函数名被混淆，确保Windows能识别DllMain
-extern "stdcall" fn DllMain(_hinst: HINSTANCE, reason: u32, _reserved: *mut c_void) -> BOOL {
+extern "stdcall" fn DllMain(hinst: HINSTANCE, reason: u32, reserved: *mut c_void) -> BOOL {
     match reason {
         DLL_PROCESS_ATTACH => {
+            MODULE.store(hinst.0 as usize, sync::atomic::Ordering::Release);
+
             // 为当前进程分配控制台
             unsafe {
                 // 分配新控制台
@@ -247,24 +199,33 @@ extern "stdcall" fn DllMain(_hinst: HINSTANCE, reason: u32, _reserved: *mut c_vo
                     windows::Win32::System::Console::STD_ERROR_HANDLE,
                 );
                 let _ = SetStdHandle(STD_ERROR_HANDLE, stderr.unwrap());
+
+                // 重定向标准输入到控制台，用于输入hook命令
+                let stdin = windows::Win32::System::Console::GetStdHandle(
+                    windows::Win32::System::Console::STD_INPUT_HANDLE,
+                );
+                let _ = SetStdHandle(STD_INPUT_HANDLE, stdin.unwrap());
+            }
+            // 拿不到引用时不启动：没有引用保护，外部 FreeLibrary 会在hook运行时卸载DLL
+            if !add_module_reference() {
+                debug_log("增加模块引用失败，不启动");
+                return BOOL::from(true);
             }
             thread::spawn(|| {
                 worker_thread();
             });
         }
         DLL_PROCESS_DETACH => {
-            // 清理hook
-            // if let Ok(mut guard) = ORIGINAL_LOAD_XML.lock() {
-            //     if let Some(detour) = guard.take() {
-            //         let _ = unsafe { detour.disable() };
-            //     }
-            // }
-
-            // if let Ok(mut guard) = ORIGINAL_WRITE_LOG.lock() {
-            //     if let Some(detour) = guard.take() {
-            //         let _ = unsafe { detour.disable() };
-            //     }
-            // }
+            // 工作线程持有模块引用，只有 eject 卸载完hook、停止后台线程后才会走到这里；
+            // 进程退出时（reserved 非空）其他线程已经结束。这里持有加载器锁，不做任何等待
+            if reserved.is_null() {
+                if !hook::hook_registry().is_empty() {
+                    debug_log("卸载DLL时仍有注册的hook");
+                }
+                // 引用被多释放时后台线程可能还在运行，关闭队列让它们不再接收新内容
+                hooks::close_dump();
+                log_capture::close();
+            }
         }
         DLL_THREAD_ATTACH => {}
         DLL_THREAD_DETACH => {}
@@ -272,3 +233,58 @@ extern "stdcall" fn DllMain(_hinst: HINSTANCE, reason: u32, _reserved: *mut c_vo
     }
     return BOOL::from(true);
 }
+
+#[cfg(all(test, target_arch = "x86"))]
+mod tests {
+    use super::*;
+    use hook::registry::HookRegistry;
+    use hook::registry::tests::{Gate, Target, call, local_target};
+    use hook::{HookDef, StubAbi, Trampoline};
+
+    #[inline(never)]
+    extern "stdcall" fn eject_target(value: u32) -> u32 {
+        hint::black_box(value).wrapping_mul(13).wrapping_add(5)
+    }
+
+    static EJECT: Trampoline<Target> = Trampoline::new();
+    static EJECT_GATE: Gate = Gate::new();
+
+    extern "stdcall" fn eject_detour(value: u32) -> u32 {
+        let call = EJECT.enter();
+        EJECT_GATE.hold();
+        call.original().map_or(0, |original| original(value) + 1000)
+    }
+
+    #[test]
+    fn eject_stops_nothing_while_hooks_are_busy() {
+        let registry: &'static HookRegistry = Box::leak(Box::new(HookRegistry::new()));
+        registry
+            .register(HookDef::new(
+                "eject",
+                local_target(eject_target),
+                StubAbi::Stdcall(1),
+                eject_detour as Target,
+                &EJECT,
+            ))
+            .unwrap();
+        registry.enable("eject").unwrap();
+        let config = log_capture::LogConfig {
+            debug_output: false,
+            ..Default::default()
+        };
+        log_capture::start(&config).unwrap();
+        let caller = thread::spawn(|| call(eject_target, 1));
+        EJECT_GATE.wait_entered();
+
+        // 超时时日志输出继续运行，hook保留
+        assert!(prepare_eject(registry, time::Duration::from_millis(50)).is_err());
+        assert!(log_capture::ring().is_some());
+        assert!(!registry.is_empty());
+
+        EJECT_GATE.release();
+        assert_eq!(caller.join().unwrap(), 1018);
+        prepare_eject(registry, time::Duration::from_secs(1)).unwrap();
+        assert!(log_capture::ring().is_none());
+        assert!(registry.is_empty());
+    }
+}
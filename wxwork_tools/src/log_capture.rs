@@ -0,0 +1,175 @@
+// 抓取到的客户端日志的输出：所有输出目标在后台写线程上执行，hook函数只负责入队
+use crate::debug_log;
+use serde::Deserialize;
+use std::sync::RwLock;
+use std::time::Instant;
+use wxwork_log::{
+    FilterConfig, LogFilter, LogPipeline, LogRecord, LogSink, PipelineStats, RingBuffer, RuleStats,
+    SinkConfig, Verdict,
+};
+
+/// 日志输出配置
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogConfig {
+    #[serde(default = "default_debug_output")]
+    pub debug_output: bool, // 同时输出到 OutputDebugString，用 DebugView 查看
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize, // 待写队列长度，满了丢弃
+    #[serde(default = "default_sinks")]
+    pub sinks: Vec<SinkConfig>,
+    #[serde(default)]
+    pub filter: FilterConfig, // 过滤规则，在hook函数里入队前执行
+}
+
+fn default_debug_output() -> bool {
+    true
+}
+
+fn default_queue_capacity() -> usize {
+    4096
+}
+
+// 没有配置时保留最近的日志供控制台查询
+fn default_sinks() -> Vec<SinkConfig> {
+    vec![SinkConfig::Ring { capacity: 1000 }]
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            debug_output: default_debug_output(),
+            queue_capacity: default_queue_capacity(),
+            sinks: default_sinks(),
+            filter: FilterConfig::default(),
+        }
+    }
+}
+
+struct LogCapture {
+    pipeline: LogPipeline,
+    ring: Option<RingBuffer>,
+    filter: LogFilter,
+}
+
+static CAPTURE: RwLock<Option<LogCapture>> = RwLock::new(None);
+
+/// 按配置创建输出目标并启动后台写线程，创建失败的目标跳过，过滤规则有误时不过滤
+pub fn start(config: &LogConfig) -> Result<(), String> {
+    let filter = LogFilter::new(&config.filter).unwrap_or_else(|e| {
+        debug_log(&format!("{}，不过滤日志", e));
+        LogFilter::pass_all()
+    });
+    let mut sinks: Vec<Box<dyn LogSink>> = Vec::new();
+    let mut ring = None;
+    if config.debug_output {
+        sinks.push(Box::new(DebugOutputSink));
+    }
+    for sink_config in &config.sinks {
+        match sink_config.build() {
+            Ok((sink, sink_ring)) => {
+                debug_log(&format!("日志输出: {}", sink.name()));
+                sinks.push(sink);
+                ring = ring.or(sink_ring);
+            }
+            Err(e) => debug_log(&format!("创建日志输出失败: {}", e)),
+        }
+    }
+
+    let pipeline = LogPipeline::start(sinks, config.queue_capacity, |e| {
+        debug_log(&format!("写日志失败: {}", e))
+    })?;
+    let mut capture = CAPTURE
+        .write()
+        .map_err(|_| "日志输出锁已损坏".to_string())?;
+    if let Some(previous) = capture.replace(LogCapture {
+        pipeline,
+        ring,
+        filter,
+    }) {
+        previous.pipeline.shutdown();
+    }
+    Ok(())
+}
+
+/// 替换过滤规则，规则有误时保留原来的规则，计数从零开始
+pub fn set_filter(config: &FilterConfig) -> Result<(), String> {
+    let filter = LogFilter::new(config)?;
+    let mut capture = CAPTURE
+        .write()
+        .map_err(|_| "日志输出锁已损坏".to_string())?;
+    let capture = capture.as_mut().ok_or("日志输出还没启动")?;
+    capture.filter = filter;
+    debug_log(&format!("过滤规则已更新: {} 条", config.rules.len()));
+    Ok(())
+}
+
+/// 每条过滤规则的计数
+pub fn filter_stats() -> Vec<RuleStats> {
+    CAPTURE
+        .read()
+        .ok()
+        .and_then(|capture| capture.as_ref().map(|capture| capture.filter.stats()))
+        .unwrap_or_default()
+}
+
+/// 写完队列中的日志后停止写线程，卸载DLL前调用
+pub fn stop() {
+    let capture = CAPTURE.write().ok().and_then(|mut capture| capture.take());
+    if let Some(capture) = capture {
+        capture.pipeline.shutdown();
+    }
+}
+
+/// 关闭队列但不等待写线程，卸载DLL时在 DllMain 中调用
+///
+/// 持有加载器锁时不能等待线程退出，也不能等待其他线程持有的锁，拿不到锁时什么也不做。
+/// 正常卸载时 `stop` 已经停止了写线程，这里没有可关闭的队列。
+pub fn close() {
+    let capture = CAPTURE.try_write().ok().and_then(|mut capture| capture.take());
+    if let Some(capture) = capture {
+        capture.pipeline.close();
+    }
+}
+
+/// 过滤后提交一条记录，不做 I/O；还没启动时直接输出到调试器
+pub fn emit(record: LogRecord) {
+    let Ok(capture) = CAPTURE.read() else {
+        return;
+    };
+    match capture.as_ref() {
+        Some(capture) => {
+            if capture.filter.check(&record, Instant::now()) == Verdict::Keep {
+                capture.pipeline.submit(record);
+            }
+        }
+        None => debug_log(&record.to_string()),
+    }
+}
+
+/// 内存中的最近日志，没有配置环形缓冲时为 None
+pub fn ring() -> Option<RingBuffer> {
+    CAPTURE.read().ok()?.as_ref()?.ring.clone()
+}
+
+/// 队列计数
+pub fn stats() -> Option<PipelineStats> {
+    CAPTURE
+        .read()
+        .ok()?
+        .as_ref()
+        .map(|capture| capture.pipeline.stats())
+}
+
+// 输出到调试器，按客户端格式输出一行
+struct DebugOutputSink;
+
+impl LogSink for DebugOutputSink {
+    fn name(&self) -> &str {
+        "debug_output"
+    }
+
+    fn write(&mut self, record: &LogRecord) -> Result<(), String> {
+        debug_log(&record.to_string());
+        Ok(())
+    }
+}
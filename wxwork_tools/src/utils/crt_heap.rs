@@ -0,0 +1,190 @@
+// 按 MSVC std::allocator 的方式在客户端使用的 C 运行库堆上分配内存
+//
+// 客户端释放 std::wstring 时调用 std::allocator::deallocate，最终是 CRT 的 free。
+// 交给客户端的缓冲区必须用同一个 CRT 的 malloc 分配，还要遵守 VS2017 以后 std::allocator 的约定：
+// 4096 字节及以上的块按 32 字节对齐，原始指针存在返回地址的前一个指针位置，释放时据此找回。
+use std::ffi::{CString, c_void};
+use windows::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress};
+use windows::core::{HSTRING, PCSTR};
+
+// std::allocator 按对齐方式分配的阈值和对齐
+const BIG_ALLOCATION_THRESHOLD: usize = 4096;
+const BIG_ALLOCATION_ALIGNMENT: usize = 32;
+
+// Release 版本在块前面预留的字节数：保存原始指针，再留出对齐的余量
+const NON_USER_SIZE: usize = size_of::<usize>() + BIG_ALLOCATION_ALIGNMENT - 1;
+
+type MallocFn = unsafe extern "C" fn(usize) -> *mut c_void;
+type FreeFn = unsafe extern "C" fn(*mut c_void);
+
+/// 客户端使用的 C 运行库的 malloc/free
+pub struct CrtHeap {
+    module: String,
+    malloc: MallocFn,
+    free: FreeFn,
+}
+
+impl CrtHeap {
+    /// 取已加载的 CRT 模块（VS2015 以后是 ucrtbase.dll）中的 malloc/free，不主动加载
+    pub fn open(module: &str) -> Result<Self, String> {
+        let handle = unsafe { GetModuleHandleW(&HSTRING::from(module)) }
+            .map_err(|_| format!("{} 没有加载，客户端可能使用其他运行库", module))?;
+        let export = |name: &str| {
+            let symbol = CString::new(name).map_err(|_| "导出名称无效".to_string())?;
+            unsafe { GetProcAddress(handle, PCSTR(symbol.as_ptr() as *const u8)) }
+                .ok_or_else(|| format!("{} 中没有导出 {}", module, name))
+        };
+        let malloc = export("malloc")?;
+        let free = export("free")?;
+        unsafe {
+            Ok(CrtHeap {
+                module: module.to_string(),
+                malloc: std::mem::transmute::<unsafe extern "system" fn() -> isize, MallocFn>(
+                    malloc,
+                ),
+                free: std::mem::transmute::<unsafe extern "system" fn() -> isize, FreeFn>(free),
+            })
+        }
+    }
+
+    /// CRT 模块名
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// 按 std::allocator::allocate 的方式分配 `bytes` 字节，失败时返回 None
+    pub fn allocate(&self, bytes: usize) -> Option<*mut u8> {
+        if bytes < BIG_ALLOCATION_THRESHOLD {
+            let ptr = unsafe { (self.malloc)(bytes) } as *mut u8;
+            return (!ptr.is_null()).then_some(ptr);
+        }
+        let block_size = bytes.checked_add(NON_USER_SIZE)?;
+        let container = unsafe { (self.malloc)(block_size) } as usize;
+        if container == 0 {
+            return None;
+        }
+        let user = (container + NON_USER_SIZE) & !(BIG_ALLOCATION_ALIGNMENT - 1);
+        unsafe {
+            *(user as *mut usize).sub(1) = container;
+        }
+        Some(user as *mut u8)
+    }
+
+    /// 按 std::allocator::deallocate 的方式释放，`bytes` 必须与分配时相同
+    ///
+    /// # Safety
+    /// `ptr` 必须是同一个 CRT 按 std::allocator 的方式分配的 `bytes` 字节的块。
+    pub unsafe fn deallocate(&self, ptr: *mut u8, bytes: usize) {
+        if ptr.is_null() {
+            return;
+        }
+        if bytes < BIG_ALLOCATION_THRESHOLD {
+            unsafe { (self.free)(ptr as *mut c_void) };
+            return;
+        }
+        // 与 std::allocator 一样检查原始指针的位置，不对时宁可泄漏也不释放错误的地址
+        let container = unsafe { *(ptr as *const usize).sub(1) };
+        let back_shift = (ptr as usize).wrapping_sub(container);
+        if (size_of::<usize>()..=NON_USER_SIZE).contains(&back_shift) {
+            unsafe { (self.free)(container as *mut c_void) };
+        }
+    }
+}
+
+#[cfg(all(test, target_arch = "x86"))]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    thread_local! {
+        // 假的 CRT：分配的块和 free 收到的地址，每个测试线程单独一份
+        static BLOCKS: RefCell<Vec<Box<[u8]>>> = const { RefCell::new(Vec::new()) };
+        static FREED: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+    }
+
+    extern "C" fn fake_malloc(bytes: usize) -> *mut c_void {
+        BLOCKS.with_borrow_mut(|blocks| {
+            let mut block = vec![0u8; bytes].into_boxed_slice();
+            let ptr = block.as_mut_ptr();
+            blocks.push(block);
+            ptr as *mut c_void
+        })
+    }
+
+    extern "C" fn fake_free(ptr: *mut c_void) {
+        FREED.with_borrow_mut(|freed| freed.push(ptr as usize));
+    }
+
+    /// 记录 malloc/free 的假 CRT，块在线程结束时才真正释放
+    pub(crate) fn fake_heap() -> CrtHeap {
+        CrtHeap {
+            module: "fake_crt".to_string(),
+            malloc: fake_malloc,
+            free: fake_free,
+        }
+    }
+
+    /// 当前线程中 free 收到的地址
+    pub(crate) fn freed() -> Vec<usize> {
+        FREED.with_borrow(Clone::clone)
+    }
+
+    fn last_block() -> usize {
+        BLOCKS.with_borrow(|blocks| blocks.last().unwrap().as_ptr() as usize)
+    }
+
+    #[test]
+    fn small_blocks_use_malloc_directly() {
+        let heap = fake_heap();
+        let ptr = heap.allocate(BIG_ALLOCATION_THRESHOLD - 1).unwrap();
+        assert_eq!(ptr as usize, last_block());
+        unsafe { heap.deallocate(ptr, BIG_ALLOCATION_THRESHOLD - 1) };
+        assert_eq!(freed(), [ptr as usize]);
+    }
+
+    #[test]
+    fn big_blocks_are_aligned_with_back_pointer() {
+        let heap = fake_heap();
+        for bytes in [BIG_ALLOCATION_THRESHOLD, 10000] {
+            let ptr = heap.allocate(bytes).unwrap();
+            let container = last_block();
+            assert_eq!(ptr as usize % BIG_ALLOCATION_ALIGNMENT, 0);
+            assert_eq!(unsafe { *(ptr as *const usize).sub(1) }, container);
+            let shift = ptr as usize - container;
+            assert!((size_of::<usize>()..=NON_USER_SIZE).contains(&shift));
+            unsafe { heap.deallocate(ptr, bytes) };
+            assert_eq!(freed().last(), Some(&container));
+        }
+    }
+
+    #[test]
+    fn bad_back_pointer_is_not_freed() {
+        let heap = fake_heap();
+        let ptr = heap.allocate(BIG_ALLOCATION_THRESHOLD).unwrap();
+        let back = unsafe { (ptr as *mut usize).sub(1) };
+        // 原始指针不在返回地址前面的余量范围内时不释放
+        for wrong in [ptr as usize, ptr as usize - NON_USER_SIZE - 1, 0] {
+            unsafe {
+                *back = wrong;
+                heap.deallocate(ptr, BIG_ALLOCATION_THRESHOLD);
+            }
+        }
+        assert!(freed().is_empty());
+        unsafe { heap.deallocate(std::ptr::null_mut(), BIG_ALLOCATION_THRESHOLD) };
+        assert!(freed().is_empty());
+    }
+
+    #[test]
+    fn uses_loaded_crt() {
+        assert!(CrtHeap::open("wxwork_missing_crt.dll").is_err());
+        // 测试程序本身也使用 ucrtbase.dll
+        let heap = CrtHeap::open("ucrtbase.dll").unwrap();
+        for bytes in [16, 8192] {
+            let ptr = heap.allocate(bytes).unwrap();
+            unsafe {
+                std::ptr::write_bytes(ptr, 0xAB, bytes);
+                heap.deallocate(ptr, bytes);
+            }
+        }
+    }
+}
@@ -0,0 +1,406 @@
+// 安全读取hook参数中以0结尾的字符串
+//
+// 参数来自客户端，指针可能无效，字符串可能很短也可能没有结尾。
+// 读取前用 VirtualQuery 检查内存是否可读：一次查询得到属性相同的一段连续页，
+// 读到这段的末尾再查询下一段，读到不可读的页或者超过上限时返回错误，不会越界访问。
+use std::ffi::c_void;
+use std::fmt;
+use windows::Win32::System::Memory::{
+    MEM_COMMIT, MEMORY_BASIC_INFORMATION, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE,
+    PAGE_EXECUTE_WRITECOPY, PAGE_GUARD, PAGE_NOACCESS, PAGE_READONLY, PAGE_READWRITE,
+    PAGE_WRITECOPY, VirtualQuery,
+};
+use wxwork_log::{decode_bytes, decode_utf16};
+
+/// 路径类参数的默认上限（字符数），与 Windows 长路径的上限相同
+pub const PATH_LIMIT: usize = 32767;
+
+/// 读取字符串失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CStrError {
+    Null,
+    Unreadable { address: usize, offset: usize }, // 第 offset 个字符所在的地址不可读
+    Unterminated { limit: usize },                // 读了 limit 个字符还没有遇到结尾的0
+    TooLong { len: usize, limit: usize },         // std::basic_string 的长度超过上限
+    Malformed { len: usize, capacity: usize }, // std::basic_string 的长度大于容量，不是有效的字符串
+}
+
+impl fmt::Display for CStrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CStrError::Null => write!(f, "空指针"),
+            CStrError::Unreadable { address, offset } => {
+                write!(f, "地址 0x{:08X} 不可读（第 {} 个字符）", address, offset)
+            }
+            CStrError::Unterminated { limit } => write!(f, "超过 {} 个字符没有结尾", limit),
+            CStrError::TooLong { len, limit } => {
+                write!(f, "长度 {} 超过上限 {}", len, limit)
+            }
+            CStrError::Malformed { len, capacity } => {
+                write!(f, "长度 {} 大于容量 {}", len, capacity)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CStrError {}
+
+/// 读取以0结尾的UTF-16字符串，最多 `limit` 个字符（不含结尾的0）
+///
+/// # Safety
+/// 读取期间其他线程不能释放或修改这段内存的保护属性。
+pub unsafe fn read_wide(ptr: *const u16, limit: usize) -> Result<Vec<u16>, CStrError> {
+    unsafe { read_units(ptr, limit) }
+}
+
+/// 读取以0结尾的多字节字符串，最多 `limit` 个字节（不含结尾的0）
+///
+/// # Safety
+/// 同 [`read_wide`]。
+pub unsafe fn read_ansi(ptr: *const u8, limit: usize) -> Result<Vec<u8>, CStrError> {
+    unsafe { read_units(ptr, limit) }
+}
+
+/// 读取UTF-16字符串并解码，不成对的代理项转义保留
+///
+/// # Safety
+/// 同 [`read_wide`]。
+pub unsafe fn read_wide_text(ptr: *const u16, limit: usize) -> Result<String, CStrError> {
+    Ok(decode_utf16(&unsafe { read_wide(ptr, limit) }?).text)
+}
+
+/// 读取多字节字符串并按识别出的编码解码，识别不了的字节转义保留
+///
+/// # Safety
+/// 同 [`read_wide`]。
+pub unsafe fn read_ansi_text(ptr: *const u8, limit: usize) -> Result<String, CStrError> {
+    Ok(decode_bytes(&unsafe { read_ansi(ptr, limit) }?).text)
+}
+
+/// 读取MSVC的std::wstring（VS2015以后的布局），长度超过 `limit` 时返回错误
+///
+/// # Safety
+/// 同 [`read_wide`]。
+pub unsafe fn read_msvc_wstring(ptr: *const c_void, limit: usize) -> Result<String, CStrError> {
+    Ok(decode_utf16(&unsafe { read_basic_string::<u16>(ptr, limit) }?).text)
+}
+
+/// 读取MSVC的std::string，按识别出的编码解码
+///
+/// # Safety
+/// 同 [`read_wide`]。
+pub unsafe fn read_msvc_string(ptr: *const c_void, limit: usize) -> Result<String, CStrError> {
+    Ok(decode_bytes(&unsafe { read_basic_string::<u8>(ptr, limit) }?).text)
+}
+
+// std::basic_string 的布局：16字节的内联缓冲区（容量足够大时存放堆指针）、长度、容量，
+// 容量不超过内联缓冲区能放下的字符数（不含结尾的0）时内容在内联缓冲区中
+unsafe fn read_basic_string<T: Copy>(
+    ptr: *const c_void,
+    limit: usize,
+) -> Result<Vec<T>, CStrError> {
+    const BUFFER_SIZE: usize = 16;
+    if ptr.is_null() {
+        return Err(CStrError::Null);
+    }
+    let header = unsafe { read_exact(ptr as *const u8, BUFFER_SIZE + 2 * size_of::<usize>()) }?;
+    let field = |index: usize| {
+        let start = BUFFER_SIZE + index * size_of::<usize>();
+        usize::from_ne_bytes(
+            header[start..start + size_of::<usize>()]
+                .try_into()
+                .unwrap_or_default(),
+        )
+    };
+    let (len, capacity) = (field(0), field(1));
+    if len > capacity {
+        return Err(CStrError::Malformed { len, capacity });
+    }
+    if len > limit {
+        return Err(CStrError::TooLong { len, limit });
+    }
+
+    let small_capacity = BUFFER_SIZE / size_of::<T>() - 1;
+    let data = if capacity > small_capacity {
+        usize::from_ne_bytes(header[..size_of::<usize>()].try_into().unwrap_or_default())
+    } else {
+        ptr as usize
+    };
+    unsafe { read_exact(data as *const T, len) }
+}
+
+// 读取固定个数，整段可读才读取
+unsafe fn read_exact<T: Copy>(ptr: *const T, count: usize) -> Result<Vec<T>, CStrError> {
+    if ptr.is_null() {
+        return Err(CStrError::Null);
+    }
+    let start = ptr as usize;
+    let end = count
+        .checked_mul(size_of::<T>())
+        .and_then(|bytes| start.checked_add(bytes))
+        .ok_or(CStrError::Unreadable {
+            address: start,
+            offset: 0,
+        })?;
+    let mut readable_end = start;
+    while readable_end < end {
+        readable_end = readable_region_end(readable_end).ok_or(CStrError::Unreadable {
+            address: readable_end,
+            offset: (readable_end - start) / size_of::<T>(),
+        })?;
+    }
+    Ok((0..count)
+        .map(|index| unsafe { ptr.add(index).read_unaligned() })
+        .collect())
+}
+
+// 逐个读取直到遇到0，每跨过一段可读区域重新检查
+unsafe fn read_units<T: Copy + Default + PartialEq>(
+    ptr: *const T,
+    limit: usize,
+) -> Result<Vec<T>, CStrError> {
+    if ptr.is_null() {
+        return Err(CStrError::Null);
+    }
+    let unit = size_of::<T>();
+    let mut units = Vec::new();
+    let mut readable_end = ptr as usize;
+    // 多读一个位置用来检查结尾的0
+    while units.len() <= limit {
+        let offset = units.len();
+        let unreadable = |address| CStrError::Unreadable { address, offset };
+        let address = (ptr as usize)
+            .checked_add(offset * unit)
+            .ok_or(unreadable(usize::MAX))?;
+        let end = address.checked_add(unit).ok_or(unreadable(address))?;
+        // 字符可能跨过区域的边界（指针没有对齐时），需要连续查询
+        while end > readable_end {
+            let start = readable_end.max(address);
+            readable_end = readable_region_end(start).ok_or(unreadable(start))?;
+        }
+
+        let value = unsafe { (address as *const T).read_unaligned() };
+        if value == T::default() {
+            return Ok(units);
+        }
+        if offset == limit {
+            break;
+        }
+        units.push(value);
+    }
+    Err(CStrError::Unterminated { limit })
+}
+
+// `address` 所在区域可读时返回区域的结束地址
+fn readable_region_end(address: usize) -> Option<usize> {
+    let mut info = MEMORY_BASIC_INFORMATION::default();
+    let size = unsafe {
+        VirtualQuery(
+            Some(address as *const c_void),
+            &mut info,
+            size_of::<MEMORY_BASIC_INFORMATION>(),
+        )
+    };
+    if size == 0 || info.State != MEM_COMMIT {
+        return None;
+    }
+    let protect = info.Protect;
+    if (protect & (PAGE_GUARD | PAGE_NOACCESS)).0 != 0 {
+        return None;
+    }
+    let readable = PAGE_READONLY
+        | PAGE_READWRITE
+        | PAGE_WRITECOPY
+        | PAGE_EXECUTE_READ
+        | PAGE_EXECUTE_READWRITE
+        | PAGE_EXECUTE_WRITECOPY;
+    if (protect & readable).0 == 0 {
+        return None;
+    }
+    (info.BaseAddress as usize).checked_add(info.RegionSize)
+}
+
+#[cfg(all(test, target_arch = "x86"))]
+mod tests {
+    use super::*;
+    use windows::Win32::System::Memory::{
+        MEM_RELEASE, MEM_RESERVE, PAGE_PROTECTION_FLAGS, VirtualAlloc, VirtualFree, VirtualProtect,
+    };
+
+    const PAGE: usize = 4096;
+
+    // 两页内存，第二页设为 PAGE_NOACCESS，数据放在第一页的末尾
+    struct Guarded(*mut u8);
+
+    impl Guarded {
+        fn new() -> Self {
+            let base =
+                unsafe { VirtualAlloc(None, 2 * PAGE, MEM_COMMIT | MEM_RESERVE, PAGE_READWRITE) }
+                    as *mut u8;
+            assert!(!base.is_null());
+            let mut old = PAGE_PROTECTION_FLAGS::default();
+            unsafe {
+                VirtualProtect(
+                    base.add(PAGE) as *const c_void,
+                    PAGE,
+                    PAGE_NOACCESS,
+                    &mut old,
+                )
+            }
+            .unwrap();
+            Guarded(base)
+        }
+
+        // 不可访问的页的起始地址
+        fn guard(&self) -> usize {
+            self.0 as usize + PAGE
+        }
+
+        // 把 `bytes` 复制到不可访问的页前面，返回开始的地址
+        fn place(&self, bytes: &[u8]) -> *const u8 {
+            unsafe {
+                let ptr = self.0.add(PAGE - bytes.len());
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+                ptr
+            }
+        }
+    }
+
+    impl Drop for Guarded {
+        fn drop(&mut self) {
+            unsafe { VirtualFree(self.0 as *mut c_void, 0, MEM_RELEASE) }.unwrap();
+        }
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    fn wide_bytes(units: &[u16]) -> Vec<u8> {
+        units.iter().flat_map(|unit| unit.to_ne_bytes()).collect()
+    }
+
+    // std::basic_string 的对象：16字节缓冲区、长度、容量
+    fn basic_string(buffer: [u8; 16], len: usize, capacity: usize) -> Vec<u8> {
+        let mut object = buffer.to_vec();
+        object.extend_from_slice(&len.to_ne_bytes());
+        object.extend_from_slice(&capacity.to_ne_bytes());
+        object
+    }
+
+    #[test]
+    fn reads_terminated_strings() {
+        let text = wide("login\\LoginWnd.xml\0");
+        assert_eq!(
+            unsafe { read_wide_text(text.as_ptr(), PATH_LIMIT) },
+            Ok("login\\LoginWnd.xml".to_string())
+        );
+        // 结尾的0正好在不可访问的页前面
+        let page = Guarded::new();
+        let ptr = page.place(b"abc\0");
+        assert_eq!(unsafe { read_ansi(ptr, 3) }, Ok(b"abc".to_vec()));
+        assert_eq!(
+            unsafe { read_ansi(std::ptr::null(), 3) },
+            Err(CStrError::Null)
+        );
+    }
+
+    #[test]
+    fn stops_at_noaccess_page() {
+        let page = Guarded::new();
+        let ptr = page.place(b"abc");
+        assert_eq!(
+            unsafe { read_ansi(ptr, PATH_LIMIT) },
+            Err(CStrError::Unreadable {
+                address: page.guard(),
+                offset: 3,
+            })
+        );
+        // 没有对齐的字符跨过边界时也不读取
+        let ptr = page.place(b"abc") as *const u16;
+        assert_eq!(
+            unsafe { read_wide(ptr, PATH_LIMIT) },
+            Err(CStrError::Unreadable {
+                address: page.guard(),
+                offset: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn stops_at_limit() {
+        let text = wide("abcdef\0");
+        assert_eq!(
+            unsafe { read_wide(text.as_ptr(), 5) },
+            Err(CStrError::Unterminated { limit: 5 })
+        );
+        assert_eq!(unsafe { read_wide(text.as_ptr(), 6) }, Ok(wide("abcdef")));
+    }
+
+    #[test]
+    fn reads_small_basic_string() {
+        let bytes = wide_bytes(&wide("short\0"));
+        let mut buffer = [0u8; 16];
+        buffer[..bytes.len()].copy_from_slice(&bytes);
+        let object = basic_string(buffer, 5, 7);
+        assert_eq!(
+            unsafe { read_msvc_wstring(object.as_ptr() as *const c_void, PATH_LIMIT) },
+            Ok("short".to_string())
+        );
+        // 对象本身在不可访问的页前面被截断
+        let page = Guarded::new();
+        let ptr = page.place(&object[..16]);
+        assert_eq!(
+            unsafe { read_basic_string::<u16>(ptr as *const c_void, PATH_LIMIT) },
+            Err(CStrError::Unreadable {
+                address: page.guard(),
+                offset: 16,
+            })
+        );
+    }
+
+    #[test]
+    fn reads_heap_basic_string() {
+        let text = b"common\\titlebar.xml\0";
+        let mut buffer = [0u8; 16];
+        buffer[..4].copy_from_slice(&(text.as_ptr() as usize).to_ne_bytes());
+        let object = basic_string(buffer, text.len() - 1, 31);
+        assert_eq!(
+            unsafe { read_msvc_string(object.as_ptr() as *const c_void, PATH_LIMIT) },
+            Ok("common\\titlebar.xml".to_string())
+        );
+        assert_eq!(
+            unsafe { read_basic_string::<u8>(object.as_ptr() as *const c_void, 4) },
+            Err(CStrError::TooLong { len: 19, limit: 4 })
+        );
+
+        // 堆上的内容读到不可访问的页
+        let page = Guarded::new();
+        let ptr = page.place(b"common");
+        buffer[..4].copy_from_slice(&(ptr as usize).to_ne_bytes());
+        let object = basic_string(buffer, 19, 31);
+        assert_eq!(
+            unsafe { read_basic_string::<u8>(object.as_ptr() as *const c_void, PATH_LIMIT) },
+            Err(CStrError::Unreadable {
+                address: page.guard(),
+                offset: 6,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_basic_string() {
+        let object = basic_string([0; 16], 8, 7);
+        assert_eq!(
+            unsafe { read_basic_string::<u16>(object.as_ptr() as *const c_void, PATH_LIMIT) },
+            Err(CStrError::Malformed {
+                len: 8,
+                capacity: 7,
+            })
+        );
+        assert_eq!(
+            unsafe { read_basic_string::<u8>(std::ptr::null(), PATH_LIMIT) },
+            Err(CStrError::Null)
+        );
+    }
+}
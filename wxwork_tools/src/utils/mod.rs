@@ -1,7 +1,13 @@
 // 重新导出WString结构体和相关类型，使它们可以从外部访问
 pub mod wstring;
 pub mod string;
+pub mod crt_heap;
+pub mod cstr;
 
 // 为了方便使用，可以直接重新导出WString结构体
 pub use wstring::WString;
-pub use string::String;
\ No newline at end of file
+pub use string::String;
+pub use crt_heap::CrtHeap;
+pub use cstr::{
+    CStrError, PATH_LIMIT, read_ansi_text, read_msvc_string, read_msvc_wstring, read_wide_text,
+};
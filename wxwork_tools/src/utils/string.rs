@@ -89,6 +89,11 @@ impl String {
         Ok(s.to_owned())
     }
     
+    // 识别编码（UTF-8、GBK、UTF-16）后转换为Rust字符串，识别不了的字节转义保留，不会失败
+    pub fn decode(&self) -> wxwork_log::DecodedText {
+        wxwork_log::decode_bytes(self.as_bytes())
+    }
+    
     // 设置新的字符串内容
     pub fn set_str(&mut self, s: &str) {
         let bytes = s.as_bytes();
@@ -1,3 +1,4 @@
+use super::CrtHeap;
 
 #[repr(C)]
 pub struct WString {
@@ -8,10 +9,13 @@ pub struct WString {
 
 #[repr(C)]
 union WStringData {
-    wstr: [u16; 8],     // 如果数据长度小于8，就直接存储在wstr中
-    pwstr: *const u16,   // 如果数据长度大于8，就存储在pwstr中
+    wstr: [u16; 8],     // 如果数据长度不超过7，就直接存储在wstr中（最后一个位置放结尾的0）
+    pwstr: *const u16,   // 否则存储在堆上，pwstr指向堆内存
 }
 
+// 内联存储的最大长度，容量大于它时数据在堆上（与MSVC的std::wstring相同）
+const SMALL_CAPACITY: usize = 7;
+
 #[allow(dead_code)]
 impl WString {
     // 创建一个空的WString
@@ -19,7 +23,7 @@ impl WString {
         WString {
             data: WStringData { wstr: [0; 8] },
             length: 0,
-            capacity: 8,
+            capacity: SMALL_CAPACITY,
         }
     }
     
@@ -33,12 +37,12 @@ impl WString {
     pub fn from_utf16(utf16: &[u16]) -> Self {
         let len = utf16.len();
         
-        // 如果长度小于等于8，直接存储在wstr中
-        if len <= 8 {
+        // 如果长度不超过7，直接存储在wstr中
+        if len <= SMALL_CAPACITY {
             let mut result = WString {
                 data: WStringData { wstr: [0; 8] },
                 length: len,
-                capacity: 8,
+                capacity: SMALL_CAPACITY,
             };
             
             // 复制数据到wstr
@@ -66,10 +70,10 @@ impl WString {
         }
     }
     
-    // 获取UTF-16字符串内容
+    // 获取UTF-16字符串内容，按容量判断数据在哪里（字符串缩短后仍可能在堆上）
     pub fn as_utf16(&self) -> &[u16] {
         unsafe {
-            if self.length <= 8 {
+            if self.capacity <= SMALL_CAPACITY {
                 &self.data.wstr[0..self.length]
             } else {
                 std::slice::from_raw_parts(self.data.pwstr, self.length)
@@ -81,6 +85,11 @@ impl WString {
     pub fn to_string(&self) -> Result<String, std::string::FromUtf16Error> {
         String::from_utf16(self.as_utf16())
     }
+
+    // 转换为Rust字符串，不成对的代理项转义保留，不会失败
+    pub fn decode(&self) -> wxwork_log::DecodedText {
+        wxwork_log::decode_utf16(self.as_utf16())
+    }
     
     // 设置新的字符串内容
     pub fn set_str(&mut self, s: &str) {
@@ -93,7 +102,7 @@ impl WString {
         let len = utf16.len();
         
         // 如果当前是堆分配的，需要先释放
-        if self.capacity > 8 {
+        if self.capacity > SMALL_CAPACITY {
             unsafe {
                 let old_vec = Vec::from_raw_parts(
                     self.data.pwstr as *mut u16,
@@ -105,7 +114,7 @@ impl WString {
         }
         
         // 如果新内容可以放入wstr
-        if len <= 8 {
+        if len <= SMALL_CAPACITY {
             self.data = WStringData { wstr: [0; 8] };
             unsafe {
                 for i in 0..len {
@@ -113,7 +122,7 @@ impl WString {
                 }
             }
             self.length = len;
-            self.capacity = 8;
+            self.capacity = SMALL_CAPACITY;
         } else {
             // 否则，分配堆内存
             let mut heap_data = Vec::with_capacity(len);
@@ -129,10 +138,47 @@ impl WString {
         }
     }
     
+    /// 替换客户端（MSVC）创建的std::wstring的内容，新缓冲区从客户端的CRT堆上分配，
+    /// 客户端以后照常释放；原来的堆内存按同样的方式释放
+    ///
+    /// # Safety
+    /// `self` 必须是客户端创建、由客户端负责释放的std::wstring，`heap` 必须是客户端使用的CRT。
+    pub unsafe fn assign_with(&mut self, utf16: &[u16], heap: &CrtHeap) -> Result<(), String> {
+        let len = utf16.len();
+        let old = (self.capacity > SMALL_CAPACITY).then_some(unsafe { self.data.pwstr as *mut u8 });
+        let old_capacity = self.capacity;
+
+        if len <= SMALL_CAPACITY {
+            let mut wstr = [0u16; 8];
+            wstr[..len].copy_from_slice(utf16);
+            self.data = WStringData { wstr };
+            self.capacity = SMALL_CAPACITY;
+        } else {
+            // 容量等于长度，多分配一个位置放结尾的0
+            let bytes = (len + 1) * size_of::<u16>();
+            let ptr = heap
+                .allocate(bytes)
+                .ok_or_else(|| format!("在 {} 上分配 {} 字节失败", heap.module(), bytes))?
+                as *mut u16;
+            unsafe {
+                std::ptr::copy_nonoverlapping(utf16.as_ptr(), ptr, len);
+                *ptr.add(len) = 0;
+            }
+            self.data = WStringData { pwstr: ptr };
+            self.capacity = len;
+        }
+        self.length = len;
+
+        if let Some(old) = old {
+            unsafe { heap.deallocate(old, (old_capacity + 1) * size_of::<u16>()) };
+        }
+        Ok(())
+    }
+
     // 清空字符串
     pub fn clear(&mut self) {
         // 如果当前是堆分配的，需要释放
-        if self.capacity > 8 {
+        if self.capacity > SMALL_CAPACITY {
             unsafe {
                 let old_vec = Vec::from_raw_parts(
                     self.data.pwstr as *mut u16,
@@ -145,14 +191,14 @@ impl WString {
         
         self.data = WStringData { wstr: [0; 8] };
         self.length = 0;
-        self.capacity = 8;
+        self.capacity = SMALL_CAPACITY;
     }
 }
 
 // 实现Drop trait以确保堆内存被正确释放
 impl Drop for WString {
     fn drop(&mut self) {
-        if self.capacity > 8 {
+        if self.capacity > SMALL_CAPACITY {
             unsafe {
                 let _ = Vec::from_raw_parts(
                     self.data.pwstr as *mut u16,
@@ -163,4 +209,46 @@ impl Drop for WString {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, target_arch = "x86"))]
+mod tests {
+    use super::*;
+    use crate::utils::crt_heap::tests::{fake_heap, freed};
+
+    fn units(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    fn assign_with_moves_between_inline_and_heap() {
+        let heap = fake_heap();
+        let mut string = WString::new();
+        unsafe { string.assign_with(&units("short"), &heap).unwrap() };
+        assert_eq!(string.as_utf16(), units("short"));
+        assert_eq!(string.capacity, SMALL_CAPACITY);
+
+        // 超过内联容量时放到堆上，容量等于长度，结尾有0
+        let long = units("login\\LoginWnd.xml");
+        unsafe { string.assign_with(&long, &heap).unwrap() };
+        assert_eq!(string.as_utf16(), long);
+        assert_eq!(string.capacity, long.len());
+        let heap_ptr = unsafe { string.data.pwstr };
+        assert_eq!(unsafe { *heap_ptr.add(long.len()) }, 0);
+        assert!(freed().is_empty());
+
+        // 换成另一段堆上的内容时释放原来的缓冲区
+        let longer = units("common\\titlebar_next.xml");
+        unsafe { string.assign_with(&longer, &heap).unwrap() };
+        assert_eq!(string.as_utf16(), longer);
+        assert_eq!(freed(), [heap_ptr as usize]);
+
+        // 回到内联存储，最后不留堆上的缓冲区，Drop 不会释放假 CRT 的内存
+        let second = unsafe { string.data.pwstr } as usize;
+        unsafe { string.assign_with(&units("1234567"), &heap).unwrap() };
+        assert_eq!(string.as_utf16(), units("1234567"));
+        assert_eq!(string.capacity, SMALL_CAPACITY);
+        assert_eq!(unsafe { string.data.wstr[7] }, 0);
+        assert_eq!(freed(), [heap_ptr as usize, second]);
+    }
+}
@@ -0,0 +1,102 @@
+// 查看导出的函数调用跟踪结果，检查配置文件中的跟踪，可在 Linux 上运行
+//
+// 用法: trace_report [--csv | --detail] <导出的JSON>
+//       trace_report --config <wxwork_tools.toml>
+// 默认输出每个跟踪一行的汇总表，--csv 输出 CSV，--detail 输出每个跟踪的耗时分布、线程、返回值和最近的调用
+// --config 检查配置文件中的 [[trace]]，输出每个跟踪的函数位置和原型
+// 成功返回0，配置有错误返回1，参数错误或读取失败返回2
+use serde::Deserialize;
+use std::process::ExitCode;
+use wxwork_trace::{TraceReport, TraceSpec};
+
+// 配置文件中只读取跟踪部分
+#[derive(Deserialize)]
+struct TraceConfig {
+    #[serde(default)]
+    trace: Vec<toml::Value>,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let (mode, file) = match args.as_slice() {
+        [_, file] => ("table", file),
+        [_, mode, file] if ["--csv", "--detail", "--config"].contains(&mode.as_str()) => {
+            (mode.as_str(), file)
+        }
+        _ => {
+            eprintln!(
+                "用法: {} [--csv | --detail] <导出的JSON>\n      {0} --config <wxwork_tools.toml>",
+                args.first().map_or("trace_report", String::as_str)
+            );
+            return ExitCode::from(2);
+        }
+    };
+
+    let text = match std::fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("❌ 读取 {} 失败: {}", file, e);
+            return ExitCode::from(2);
+        }
+    };
+    if mode == "--config" {
+        return check_config(&text);
+    }
+
+    let report = match TraceReport::from_json(&text) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    match mode {
+        "--csv" => print!("{}", report.to_csv()),
+        "--detail" => {
+            for trace in &report.traces {
+                println!("{}", trace);
+            }
+        }
+        _ => {
+            println!("客户端版本: {}", report.client_version);
+            print!("{}", report.summary_table());
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+// 逐个检查跟踪，一个有错误不影响检查其他的
+fn check_config(text: &str) -> ExitCode {
+    let config: TraceConfig = match toml::from_str(text) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ 解析配置失败: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let mut failed = 0;
+    for (index, value) in config.trace.into_iter().enumerate() {
+        let result = value
+            .try_into::<TraceSpec>()
+            .map_err(|e| format!("第 {} 个跟踪: {}", index + 1, e))
+            .and_then(|spec| Ok((spec.validate()?, spec)));
+        match result {
+            Ok((target, spec)) => println!(
+                "✅ {} {} {}{}",
+                spec.name,
+                target,
+                spec.signature(),
+                if spec.enabled { "" } else { "（未启用）" }
+            ),
+            Err(e) => {
+                println!("❌ {}", e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,8 @@
+// hook函数调用跟踪的配置、统计和报表，不依赖 Windows，可在 Linux 上检查导出的结果
+pub mod report;
+pub mod spec;
+pub mod stats;
+
+pub use report::{TraceReport, TraceSummary, format_nanos};
+pub use spec::{CallConv, MAX_ARGS, TraceSpec, TraceTarget, ValueKind};
+pub use stats::{CallRecord, LatencyHistogram, TraceStats};
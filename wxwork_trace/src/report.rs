@@ -0,0 +1,352 @@
+use crate::spec::CallConv;
+use crate::stats::TraceStats;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// 表格中返回值最多显示的字符数
+const MAX_RETURN_WIDTH: usize = 32;
+
+/// 一个跟踪的结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceSummary {
+    pub name: String,
+    pub target: String,    // 函数位置，如 `duilib.dll!LoadXML`
+    pub signature: String, // 函数原型，如 `cdecl (hex, wstr, int) -> hex`
+    pub convention: CallConv,
+    pub stats: TraceStats,
+}
+
+/// 导出的跟踪结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceReport {
+    pub client_version: String,
+    pub traces: Vec<TraceSummary>,
+}
+
+impl TraceReport {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("生成 JSON 失败: {}", e))
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| format!("解析跟踪结果失败: {}", e))
+    }
+
+    /// 每个跟踪一行的汇总表
+    pub fn summary_table(&self) -> String {
+        let header = [
+            "名称",
+            "调用",
+            "线程",
+            "平均",
+            "P50",
+            "P99",
+            "最大",
+            "最多的返回值",
+        ];
+        let rows: Vec<Vec<String>> = self
+            .traces
+            .iter()
+            .map(|trace| {
+                let stats = &trace.stats;
+                vec![
+                    trace.name.clone(),
+                    stats.calls.to_string(),
+                    stats.threads.len().to_string(),
+                    stats.mean_ns().map_or("-".to_string(), format_nanos),
+                    format_percentile(stats, 50.0),
+                    format_percentile(stats, 99.0),
+                    stats
+                        .min_max_ns()
+                        .map_or("-".to_string(), |(_, max)| format_nanos(max)),
+                    format_top_return(stats),
+                ]
+            })
+            .collect();
+
+        let mut widths: Vec<usize> = header.iter().map(|title| display_width(title)).collect();
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(display_width(cell));
+            }
+        }
+        let mut table = String::new();
+        let header: Vec<String> = header.iter().map(|title| title.to_string()).collect();
+        for row in std::iter::once(&header).chain(&rows) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(column, (cell, width))| {
+                    let padding = " ".repeat(width - display_width(cell));
+                    // 名称和返回值左对齐，数字右对齐
+                    if column == 0 || column == row.len() - 1 {
+                        format!("{}{}", cell, padding)
+                    } else {
+                        format!("{}{}", padding, cell)
+                    }
+                })
+                .collect();
+            table.push_str(cells.join("  ").trim_end());
+            table.push('\n');
+        }
+        table
+    }
+
+    /// CSV 汇总表，耗时以微秒为单位，便于用表格软件处理
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "name,target,signature,calls,threads,mean_us,p50_us,p99_us,min_us,max_us,top_return,top_return_calls\n",
+        );
+        for trace in &self.traces {
+            let stats = &trace.stats;
+            let micros = |nanos: u64| format!("{:.3}", nanos as f64 / 1000.0);
+            let (top_return, top_calls) = stats.top_return().unwrap_or(("", 0));
+            let fields = [
+                csv_field(&trace.name),
+                csv_field(&trace.target),
+                csv_field(&trace.signature),
+                stats.calls.to_string(),
+                stats.threads.len().to_string(),
+                stats.mean_ns().map_or(String::new(), micros),
+                stats.percentile_ns(50.0).map_or(String::new(), micros),
+                stats.percentile_ns(99.0).map_or(String::new(), micros),
+                stats
+                    .min_max_ns()
+                    .map_or(String::new(), |(min, _)| micros(min)),
+                stats
+                    .min_max_ns()
+                    .map_or(String::new(), |(_, max)| micros(max)),
+                csv_field(top_return),
+                top_calls.to_string(),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl fmt::Display for TraceSummary {
+    // 一个跟踪的详细结果：耗时分布、线程、返回值和最近的调用
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stats = &self.stats;
+        writeln!(f, "== {}  {}  {}", self.name, self.target, self.signature)?;
+        match (stats.mean_ns(), stats.min_max_ns()) {
+            (Some(mean), Some((min, max))) => writeln!(
+                f,
+                "调用 {} 次，平均 {}，最短 {}，最长 {}",
+                stats.calls,
+                format_nanos(mean),
+                format_nanos(min),
+                format_nanos(max)
+            )?,
+            _ => writeln!(f, "没有调用")?,
+        }
+
+        let peak = stats
+            .histogram
+            .buckets()
+            .map(|(_, _, count)| count)
+            .max()
+            .unwrap_or(0);
+        if peak > 0 {
+            writeln!(f, "耗时分布:")?;
+            for (low, high, count) in stats.histogram.buckets().filter(|(_, _, count)| *count > 0) {
+                let range = match high {
+                    Some(high) => format!("{}-{}µs", low, high),
+                    None => format!("≥{}µs", low),
+                };
+                let bar = "#".repeat(((count * 40).div_ceil(peak)) as usize);
+                writeln!(f, "  {:>16} {:>8} {}", range, count, bar)?;
+            }
+        }
+
+        if !stats.threads.is_empty() {
+            let threads: Vec<String> = stats
+                .threads
+                .iter()
+                .map(|(thread, count)| format!("{}×{}", thread, count))
+                .collect();
+            writeln!(f, "线程: {}", threads.join(", "))?;
+        }
+
+        if !stats.returns.is_empty() {
+            let mut returns: Vec<(&String, &u64)> = stats.returns.iter().collect();
+            returns.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            writeln!(f, "返回值:")?;
+            for (ret, count) in returns.iter().take(10) {
+                writeln!(f, "  {:>8} {}", count, truncate(ret, 80))?;
+            }
+            let shown: u64 = returns.iter().take(10).map(|(_, count)| **count).sum();
+            let rest = stats.calls.saturating_sub(shown);
+            if rest > 0 {
+                writeln!(f, "  {:>8} (其他)", rest)?;
+            }
+        }
+
+        if !stats.recent.is_empty() {
+            writeln!(f, "最近的调用:")?;
+            for call in &stats.recent {
+                writeln!(
+                    f,
+                    "  #{:<6} 线程 {:<6} {:>10}  ({}) -> {}",
+                    call.seq,
+                    call.thread,
+                    format_nanos(call.elapsed_ns),
+                    call.args.join(", "),
+                    call.ret
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 耗时的简短写法，如 `850ns`、`12.3µs`、`4.56ms`、`1.23s`
+pub fn format_nanos(nanos: u64) -> String {
+    let value = nanos as f64;
+    if nanos < 1_000 {
+        format!("{}ns", nanos)
+    } else if nanos < 1_000_000 {
+        format!("{:.1}µs", value / 1e3)
+    } else if nanos < 1_000_000_000 {
+        format!("{:.2}ms", value / 1e6)
+    } else {
+        format!("{:.2}s", value / 1e9)
+    }
+}
+
+// 百分位按直方图估计，是所在桶的上限
+fn format_percentile(stats: &TraceStats, percent: f64) -> String {
+    stats
+        .percentile_ns(percent)
+        .map_or("-".to_string(), |nanos| format!("≤{}", format_nanos(nanos)))
+}
+
+fn format_top_return(stats: &TraceStats) -> String {
+    match stats.top_return() {
+        Some((ret, count)) if !ret.is_empty() => {
+            format!("{} ×{}", truncate(ret, MAX_RETURN_WIDTH), count)
+        }
+        _ => "-".to_string(),
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// 终端中的显示宽度，中日韩文字和全角符号占两列
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF => 2,
+            0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    // corpus 中的跟踪结果是按导出格式构造的样例，不是从客户端导出的
+    fn sample() -> TraceReport {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus/trace_sample.json");
+        TraceReport::from_json(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let report = sample();
+        assert_eq!(report.client_version, "sample");
+        assert_eq!(report.traces.len(), 3);
+        assert_eq!(
+            TraceReport::from_json(&report.to_json().unwrap()).unwrap(),
+            report
+        );
+        assert!(TraceReport::from_json("{").is_err());
+    }
+
+    #[test]
+    fn formats_nanos() {
+        assert_eq!(format_nanos(0), "0ns");
+        assert_eq!(format_nanos(999), "999ns");
+        assert_eq!(format_nanos(1_000), "1.0µs");
+        assert_eq!(format_nanos(260_648), "260.6µs");
+        assert_eq!(format_nanos(4_729_000), "4.73ms");
+        assert_eq!(format_nanos(1_234_567_890), "1.23s");
+    }
+
+    #[test]
+    fn summary_table_is_aligned() {
+        let table = sample().summary_table();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("名称"));
+        assert!(lines[1].starts_with("load_xml_trace"));
+        assert!(lines[1].contains("≤256.0µs"));
+        assert!(lines[1].ends_with("0x0019F3A0 ×19"));
+        assert!(lines[2].contains("1200"));
+        assert!(lines[2].ends_with("true ×1188"));
+        assert!(lines[3].starts_with("never_called"));
+
+        // 数字列右对齐：每一行的“最大”列在同一个显示宽度处结束
+        let max_end =
+            |line: &str, cell: &str| display_width(&line[..line.rfind(cell).unwrap() + cell.len()]);
+        assert_eq!(max_end(lines[0], "最大"), max_end(lines[1], "4.73ms"));
+        assert_eq!(max_end(lines[1], "4.73ms"), max_end(lines[2], "1.2µs"));
+    }
+
+    #[test]
+    fn csv_quotes_fields() {
+        let csv = sample().to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].contains(",\"cdecl (hex, wstr, int) -> hex\",37,2,260.621,"));
+        assert!(lines[1].ends_with(",4729.000,0x0019F3A0,19"));
+        assert_eq!(
+            lines[3],
+            "never_called,sample.dll!Idle,stdcall () -> none,0,0,,,,,,,0"
+        );
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+        assert_eq!(csv_field("ab"), "ab");
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn shows_details() {
+        let report = sample();
+        let detail = report.traces[0].to_string();
+        assert!(detail.starts_with("== load_xml_trace "));
+        assert!(detail.contains("调用 37 次，平均 260.6µs，最短 40.0µs，最长 4.73ms"));
+        assert!(detail.contains("128-256µs"));
+        assert!(detail.contains("线程: 7440×29, 8812×8"));
+        assert!(detail.contains("#33"));
+
+        let idle = report.traces[2].to_string();
+        assert!(idle.contains("没有调用"));
+        assert!(!idle.contains("耗时分布"));
+    }
+
+    #[test]
+    fn truncates_by_chars() {
+        assert_eq!(truncate("二维码内容", 3), "二维码…");
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(display_width("名称ab"), 6);
+    }
+}
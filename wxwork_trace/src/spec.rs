@@ -0,0 +1,475 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// 最多能跟踪的参数个数，三种调用约定相同（thiscall 包括 this）；
+/// wxwork_tools 为每种调用约定的每个参数个数生成一组hook函数
+pub const MAX_ARGS: usize = 8;
+
+/// 被跟踪函数的调用约定（x86），hook按配置的参数个数转发，参数个数必须与函数一致
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallConv {
+    #[default]
+    Cdecl, // 调用者清理栈
+    Stdcall,  // 被调用者清理栈
+    Thiscall, // 第一个参数是 ecx 中的 this，其余同 stdcall
+}
+
+impl FromStr for CallConv {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cdecl" => Ok(CallConv::Cdecl),
+            "stdcall" => Ok(CallConv::Stdcall),
+            "thiscall" => Ok(CallConv::Thiscall),
+            _ => Err(format!("未知的调用约定: {}（cdecl/stdcall/thiscall）", s)),
+        }
+    }
+}
+
+impl fmt::Display for CallConv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallConv::Cdecl => write!(f, "cdecl"),
+            CallConv::Stdcall => write!(f, "stdcall"),
+            CallConv::Thiscall => write!(f, "thiscall"),
+        }
+    }
+}
+
+/// 参数和返回值的显示方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueKind {
+    Int,  // 有符号整数
+    Uint, // 无符号整数
+    #[default]
+    #[serde(alias = "ptr")]
+    Hex, // 十六进制，指针也用它
+    Bool,
+    Wstr,    // 以0结尾的 wchar_t*
+    Str,     // 以0结尾的 char*，按识别出的编码解码
+    Wstring, // MSVC 的 std::wstring*
+    String,  // MSVC 的 std::string*
+    None,    // 不记录（没有返回值的函数）
+}
+
+impl ValueKind {
+    /// 需要读取内存才能显示
+    pub fn reads_memory(self) -> bool {
+        matches!(
+            self,
+            ValueKind::Wstr | ValueKind::Str | ValueKind::Wstring | ValueKind::String
+        )
+    }
+
+    /// 不需要读取内存的类型直接显示，其余返回 `None`
+    pub fn render_scalar(self, value: u32) -> Option<String> {
+        match self {
+            ValueKind::Int => Some((value as i32).to_string()),
+            ValueKind::Uint => Some(value.to_string()),
+            ValueKind::Hex => Some(format!("0x{:08X}", value)),
+            ValueKind::Bool => Some((value != 0).to_string()),
+            ValueKind::None => Some(String::new()),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ValueKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "int" => Ok(ValueKind::Int),
+            "uint" => Ok(ValueKind::Uint),
+            "hex" | "ptr" => Ok(ValueKind::Hex),
+            "bool" => Ok(ValueKind::Bool),
+            "wstr" => Ok(ValueKind::Wstr),
+            "str" => Ok(ValueKind::Str),
+            "wstring" => Ok(ValueKind::Wstring),
+            "string" => Ok(ValueKind::String),
+            "none" => Ok(ValueKind::None),
+            _ => Err(format!(
+                "未知的类型: {}（int/uint/hex/bool/wstr/str/wstring/string/none）",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ValueKind::Int => "int",
+            ValueKind::Uint => "uint",
+            ValueKind::Hex => "hex",
+            ValueKind::Bool => "bool",
+            ValueKind::Wstr => "wstr",
+            ValueKind::Str => "str",
+            ValueKind::Wstring => "wstring",
+            ValueKind::String => "string",
+            ValueKind::None => "none",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 被跟踪函数的位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceTarget {
+    Export { module: String, symbol: String },
+    Offset { module: String, offset: usize },
+    Signature { module: String, pattern: String },
+}
+
+impl FromStr for TraceTarget {
+    type Err = String;
+
+    /// 命令行的写法：`模块!导出名` 或 `模块+0x偏移`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((module, symbol)) = s.split_once('!') {
+            if module.is_empty() || symbol.is_empty() {
+                return Err(format!("目标 {} 缺少模块或导出名", s));
+            }
+            return Ok(TraceTarget::Export {
+                module: module.to_string(),
+                symbol: symbol.to_string(),
+            });
+        }
+        if let Some((module, offset)) = s.rsplit_once('+') {
+            let digits = offset
+                .strip_prefix("0x")
+                .or_else(|| offset.strip_prefix("0X"))
+                .ok_or_else(|| format!("偏移 {} 要写成十六进制（0x开头）", offset))?;
+            let offset = usize::from_str_radix(digits, 16)
+                .map_err(|_| format!("偏移 {} 不是十六进制数", offset))?;
+            if module.is_empty() {
+                return Err(format!("目标 {} 缺少模块", s));
+            }
+            return Ok(TraceTarget::Offset {
+                module: module.to_string(),
+                offset,
+            });
+        }
+        Err(format!("目标 {} 要写成 模块!导出名 或 模块+0x偏移", s))
+    }
+}
+
+impl fmt::Display for TraceTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceTarget::Export { module, symbol } => write!(f, "{}!{}", module, symbol),
+            TraceTarget::Offset { module, offset } => write!(f, "{}+0x{:X}", module, offset),
+            TraceTarget::Signature { module, pattern } => {
+                write!(f, "{} 特征码 [{}]", module, pattern)
+            }
+        }
+    }
+}
+
+fn default_recent() -> usize {
+    20
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 一个跟踪的配置，`export`、`offset`、`pattern` 三选一
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TraceSpec {
+    pub name: String, // hook名称，控制台 enable/disable 使用
+    pub module: String,
+    pub export: Option<String>,
+    pub offset: Option<usize>,
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub convention: CallConv,
+    #[serde(default)]
+    pub args: Vec<ValueKind>, // 每个参数的显示方式，thiscall 的第一个是 this
+    #[serde(default)]
+    pub ret: ValueKind,
+    #[serde(default = "default_true")]
+    pub enabled: bool, // 启动时启用
+    #[serde(default = "default_recent")]
+    pub recent: usize, // 保留最近几次调用的参数和返回值
+}
+
+impl TraceSpec {
+    /// 解析控制台的写法：`<名称> <模块!导出名|模块+0x偏移> <调用约定> [参数类型...] [-> 返回类型]`
+    pub fn parse_command<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        const USAGE: &str = "用法: <名称> <模块!导出名|模块+0x偏移> <cdecl|stdcall|thiscall> [参数类型...] [-> 返回类型]";
+        let name = words.next().ok_or(USAGE)?;
+        let target: TraceTarget = words.next().ok_or(USAGE)?.parse()?;
+        let convention: CallConv = words.next().ok_or(USAGE)?.parse()?;
+        let mut args = Vec::new();
+        let mut ret = ValueKind::default();
+        while let Some(word) = words.next() {
+            if word == "->" {
+                ret = words.next().ok_or(USAGE)?.parse()?;
+                if let Some(extra) = words.next() {
+                    return Err(format!("返回类型后面多了 {}", extra));
+                }
+                break;
+            }
+            args.push(word.parse()?);
+        }
+
+        let mut spec = TraceSpec {
+            name: name.to_string(),
+            module: String::new(),
+            export: None,
+            offset: None,
+            pattern: None,
+            convention,
+            args,
+            ret,
+            enabled: true,
+            recent: default_recent(),
+        };
+        match target {
+            TraceTarget::Export { module, symbol } => {
+                spec.module = module;
+                spec.export = Some(symbol);
+            }
+            TraceTarget::Offset { module, offset } => {
+                spec.module = module;
+                spec.offset = Some(offset);
+            }
+            TraceTarget::Signature { module, pattern } => {
+                spec.module = module;
+                spec.pattern = Some(pattern);
+            }
+        }
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// 检查配置，返回函数位置
+    pub fn validate(&self) -> Result<TraceTarget, String> {
+        let invalid = |reason: String| format!("跟踪 {}: {}", self.name, reason);
+        if self.name.is_empty() || self.name.contains(char::is_whitespace) {
+            return Err(invalid("名称不能为空或包含空白".to_string()));
+        }
+        if self.module.is_empty() {
+            return Err(invalid("没有指定模块".to_string()));
+        }
+        if self.args.contains(&ValueKind::None) {
+            return Err(invalid("参数不能是 none".to_string()));
+        }
+        if self.args.len() > MAX_ARGS {
+            return Err(invalid(format!("最多跟踪 {} 个参数", MAX_ARGS)));
+        }
+        if self.convention == CallConv::Thiscall && self.args.is_empty() {
+            return Err(invalid("thiscall 至少要有 this 参数".to_string()));
+        }
+
+        let module = self.module.clone();
+        match (&self.export, self.offset, &self.pattern) {
+            (Some(symbol), None, None) => Ok(TraceTarget::Export {
+                module,
+                symbol: symbol.clone(),
+            }),
+            (None, Some(offset), None) => Ok(TraceTarget::Offset { module, offset }),
+            (None, None, Some(pattern)) => Ok(TraceTarget::Signature {
+                module,
+                pattern: pattern.clone(),
+            }),
+            _ => Err(invalid(
+                "export、offset、pattern 必须指定且只能指定一个".to_string(),
+            )),
+        }
+    }
+
+    /// 函数原型的简短写法，如 `cdecl (hex, wstr, int) -> wstring`
+    pub fn signature(&self) -> String {
+        let args: Vec<String> = self.args.iter().map(ValueKind::to_string).collect();
+        format!("{} ({}) -> {}", self.convention, args.join(", "), self.ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(command: &str) -> Result<TraceSpec, String> {
+        TraceSpec::parse_command(command.split_whitespace())
+    }
+
+    #[test]
+    fn parses_targets() {
+        assert_eq!(
+            "duilib.dll!LoadXML".parse::<TraceTarget>(),
+            Ok(TraceTarget::Export {
+                module: "duilib.dll".to_string(),
+                symbol: "LoadXML".to_string()
+            })
+        );
+        assert_eq!(
+            "WXWork.exe+0x1A2B".parse::<TraceTarget>(),
+            Ok(TraceTarget::Offset {
+                module: "WXWork.exe".to_string(),
+                offset: 0x1A2B
+            })
+        );
+        // 模块名中可以有 +，按最后一个拆分
+        assert_eq!(
+            "a+b.dll+0X10".parse::<TraceTarget>(),
+            Ok(TraceTarget::Offset {
+                module: "a+b.dll".to_string(),
+                offset: 0x10
+            })
+        );
+        for invalid in [
+            "LoadXML",
+            "!LoadXML",
+            "duilib.dll!",
+            "a.dll+10",
+            "a.dll+0xZZ",
+            "+0x10",
+        ] {
+            assert!(invalid.parse::<TraceTarget>().is_err(), "{}", invalid);
+        }
+        for text in ["duilib.dll!LoadXML", "WXWork.exe+0x1A2B"] {
+            assert_eq!(text.parse::<TraceTarget>().unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn parses_kinds() {
+        assert_eq!("StdCall".parse::<CallConv>(), Ok(CallConv::Stdcall));
+        assert!("fastcall".parse::<CallConv>().is_err());
+        assert_eq!("PTR".parse::<ValueKind>(), Ok(ValueKind::Hex));
+        assert!("float".parse::<ValueKind>().is_err());
+        for kind in [
+            "int", "uint", "hex", "bool", "wstr", "str", "wstring", "string", "none",
+        ] {
+            assert_eq!(kind.parse::<ValueKind>().unwrap().to_string(), kind);
+        }
+    }
+
+    #[test]
+    fn renders_scalars() {
+        assert_eq!(
+            ValueKind::Int.render_scalar(u32::MAX),
+            Some("-1".to_string())
+        );
+        assert_eq!(
+            ValueKind::Uint.render_scalar(u32::MAX),
+            Some("4294967295".to_string())
+        );
+        assert_eq!(
+            ValueKind::Hex.render_scalar(0x1F),
+            Some("0x0000001F".to_string())
+        );
+        assert_eq!(ValueKind::Bool.render_scalar(2), Some("true".to_string()));
+        assert_eq!(ValueKind::None.render_scalar(7), Some(String::new()));
+        assert_eq!(ValueKind::Wstr.render_scalar(0), None);
+        assert!(ValueKind::String.reads_memory());
+        assert!(!ValueKind::Hex.reads_memory());
+    }
+
+    #[test]
+    fn parses_commands() {
+        let spec = parse("load_xml duilib.dll!LoadXML cdecl hex wstr int -> hex").unwrap();
+        assert_eq!(spec.name, "load_xml");
+        assert_eq!(spec.module, "duilib.dll");
+        assert_eq!(spec.export.as_deref(), Some("LoadXML"));
+        assert_eq!(spec.offset, None);
+        assert_eq!(spec.args, [ValueKind::Hex, ValueKind::Wstr, ValueKind::Int]);
+        assert_eq!(spec.signature(), "cdecl (hex, wstr, int) -> hex");
+        assert!(spec.enabled);
+        assert_eq!(spec.recent, 20);
+
+        let spec = parse("check WXWork.exe+0x10 thiscall ptr int").unwrap();
+        assert_eq!(spec.offset, Some(0x10));
+        assert_eq!(spec.signature(), "thiscall (hex, int) -> hex");
+
+        let spec = parse("tick kernel32.dll!GetTickCount stdcall -> uint").unwrap();
+        assert!(spec.args.is_empty());
+        assert_eq!(spec.ret, ValueKind::Uint);
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        let invalid = [
+            "",
+            "name",
+            "name a.dll!f",
+            "name a.dll!f cdecl ->",
+            "name a.dll!f cdecl -> int int",
+            "name a.dll!f cdecl none",
+            "name a.dll!f cdecl float",
+            "name a.dll!f thiscall",
+        ];
+        for command in invalid {
+            assert!(parse(command).is_err(), "{}", command);
+        }
+        for convention in ["cdecl", "stdcall", "thiscall"] {
+            let command =
+                |args: usize| format!("name a.dll!f {}{}", convention, " hex".repeat(args));
+            assert!(parse(&command(MAX_ARGS)).is_ok(), "{}", convention);
+            assert!(parse(&command(MAX_ARGS + 1)).is_err(), "{}", convention);
+        }
+    }
+
+    #[test]
+    fn validates_config() {
+        #[derive(Deserialize)]
+        struct Config {
+            trace: Vec<TraceSpec>,
+        }
+        let config: Config = toml::from_str(
+            r#"
+            [[trace]]
+            name = "message_box"
+            module = "user32.dll"
+            export = "MessageBoxW"
+            convention = "stdcall"
+            args = ["ptr", "wstr", "wstr", "uint"]
+            ret = "int"
+            recent = 5
+
+            [[trace]]
+            name = "defaults"
+            module = "a.dll"
+            pattern = "55 8B EC ??"
+
+            [[trace]]
+            name = "two_targets"
+            module = "a.dll"
+            export = "f"
+            offset = 16
+
+            [[trace]]
+            name = "no target"
+            module = "a.dll"
+            "#,
+        )
+        .unwrap();
+        let trace = &config.trace;
+        assert_eq!(
+            trace[0].validate(),
+            Ok(TraceTarget::Export {
+                module: "user32.dll".to_string(),
+                symbol: "MessageBoxW".to_string()
+            })
+        );
+        assert_eq!(trace[0].args[0], ValueKind::Hex);
+        assert_eq!(trace[0].recent, 5);
+
+        assert_eq!(trace[1].convention, CallConv::Cdecl);
+        assert_eq!(trace[1].ret, ValueKind::Hex);
+        assert!(trace[1].enabled);
+        assert_eq!(
+            trace[1].validate().unwrap().to_string(),
+            "a.dll 特征码 [55 8B EC ??]"
+        );
+
+        assert!(trace[2].validate().unwrap_err().contains("只能指定一个"));
+        assert!(trace[3].validate().unwrap_err().contains("名称"));
+    }
+}
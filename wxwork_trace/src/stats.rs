@@ -0,0 +1,282 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+// 直方图的桶数：第0个桶是不到1微秒，第 i 个桶是 [2^(i-1), 2^i) 微秒，最后一个桶不设上限
+const HISTOGRAM_BUCKETS: usize = 24;
+
+// 最多分别统计的返回值个数，之后出现的新值只计入 other_returns
+const MAX_DISTINCT_RETURNS: usize = 64;
+
+/// 耗时直方图，桶的边界按微秒的2的幂次划分
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencyHistogram {
+    buckets: Vec<u64>,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            buckets: vec![0; HISTOGRAM_BUCKETS],
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, elapsed_ns: u64) {
+        let micros = elapsed_ns / 1000;
+        let index = (u64::BITS - micros.leading_zeros()) as usize;
+        let index = index.min(self.buckets.len() - 1);
+        self.buckets[index] += 1;
+    }
+
+    /// 每个桶的范围（微秒，上限不含）和次数
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, Option<u64>, u64)> + '_ {
+        let last = self.buckets.len() - 1;
+        self.buckets.iter().enumerate().map(move |(index, count)| {
+            let low = if index == 0 { 0 } else { 1u64 << (index - 1) };
+            let high = (index < last).then(|| 1u64 << index);
+            (low, high, *count)
+        })
+    }
+
+    /// 第 `percent` 百分位所在桶的上限（微秒），落在最后一个桶时返回它的下限
+    pub fn percentile(&self, percent: f64) -> Option<u64> {
+        let total: u64 = self.buckets.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((total as f64) * percent / 100.0).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (low, high, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return Some(high.unwrap_or(low));
+            }
+        }
+        None
+    }
+}
+
+/// 一次调用
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallRecord {
+    pub seq: u64, // 第几次调用，从1开始
+    pub thread: u32,
+    pub args: Vec<String>,
+    pub ret: String,
+    pub elapsed_ns: u64,
+}
+
+/// 一个跟踪的统计
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceStats {
+    pub calls: u64,
+    pub total_ns: u64,
+    pub min_ns: u64,
+    pub max_ns: u64,
+    pub histogram: LatencyHistogram,
+    pub threads: BTreeMap<u32, u64>,    // 线程ID和调用次数
+    pub returns: BTreeMap<String, u64>, // 返回值和次数
+    pub other_returns: u64,             // 超过分别统计的个数后出现的返回值
+    pub recent: VecDeque<CallRecord>,   // 最近的调用，旧的在前
+    pub recent_capacity: usize,
+}
+
+impl TraceStats {
+    pub fn new(recent_capacity: usize) -> Self {
+        TraceStats {
+            calls: 0,
+            total_ns: 0,
+            min_ns: 0,
+            max_ns: 0,
+            histogram: LatencyHistogram::default(),
+            threads: BTreeMap::new(),
+            returns: BTreeMap::new(),
+            other_returns: 0,
+            recent: VecDeque::new(),
+            recent_capacity,
+        }
+    }
+
+    /// 记录一次调用，返回它的序号
+    pub fn record(&mut self, thread: u32, args: Vec<String>, ret: String, elapsed_ns: u64) -> u64 {
+        self.calls += 1;
+        self.total_ns = self.total_ns.saturating_add(elapsed_ns);
+        self.min_ns = if self.calls == 1 {
+            elapsed_ns
+        } else {
+            self.min_ns.min(elapsed_ns)
+        };
+        self.max_ns = self.max_ns.max(elapsed_ns);
+        self.histogram.record(elapsed_ns);
+        *self.threads.entry(thread).or_default() += 1;
+
+        if let Some(count) = self.returns.get_mut(&ret) {
+            *count += 1;
+        } else if self.returns.len() < MAX_DISTINCT_RETURNS {
+            self.returns.insert(ret.clone(), 1);
+        } else {
+            self.other_returns += 1;
+        }
+
+        if self.recent_capacity > 0 {
+            if self.recent.len() == self.recent_capacity {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(CallRecord {
+                seq: self.calls,
+                thread,
+                args,
+                ret,
+                elapsed_ns,
+            });
+        }
+        self.calls
+    }
+
+    /// 清空统计，保留设置
+    pub fn reset(&mut self) {
+        *self = TraceStats::new(self.recent_capacity);
+    }
+
+    pub fn mean_ns(&self) -> Option<u64> {
+        (self.calls > 0).then(|| self.total_ns / self.calls)
+    }
+
+    /// 按直方图估计的百分位耗时（纳秒），是所在桶的上限，不超过最长的一次
+    pub fn percentile_ns(&self, percent: f64) -> Option<u64> {
+        self.histogram
+            .percentile(percent)
+            .map(|micros| micros.saturating_mul(1000).min(self.max_ns))
+    }
+
+    pub fn min_max_ns(&self) -> Option<(u64, u64)> {
+        (self.calls > 0).then_some((self.min_ns, self.max_ns))
+    }
+
+    /// 次数最多的返回值
+    pub fn top_return(&self) -> Option<(&str, u64)> {
+        self.returns
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(ret, count)| (ret.as_str(), *count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn histogram(elapsed_ns: &[u64]) -> LatencyHistogram {
+        let mut histogram = LatencyHistogram::default();
+        for elapsed in elapsed_ns {
+            histogram.record(*elapsed);
+        }
+        histogram
+    }
+
+    fn counts(histogram: &LatencyHistogram) -> Vec<(u64, Option<u64>, u64)> {
+        histogram
+            .buckets()
+            .filter(|(_, _, count)| *count > 0)
+            .collect()
+    }
+
+    #[test]
+    fn buckets_by_powers_of_two() {
+        let histogram = histogram(&[0, 999, 1_000, 1_999, 2_000, 3_999, 1_000_000]);
+        assert_eq!(
+            counts(&histogram),
+            [
+                (0, Some(1), 2),
+                (1, Some(2), 2),
+                (2, Some(4), 2),
+                (512, Some(1024), 1)
+            ]
+        );
+        let (low, high, _) = histogram.buckets().last().unwrap();
+        assert_eq!((low, high), (1 << 22, None));
+    }
+
+    #[test]
+    fn slow_calls_go_to_last_bucket() {
+        let histogram = histogram(&[u64::MAX, 10_000_000_000]);
+        assert_eq!(counts(&histogram), [(1 << 22, None, 2)]);
+        assert_eq!(histogram.percentile(50.0), Some(1 << 22));
+    }
+
+    #[test]
+    fn percentile_is_bucket_upper_bound() {
+        assert_eq!(LatencyHistogram::default().percentile(50.0), None);
+
+        // 90 次约 1.5µs，10 次约 100µs
+        let mut elapsed = vec![1_500; 90];
+        elapsed.extend([100_000; 10]);
+        let histogram = histogram(&elapsed);
+        assert_eq!(histogram.percentile(0.0), Some(2));
+        assert_eq!(histogram.percentile(50.0), Some(2));
+        assert_eq!(histogram.percentile(90.0), Some(2));
+        assert_eq!(histogram.percentile(90.5), Some(128));
+        assert_eq!(histogram.percentile(100.0), Some(128));
+    }
+
+    #[test]
+    fn records_calls() {
+        let mut stats = TraceStats::new(2);
+        assert_eq!(stats.mean_ns(), None);
+        assert_eq!(stats.min_max_ns(), None);
+        assert_eq!(stats.percentile_ns(50.0), None);
+        assert_eq!(stats.top_return(), None);
+
+        assert_eq!(
+            stats.record(1, vec!["a".to_string()], "0".to_string(), 3_000),
+            1
+        );
+        assert_eq!(
+            stats.record(2, vec!["b".to_string()], "1".to_string(), 1_000),
+            2
+        );
+        assert_eq!(
+            stats.record(1, vec!["c".to_string()], "1".to_string(), 5_000),
+            3
+        );
+        assert_eq!(stats.mean_ns(), Some(3_000));
+        assert_eq!(stats.min_max_ns(), Some((1_000, 5_000)));
+        assert_eq!(stats.threads, BTreeMap::from([(1, 2), (2, 1)]));
+        assert_eq!(stats.top_return(), Some(("1", 2)));
+
+        // 只保留最近两次
+        let recent: Vec<(u64, &str)> = stats
+            .recent
+            .iter()
+            .map(|call| (call.seq, call.args[0].as_str()))
+            .collect();
+        assert_eq!(recent, [(2, "b"), (3, "c")]);
+
+        // 百分位按桶的上限估计，不超过最长的一次
+        assert_eq!(stats.percentile_ns(50.0), Some(4_000));
+        assert_eq!(stats.percentile_ns(100.0), Some(5_000));
+    }
+
+    #[test]
+    fn limits_distinct_returns() {
+        let mut stats = TraceStats::new(0);
+        for value in 0..MAX_DISTINCT_RETURNS + 10 {
+            stats.record(1, Vec::new(), value.to_string(), 1);
+        }
+        stats.record(1, Vec::new(), "0".to_string(), 1);
+        assert_eq!(stats.returns.len(), MAX_DISTINCT_RETURNS);
+        assert_eq!(stats.returns["0"], 2);
+        assert_eq!(stats.other_returns, 10);
+        assert!(stats.recent.is_empty());
+    }
+
+    #[test]
+    fn reset_keeps_recent_capacity() {
+        let mut stats = TraceStats::new(5);
+        stats.record(1, Vec::new(), String::new(), 10);
+        stats.reset();
+        assert_eq!(stats, TraceStats::new(5));
+        assert_eq!(stats.record(1, Vec::new(), String::new(), 10), 1);
+    }
+}
@@ -0,0 +1,366 @@
+use crate::dump_path::DumpLayout;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 清单文件名
+pub const MANIFEST_FILE_NAME: &str = "manifest.jsonl";
+
+// 内容文件目录
+const BLOBS_DIR: &str = "blobs";
+
+/// 清单中的一项：某个客户端版本在某个加载参数下读到的某个内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,           // 客户端传给 LoadXML 的路径
+    pub hash: String,           // 内容的 SHA-256，十六进制
+    pub size: u64,              // 内容字节数
+    pub first_seen: u64,        // 第一次读到的时间，Unix 毫秒
+    pub client_version: String, // 客户端版本
+    pub flag: i32,              // LoadXML 的第二个参数
+    #[serde(default)]
+    pub seq: u64, // 清单中的序号，按记录顺序递增；旧清单没有这个字段，打开时按行号补上
+}
+
+// 同一版本、同一路径、同一参数的内容归为一组，每组只和最后记录的内容比较
+type LoadKey = (String, String, i32);
+
+impl ManifestEntry {
+    fn load_key(&self) -> LoadKey {
+        (self.client_version.clone(), self.path.clone(), self.flag)
+    }
+}
+
+/// 保存一次的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreOutcome {
+    pub hash: String,
+    pub new_blob: bool,  // 内容第一次出现，写了新文件
+    pub new_entry: bool, // 清单中增加了一项
+}
+
+/// 按内容寻址的界面资源存档
+///
+/// 目录结构：
+/// - `blobs/<哈希前两位>/<哈希>.xml`：内容文件，相同内容只存一份；
+/// - `manifest.jsonl`：每行一项 `ManifestEntry`，只追加不修改。
+///
+/// 同一版本、同一路径、同一参数读到的内容与最后记录的相同时不再记录，内容变化（例如登录前后）时增加一项，
+/// 变回以前的内容（A→B→A）也会再记录一次，所以最后一项总是当前的内容。
+/// 多个客户端版本可以放在同一个存档里。多个进程同时写同一个存档时清单可能出现重复项，读取时会合并。
+pub struct Archive {
+    root: PathBuf,
+    entries: Vec<ManifestEntry>,
+    latest: HashMap<LoadKey, String>, // 每组最后记录的内容哈希
+    next_seq: u64,
+    skipped_lines: usize,
+}
+
+impl Archive {
+    /// 打开存档，目录不存在时创建
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, String> {
+        let root = root.into();
+        std::fs::create_dir_all(root.join(BLOBS_DIR))
+            .map_err(|e| format!("创建存档目录 {} 失败: {}", root.display(), e))?;
+
+        let mut archive = Archive {
+            root,
+            entries: Vec::new(),
+            latest: HashMap::new(),
+            next_seq: 1,
+            skipped_lines: 0,
+        };
+        let manifest_path = archive.manifest_path();
+        let text = match std::fs::read_to_string(&manifest_path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("读取清单 {} 失败: {}", manifest_path.display(), e)),
+        };
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str::<ManifestEntry>(line) {
+                Ok(mut entry) => {
+                    if archive.latest.get(&entry.load_key()) == Some(&entry.hash) {
+                        continue;
+                    }
+                    if entry.seq == 0 {
+                        entry.seq = archive.next_seq;
+                    }
+                    archive.next_seq = archive.next_seq.max(entry.seq + 1);
+                    archive.latest.insert(entry.load_key(), entry.hash.clone());
+                    archive.entries.push(entry);
+                }
+                // 写到一半的行（进程被结束）跳过
+                Err(_) => archive.skipped_lines += 1,
+            }
+        }
+        Ok(archive)
+    }
+
+    /// 存档目录
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 清单文件路径
+    pub fn manifest_path(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILE_NAME)
+    }
+
+    /// 内容文件路径
+    pub fn blob_path(&self, hash: &str) -> PathBuf {
+        let prefix = hash.get(..2).unwrap_or(hash);
+        self.root
+            .join(BLOBS_DIR)
+            .join(prefix)
+            .join(format!("{}.xml", hash))
+    }
+
+    /// 清单中无法解析而跳过的行数
+    pub fn skipped_lines(&self) -> usize {
+        self.skipped_lines
+    }
+
+    /// 清单中的所有项，按记录顺序
+    pub fn entries(&self) -> &[ManifestEntry] {
+        &self.entries
+    }
+
+    /// 存档中的客户端版本
+    pub fn versions(&self) -> BTreeSet<&str> {
+        self.entries
+            .iter()
+            .map(|entry| entry.client_version.as_str())
+            .collect()
+    }
+
+    /// 某个版本下每个路径最后读到的内容（序号最大的一项），按路径排序
+    pub fn snapshot(&self, client_version: &str) -> BTreeMap<&str, &ManifestEntry> {
+        let mut snapshot = BTreeMap::new();
+        for entry in self
+            .entries
+            .iter()
+            .filter(|entry| entry.client_version == client_version)
+        {
+            snapshot
+                .entry(entry.path.as_str())
+                .and_modify(|current: &mut &ManifestEntry| {
+                    if entry.seq >= current.seq {
+                        *current = entry;
+                    }
+                })
+                .or_insert(entry);
+        }
+        snapshot
+    }
+
+    /// 保存一次读到的内容
+    pub fn store(
+        &mut self,
+        path: &str,
+        content: &[u8],
+        client_version: &str,
+        flag: i32,
+        now_millis: u64,
+    ) -> Result<StoreOutcome, String> {
+        let hash = hash_content(content);
+        let new_blob = self.write_blob(&hash, content)?;
+
+        let entry = ManifestEntry {
+            path: path.to_string(),
+            hash: hash.clone(),
+            size: content.len() as u64,
+            first_seen: now_millis,
+            client_version: client_version.to_string(),
+            flag,
+            seq: self.next_seq,
+        };
+        let new_entry = self.latest.get(&entry.load_key()) != Some(&hash);
+        if new_entry {
+            self.append_entry(&entry)?;
+            self.next_seq += 1;
+            self.latest.insert(entry.load_key(), hash.clone());
+            self.entries.push(entry);
+        }
+        Ok(StoreOutcome {
+            hash,
+            new_blob,
+            new_entry,
+        })
+    }
+
+    /// 读取内容
+    pub fn read_blob(&self, hash: &str) -> Result<Vec<u8>, String> {
+        let path = self.blob_path(hash);
+        std::fs::read(&path).map_err(|e| format!("读取 {} 失败: {}", path.display(), e))
+    }
+
+    /// 把某个版本的快照按原来的目录结构写到 `layout` 下，返回写出的文件数
+    pub fn export(&self, client_version: &str, layout: &DumpLayout) -> Result<usize, String> {
+        let snapshot = self.snapshot(client_version);
+        for (path, entry) in &snapshot {
+            let content = self.read_blob(&entry.hash)?;
+            let text =
+                String::from_utf8(content).map_err(|_| format!("{} 的内容不是 UTF-8", path))?;
+            layout.write(path, &text)?;
+        }
+        Ok(snapshot.len())
+    }
+
+    // 写内容文件，已经存在时跳过；先写临时文件再改名，避免留下写了一半的内容
+    fn write_blob(&self, hash: &str, content: &[u8]) -> Result<bool, String> {
+        let path = self.blob_path(hash);
+        if path.is_file() {
+            return Ok(false);
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("创建目录 {} 失败: {}", parent.display(), e))?;
+        }
+        let temp = path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&temp, content)
+            .and_then(|()| std::fs::rename(&temp, &path))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&temp);
+                format!("保存 {} 失败: {}", path.display(), e)
+            })?;
+        Ok(true)
+    }
+
+    // 清单追加一行，一次写入整行
+    fn append_entry(&self, entry: &ManifestEntry) -> Result<(), String> {
+        let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        line.push('\n');
+        let path = self.manifest_path();
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| format!("写清单 {} 失败: {}", path.display(), e))
+    }
+}
+
+/// 内容的 SHA-256，十六进制小写
+pub fn hash_content(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const PATH: &str = "login\\LoginWnd.xml";
+    const VERSION: &str = "4.1.38.6006";
+
+    #[test]
+    fn stores_changed_content_only() {
+        let dir = TempDir::new().unwrap();
+        let mut archive = Archive::open(dir.path()).unwrap();
+        let first = archive.store(PATH, b"<A/>", VERSION, 0, 1).unwrap();
+        assert!(first.new_blob && first.new_entry);
+        let again = archive.store(PATH, b"<A/>", VERSION, 0, 2).unwrap();
+        assert!(!again.new_blob && !again.new_entry);
+        // 另一个参数或版本是单独的一组，内容文件共用
+        let other_flag = archive.store(PATH, b"<A/>", VERSION, 1, 3).unwrap();
+        assert!(!other_flag.new_blob && other_flag.new_entry);
+        let other_version = archive.store(PATH, b"<A/>", "4.1.39.0", 0, 3).unwrap();
+        assert!(other_version.new_entry);
+        assert_eq!(archive.entries().len(), 3);
+        assert_eq!(archive.read_blob(&first.hash).unwrap(), b"<A/>");
+        assert_eq!(first.hash, hash_content(b"<A/>"));
+    }
+
+    #[test]
+    fn content_changing_back_is_recorded() {
+        let dir = TempDir::new().unwrap();
+        let mut archive = Archive::open(dir.path()).unwrap();
+        let a = archive.store(PATH, b"<A/>", VERSION, 0, 1).unwrap();
+        let b = archive.store(PATH, b"<B/>", VERSION, 0, 2).unwrap();
+        let back = archive.store(PATH, b"<A/>", VERSION, 0, 3).unwrap();
+        assert!(b.new_entry);
+        assert!(back.new_entry && !back.new_blob);
+        assert_eq!(archive.snapshot(VERSION)[PATH].hash, a.hash);
+        let seqs: Vec<u64> = archive.entries().iter().map(|entry| entry.seq).collect();
+        assert_eq!(seqs, [1, 2, 3]);
+
+        // 时间相同（或时钟回拨）时按序号判断哪个是最后的内容
+        let c = archive.store(PATH, b"<C/>", VERSION, 0, 0).unwrap();
+        assert_eq!(archive.snapshot(VERSION)[PATH].hash, c.hash);
+    }
+
+    #[test]
+    fn reopen_keeps_order_and_merges_duplicates() {
+        let dir = TempDir::new().unwrap();
+        let mut archive = Archive::open(dir.path()).unwrap();
+        archive.store(PATH, b"<A/>", VERSION, 0, 1).unwrap();
+        archive.store(PATH, b"<B/>", VERSION, 0, 2).unwrap();
+        // 另一个进程同时写入的重复项，和写到一半的行
+        let duplicate = archive.entries()[1].clone();
+        drop(archive);
+        let mut manifest = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(MANIFEST_FILE_NAME))
+            .unwrap();
+        writeln!(manifest, "{}", serde_json::to_string(&duplicate).unwrap()).unwrap();
+        write!(manifest, "{{\"path\":\"half").unwrap();
+        drop(manifest);
+
+        let mut archive = Archive::open(dir.path()).unwrap();
+        assert_eq!(archive.entries().len(), 2);
+        assert_eq!(archive.skipped_lines(), 1);
+        assert!(
+            !archive
+                .store(PATH, b"<B/>", VERSION, 0, 3)
+                .unwrap()
+                .new_entry
+        );
+        assert!(
+            archive
+                .store(PATH, b"<A/>", VERSION, 0, 4)
+                .unwrap()
+                .new_entry
+        );
+        assert_eq!(archive.entries()[2].seq, 3);
+        assert_eq!(archive.snapshot(VERSION)[PATH].hash, hash_content(b"<A/>"));
+    }
+
+    #[test]
+    fn old_manifest_without_seq() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path()).unwrap();
+        let line = |hash: &str, first_seen: u64| {
+            format!(
+                "{{\"path\":\"a.xml\",\"hash\":\"{}\",\"size\":4,\"first_seen\":{},\"client_version\":\"1\",\"flag\":0}}\n",
+                hash, first_seen
+            )
+        };
+        let text = line("aa", 5) + &line("bb", 5);
+        std::fs::write(dir.path().join(MANIFEST_FILE_NAME), text).unwrap();
+
+        let archive = Archive::open(dir.path()).unwrap();
+        let seqs: Vec<u64> = archive.entries().iter().map(|entry| entry.seq).collect();
+        assert_eq!(seqs, [1, 2]);
+        assert_eq!(archive.snapshot("1")["a.xml"].hash, "bb");
+        assert_eq!(archive.versions().into_iter().collect::<Vec<_>>(), ["1"]);
+    }
+
+    #[test]
+    fn exports_latest_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let mut archive = Archive::open(dir.path().join("archive")).unwrap();
+        archive.store(PATH, b"<A/>", VERSION, 0, 1).unwrap();
+        archive.store(PATH, b"<B/>", VERSION, 0, 2).unwrap();
+        archive.store("main.xml", b"<M/>", VERSION, 0, 3).unwrap();
+        let layout = DumpLayout::new(dir.path().join("export"), false);
+        assert_eq!(archive.export(VERSION, &layout).unwrap(), 2);
+        let exported = std::fs::read(layout.path_for(PATH).unwrap()).unwrap();
+        assert_eq!(exported, b"<B/>");
+    }
+}
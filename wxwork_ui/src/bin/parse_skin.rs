@@ -0,0 +1,126 @@
+// 解析抓到的 DuiLib 界面XML，可在 Linux 上检查解析结果
+//
+// 用法: parse_skin [--json] [--check] <XML文件>...
+// 默认输出缩进的节点树和按类型解析的属性，最后列出字体、引用和自定义控件
+// 带 --json 时每个文件输出一个 JSON 文档
+// 带 --check 时只输出每个文件的统计，有属性（包括 Default 中的属性列表）按类型解析失败时报告，
+// 用于回归检查样例文件（corpus/skin/）
+// 全部通过时返回0，有文件解析失败或校验失败返回1，参数错误返回2
+use std::path::Path;
+use std::process::ExitCode;
+use wxwork_ui::SkinDocument;
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    let check = args.iter().any(|arg| arg == "--check");
+    args.retain(|arg| arg != "--json" && arg != "--check");
+    if args.len() < 2 {
+        eprintln!(
+            "用法: {} [--json] [--check] <XML文件>...",
+            args.first().map_or("parse_skin", String::as_str)
+        );
+        return ExitCode::from(2);
+    }
+
+    let mut failed = 0;
+    for file in &args[1..] {
+        let document = match SkinDocument::load(Path::new(file)) {
+            Ok(document) => document,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                failed += 1;
+                continue;
+            }
+        };
+        if json {
+            match serde_json::to_string_pretty(&document) {
+                Ok(text) => println!("{}", text),
+                Err(e) => eprintln!("❌ {}: {}", file, e),
+            }
+            continue;
+        }
+
+        let problems = problems(&document);
+        if check {
+            println!(
+                "{} {}: {} 个节点，{} 个自定义控件类型，{} 个问题",
+                if problems.is_empty() { "✅" } else { "❌" },
+                file,
+                document.walk().count(),
+                document.custom_tags().len(),
+                problems.len()
+            );
+        } else {
+            println!("== {}", file);
+            print!("{}", document.root);
+            print_summary(&document);
+        }
+        for problem in &problems {
+            println!("  ⚠ {}", problem);
+        }
+        if !problems.is_empty() {
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// 按类型解析失败的属性和无法解析的默认属性列表
+fn problems(document: &SkinDocument) -> Vec<String> {
+    let mut problems: Vec<String> = document
+        .invalid_attributes()
+        .into_iter()
+        .map(|(node, attr)| {
+            format!(
+                "第 {} 行 {}.{}: {}",
+                node.line,
+                node.tag,
+                attr.name,
+                attr.invalid.as_deref().unwrap_or_default()
+            )
+        })
+        .collect();
+    for default in document.defaults() {
+        match default.attributes() {
+            Ok(attributes) => problems.extend(
+                attributes
+                    .iter()
+                    .filter_map(|attr| attr.invalid.as_ref().map(|e| (attr, e)))
+                    .map(|(attr, e)| format!("Default {}.{}: {}", default.class, attr.name, e)),
+            ),
+            Err(e) => problems.push(e),
+        }
+    }
+    problems
+}
+
+fn print_summary(document: &SkinDocument) {
+    for font in document.fonts() {
+        println!(
+            "字体 {:?}: {} {:?}{}{}{}{}",
+            font.id,
+            font.name,
+            font.size,
+            if font.bold { " 粗体" } else { "" },
+            if font.italic { " 斜体" } else { "" },
+            if font.underline { " 下划线" } else { "" },
+            if font.default { " 默认" } else { "" }
+        );
+    }
+    for include in document.includes() {
+        println!("引用 {} x{}", include.source, include.count);
+    }
+    let custom = document.custom_tags();
+    if !custom.is_empty() {
+        println!(
+            "自定义控件: {}",
+            custom.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+}
@@ -0,0 +1,51 @@
+// 比较两个客户端版本的界面XML，可在 Linux 上运行
+//
+// 用法: skin_diff [--json] <旧版本> <新版本>
+// 每个版本可以是 `<存档目录>@<客户端版本>`（ui_archive 的存档中某个版本的快照），
+// 也可以是按目录保存的结果（wxwork_ui_rs 或 export 导出的目录）
+// 文件按规范化后的路径对应，有名称的控件按名称对应，其余按在父节点下的位置对应，
+// 列出新增、删除、移动的控件和属性变化；带 --json 时输出 JSON
+// 没有差异返回0，有差异返回1，参数错误或读取失败返回2
+use std::process::ExitCode;
+use wxwork_ui::{SkinSet, diff_sets};
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    args.retain(|arg| arg != "--json");
+    if args.len() != 3 {
+        eprintln!(
+            "用法: {} [--json] <存档目录@版本 | 目录> <存档目录@版本 | 目录>",
+            args.first().map_or("skin_diff", String::as_str)
+        );
+        return ExitCode::from(2);
+    }
+
+    let (old, new) = match SkinSet::load_spec(&args[1])
+        .and_then(|old| Ok((old, SkinSet::load_spec(&args[2])?)))
+    {
+        Ok(sets) => sets,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let diff = diff_sets(&old, &new);
+    if json {
+        match serde_json::to_string_pretty(&diff) {
+            Ok(text) => println!("{}", text),
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                return ExitCode::from(2);
+            }
+        }
+    } else {
+        print!("{}", diff);
+    }
+
+    if diff.files.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
@@ -0,0 +1,92 @@
+// 展开窗口的 Include，列出引用的界面XML、图片和字体，可在 Linux 上运行
+//
+// 用法: skin_resolve [--json] [--tree] [--resources <目录>] <存档目录@版本 | 目录> <窗口XML路径 | --all>
+// 窗口XML路径与 LoadXML 收到的路径相同（例如 login\LoginWnd.xml），--all 展开所有以 Window 为根的文件
+// 默认列出引用的资源，找不到的标出引用位置；--tree 同时输出展开后的节点树，--json 输出 JSON
+// 带 --resources 时按解包出的皮肤资源目录检查图片，否则图片只列出不检查
+// 最后汇总所有窗口引用但没有抓到的XML，即从未观察到的 LoadXML 调用
+// 没有缺失的资源返回0，有缺失返回1，参数错误或读取失败返回2
+use std::collections::BTreeSet;
+use std::process::ExitCode;
+use wxwork_ui::skin::ResourceKind;
+use wxwork_ui::{Resolver, SkinSet};
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    let tree = args.iter().any(|arg| arg == "--tree");
+    args.retain(|arg| arg != "--json" && arg != "--tree");
+    let resource_dir = take_option(&mut args, "--resources");
+    if args.len() != 3 {
+        eprintln!(
+            "用法: {} [--json] [--tree] [--resources <目录>] <存档目录@版本 | 目录> <窗口XML路径 | --all>",
+            args.first().map_or("skin_resolve", String::as_str)
+        );
+        return ExitCode::from(2);
+    }
+
+    let set = match SkinSet::load_spec(&args[1]) {
+        Ok(set) => set,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let mut resolver = Resolver::new(&set);
+    if let Some(dir) = resource_dir {
+        resolver = resolver.with_resource_dir(dir);
+    }
+    let windows = if args[2] == "--all" {
+        resolver.windows()
+    } else {
+        vec![args[2].as_str()]
+    };
+
+    let mut missing_xml = BTreeSet::new();
+    let mut missing = 0;
+    for window in windows {
+        let resolved = match resolver.resolve(window) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                return ExitCode::from(2);
+            }
+        };
+        if json {
+            match serde_json::to_string_pretty(&resolved) {
+                Ok(text) => println!("{}", text),
+                Err(e) => eprintln!("❌ {}", e),
+            }
+        } else {
+            print!("{}", resolved);
+            if tree {
+                print!("{}", resolved.root);
+            }
+        }
+        for reference in resolved.missing() {
+            missing += 1;
+            if reference.kind == ResourceKind::Xml {
+                missing_xml.insert(reference.name.clone());
+            }
+        }
+    }
+
+    if !json && !missing_xml.is_empty() {
+        println!("没有抓到的XML（从未观察到的 LoadXML 调用）:");
+        for name in &missing_xml {
+            println!("  {}", name);
+        }
+    }
+    if missing > 0 {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// 取出 `--name <值>` 形式的参数
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == name)?;
+    args.remove(index);
+    (index < args.len()).then(|| args.remove(index))
+}
@@ -0,0 +1,108 @@
+// 查看和导出 LoadXML 抓到的界面资源存档，可在 Linux 上运行
+//
+// 用法:
+//   ui_archive <存档目录> versions                     列出存档中的客户端版本
+//   ui_archive <存档目录> list [版本]                   列出清单，指定版本时只列出该版本每个路径最后的内容
+//   ui_archive <存档目录> export <版本> <目录> [--bom]   把某个版本的快照按原目录结构导出
+//   ui_archive <存档目录> import <版本> <目录>           把按目录保存的旧结果导入存档（加载参数记为0）
+// 成功返回0，失败返回1，参数错误返回2
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::UNIX_EPOCH;
+use wxwork_ui::{Archive, DumpLayout, list_dump_files};
+
+// UTF-8 BOM，导入时去掉
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().collect();
+    let bom = args.iter().any(|arg| arg == "--bom");
+    args.retain(|arg| arg != "--bom");
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args.as_slice() {
+        [_, root, "versions"] => open(root).map(|archive| {
+            for version in archive.versions() {
+                println!("{}", version);
+            }
+        }),
+        [_, root, "list"] => open(root).map(|archive| {
+            for entry in archive.entries() {
+                print_entry(entry);
+            }
+        }),
+        [_, root, "list", version] => open(root).map(|archive| {
+            for entry in archive.snapshot(version).values() {
+                print_entry(entry);
+            }
+        }),
+        [_, root, "export", version, dir] => open(root).and_then(|archive| {
+            let count = archive.export(version, &DumpLayout::new(dir, bom))?;
+            println!("导出 {} 个文件到 {}", count, dir);
+            Ok(())
+        }),
+        [_, root, "import", version, dir] => {
+            open(root).and_then(|mut archive| import(&mut archive, version, Path::new(dir)))
+        }
+        _ => {
+            eprintln!(
+                "用法: {} <存档目录> versions | list [版本] | export <版本> <目录> [--bom] | import <版本> <目录>",
+                args.first().copied().unwrap_or("ui_archive")
+            );
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn open(root: &str) -> Result<Archive, String> {
+    let archive = Archive::open(root)?;
+    if archive.skipped_lines() > 0 {
+        eprintln!("清单中有 {} 行无法解析，已跳过", archive.skipped_lines());
+    }
+    Ok(archive)
+}
+
+fn print_entry(entry: &wxwork_ui::ManifestEntry) {
+    println!(
+        "{:>5}  {}  {:>8}  {:>3}  {}  {}  {}",
+        entry.seq,
+        entry.client_version,
+        entry.size,
+        entry.flag,
+        entry.first_seen,
+        &entry.hash[..12.min(entry.hash.len())],
+        entry.path
+    );
+}
+
+// 导入目录下所有文件，时间取文件修改时间
+fn import(archive: &mut Archive, version: &str, dir: &Path) -> Result<(), String> {
+    let files = list_dump_files(dir)?;
+    let mut added = 0;
+    for (path, file) in &files {
+        let content =
+            std::fs::read(file).map_err(|e| format!("读取 {} 失败: {}", file.display(), e))?;
+        let content = content.strip_prefix(UTF8_BOM).unwrap_or(&content);
+        let modified = std::fs::metadata(file)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        if archive
+            .store(path, content, version, 0, modified)?
+            .new_entry
+        {
+            added += 1;
+        }
+    }
+    println!("导入 {} 个文件，新增 {} 项", files.len(), added);
+    Ok(())
+}
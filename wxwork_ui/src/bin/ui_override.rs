@@ -0,0 +1,68 @@
+// 检查替换目录：客户端路径会被替换成哪个文件，内容是否是格式正确的界面XML，可在 Linux 上运行
+//
+// 用法: ui_override <替换目录> [客户端路径]...
+// 不指定客户端路径时列出替换目录中的所有文件并逐个检查
+// 所有路径都有格式正确的替换文件时返回0，有路径没有替换文件或内容有问题返回1，参数错误返回2
+use std::process::ExitCode;
+use wxwork_ui::OverrideStore;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!(
+            "用法: {} <替换目录> [客户端路径]...",
+            args.first().map_or("ui_override", String::as_str)
+        );
+        return ExitCode::from(2);
+    }
+
+    let store = OverrideStore::new(&args[1]);
+    let paths = if args.len() > 2 {
+        args[2..].to_vec()
+    } else {
+        match store.files() {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                return ExitCode::from(2);
+            }
+        }
+    };
+
+    let mut failed = 0;
+    for path in &paths {
+        match store.lookup(path) {
+            Ok(Some(found)) => {
+                println!(
+                    "{} {} <- {} ({} 个字符)",
+                    if found.warning.is_none() {
+                        "✅"
+                    } else {
+                        "⚠"
+                    },
+                    path,
+                    found.file.display(),
+                    found.content.len()
+                );
+                if let Some(warning) = &found.warning {
+                    println!("    {}", warning);
+                    failed += 1;
+                }
+            }
+            Ok(None) => {
+                println!("-  {} 没有替换文件", path);
+                failed += 1;
+            }
+            Err(e) => {
+                println!("❌ {}: {}", path, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
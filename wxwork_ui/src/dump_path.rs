@@ -0,0 +1,312 @@
+use std::path::{Path, PathBuf};
+
+// UTF-8 BOM，C++ 版本的工具保存的文件带 BOM
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// Windows 保留的设备名，不能作为文件名（不区分大小写，带扩展名也不行）
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM0", "COM1", "COM2", "COM3", "COM4",
+    "COM5", "COM6", "COM7", "COM8", "COM9", "COM¹", "COM²", "COM³", "LPT0", "LPT1", "LPT2", "LPT3",
+    "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9", "LPT¹", "LPT²", "LPT³",
+];
+
+/// 把客户端传给 LoadXML 的路径转换为保存目录下的相对路径
+///
+/// - `\` 和 `/` 都作为分隔符，`.` 和空的部分忽略；
+/// - 包含 `..` 时拒绝，不做任何猜测；
+/// - 带盘符的绝对路径放到 `_drive_<盘符>/` 下，UNC 路径放到 `_unc/` 下，
+///   以分隔符开头的路径放到 `_root/` 下，`\\?\` 前缀先去掉；
+/// - Windows 不允许的字符替换为 `_`，保留设备名和以点或空格结尾的部分加 `_`。
+pub fn sanitize_resource_path(raw: &str) -> Result<PathBuf, String> {
+    let normalized = raw.replace('\\', "/");
+    let mut rest = normalized.as_str();
+    let mut unc = false;
+    if let Some(stripped) = rest
+        .strip_prefix("//?/")
+        .or_else(|| rest.strip_prefix("//./"))
+    {
+        // \\?\UNC\server\share 与 \\server\share 相同，UNC 不区分大小写
+        match stripped
+            .get(..4)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("UNC/"))
+        {
+            Some(_) => {
+                rest = &stripped[4..];
+                unc = true;
+            }
+            None => rest = stripped,
+        }
+    } else if let Some(stripped) = rest.strip_prefix("//") {
+        rest = stripped;
+        unc = true;
+    }
+
+    let mut path = PathBuf::new();
+    let bytes = rest.as_bytes();
+    if unc {
+        path.push("_unc");
+    } else if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        // C:\foo 和 C:foo 都放到盘符目录下
+        path.push(format!(
+            "_drive_{}",
+            (bytes[0] as char).to_ascii_lowercase()
+        ));
+        rest = &rest[2..];
+    } else if rest.starts_with('/') {
+        path.push("_root");
+    }
+
+    let mut has_component = false;
+    for component in rest.split('/') {
+        match component {
+            "" | "." => continue,
+            ".." => return Err(format!("路径中包含 ..: {}", raw)),
+            component => {
+                path.push(sanitize_component(component));
+                has_component = true;
+            }
+        }
+    }
+    if !has_component {
+        return Err(format!("路径中没有文件名: {:?}", raw));
+    }
+    Ok(path)
+}
+
+// 清理一段文件名
+fn sanitize_component(component: &str) -> String {
+    let mut name: String = component
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    // Windows 会去掉结尾的点和空格，不同的名字可能落到同一个文件
+    if name.ends_with(['.', ' ']) {
+        name.push('_');
+    }
+    // 设备名后面跟空格再跟扩展名（如 `NUL .txt`）也是设备
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// 列出保存目录下的所有文件，返回按客户端写法（`\` 分隔）的相对路径和文件路径，按路径排序
+pub fn list_dump_files(root: &Path) -> Result<Vec<(String, PathBuf)>, String> {
+    fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("读取目录 {} 失败: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.is_dir() {
+                collect(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    collect(root, &mut files)?;
+    let mut listed: Vec<(String, PathBuf)> = files
+        .into_iter()
+        .map(|file| {
+            let relative = file
+                .strip_prefix(root)
+                .unwrap_or(&file)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("\\");
+            (relative, file)
+        })
+        .collect();
+    listed.sort();
+    Ok(listed)
+}
+
+/// 保存目录和文件格式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpLayout {
+    root: PathBuf,
+    bom: bool, // 文件开头写 UTF-8 BOM，与 C++ 版本的工具一致
+}
+
+impl DumpLayout {
+    pub fn new(root: impl Into<PathBuf>, bom: bool) -> Self {
+        DumpLayout {
+            root: root.into(),
+            bom,
+        }
+    }
+
+    /// 保存目录
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 客户端路径对应的保存路径，保证在保存目录下
+    pub fn path_for(&self, raw: &str) -> Result<PathBuf, String> {
+        Ok(self.root.join(sanitize_resource_path(raw)?))
+    }
+
+    /// 按配置的格式编码文件内容
+    pub fn encode(&self, content: &str) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(content.len() + UTF8_BOM.len());
+        if self.bom {
+            bytes.extend_from_slice(UTF8_BOM);
+        }
+        bytes.extend_from_slice(content.as_bytes());
+        bytes
+    }
+
+    /// 保存一个文件，返回保存路径
+    pub fn write(&self, raw: &str, content: &str) -> Result<PathBuf, String> {
+        let path = self.path_for(raw)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("创建目录 {} 失败: {}", parent.display(), e))?;
+        }
+        std::fs::write(&path, self.encode(content))
+            .map_err(|e| format!("保存 {} 失败: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 按 `/` 连接的相对路径，便于比较
+    fn sanitized(raw: &str) -> String {
+        let path = sanitize_resource_path(raw).unwrap();
+        assert!(path.is_relative(), "{}", path.display());
+        path.components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[test]
+    fn keeps_relative_paths() {
+        assert_eq!(sanitized("login\\LoginWnd.xml"), "login/LoginWnd.xml");
+        assert_eq!(sanitized("skin/./main//Main.xml"), "skin/main/Main.xml");
+        assert_eq!(sanitized("中文目录\\窗口.xml"), "中文目录/窗口.xml");
+    }
+
+    #[test]
+    fn rejects_parent_components() {
+        for raw in [
+            "..\\secret.xml",
+            "skin\\..\\..\\secret.xml",
+            "skin/../secret.xml",
+            "C:\\skin\\..\\secret.xml",
+            "\\\\server\\share\\..\\x.xml",
+            "\\\\?\\C:\\..\\x.xml",
+            "..",
+        ] {
+            assert!(sanitize_resource_path(raw).is_err(), "{}", raw);
+        }
+        // 不是 .. 的点开头名字可以保留
+        assert_eq!(sanitized("..skin\\.hidden.xml"), "..skin/.hidden.xml");
+        assert_eq!(sanitized("skin\\..."), "skin/..._");
+    }
+
+    #[test]
+    fn rejects_paths_without_file_name() {
+        for raw in ["", ".", "\\", "C:", "C:\\", "\\\\?\\", "\\\\"] {
+            assert!(sanitize_resource_path(raw).is_err(), "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn maps_drive_letters() {
+        assert_eq!(
+            sanitized("C:\\Program Files\\a.xml"),
+            "_drive_c/Program Files/a.xml"
+        );
+        assert_eq!(sanitized("d:/skin/b.xml"), "_drive_d/skin/b.xml");
+        // 盘符相对路径
+        assert_eq!(sanitized("E:skin\\c.xml"), "_drive_e/skin/c.xml");
+        // 不是盘符的冒号替换掉
+        assert_eq!(sanitized("skin\\a:b.xml"), "skin/a_b.xml");
+        assert_eq!(sanitized("1:\\a.xml"), "1_/a.xml");
+    }
+
+    #[test]
+    fn maps_unc_and_rooted_paths() {
+        assert_eq!(
+            sanitized("\\\\fileserver\\share\\skin.xml"),
+            "_unc/fileserver/share/skin.xml"
+        );
+        assert_eq!(
+            sanitized("//fileserver/share/skin.xml"),
+            "_unc/fileserver/share/skin.xml"
+        );
+        assert_eq!(sanitized("\\skin\\root.xml"), "_root/skin/root.xml");
+    }
+
+    #[test]
+    fn strips_extended_length_prefixes() {
+        assert_eq!(sanitized("\\\\?\\C:\\skin\\a.xml"), "_drive_c/skin/a.xml");
+        assert_eq!(sanitized("\\\\.\\C:\\skin\\a.xml"), "_drive_c/skin/a.xml");
+        assert_eq!(
+            sanitized("\\\\?\\UNC\\server\\share\\a.xml"),
+            "_unc/server/share/a.xml"
+        );
+        assert_eq!(
+            sanitized("\\\\?\\unc\\server\\share\\a.xml"),
+            "_unc/server/share/a.xml"
+        );
+        // 设备命名空间中的设备名按保留名处理
+        assert_eq!(sanitized("\\\\.\\COM1"), "_COM1");
+        assert_eq!(sanitized("\\\\?\\GLOBALROOT\\x.xml"), "GLOBALROOT/x.xml");
+    }
+
+    #[test]
+    fn renames_reserved_device_names() {
+        assert_eq!(sanitized("skin\\CON"), "skin/_CON");
+        assert_eq!(sanitized("skin\\nul.xml"), "skin/_nul.xml");
+        assert_eq!(sanitized("Com1.tar.gz"), "_Com1.tar.gz");
+        assert_eq!(sanitized("LPT9\\a.xml"), "_LPT9/a.xml");
+        assert_eq!(sanitized("aux .xml"), "_aux .xml");
+        assert_eq!(sanitized("CONOUT$"), "_CONOUT$");
+        assert_eq!(sanitized("COM¹.xml"), "_COM¹.xml");
+        // 只是以设备名开头的名字不受影响
+        assert_eq!(sanitized("CONFIG.xml"), "CONFIG.xml");
+        assert_eq!(sanitized("COM10.xml"), "COM10.xml");
+    }
+
+    #[test]
+    fn replaces_invalid_characters() {
+        assert_eq!(sanitized("a<b>c|d?e*f\"g.xml"), "a_b_c_d_e_f_g.xml");
+        assert_eq!(sanitized("tab\there\u{1}.xml"), "tab_here_.xml");
+        // Windows 会去掉结尾的点和空格
+        assert_eq!(sanitized("name. "), "name. _");
+        assert_eq!(sanitized("dir.\\a.xml"), "dir._/a.xml");
+    }
+
+    #[test]
+    fn layout_stays_under_root() {
+        let layout = DumpLayout::new("dump", false);
+        assert_eq!(
+            layout.path_for("C:\\a\\b.xml").unwrap(),
+            Path::new("dump").join("_drive_c").join("a").join("b.xml")
+        );
+        assert!(layout.path_for("..\\b.xml").is_err());
+        assert_eq!(layout.encode("<a/>"), b"<a/>");
+        assert_eq!(
+            DumpLayout::new("dump", true).encode("<a/>"),
+            b"\xEF\xBB\xBF<a/>"
+        );
+    }
+}
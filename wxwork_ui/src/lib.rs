@@ -0,0 +1,15 @@
+// 企业微信 DuiLib 界面资源（LoadXML 抓到的皮肤文件）的保存和分析，不依赖 Windows，可在 Linux 上检查
+pub mod archive;
+pub mod dump_path;
+pub mod overrides;
+pub mod skin;
+pub mod writer;
+
+pub use archive::{Archive, MANIFEST_FILE_NAME, ManifestEntry, StoreOutcome, hash_content};
+pub use dump_path::{DumpLayout, list_dump_files, sanitize_resource_path};
+pub use overrides::{Override, OverrideStore};
+pub use skin::{
+    AttrValue, Attribute, ControlKind, ImageDescriptor, ResolvedWindow, Resolver, ResourceStatus,
+    SkinDiff, SkinDocument, SkinNode, SkinSet, diff_sets,
+};
+pub use writer::{DumpJob, DumpWriter};
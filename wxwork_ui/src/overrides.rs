@@ -0,0 +1,237 @@
+use crate::dump_path::{DumpLayout, list_dump_files};
+use crate::skin::SkinDocument;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// 替换用的内容
+#[derive(Debug, Clone)]
+pub struct Override {
+    pub file: PathBuf,
+    pub content: Arc<[u16]>,     // UTF-16，不含结尾的0
+    pub reloaded: bool,          // 这次调用时第一次读取或者文件有变化
+    pub warning: Option<String>, // 内容不是格式正确的界面XML，仍然替换
+}
+
+// 读取过的文件，修改时间或大小变化时重新读取
+struct Cached {
+    modified: Option<SystemTime>,
+    len: u64,
+    content: Arc<[u16]>,
+    warning: Option<String>,
+}
+
+/// 替换目录：目录结构与保存目录相同，把抓到的文件复制过来修改即可替换客户端读取的内容
+///
+/// 每次查找都检查文件是否存在和是否修改，增加、修改、删除文件后下一次 LoadXML 立即生效。
+/// 查找在被hook的线程上调用，检查和读取文件时不持有锁，多个线程可以同时查找。
+pub struct OverrideStore {
+    layout: DumpLayout,
+    cache: Mutex<HashMap<PathBuf, Cached>>,
+    hits: Mutex<BTreeMap<String, u64>>, // 客户端路径和替换次数
+}
+
+impl OverrideStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        OverrideStore {
+            layout: DumpLayout::new(root, false),
+            cache: Mutex::new(HashMap::new()),
+            hits: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// 替换目录
+    pub fn root(&self) -> &Path {
+        self.layout.root()
+    }
+
+    /// 查找客户端路径对应的替换内容，没有替换文件时返回 `None`
+    pub fn lookup(&self, raw: &str) -> Result<Option<Override>, String> {
+        let file = self.layout.path_for(raw)?;
+        let metadata = std::fs::metadata(&file)
+            .ok()
+            .filter(|metadata| metadata.is_file());
+        let Some(metadata) = metadata else {
+            self.lock_cache()?.remove(&file);
+            return Ok(None);
+        };
+
+        let modified = metadata.modified().ok();
+        let len = metadata.len();
+        let cached = self
+            .lock_cache()?
+            .get(&file)
+            .filter(|cached| cached.modified == modified && cached.len == len)
+            .map(|cached| (cached.content.clone(), cached.warning.clone()));
+        let reloaded = cached.is_none();
+        let (content, warning) = match cached {
+            Some(cached) => cached,
+            None => {
+                // 读取和检查可能较慢，不持有锁
+                let bytes = std::fs::read(&file)
+                    .map_err(|e| format!("读取 {} 失败: {}", file.display(), e))?;
+                let text = decode_override(&bytes).ok_or_else(|| {
+                    format!("{} 不是 UTF-8 或带 BOM 的 UTF-16 文件", file.display())
+                })?;
+                let warning = SkinDocument::parse(&text).err();
+                let content: Arc<[u16]> = text.encode_utf16().collect();
+                self.lock_cache()?.insert(
+                    file.clone(),
+                    Cached {
+                        modified,
+                        len,
+                        content: content.clone(),
+                        warning: warning.clone(),
+                    },
+                );
+                (content, warning)
+            }
+        };
+
+        if let Ok(mut hits) = self.hits.lock() {
+            *hits.entry(raw.to_string()).or_default() += 1;
+        }
+        Ok(Some(Override {
+            file,
+            content,
+            reloaded,
+            warning,
+        }))
+    }
+
+    fn lock_cache(&self) -> Result<MutexGuard<'_, HashMap<PathBuf, Cached>>, String> {
+        self.cache
+            .lock()
+            .map_err(|_| "替换缓存的锁已损坏".to_string())
+    }
+
+    /// 替换过的客户端路径和次数
+    pub fn hits(&self) -> BTreeMap<String, u64> {
+        self.hits
+            .lock()
+            .map(|hits| hits.clone())
+            .unwrap_or_default()
+    }
+
+    /// 替换目录中的所有文件（按客户端写法的相对路径）
+    pub fn files(&self) -> Result<Vec<String>, String> {
+        if !self.root().is_dir() {
+            return Ok(Vec::new());
+        }
+        Ok(list_dump_files(self.root())?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect())
+    }
+
+    /// 清空缓存，下次查找时重新读取所有文件
+    pub fn reload(&self) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.clear();
+        }
+    }
+}
+
+// UTF-8（可以带 BOM），或者带 BOM 的 UTF-16LE
+fn decode_override(bytes: &[u8]) -> Option<String> {
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        if rest.len() % 2 != 0 {
+            return None;
+        }
+        let units: Vec<u16> = rest
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        return String::from_utf16(&units).ok();
+    }
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    const PATH: &str = "login\\LoginWnd.xml";
+
+    fn write(dir: &TempDir, content: &str, modified: SystemTime) {
+        let file = dir.path().join("login").join("LoginWnd.xml");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, content).unwrap();
+        File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    fn text(found: &Override) -> String {
+        String::from_utf16(&found.content).unwrap()
+    }
+
+    #[test]
+    fn finds_override_files() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "\u{FEFF}<Window />", SystemTime::now());
+        let store = OverrideStore::new(dir.path());
+        let found = store.lookup(PATH).unwrap().unwrap();
+        assert_eq!(text(&found), "<Window />");
+        assert!(found.reloaded && found.warning.is_none());
+        assert!(store.lookup("login\\other.xml").unwrap().is_none());
+        assert!(store.lookup("..\\LoginWnd.xml").is_err());
+
+        // 没有变化时使用缓存
+        let again = store.lookup(PATH).unwrap().unwrap();
+        assert!(!again.reloaded);
+        assert_eq!(store.hits().get(PATH), Some(&2));
+        assert_eq!(store.files().unwrap(), [PATH]);
+    }
+
+    #[test]
+    fn reloads_after_change() {
+        let dir = TempDir::new().unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(60);
+        write(&dir, "<Window a=\"1\" />", modified);
+        let store = OverrideStore::new(dir.path());
+        assert!(store.lookup(PATH).unwrap().unwrap().reloaded);
+
+        // 大小相同，只有修改时间变化
+        write(
+            &dir,
+            "<Window a=\"2\" />",
+            modified + Duration::from_secs(1),
+        );
+        let found = store.lookup(PATH).unwrap().unwrap();
+        assert!(found.reloaded);
+        assert_eq!(text(&found), "<Window a=\"2\" />");
+
+        store.reload();
+        assert!(store.lookup(PATH).unwrap().unwrap().reloaded);
+    }
+
+    #[test]
+    fn deleted_file_is_not_used() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "<Window>", SystemTime::now());
+        let store = OverrideStore::new(dir.path());
+        assert!(store.lookup(PATH).unwrap().unwrap().warning.is_some());
+        std::fs::remove_file(dir.path().join("login").join("LoginWnd.xml")).unwrap();
+        assert!(store.lookup(PATH).unwrap().is_none());
+        assert!(store.cache.lock().unwrap().is_empty());
+        assert_eq!(store.hits().get(PATH), Some(&1));
+    }
+
+    #[test]
+    fn decodes_utf16_with_bom() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("<Window />".encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(decode_override(&bytes).as_deref(), Some("<Window />"));
+        assert_eq!(decode_override(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(decode_override(b"\xFF\x00"), None);
+    }
+}
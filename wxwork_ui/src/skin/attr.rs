@@ -0,0 +1,629 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// 宽高，例如 `size="800,600"`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct Size {
+    pub width: i32,
+    pub height: i32,
+}
+
+/// 矩形或四边距，例如 `pos="0,0,100,30"`、`padding="4,2,4,2"`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+/// 颜色，按 DuiLib 的写法是 ARGB，例如 `#FF2B2B2B`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Color(pub u32);
+
+impl Color {
+    pub fn alpha(&self) -> u8 {
+        (self.0 >> 24) as u8
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:08X}", self.0)
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// 图片描述
+///
+/// DuiLib 的图片属性可以只写文件名，也可以写成 `file='a.png' source='0,0,10,10' corner='2,2,2,2'`。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImageDescriptor {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub res: Option<String>, // 资源名，与 restype 一起从资源中加载
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restype: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Rect>, // 图片中使用的区域
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dest: Option<Rect>, // 绘制到控件中的区域
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corner: Option<Rect>, // 九宫格拉伸时不拉伸的四边
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mask: Option<Color>, // 透明色
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fade: Option<u8>, // 透明度
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hole: Option<bool>, // 九宫格中间不绘制
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xtiled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ytiled: Option<bool>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, String>, // 不认识的项，原样保留
+}
+
+impl ImageDescriptor {
+    /// 解析图片描述
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if !text.contains('=') {
+            return Ok(ImageDescriptor {
+                file: text.to_string(),
+                ..Default::default()
+            });
+        }
+        let mut image = ImageDescriptor::default();
+        for (key, value) in parse_attribute_list(text)? {
+            let invalid = |e: String| format!("图片描述中 {} 无效: {}", key, e);
+            match key.as_str() {
+                "file" => image.file = value,
+                "res" => image.res = Some(value),
+                "restype" => image.restype = Some(value),
+                "source" => image.source = Some(parse_rect(&value).map_err(invalid)?),
+                "dest" => image.dest = Some(parse_rect(&value).map_err(invalid)?),
+                "corner" => image.corner = Some(parse_rect(&value).map_err(invalid)?),
+                "mask" => image.mask = Some(parse_color(&value).map_err(invalid)?),
+                "fade" => {
+                    image.fade = Some(value.trim().parse().map_err(|_| invalid(value.clone()))?)
+                }
+                "hole" => image.hole = Some(parse_bool(&value).map_err(invalid)?),
+                "xtiled" => image.xtiled = Some(parse_bool(&value).map_err(invalid)?),
+                "ytiled" => image.ytiled = Some(parse_bool(&value).map_err(invalid)?),
+                _ => {
+                    image.extra.insert(key, value);
+                }
+            }
+        }
+        Ok(image)
+    }
+
+    /// 引用的资源名：有 `res` 时是资源名，否则是文件名；空字符串表示没有图片
+    pub fn resource(&self) -> Option<&str> {
+        let name = self.res.as_deref().unwrap_or(&self.file);
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+impl fmt::Display for ImageDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "image({:?}", self.resource().unwrap_or_default())?;
+        let rects = [
+            ("source", self.source),
+            ("dest", self.dest),
+            ("corner", self.corner),
+        ];
+        for (name, rect) in rects {
+            if let Some(rect) = rect {
+                write!(f, " {}={}", name, rect)?;
+            }
+        }
+        if let Some(mask) = self.mask {
+            write!(f, " mask={}", mask)?;
+        }
+        if let Some(fade) = self.fade {
+            write!(f, " fade={}", fade)?;
+        }
+        let flags = [
+            ("hole", self.hole),
+            ("xtiled", self.xtiled),
+            ("ytiled", self.ytiled),
+        ];
+        for (name, flag) in flags {
+            if let Some(flag) = flag {
+                write!(f, " {}={}", name, flag)?;
+            }
+        }
+        for (key, value) in &self.extra {
+            write!(f, " {}={:?}", key, value)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl fmt::Display for Rect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{},{},{},{}]",
+            self.left, self.top, self.right, self.bottom
+        )
+    }
+}
+
+/// 属性值的类型，由属性名决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttrKind {
+    Text,
+    Int,
+    Bool,
+    Size,
+    Rect,
+    IntOrRect, // 新版本 DuiLib 的 bordersize 可以是一个数，也可以是四边
+    Color,
+    Image,
+}
+
+// 以下属性名按类型解析，其余的按名字结尾判断（*color、*image），都不是的保留为文本
+const INT_ATTRS: &[&str] = &[
+    "width",
+    "height",
+    "minwidth",
+    "minheight",
+    "maxwidth",
+    "maxheight",
+    "childpadding",
+    "font",
+    "hotfont",
+    "pushedfont",
+    "focusedfont",
+    "selectedfont",
+    "itemfont",
+    "columns",
+    "maxchar",
+    "alpha",
+    "borderstyle",
+    "vscrollbarwidth",
+    "hscrollbarheight",
+    "scrollstepsize",
+    "selectedid",
+];
+const BOOL_ATTRS: &[&str] = &[
+    "visible",
+    "enabled",
+    "float",
+    "mouse",
+    "mousechild",
+    "keyboard",
+    "menu",
+    "selected",
+    "showhtml",
+    "multiline",
+    "password",
+    "readonly",
+    "numberonly",
+    "autocalcwidth",
+    "autocalcheight",
+    "vscrollbar",
+    "hscrollbar",
+    "endellipsis",
+    "singleline",
+    "wordbreak",
+    "noprefix",
+    "layered",
+    "showdirty",
+    "showshadow",
+    "wantreturn",
+    "wanttab",
+    "rich",
+    "transparent",
+];
+const SIZE_ATTRS: &[&str] = &[
+    "size",
+    "mininfo",
+    "maxinfo",
+    "roundcorner",
+    "borderround",
+    "itemsize",
+    "dropboxsize",
+];
+const RECT_ATTRS: &[&str] = &[
+    "pos",
+    "padding",
+    "inset",
+    "textpadding",
+    "caption",
+    "sizebox",
+    "itemtextpadding",
+    "shadowcorner",
+];
+
+/// 属性值的类型
+///
+/// 同名的属性在资源节点上含义不同：`Font` 的 `size` 是字号，`Default` 的 `value` 是属性列表文本。
+pub fn attr_kind(tag: &str, name: &str) -> AttrKind {
+    match (tag, name) {
+        ("Font", "id" | "size") => return AttrKind::Int,
+        ("Font", "bold" | "italic" | "underline" | "default" | "shared") => return AttrKind::Bool,
+        ("Include", "count") => return AttrKind::Int,
+        ("Font" | "Default" | "Include" | "Image" | "Style", _) => return AttrKind::Text,
+        _ => {}
+    }
+    if name == "bordersize" {
+        AttrKind::IntOrRect
+    } else if INT_ATTRS.contains(&name) {
+        AttrKind::Int
+    } else if BOOL_ATTRS.contains(&name) {
+        AttrKind::Bool
+    } else if SIZE_ATTRS.contains(&name) {
+        AttrKind::Size
+    } else if RECT_ATTRS.contains(&name) {
+        AttrKind::Rect
+    } else if name.ends_with("color") {
+        AttrKind::Color
+    } else if name.ends_with("image") {
+        AttrKind::Image
+    } else {
+        AttrKind::Text
+    }
+}
+
+/// 按类型解析后的属性值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum AttrValue {
+    Text(String),
+    Int(i32),
+    Bool(bool),
+    Size(Size),
+    Rect(Rect),
+    Color(Color),
+    Image(ImageDescriptor),
+}
+
+impl AttrValue {
+    /// 按类型解析属性值
+    pub fn parse(kind: AttrKind, raw: &str) -> Result<Self, String> {
+        Ok(match kind {
+            AttrKind::Text => AttrValue::Text(raw.to_string()),
+            AttrKind::Int => AttrValue::Int(parse_int(raw)?),
+            AttrKind::Bool => AttrValue::Bool(parse_bool(raw)?),
+            AttrKind::Size => AttrValue::Size(parse_size(raw)?),
+            AttrKind::Rect => AttrValue::Rect(parse_rect(raw)?),
+            AttrKind::IntOrRect => match parse_int(raw) {
+                Ok(value) => AttrValue::Int(value),
+                Err(_) => AttrValue::Rect(parse_rect(raw)?),
+            },
+            AttrKind::Color => AttrValue::Color(parse_color(raw)?),
+            AttrKind::Image => AttrValue::Image(ImageDescriptor::parse(raw)?),
+        })
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            AttrValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            AttrValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            AttrValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_image(&self) -> Option<&ImageDescriptor> {
+        match self {
+            AttrValue::Image(image) => Some(image),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttrValue::Text(text) => write!(f, "{:?}", text),
+            AttrValue::Int(value) => write!(f, "{}", value),
+            AttrValue::Bool(value) => write!(f, "{}", value),
+            AttrValue::Size(size) => write!(f, "{}", size),
+            AttrValue::Rect(rect) => write!(f, "{}", rect),
+            AttrValue::Color(color) => write!(f, "{}", color),
+            AttrValue::Image(image) => write!(f, "{}", image),
+        }
+    }
+}
+
+/// 控件上的一个属性，保留原始文本
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Attribute {
+    pub name: String,
+    pub raw: String,
+    pub value: AttrValue,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invalid: Option<String>, // 按类型解析失败的原因，此时 value 是原始文本
+}
+
+impl Attribute {
+    /// 按所在节点的标签和属性名解析，解析失败时保留为文本并记录原因
+    pub fn parse(tag: &str, name: &str, raw: &str) -> Self {
+        let (value, invalid) = match AttrValue::parse(attr_kind(tag, name), raw) {
+            Ok(value) => (value, None),
+            Err(e) => (AttrValue::Text(raw.to_string()), Some(e)),
+        };
+        Attribute {
+            name: name.to_string(),
+            raw: raw.to_string(),
+            value,
+            invalid,
+        }
+    }
+}
+
+/// 解析属性列表文本，例如 `Default` 的 `value` 和图片描述
+///
+/// 格式是空白分隔的 `名称="值"` 或 `名称='值'`，值中不能出现同一种引号。
+pub fn parse_attribute_list(text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut items = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let Some((key, tail)) = rest.split_once('=') else {
+            return Err(format!("缺少 =: {:?}", rest));
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("属性名无效: {:?}", key));
+        }
+        let tail = tail.trim_start();
+        let Some(quote) = tail.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            return Err(format!("{} 的值缺少引号", key));
+        };
+        let Some((value, tail)) = tail[1..].split_once(quote) else {
+            return Err(format!("{} 的值缺少结束引号", key));
+        };
+        items.push((key.to_string(), value.to_string()));
+        rest = tail.trim_start();
+    }
+    Ok(items)
+}
+
+fn parse_int(raw: &str) -> Result<i32, String> {
+    raw.trim()
+        .parse()
+        .map_err(|_| format!("不是整数: {:?}", raw))
+}
+
+fn parse_bool(raw: &str) -> Result<bool, String> {
+    match raw.trim() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(format!("不是 true/false: {:?}", raw)),
+    }
+}
+
+// 逗号分隔的整数，个数必须一致
+fn parse_ints<const N: usize>(raw: &str) -> Result<[i32; N], String> {
+    let parts: Vec<&str> = raw.split(',').collect();
+    if parts.len() != N {
+        return Err(format!("需要 {} 个整数: {:?}", N, raw));
+    }
+    let mut values = [0; N];
+    for (value, part) in values.iter_mut().zip(parts) {
+        *value = parse_int(part)?;
+    }
+    Ok(values)
+}
+
+fn parse_size(raw: &str) -> Result<Size, String> {
+    let [width, height] = parse_ints(raw)?;
+    Ok(Size { width, height })
+}
+
+fn parse_rect(raw: &str) -> Result<Rect, String> {
+    let [left, top, right, bottom] = parse_ints(raw)?;
+    Ok(Rect {
+        left,
+        top,
+        right,
+        bottom,
+    })
+}
+
+// `#AARRGGBB`，也接受 `0x` 前缀和不足8位的写法（DuiLib 按16进制整数读取，不补透明度）
+fn parse_color(raw: &str) -> Result<Color, String> {
+    let text = raw.trim();
+    let hex = text
+        .strip_prefix('#')
+        .or_else(|| text.strip_prefix("0x"))
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    if hex.is_empty() || hex.len() > 8 {
+        return Err(format!("颜色无效: {:?}", raw));
+    }
+    u32::from_str_radix(hex, 16)
+        .map(Color)
+        .map_err(|_| format!("颜色无效: {:?}", raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: i32, top: i32, right: i32, bottom: i32) -> Rect {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    #[test]
+    fn kind_depends_on_tag_and_name() {
+        assert_eq!(attr_kind("Font", "size"), AttrKind::Int);
+        assert_eq!(attr_kind("Font", "bold"), AttrKind::Bool);
+        assert_eq!(attr_kind("Font", "name"), AttrKind::Text);
+        assert_eq!(attr_kind("Default", "value"), AttrKind::Text);
+        assert_eq!(attr_kind("Include", "count"), AttrKind::Int);
+        assert_eq!(attr_kind("Window", "size"), AttrKind::Size);
+        assert_eq!(attr_kind("Button", "pos"), AttrKind::Rect);
+        assert_eq!(attr_kind("Button", "bordersize"), AttrKind::IntOrRect);
+        assert_eq!(attr_kind("Button", "hotbkcolor"), AttrKind::Color);
+        assert_eq!(attr_kind("Button", "pushedimage"), AttrKind::Image);
+        assert_eq!(attr_kind("WxQRCodeView", "refreshinterval"), AttrKind::Text);
+    }
+
+    #[test]
+    fn parses_scalar_values() {
+        let parse = |kind, raw| AttrValue::parse(kind, raw);
+        assert_eq!(parse(AttrKind::Int, " -12 "), Ok(AttrValue::Int(-12)));
+        assert!(parse(AttrKind::Int, "12px").is_err());
+        assert_eq!(parse(AttrKind::Bool, "true"), Ok(AttrValue::Bool(true)));
+        // DuiLib 只认 true，其余写法按无效处理
+        assert!(parse(AttrKind::Bool, "1").is_err());
+        assert!(parse(AttrKind::Bool, "TRUE").is_err());
+        assert_eq!(
+            parse(AttrKind::Size, "380, 520"),
+            Ok(AttrValue::Size(Size {
+                width: 380,
+                height: 520
+            }))
+        );
+        assert!(parse(AttrKind::Size, "380").is_err());
+        assert_eq!(
+            parse(AttrKind::Rect, "0,24,0,0"),
+            Ok(AttrValue::Rect(rect(0, 24, 0, 0)))
+        );
+        assert!(parse(AttrKind::Rect, "0,24,0").is_err());
+        assert!(parse(AttrKind::Rect, "0,24,0,0,0").is_err());
+        assert_eq!(parse(AttrKind::IntOrRect, "1"), Ok(AttrValue::Int(1)));
+        assert_eq!(
+            parse(AttrKind::IntOrRect, "1,2,1,2"),
+            Ok(AttrValue::Rect(rect(1, 2, 1, 2)))
+        );
+        assert!(parse(AttrKind::IntOrRect, "1,2").is_err());
+    }
+
+    #[test]
+    fn parses_colors() {
+        let color = |raw| parse_color(raw).map(|color| color.0);
+        assert_eq!(color("#FF1890FF"), Ok(0xFF1890FF));
+        assert_eq!(color("#ff1890ff"), Ok(0xFF1890FF));
+        assert_eq!(color("0xFF000000"), Ok(0xFF000000));
+        assert_eq!(color("FFFFFF"), Ok(0x00FFFFFF));
+        for invalid in ["", "#", "#1FF1890FF", "#GG000000", "red"] {
+            assert!(color(invalid).is_err(), "{:?}", invalid);
+        }
+        let parsed = parse_color("#801F1F1F").unwrap();
+        assert_eq!(parsed.alpha(), 0x80);
+        assert_eq!(parsed.to_string(), "#801F1F1F");
+    }
+
+    #[test]
+    fn parses_image_descriptors() {
+        let plain = ImageDescriptor::parse(" login\\btn_refresh.png ").unwrap();
+        assert_eq!(plain.file, "login\\btn_refresh.png");
+        assert_eq!(plain.resource(), Some("login\\btn_refresh.png"));
+
+        let image = ImageDescriptor::parse(
+            "file='common\\sys_btn_close.png' source='112,0,140,28' corner=\"2,2,2,2\" mask='#FFFF00FF' fade='200' hole='false' xtiled='true' align='center'",
+        )
+        .unwrap();
+        assert_eq!(image.file, "common\\sys_btn_close.png");
+        assert_eq!(image.source, Some(rect(112, 0, 140, 28)));
+        assert_eq!(image.corner, Some(rect(2, 2, 2, 2)));
+        assert_eq!(image.mask, Some(Color(0xFFFF00FF)));
+        assert_eq!(image.fade, Some(200));
+        assert_eq!(image.hole, Some(false));
+        assert_eq!(image.xtiled, Some(true));
+        assert_eq!(image.ytiled, None);
+        assert_eq!(image.extra["align"], "center");
+        assert_eq!(
+            image.to_string(),
+            "image(\"common\\\\sys_btn_close.png\" source=[112,0,140,28] corner=[2,2,2,2] mask=#FFFF00FF fade=200 hole=false xtiled=true align=\"center\")"
+        );
+
+        let resource = ImageDescriptor::parse("res='IDB_LOGO' restype='PNG'").unwrap();
+        assert_eq!(resource.resource(), Some("IDB_LOGO"));
+        assert_eq!(resource.restype.as_deref(), Some("PNG"));
+        assert_eq!(ImageDescriptor::parse("").unwrap().resource(), None);
+
+        for invalid in [
+            "file='a.png' source='0,0,10'",
+            "file='a.png' fade='300'",
+            "file='a.png' hole='yes'",
+            "file='a.png",
+        ] {
+            assert!(ImageDescriptor::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn parses_attribute_lists() {
+        let items = parse_attribute_list(
+            " textcolor=\"#FF1F1F1F\"  normalimage = \"file='a.png' source='0,0,1,1'\" tip='say \"hi\"' ",
+        )
+        .unwrap();
+        assert_eq!(
+            items,
+            [
+                ("textcolor".to_string(), "#FF1F1F1F".to_string()),
+                (
+                    "normalimage".to_string(),
+                    "file='a.png' source='0,0,1,1'".to_string()
+                ),
+                ("tip".to_string(), "say \"hi\"".to_string()),
+            ]
+        );
+        assert_eq!(parse_attribute_list("").unwrap(), []);
+        for invalid in ["textcolor", "=\"a\"", "a b=\"c\"", "a=b", "a=\"b"] {
+            assert!(parse_attribute_list(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn invalid_attribute_keeps_raw_text() {
+        let attr = Attribute::parse("Button", "textcolor", "blue");
+        assert_eq!(attr.value, AttrValue::Text("blue".to_string()));
+        assert_eq!(attr.raw, "blue");
+        assert!(attr.invalid.is_some());
+
+        let attr = Attribute::parse("Button", "pos", "1,2,3,4");
+        assert_eq!(attr.value, AttrValue::Rect(rect(1, 2, 3, 4)));
+        assert_eq!(attr.invalid, None);
+        assert_eq!(attr.value.to_string(), "[1,2,3,4]");
+    }
+
+    #[test]
+    fn serializes_tagged_values() {
+        let json = serde_json::to_string(&AttrValue::Color(Color(0xFF1890FF))).unwrap();
+        assert_eq!(json, r##"{"type":"color","value":"#FF1890FF"}"##);
+        let json = serde_json::to_string(&AttrValue::Image(ImageDescriptor {
+            file: "a.png".to_string(),
+            ..ImageDescriptor::default()
+        }))
+        .unwrap();
+        assert_eq!(json, r#"{"type":"image","value":{"file":"a.png"}}"#);
+    }
+}
@@ -0,0 +1,282 @@
+use super::attr::{AttrValue, Attribute, parse_attribute_list};
+use serde::Serialize;
+use std::fmt;
+
+/// 节点类型
+///
+/// DuiLib 按标签名创建控件（区分大小写），不认识的标签由客户端注册的回调创建，
+/// 这里保留为 `Custom`，属性和子节点照常解析。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ControlKind {
+    // 窗口和资源定义，不是控件
+    Window,
+    Font,
+    Default,
+    Image,
+    Style,
+    Include,
+    // 布局
+    Container,
+    VerticalLayout,
+    HorizontalLayout,
+    TileLayout,
+    TabLayout,
+    ChildLayout,
+    // 控件
+    Control,
+    Label,
+    Text,
+    Button,
+    Option,
+    CheckBox,
+    Edit,
+    RichEdit,
+    Combo,
+    List,
+    ListHeader,
+    ListHeaderItem,
+    ListLabelElement,
+    ListTextElement,
+    ListContainerElement,
+    Progress,
+    Slider,
+    ScrollBar,
+    TreeView,
+    TreeNode,
+    ActiveX,
+    WebBrowser,
+    GifAnim,
+    Custom(String),
+}
+
+// 标签名和类型的对应
+const KNOWN_TAGS: &[(&str, ControlKind)] = &[
+    ("Window", ControlKind::Window),
+    ("Font", ControlKind::Font),
+    ("Default", ControlKind::Default),
+    ("Image", ControlKind::Image),
+    ("Style", ControlKind::Style),
+    ("Include", ControlKind::Include),
+    ("Container", ControlKind::Container),
+    ("VerticalLayout", ControlKind::VerticalLayout),
+    ("HorizontalLayout", ControlKind::HorizontalLayout),
+    ("TileLayout", ControlKind::TileLayout),
+    ("TabLayout", ControlKind::TabLayout),
+    ("ChildLayout", ControlKind::ChildLayout),
+    ("Control", ControlKind::Control),
+    ("Label", ControlKind::Label),
+    ("Text", ControlKind::Text),
+    ("Button", ControlKind::Button),
+    ("Option", ControlKind::Option),
+    ("CheckBox", ControlKind::CheckBox),
+    ("Edit", ControlKind::Edit),
+    ("RichEdit", ControlKind::RichEdit),
+    ("Combo", ControlKind::Combo),
+    ("List", ControlKind::List),
+    ("ListHeader", ControlKind::ListHeader),
+    ("ListHeaderItem", ControlKind::ListHeaderItem),
+    ("ListLabelElement", ControlKind::ListLabelElement),
+    ("ListTextElement", ControlKind::ListTextElement),
+    ("ListContainerElement", ControlKind::ListContainerElement),
+    ("Progress", ControlKind::Progress),
+    ("Slider", ControlKind::Slider),
+    ("ScrollBar", ControlKind::ScrollBar),
+    ("TreeView", ControlKind::TreeView),
+    ("TreeNode", ControlKind::TreeNode),
+    ("ActiveX", ControlKind::ActiveX),
+    ("WebBrowser", ControlKind::WebBrowser),
+    ("GifAnim", ControlKind::GifAnim),
+];
+
+impl ControlKind {
+    pub fn from_tag(tag: &str) -> Self {
+        KNOWN_TAGS
+            .iter()
+            .find(|(name, _)| *name == tag)
+            .map_or_else(
+                || ControlKind::Custom(tag.to_string()),
+                |(_, kind)| kind.clone(),
+            )
+    }
+
+    /// 窗口属性和资源定义（字体、默认属性、图片、样式、引用），不会创建控件
+    pub fn is_resource(&self) -> bool {
+        matches!(
+            self,
+            ControlKind::Window
+                | ControlKind::Font
+                | ControlKind::Default
+                | ControlKind::Image
+                | ControlKind::Style
+                | ControlKind::Include
+        )
+    }
+
+    pub fn is_custom(&self) -> bool {
+        matches!(self, ControlKind::Custom(_))
+    }
+}
+
+/// 界面XML中的一个节点
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkinNode {
+    #[serde(skip)]
+    pub kind: ControlKind,
+    pub tag: String, // 原始标签名
+    pub line: u32,   // 所在行，从1开始
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attributes: Vec<Attribute>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<SkinNode>,
+}
+
+impl SkinNode {
+    pub fn new(tag: &str, line: u32) -> Self {
+        SkinNode {
+            kind: ControlKind::from_tag(tag),
+            tag: tag.to_string(),
+            line,
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// 按名称取属性，重复的属性取最后一个（DuiLib 依次设置，后面的覆盖前面的）
+    pub fn attr(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().rev().find(|attr| attr.name == name)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&AttrValue> {
+        self.attr(name).map(|attr| &attr.value)
+    }
+
+    pub fn raw(&self, name: &str) -> Option<&str> {
+        self.attr(name).map(|attr| attr.raw.as_str())
+    }
+
+    /// 控件名（`name` 属性）
+    pub fn name(&self) -> Option<&str> {
+        self.raw("name").filter(|name| !name.is_empty())
+    }
+
+    /// 深度优先遍历自身和所有子节点
+    pub fn walk(&self) -> Walk<'_> {
+        Walk { stack: vec![self] }
+    }
+
+    /// 作为字体定义
+    pub fn as_font(&self) -> Option<FontDef<'_>> {
+        (self.kind == ControlKind::Font).then(|| FontDef {
+            id: self.value("id").and_then(AttrValue::as_int),
+            name: self.raw("name").unwrap_or_default(),
+            size: self.value("size").and_then(AttrValue::as_int),
+            bold: self.flag("bold"),
+            italic: self.flag("italic"),
+            underline: self.flag("underline"),
+            default: self.flag("default"),
+        })
+    }
+
+    /// 作为控件默认属性定义
+    pub fn as_default(&self) -> Option<DefaultDef<'_>> {
+        (self.kind == ControlKind::Default).then(|| DefaultDef {
+            class: self.raw("name").unwrap_or_default(),
+            value: self.raw("value").unwrap_or_default(),
+        })
+    }
+
+    /// 作为引用
+    pub fn as_include(&self) -> Option<IncludeDef<'_>> {
+        (self.kind == ControlKind::Include).then(|| IncludeDef {
+            source: self.raw("source").unwrap_or_default(),
+            count: self.value("count").and_then(AttrValue::as_int).unwrap_or(1),
+        })
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.value(name)
+            .and_then(AttrValue::as_bool)
+            .unwrap_or(false)
+    }
+}
+
+impl fmt::Display for SkinNode {
+    // 缩进的树形文本，每个节点一行，属性逐行列出
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_node(f: &mut fmt::Formatter<'_>, node: &SkinNode, depth: usize) -> fmt::Result {
+            let indent = "  ".repeat(depth);
+            write!(f, "{}{}", indent, node.tag)?;
+            if let Some(name) = node.name() {
+                write!(f, " {:?}", name)?;
+            }
+            if node.kind.is_custom() {
+                write!(f, " (自定义)")?;
+            }
+            writeln!(f, "  @{}", node.line)?;
+            for attr in &node.attributes {
+                write!(f, "{}  .{} = {}", indent, attr.name, attr.value)?;
+                if let Some(invalid) = &attr.invalid {
+                    write!(f, "  ⚠ {}", invalid)?;
+                }
+                writeln!(f)?;
+            }
+            for child in &node.children {
+                write_node(f, child, depth + 1)?;
+            }
+            Ok(())
+        }
+        write_node(f, self, 0)
+    }
+}
+
+/// 深度优先遍历
+pub struct Walk<'a> {
+    stack: Vec<&'a SkinNode>,
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = &'a SkinNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev());
+        Some(node)
+    }
+}
+
+/// 字体定义 `<Font id="0" name="微软雅黑" size="12" bold="true" default="true"/>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontDef<'a> {
+    pub id: Option<i32>, // 控件的 font 属性引用的编号
+    pub name: &'a str,
+    pub size: Option<i32>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub default: bool, // 作为窗口的默认字体
+}
+
+/// 控件默认属性 `<Default name="Button" value="textcolor=&quot;#FF000000&quot;"/>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefaultDef<'a> {
+    pub class: &'a str, // 控件标签名
+    pub value: &'a str, // 属性列表文本
+}
+
+impl DefaultDef<'_> {
+    /// 按目标控件的类型解析属性列表
+    pub fn attributes(&self) -> Result<Vec<Attribute>, String> {
+        Ok(parse_attribute_list(self.value)
+            .map_err(|e| format!("{} 的默认属性无效: {}", self.class, e))?
+            .into_iter()
+            .map(|(name, raw)| Attribute::parse(self.class, &name, &raw))
+            .collect())
+    }
+}
+
+/// 引用其他界面XML `<Include source="common\titlebar.xml" count="1"/>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludeDef<'a> {
+    pub source: &'a str,
+    pub count: i32, // 重复引用的次数
+}
@@ -0,0 +1,594 @@
+use super::set::{SkinFile, SkinSet};
+use super::{SkinDocument, SkinNode};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// 一个节点的变化
+///
+/// 路径由各级的 `标签[name]` 组成，没有名称的节点写成 `标签#序号`（同一父节点下同样标签的第几个）。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum NodeChange {
+    /// 新增的子树，`nodes` 是子树中的节点数
+    Added { path: String, nodes: usize },
+    /// 删除的子树
+    Removed { path: String, nodes: usize },
+    /// 有名称的节点换了父节点
+    Moved { from: String, to: String },
+    /// 标签或属性变化
+    Modified {
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tag: Option<(String, String)>, // 原来的标签和新的标签
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        attributes: Vec<AttrChange>,
+    },
+}
+
+/// 一个属性的变化，`None` 表示没有这个属性
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AttrChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// 文件的变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Added,
+    Removed,
+    Modified,
+    Equivalent, // 内容不同，但节点和属性值都相同（格式、属性写法、注释不同）
+    Unparsable, // 至少有一边不是格式正确的XML，只能比较原文
+}
+
+/// 一个文件的比较结果，内容完全相同的文件不列出
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileDiff {
+    pub path: String,
+    pub status: FileStatus,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<NodeChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FileDiff {
+    fn new(path: &str, status: FileStatus) -> Self {
+        FileDiff {
+            path: path.to_string(),
+            status,
+            changes: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+/// 两组界面XML的比较结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SkinDiff {
+    pub files: Vec<FileDiff>,
+    pub unchanged: usize, // 内容完全相同的文件数
+}
+
+/// 比较两组界面XML，文件按规范化后的路径对应
+pub fn diff_sets(old: &SkinSet, new: &SkinSet) -> SkinDiff {
+    let mut pairs: BTreeMap<&str, (Option<&SkinFile>, Option<&SkinFile>)> = BTreeMap::new();
+    for (key, file) in old.files() {
+        pairs.entry(key).or_default().0 = Some(file);
+    }
+    for (key, file) in new.files() {
+        pairs.entry(key).or_default().1 = Some(file);
+    }
+
+    let mut diff = SkinDiff::default();
+    for pair in pairs.into_values() {
+        let file_diff = match pair {
+            (Some(old_file), None) => FileDiff::new(&old_file.path, FileStatus::Removed),
+            (None, Some(new_file)) => FileDiff::new(&new_file.path, FileStatus::Added),
+            (Some(old_file), Some(new_file)) if old_file.content == new_file.content => {
+                diff.unchanged += 1;
+                continue;
+            }
+            (Some(old_file), Some(new_file)) => diff_files(old_file, new_file),
+            (None, None) => continue,
+        };
+        diff.files.push(file_diff);
+    }
+    diff
+}
+
+// 比较内容不同的同一个文件
+fn diff_files(old_file: &SkinFile, new_file: &SkinFile) -> FileDiff {
+    let parsed = SkinDocument::parse(&old_file.content)
+        .and_then(|old| Ok((old, SkinDocument::parse(&new_file.content)?)));
+    match parsed {
+        Ok((old_document, new_document)) => {
+            let changes = diff_documents(&old_document, &new_document);
+            let status = if changes.is_empty() {
+                FileStatus::Equivalent
+            } else {
+                FileStatus::Modified
+            };
+            FileDiff {
+                changes,
+                ..FileDiff::new(&new_file.path, status)
+            }
+        }
+        Err(e) => FileDiff {
+            error: Some(e),
+            ..FileDiff::new(&new_file.path, FileStatus::Unparsable)
+        },
+    }
+}
+
+// 展开后的节点
+struct FlatNode<'a> {
+    node: &'a SkinNode,
+    parent: Option<usize>,
+    segment: String, // 在父节点下的标识
+    path: String,
+    size: usize, // 子树中的节点数
+}
+
+// 只出现一次的名称对应的节点，出现多次的名称对应 None
+fn unique_names<'a>(nodes: &[FlatNode<'a>]) -> HashMap<&'a str, Option<usize>> {
+    let mut names = HashMap::new();
+    for (index, flat) in nodes.iter().enumerate().skip(1) {
+        if let Some(name) = flat.node.name() {
+            names
+                .entry(name)
+                .and_modify(|slot| *slot = None)
+                .or_insert(Some(index));
+        }
+    }
+    names
+}
+
+// 先序展开，父节点总在子节点前面
+fn flatten(root: &SkinNode) -> Vec<FlatNode<'_>> {
+    fn visit<'a>(
+        node: &'a SkinNode,
+        parent: Option<usize>,
+        segment: String,
+        nodes: &mut Vec<FlatNode<'a>>,
+    ) -> usize {
+        let index = nodes.len();
+        let path = match parent {
+            Some(parent) => format!("{}/{}", nodes[parent].path, segment),
+            None => segment.clone(),
+        };
+        nodes.push(FlatNode {
+            node,
+            parent,
+            segment,
+            path,
+            size: 1,
+        });
+        let mut seen: HashMap<(&str, Option<&str>), usize> = HashMap::new();
+        let mut size = 1;
+        for child in &node.children {
+            let occurrence = seen.entry((&child.tag, child.name())).or_default();
+            let segment = match child.name() {
+                Some(name) if *occurrence == 0 => format!("{}[{}]", child.tag, name),
+                Some(name) => format!("{}[{}]#{}", child.tag, name, occurrence),
+                None => format!("{}#{}", child.tag, occurrence),
+            };
+            *occurrence += 1;
+            size += visit(child, Some(index), segment, nodes);
+        }
+        nodes[index].size = size;
+        size
+    }
+
+    let mut nodes = Vec::new();
+    visit(root, None, root.tag.clone(), &mut nodes);
+    nodes
+}
+
+/// 比较同一个文件的两个版本
+///
+/// 先按名称对应在两边都只出现一次的有名称的节点（即使位置变了），
+/// 其余节点在已对应的父节点下按标签、名称和序号对应。
+/// 没有对应的节点作为新增或删除的子树列出，只列子树的根。
+pub fn diff_documents(old: &SkinDocument, new: &SkinDocument) -> Vec<NodeChange> {
+    let old_nodes = flatten(&old.root);
+    let new_nodes = flatten(&new.root);
+    let mut old_to_new: Vec<Option<usize>> = vec![None; old_nodes.len()];
+    let mut new_to_old: Vec<Option<usize>> = vec![None; new_nodes.len()];
+
+    // 根节点总是对应
+    old_to_new[0] = Some(0);
+    new_to_old[0] = Some(0);
+
+    // 两边都只出现一次的名称
+    let old_names = unique_names(&old_nodes);
+    for (name, new_index) in unique_names(&new_nodes) {
+        if let (Some(new_index), Some(Some(old_index))) = (new_index, old_names.get(name)) {
+            old_to_new[*old_index] = Some(new_index);
+            new_to_old[new_index] = Some(*old_index);
+        }
+    }
+
+    // 在已对应的父节点下按标识对应
+    for new_index in 1..new_nodes.len() {
+        if new_to_old[new_index].is_some() {
+            continue;
+        }
+        let new_flat = &new_nodes[new_index];
+        let Some(old_parent) = new_flat.parent.and_then(|parent| new_to_old[parent]) else {
+            continue;
+        };
+        let found = (old_parent + 1..old_nodes.len()).find(|&old_index| {
+            let old_flat = &old_nodes[old_index];
+            old_flat.parent == Some(old_parent)
+                && old_to_new[old_index].is_none()
+                && old_flat.segment == new_flat.segment
+        });
+        if let Some(old_index) = found {
+            old_to_new[old_index] = Some(new_index);
+            new_to_old[new_index] = Some(old_index);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (new_index, new_flat) in new_nodes.iter().enumerate() {
+        let Some(old_index) = new_to_old[new_index] else {
+            // 父节点也是新增的时已经包含在父节点的子树中
+            if new_flat
+                .parent
+                .is_none_or(|parent| new_to_old[parent].is_some())
+            {
+                changes.push(NodeChange::Added {
+                    path: new_flat.path.clone(),
+                    nodes: new_flat.size,
+                });
+            }
+            continue;
+        };
+        let old_flat = &old_nodes[old_index];
+        let moved = match (old_flat.parent, new_flat.parent) {
+            (Some(old_parent), Some(new_parent)) => old_to_new[old_parent] != Some(new_parent),
+            _ => false,
+        };
+        if moved {
+            changes.push(NodeChange::Moved {
+                from: old_flat.path.clone(),
+                to: new_flat.path.clone(),
+            });
+        }
+        let tag = (old_flat.node.tag != new_flat.node.tag)
+            .then(|| (old_flat.node.tag.clone(), new_flat.node.tag.clone()));
+        let attributes = diff_attributes(old_flat.node, new_flat.node);
+        if tag.is_some() || !attributes.is_empty() {
+            changes.push(NodeChange::Modified {
+                path: new_flat.path.clone(),
+                tag,
+                attributes,
+            });
+        }
+    }
+    for (old_index, old_flat) in old_nodes.iter().enumerate() {
+        if old_to_new[old_index].is_none()
+            && old_flat
+                .parent
+                .is_none_or(|parent| old_to_new[parent].is_some())
+        {
+            changes.push(NodeChange::Removed {
+                path: old_flat.path.clone(),
+                nodes: old_flat.size,
+            });
+        }
+    }
+    changes
+}
+
+// 按名称比较属性，值按类型比较（`#ff000000` 和 `#FF000000` 相同），重复的属性取最后一个
+fn diff_attributes(old: &SkinNode, new: &SkinNode) -> Vec<AttrChange> {
+    let mut names: Vec<&str> = Vec::new();
+    for attr in old.attributes.iter().chain(&new.attributes) {
+        if !names.contains(&attr.name.as_str()) {
+            names.push(&attr.name);
+        }
+    }
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let old_attr = old.attr(name);
+            let new_attr = new.attr(name);
+            let same = match (old_attr, new_attr) {
+                (Some(old_attr), Some(new_attr)) => old_attr.value == new_attr.value,
+                _ => false,
+            };
+            (!same).then(|| AttrChange {
+                name: name.to_string(),
+                old: old_attr.map(|attr| attr.raw.clone()),
+                new: new_attr.map(|attr| attr.raw.clone()),
+            })
+        })
+        .collect()
+}
+
+impl fmt::Display for NodeChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeChange::Added { path, nodes } => writeln!(f, "  + {} ({} 个节点)", path, nodes),
+            NodeChange::Removed { path, nodes } => {
+                writeln!(f, "  - {} ({} 个节点)", path, nodes)
+            }
+            NodeChange::Moved { from, to } => writeln!(f, "  > {}\n      -> {}", from, to),
+            NodeChange::Modified {
+                path,
+                tag,
+                attributes,
+            } => {
+                writeln!(f, "  ~ {}", path)?;
+                if let Some((old, new)) = tag {
+                    writeln!(f, "      <{}> -> <{}>", old, new)?;
+                }
+                for attr in attributes {
+                    match (&attr.old, &attr.new) {
+                        (Some(old), Some(new)) => {
+                            writeln!(f, "      {}: {:?} -> {:?}", attr.name, old, new)?
+                        }
+                        (None, Some(new)) => writeln!(f, "      + {} = {:?}", attr.name, new)?,
+                        (Some(old), None) => writeln!(f, "      - {} = {:?}", attr.name, old)?,
+                        (None, None) => {}
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for SkinDiff {
+    // 可读的报告，先列出每个有变化的文件，最后是统计
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut counts: HashMap<FileStatus, usize> = HashMap::new();
+        for file in &self.files {
+            *counts.entry(file.status).or_default() += 1;
+            let status = match file.status {
+                FileStatus::Added => "新增",
+                FileStatus::Removed => "删除",
+                FileStatus::Modified => "修改",
+                FileStatus::Equivalent => "等价",
+                FileStatus::Unparsable => "无法解析",
+            };
+            writeln!(f, "== {} {}", status, file.path)?;
+            if let Some(error) = &file.error {
+                writeln!(f, "  {}", error)?;
+            }
+            for change in &file.changes {
+                write!(f, "{}", change)?;
+            }
+        }
+        let count = |status| counts.get(&status).copied().unwrap_or(0);
+        writeln!(
+            f,
+            "新增 {} 个文件，删除 {} 个，修改 {} 个，只有格式变化 {} 个，无法解析 {} 个，相同 {} 个",
+            count(FileStatus::Added),
+            count(FileStatus::Removed),
+            count(FileStatus::Modified),
+            count(FileStatus::Equivalent),
+            count(FileStatus::Unparsable),
+            self.unchanged
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const LOGIN: &str = "Window/VerticalLayout[root]/TabLayout[login_tab]";
+
+    fn corpus(name: &str) -> SkinSet {
+        SkinSet::from_dump_dir(
+            &Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("corpus")
+                .join(name),
+        )
+        .unwrap()
+    }
+
+    fn diff(old: &str, new: &str) -> Vec<NodeChange> {
+        diff_documents(
+            &SkinDocument::parse(old).unwrap(),
+            &SkinDocument::parse(new).unwrap(),
+        )
+    }
+
+    fn attr(name: &str, old: Option<&str>, new: Option<&str>) -> AttrChange {
+        AttrChange {
+            name: name.to_string(),
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn diffs_corpus_sets() {
+        let diff = diff_sets(&corpus("skin"), &corpus("skin_next"));
+        assert_eq!(diff.unchanged, 1); // common\defaults.xml
+        let statuses: Vec<(&str, FileStatus)> = diff
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), file.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("common\\titlebar.xml", FileStatus::Equivalent),
+                ("common\\toast.xml", FileStatus::Added),
+                ("login\\LoginWnd.xml", FileStatus::Modified),
+            ]
+        );
+        assert!(diff.files[..2].iter().all(|file| file.changes.is_empty()));
+
+        let forget_password =
+            "Window/VerticalLayout[root]/HorizontalLayout#0/WxLinkLabel[forget_password]";
+        assert_eq!(
+            diff.files[2].changes,
+            [
+                NodeChange::Added {
+                    path: format!("{}/VerticalLayout[qrcode_page]/Include#0", LOGIN),
+                    nodes: 1,
+                },
+                NodeChange::Added {
+                    path: format!("{}/VerticalLayout[qrcode_page]/HorizontalLayout#1", LOGIN),
+                    nodes: 5,
+                },
+                NodeChange::Modified {
+                    path: format!("{}/VerticalLayout[account_page]/Button[login]", LOGIN),
+                    tag: None,
+                    attributes: vec![attr("bkcolor", Some("#FF1890FF"), Some("#FF0082EF"))],
+                },
+                NodeChange::Moved {
+                    from: format!(
+                        "{}/VerticalLayout[account_page]/WxLinkLabel[forget_password]",
+                        LOGIN
+                    ),
+                    to: forget_password.to_string(),
+                },
+                NodeChange::Modified {
+                    path: forget_password.to_string(),
+                    tag: None,
+                    attributes: vec![
+                        attr("height", Some("20"), None),
+                        attr("width", None, Some("60")),
+                    ],
+                },
+                NodeChange::Removed {
+                    path: format!("{}/VerticalLayout[qrcode_page]/Label[qrcode_tip]", LOGIN),
+                    nodes: 1,
+                },
+            ]
+        );
+
+        let report = diff.to_string();
+        assert!(report.contains("== 修改 login\\LoginWnd.xml"), "{}", report);
+        assert!(report.ends_with(
+            "新增 1 个文件，删除 0 个，修改 1 个，只有格式变化 1 个，无法解析 0 个，相同 1 个\n"
+        ));
+
+        // 反过来比较时新增和删除对调
+        let reverse = diff_sets(&corpus("skin_next"), &corpus("skin"));
+        assert_eq!(reverse.files[1].status, FileStatus::Removed);
+    }
+
+    #[test]
+    fn unparsable_file_keeps_error() {
+        let mut old = SkinSet::new();
+        old.insert("a.xml", "<Window/>".to_string());
+        let mut new = SkinSet::new();
+        new.insert("A.xml", "<Window>".to_string());
+        let diff = diff_sets(&old, &new);
+        assert_eq!(diff.files.len(), 1);
+        assert_eq!(diff.files[0].path, "A.xml");
+        assert_eq!(diff.files[0].status, FileStatus::Unparsable);
+        assert!(diff.files[0].error.is_some());
+    }
+
+    #[test]
+    fn named_node_moves_with_children() {
+        let changes = diff(
+            r#"<Window><A name="a"><B name="b"><Label /></B></A><C name="c" /></Window>"#,
+            r#"<Window><A name="a" /><C name="c"><B name="b"><Label /></B></C></Window>"#,
+        );
+        // 子节点跟着有名称的节点对应，不列为删除和新增
+        assert_eq!(
+            changes,
+            [NodeChange::Moved {
+                from: "Window/A[a]/B[b]".to_string(),
+                to: "Window/C[c]/B[b]".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn unnamed_nodes_match_by_tag_and_occurrence() {
+        let changes = diff(
+            r#"<Window><Control /><Label /><Control width="1" /></Window>"#,
+            r#"<Window><Label /><Control /><Control width="2" /></Window>"#,
+        );
+        assert_eq!(
+            changes,
+            [NodeChange::Modified {
+                path: "Window/Control#1".to_string(),
+                tag: None,
+                attributes: vec![attr("width", Some("1"), Some("2"))],
+            }]
+        );
+    }
+
+    #[test]
+    fn named_node_changing_tag_is_modified() {
+        let changes = diff(
+            r#"<Window><Label name="tip" /></Window>"#,
+            r#"<Window><Text name="tip" /></Window>"#,
+        );
+        assert_eq!(
+            changes,
+            [NodeChange::Modified {
+                path: "Window/Text[tip]".to_string(),
+                tag: Some(("Label".to_string(), "Text".to_string())),
+                attributes: Vec::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn duplicate_names_match_by_position() {
+        // 名称出现多次时不按名称对应，只在同一父节点下按序号对应
+        let changes = diff(
+            r#"<Window><A><Label name="x" /></A><B><Label name="x" /></B></Window>"#,
+            r#"<Window><A /><B><Label name="x" /><Label name="x" /></B></Window>"#,
+        );
+        assert_eq!(
+            changes,
+            [
+                NodeChange::Added {
+                    path: "Window/B#0/Label[x]#1".to_string(),
+                    nodes: 1,
+                },
+                NodeChange::Removed {
+                    path: "Window/A#0/Label[x]".to_string(),
+                    nodes: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn added_subtree_lists_only_its_root() {
+        let changes = diff(
+            "<Window />",
+            r#"<Window><VerticalLayout name="v"><Label /><Label /></VerticalLayout></Window>"#,
+        );
+        assert_eq!(
+            changes,
+            [NodeChange::Added {
+                path: "Window/VerticalLayout[v]".to_string(),
+                nodes: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn attribute_values_compare_by_type() {
+        let old = r##"<Window><Label textcolor="#ff000000" width="10" /></Window>"##;
+        assert!(diff(old, old).is_empty());
+        assert!(
+            diff(
+                old,
+                "<Window>\n  <Label width=\"10\" textcolor=\"#FF000000\"></Label>\n</Window>"
+            )
+            .is_empty()
+        );
+    }
+}
@@ -0,0 +1,250 @@
+// DuiLib 界面XML的解析：节点树、按类型解析的属性、引用的展开，以及两个版本之间的比较
+pub mod attr;
+pub mod control;
+pub mod diff;
+pub mod resolve;
+pub mod set;
+
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+pub use attr::{
+    AttrKind, AttrValue, Attribute, Color, ImageDescriptor, Rect, Size, attr_kind,
+    parse_attribute_list,
+};
+pub use control::{ControlKind, DefaultDef, FontDef, IncludeDef, SkinNode, Walk};
+pub use diff::{AttrChange, FileDiff, FileStatus, NodeChange, SkinDiff, diff_documents, diff_sets};
+pub use resolve::{ResolvedWindow, Resolver, ResourceKind, ResourceRef, ResourceStatus};
+pub use set::{SkinFile, SkinSet, normalize_key};
+
+/// 一个界面XML文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkinDocument {
+    pub root: SkinNode,
+}
+
+impl SkinDocument {
+    /// 解析XML文本，开头的 BOM 忽略
+    ///
+    /// 只要是格式正确的XML都能解析，不认识的标签作为自定义控件保留，
+    /// 属性按类型解析失败时保留为文本（见 `Attribute::invalid`）。
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+        let document =
+            roxmltree::Document::parse(text).map_err(|e| format!("XML格式错误: {}", e))?;
+        Ok(SkinDocument {
+            root: convert(&document, document.root_element()),
+        })
+    }
+
+    /// 读取并解析文件，内容必须是 UTF-8（抓取和存档的文件都是 UTF-8）
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+        let text =
+            String::from_utf8(bytes).map_err(|_| format!("{} 不是 UTF-8 文件", path.display()))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// 所有节点，深度优先
+    pub fn walk(&self) -> Walk<'_> {
+        self.root.walk()
+    }
+
+    /// 字体定义
+    pub fn fonts(&self) -> Vec<FontDef<'_>> {
+        self.walk().filter_map(SkinNode::as_font).collect()
+    }
+
+    /// 控件默认属性定义
+    pub fn defaults(&self) -> Vec<DefaultDef<'_>> {
+        self.walk().filter_map(SkinNode::as_default).collect()
+    }
+
+    /// 引用的其他界面XML
+    pub fn includes(&self) -> Vec<IncludeDef<'_>> {
+        self.walk().filter_map(SkinNode::as_include).collect()
+    }
+
+    /// 按控件名查找第一个节点
+    pub fn find(&self, name: &str) -> Option<&SkinNode> {
+        self.walk().find(|node| node.name() == Some(name))
+    }
+
+    /// 出现的自定义控件标签
+    pub fn custom_tags(&self) -> BTreeSet<&str> {
+        self.walk()
+            .filter(|node| node.kind.is_custom())
+            .map(|node| node.tag.as_str())
+            .collect()
+    }
+
+    /// 按类型解析失败的属性
+    pub fn invalid_attributes(&self) -> Vec<(&SkinNode, &Attribute)> {
+        self.walk()
+            .flat_map(|node| {
+                node.attributes
+                    .iter()
+                    .filter(|attr| attr.invalid.is_some())
+                    .map(move |attr| (node, attr))
+            })
+            .collect()
+    }
+}
+
+// 转换为节点树，注释、文本和处理指令忽略（DuiLib 也不读取）
+fn convert(document: &roxmltree::Document, element: roxmltree::Node) -> SkinNode {
+    let tag = element.tag_name().name();
+    let line = document.text_pos_at(element.range().start).row;
+    let mut node = SkinNode::new(tag, line);
+    node.attributes = element
+        .attributes()
+        .map(|attr| Attribute::parse(tag, attr.name(), attr.value()))
+        .collect();
+    node.children = element
+        .children()
+        .filter(roxmltree::Node::is_element)
+        .map(|child| convert(document, child))
+        .collect();
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    // corpus 中的界面XML是按 DuiLib 的写法手工编写的，不是从客户端抓取的
+    fn corpus(path: &str) -> SkinDocument {
+        SkinDocument::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join(path)).unwrap()
+    }
+
+    #[test]
+    fn parses_login_window() {
+        let document = corpus("corpus/skin/login/LoginWnd.xml");
+        let root = &document.root;
+        assert_eq!(root.kind, ControlKind::Window);
+        assert_eq!(root.line, 3);
+        assert_eq!(
+            root.value("size"),
+            Some(&AttrValue::Size(Size {
+                width: 380,
+                height: 520
+            }))
+        );
+        assert_eq!(root.value("showshadow"), Some(&AttrValue::Bool(true)));
+
+        let fonts = document.fonts();
+        let ids: Vec<Option<i32>> = fonts.iter().map(|font| font.id).collect();
+        assert_eq!(ids, [Some(0), Some(1), Some(2)]);
+        assert!(fonts[0].default && !fonts[0].bold);
+        assert!(fonts[1].bold);
+        assert_eq!(fonts[2].size, Some(18));
+        assert_eq!(fonts[2].name, "微软雅黑");
+
+        let includes: Vec<(&str, i32)> = document
+            .includes()
+            .iter()
+            .map(|include| (include.source, include.count))
+            .collect();
+        assert_eq!(
+            includes,
+            [("common\\defaults.xml", 1), ("common\\titlebar.xml", 1)]
+        );
+
+        assert_eq!(
+            document.custom_tags().into_iter().collect::<Vec<_>>(),
+            ["WxLinkLabel", "WxQRCodeView"]
+        );
+        assert!(document.invalid_attributes().is_empty());
+    }
+
+    #[test]
+    fn finds_controls_and_typed_attributes() {
+        let document = corpus("corpus/skin/login/LoginWnd.xml");
+        let qrcode = document.find("qrcode").unwrap();
+        assert!(qrcode.kind.is_custom());
+        assert_eq!(qrcode.value("width"), Some(&AttrValue::Int(200)));
+        // 自定义控件上不认识的属性保留为文本
+        assert_eq!(qrcode.raw("refreshinterval"), Some("60000"));
+        let background = qrcode
+            .value("bkimage")
+            .and_then(AttrValue::as_image)
+            .unwrap();
+        assert_eq!(background.file, "login\\qrcode_bg.png");
+        assert_eq!(background.hole, Some(false));
+
+        let account = document.find("account").unwrap();
+        assert_eq!(account.kind, ControlKind::Edit);
+        assert!(matches!(
+            account.value("bordersize"),
+            Some(AttrValue::Rect(_))
+        ));
+        let password = document.find("password").unwrap();
+        assert_eq!(password.value("bordersize"), Some(&AttrValue::Int(1)));
+        assert_eq!(password.value("password"), Some(&AttrValue::Bool(true)));
+        assert!(document.find("missing").is_none());
+    }
+
+    #[test]
+    fn walks_depth_first() {
+        let document = SkinDocument::parse(
+            "<Window><VerticalLayout name=\"a\"><Label name=\"b\"/></VerticalLayout><Button name=\"c\"/></Window>",
+        )
+        .unwrap();
+        let tags: Vec<&str> = document.walk().map(|node| node.tag.as_str()).collect();
+        assert_eq!(tags, ["Window", "VerticalLayout", "Label", "Button"]);
+        let names: Vec<Option<&str>> = document.walk().map(SkinNode::name).collect();
+        assert_eq!(names, [None, Some("a"), Some("b"), Some("c")]);
+    }
+
+    #[test]
+    fn parses_defaults_with_nested_quotes() {
+        let document = corpus("corpus/skin/common/defaults.xml");
+        let defaults = document.defaults();
+        let classes: Vec<&str> = defaults.iter().map(|default| default.class).collect();
+        assert_eq!(classes, ["Button", "Edit", "VScrollBar"]);
+        let scrollbar = defaults[2].attributes().unwrap();
+        let thumb = scrollbar
+            .iter()
+            .find(|attr| attr.name == "thumbnormalimage")
+            .and_then(|attr| attr.value.as_image())
+            .unwrap();
+        assert_eq!(thumb.source.map(|source| source.bottom), Some(32));
+        assert_eq!(thumb.corner.map(|corner| corner.top), Some(4));
+        let width = scrollbar.iter().find(|attr| attr.name == "width").unwrap();
+        assert_eq!(width.value, AttrValue::Int(8));
+        assert!(document.fonts()[0].underline);
+    }
+
+    #[test]
+    fn keeps_invalid_attributes_and_strips_bom() {
+        let document = SkinDocument::parse(
+            "\u{FEFF}<Window size=\"380\">\n  <!-- 注释 -->\n  文本\n  <Button textcolor=\"blue\" pos=\"1,2,3,4\"/>\n</Window>",
+        )
+        .unwrap();
+        let invalid: Vec<(&str, &str)> = document
+            .invalid_attributes()
+            .into_iter()
+            .map(|(node, attr)| (node.tag.as_str(), attr.name.as_str()))
+            .collect();
+        assert_eq!(invalid, [("Window", "size"), ("Button", "textcolor")]);
+        assert_eq!(document.root.children.len(), 1);
+        assert_eq!(document.root.children[0].line, 4);
+    }
+
+    #[test]
+    fn rejects_malformed_xml() {
+        assert!(SkinDocument::parse("<Window><Label></Window>").is_err());
+        assert!(SkinDocument::parse("").is_err());
+        assert!(SkinDocument::parse("<Window a=\"1\" a=\"2\"/>").is_err());
+    }
+
+    #[test]
+    fn load_requires_utf8() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), b"<Window text=\"\xC6\xF3\xD2\xB5\"/>").unwrap();
+        let result = SkinDocument::load(file.path());
+        assert!(result.unwrap_err().contains("不是 UTF-8"));
+    }
+}
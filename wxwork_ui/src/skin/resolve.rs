@@ -0,0 +1,616 @@
+use super::SkinDocument;
+use super::attr::{AttrValue, Attribute};
+use super::control::{ControlKind, SkinNode};
+use super::set::{SkinFile, SkinSet, normalize_key};
+use crate::dump_path::sanitize_resource_path;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+
+// 控件上引用字体编号的属性
+const FONT_ATTRS: &[&str] = &[
+    "font",
+    "hotfont",
+    "pushedfont",
+    "focusedfont",
+    "selectedfont",
+    "itemfont",
+];
+
+// 引用嵌套的最大深度，超过时按循环处理
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+// 一个 Include 最多重复展开的次数，count 超过时按这个次数展开
+const MAX_INCLUDE_COUNT: i32 = 256;
+
+// 一个窗口最多展开的节点数，嵌套的 Include 重复展开时节点数按乘积增长，超过后不再展开子节点
+const MAX_EXPANDED_NODES: usize = 100_000;
+
+/// 引用的资源类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    Xml,   // Include 引用的界面XML
+    Image, // 图片文件或内嵌资源
+    Font,  // 控件引用的字体编号
+}
+
+/// 引用的资源是否找到
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceStatus {
+    Found,
+    Missing,   // XML 不在抓到的文件中，图片不在资源目录中，字体编号没有定义
+    Embedded,  // 图片通过 res/restype 从程序资源中加载，无法检查
+    Unchecked, // 图片，没有指定资源目录
+}
+
+/// 一个被引用的资源
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResourceRef {
+    pub kind: ResourceKind,
+    pub name: String, // XML 和图片是引用时写的路径，字体是编号
+    pub status: ResourceStatus,
+    pub used_by: Vec<String>, // 引用的位置：`文件:行 标签.属性`
+}
+
+/// 展开后的窗口
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedWindow {
+    pub path: String,
+    /// 展开后的节点树：`Include` 节点保留，引用的内容作为它的子节点（重复 `count` 次），
+    /// 引用的文件以 `Window` 为根时放入根的子节点（DuiLib 也是这样处理的）
+    pub root: SkinNode,
+    pub references: Vec<ResourceRef>, // 按类型和名称排序
+    pub problems: Vec<String>,        // 循环引用、引用的文件无法解析等
+}
+
+impl ResolvedWindow {
+    /// 找不到的资源
+    pub fn missing(&self) -> impl Iterator<Item = &ResourceRef> {
+        self.references
+            .iter()
+            .filter(|reference| reference.status == ResourceStatus::Missing)
+    }
+}
+
+/// 解析窗口的引用
+///
+/// `Include` 的 `source` 和图片路径都相对于皮肤的资源目录（与 LoadXML 收到的路径相同），
+/// 在抓到的文件中找不到时再按相对于引用者所在目录查找。
+pub struct Resolver<'a> {
+    set: &'a SkinSet,
+    resource_dir: Option<PathBuf>, // 解包出的皮肤资源目录，用于检查图片
+}
+
+// 展开过程中收集的结果
+#[derive(Default)]
+struct Collector {
+    references: BTreeMap<(ResourceKind, String), ResourceRef>,
+    defined_fonts: Vec<i32>,
+    font_uses: Vec<(i32, String)>,
+    problems: Vec<String>,
+    documents: HashMap<String, Result<SkinDocument, String>>,
+    expanded: usize, // 已展开的节点数
+    truncated: bool, // 节点数超过上限，之后的子节点没有展开
+}
+
+impl Collector {
+    fn add(&mut self, kind: ResourceKind, name: &str, status: ResourceStatus, used_by: String) {
+        let reference = self
+            .references
+            .entry((kind, name.to_lowercase()))
+            .or_insert_with(|| ResourceRef {
+                kind,
+                name: name.to_string(),
+                status,
+                used_by: Vec::new(),
+            });
+        if !reference.used_by.contains(&used_by) {
+            reference.used_by.push(used_by);
+        }
+    }
+
+    // 计入一个展开的节点，超过上限时记录一次问题并返回 false
+    fn count_node(&mut self, used_by: &str) -> bool {
+        self.expanded += 1;
+        if self.expanded <= MAX_EXPANDED_NODES {
+            return true;
+        }
+        if !self.truncated {
+            self.truncated = true;
+            self.problems.push(format!(
+                "{}: 展开的节点超过 {} 个，之后的子节点不再展开",
+                used_by, MAX_EXPANDED_NODES
+            ));
+        }
+        false
+    }
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(set: &'a SkinSet) -> Self {
+        Resolver {
+            set,
+            resource_dir: None,
+        }
+    }
+
+    /// 按解包出的资源目录检查图片是否存在
+    pub fn with_resource_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.resource_dir = Some(dir.into());
+        self
+    }
+
+    /// 展开一个窗口，`path` 是组中的文件
+    pub fn resolve(&self, path: &str) -> Result<ResolvedWindow, String> {
+        let file = self
+            .set
+            .get(path)
+            .ok_or_else(|| format!("没有抓到 {}", path))?;
+        let document =
+            SkinDocument::parse(&file.content).map_err(|e| format!("{}: {}", path, e))?;
+
+        let mut collector = Collector::default();
+        let mut stack = vec![normalize_key(&file.path).unwrap_or_default()];
+        let root = self.expand(&document.root, &file.path, &mut stack, &mut collector);
+
+        // 字体编号按整个窗口的定义检查，Include 的文件中定义的也算
+        for (id, used_by) in std::mem::take(&mut collector.font_uses) {
+            let status = if collector.defined_fonts.contains(&id) {
+                ResourceStatus::Found
+            } else {
+                ResourceStatus::Missing
+            };
+            collector.add(ResourceKind::Font, &id.to_string(), status, used_by);
+        }
+
+        Ok(ResolvedWindow {
+            path: file.path.clone(),
+            root,
+            references: collector.references.into_values().collect(),
+            problems: collector.problems,
+        })
+    }
+
+    /// 组中可以作为窗口展开的文件：以 `Window` 为根，并且没有被其他文件 `Include`
+    pub fn windows(&self) -> Vec<&'a str> {
+        let documents: Vec<(&SkinFile, SkinDocument)> = self
+            .set
+            .files()
+            .filter_map(|(_, file)| Some((file, SkinDocument::parse(&file.content).ok()?)))
+            .collect();
+        let included: HashSet<String> = documents
+            .iter()
+            .flat_map(|(file, document)| {
+                document
+                    .includes()
+                    .into_iter()
+                    .filter_map(|include| self.find_xml(include.source, &file.path))
+                    .filter_map(|included| normalize_key(&included.path))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        documents
+            .iter()
+            .filter(|(file, document)| {
+                document.root.kind == ControlKind::Window
+                    && normalize_key(&file.path).is_none_or(|key| !included.contains(&key))
+            })
+            .map(|(file, _)| file.path.as_str())
+            .collect()
+    }
+
+    // 复制节点并展开其中的引用
+    fn expand(
+        &self,
+        node: &SkinNode,
+        file: &str,
+        stack: &mut Vec<String>,
+        collector: &mut Collector,
+    ) -> SkinNode {
+        let mut expanded = SkinNode {
+            children: Vec::new(),
+            ..node.clone()
+        };
+        let location = |attr: &str| format!("{}:{} {}.{}", file, node.line, node.tag, attr);
+        for attr in &node.attributes {
+            self.collect_attribute(attr, &location(&attr.name), collector);
+        }
+        if let Some(font) = node.as_font() {
+            collector.defined_fonts.extend(font.id);
+        }
+        if let Some(default) = node.as_default() {
+            match default.attributes() {
+                Ok(attributes) => {
+                    for attr in &attributes {
+                        let used_by = format!("{} [{}]", location("value"), attr.name);
+                        self.collect_attribute(attr, &used_by, collector);
+                    }
+                }
+                Err(e) => collector
+                    .problems
+                    .push(format!("{}: {}", location("value"), e)),
+            }
+        }
+        if node.kind == ControlKind::Image {
+            // <Image name="..."/> 预加载图片
+            if let Some(name) = node.raw("name").filter(|name| !name.is_empty()) {
+                let status = self.image_status(name);
+                collector.add(ResourceKind::Image, name, status, location("name"));
+            }
+        }
+
+        if !collector.count_node(&format!("{}:{} {}", file, node.line, node.tag)) {
+            return expanded;
+        }
+        if let Some(include) = node.as_include() {
+            expanded.children = self.expand_include(
+                include.source,
+                include.count,
+                file,
+                &location("source"),
+                stack,
+                collector,
+            );
+        } else {
+            for child in &node.children {
+                if collector.truncated {
+                    break;
+                }
+                expanded
+                    .children
+                    .push(self.expand(child, file, stack, collector));
+            }
+        }
+        expanded
+    }
+
+    fn expand_include(
+        &self,
+        source: &str,
+        count: i32,
+        file: &str,
+        used_by: &str,
+        stack: &mut Vec<String>,
+        collector: &mut Collector,
+    ) -> Vec<SkinNode> {
+        let Some(included) = self.find_xml(source, file) else {
+            collector.add(
+                ResourceKind::Xml,
+                source,
+                ResourceStatus::Missing,
+                used_by.to_string(),
+            );
+            return Vec::new();
+        };
+        collector.add(
+            ResourceKind::Xml,
+            source,
+            ResourceStatus::Found,
+            used_by.to_string(),
+        );
+
+        let key = normalize_key(&included.path).unwrap_or_default();
+        if stack.contains(&key) || stack.len() >= MAX_INCLUDE_DEPTH {
+            collector.problems.push(format!(
+                "{}: 循环引用 {}",
+                used_by,
+                stack
+                    .iter()
+                    .chain([&key])
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            ));
+            return Vec::new();
+        }
+        let document = collector
+            .documents
+            .entry(key.clone())
+            .or_insert_with(|| SkinDocument::parse(&included.content))
+            .clone();
+        let document = match document {
+            Ok(document) => document,
+            Err(e) => {
+                collector
+                    .problems
+                    .push(format!("{}: {} 无法解析: {}", used_by, included.path, e));
+                return Vec::new();
+            }
+        };
+
+        // 以 Window 为根的文件只取子节点，窗口属性不生效
+        let roots: Vec<&SkinNode> = if document.root.kind == ControlKind::Window {
+            document.root.children.iter().collect()
+        } else {
+            vec![&document.root]
+        };
+        if count > MAX_INCLUDE_COUNT {
+            collector.problems.push(format!(
+                "{}: count={} 超过上限，按 {} 次展开",
+                used_by, count, MAX_INCLUDE_COUNT
+            ));
+        }
+        stack.push(key);
+        let mut children = Vec::new();
+        'repeat: for _ in 0..count.clamp(0, MAX_INCLUDE_COUNT) {
+            for root in &roots {
+                if collector.truncated {
+                    break 'repeat;
+                }
+                children.push(self.expand(root, &included.path, stack, collector));
+            }
+        }
+        stack.pop();
+        children
+    }
+
+    // 先按资源目录下的路径查找，再按相对于引用者所在目录查找
+    fn find_xml(&self, source: &str, file: &str) -> Option<&'a SkinFile> {
+        self.set.get(source).or_else(|| {
+            let dir = file.rsplit_once(['\\', '/'])?.0;
+            self.set.get(&format!("{}\\{}", dir, source))
+        })
+    }
+
+    fn collect_attribute(&self, attr: &Attribute, used_by: &str, collector: &mut Collector) {
+        match &attr.value {
+            AttrValue::Image(image) => {
+                let Some(name) = image.resource() else {
+                    return;
+                };
+                let status = if image.res.is_some() {
+                    ResourceStatus::Embedded
+                } else {
+                    self.image_status(name)
+                };
+                collector.add(ResourceKind::Image, name, status, used_by.to_string());
+            }
+            // -1 表示使用默认字体
+            AttrValue::Int(id) if *id >= 0 && FONT_ATTRS.contains(&attr.name.as_str()) => {
+                collector.font_uses.push((*id, used_by.to_string()));
+            }
+            _ => {}
+        }
+    }
+
+    fn image_status(&self, file: &str) -> ResourceStatus {
+        let Some(dir) = &self.resource_dir else {
+            return ResourceStatus::Unchecked;
+        };
+        if sanitize_resource_path(file).is_ok_and(|relative| dir.join(relative).is_file()) {
+            ResourceStatus::Found
+        } else {
+            ResourceStatus::Missing
+        }
+    }
+}
+
+impl fmt::Display for ResolvedWindow {
+    // 按类型列出引用的资源，找不到的标出，最后是问题
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "== {}", self.path)?;
+        for reference in &self.references {
+            let kind = match reference.kind {
+                ResourceKind::Xml => "XML ",
+                ResourceKind::Image => "图片",
+                ResourceKind::Font => "字体",
+            };
+            let status = match reference.status {
+                ResourceStatus::Found => "找到",
+                ResourceStatus::Missing => "缺失",
+                ResourceStatus::Embedded => "内嵌",
+                ResourceStatus::Unchecked => "未查",
+            };
+            writeln!(
+                f,
+                "[{}] {} {}  ({} 处引用)",
+                status,
+                kind,
+                reference.name,
+                reference.used_by.len()
+            )?;
+            if reference.status == ResourceStatus::Missing {
+                for used_by in &reference.used_by {
+                    writeln!(f, "        {}", used_by)?;
+                }
+            }
+        }
+        for problem in &self.problems {
+            writeln!(f, "⚠ {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skin_set(files: &[(&str, &str)]) -> SkinSet {
+        let mut set = SkinSet::new();
+        for (path, content) in files {
+            assert!(set.insert(path, content.to_string()));
+        }
+        set
+    }
+
+    fn node_count(node: &SkinNode) -> usize {
+        1 + node.children.iter().map(node_count).sum::<usize>()
+    }
+
+    fn reference<'a>(
+        window: &'a ResolvedWindow,
+        kind: ResourceKind,
+        name: &str,
+    ) -> &'a ResourceRef {
+        window
+            .references
+            .iter()
+            .find(|reference| reference.kind == kind && reference.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn clamps_include_count() {
+        let set = skin_set(&[
+            (
+                "main.xml",
+                r#"<Window><Include source="item.xml" count="2147483647"/></Window>"#,
+            ),
+            ("item.xml", "<Control/>"),
+        ]);
+        let window = Resolver::new(&set).resolve("main.xml").unwrap();
+        let include = &window.root.children[0];
+        assert_eq!(include.children.len(), MAX_INCLUDE_COUNT as usize);
+        assert!(window.problems[0].contains("count=2147483647"));
+
+        let set = skin_set(&[
+            (
+                "main.xml",
+                r#"<Window><Include source="item.xml" count="-5"/></Window>"#,
+            ),
+            ("item.xml", "<Control/>"),
+        ]);
+        let window = Resolver::new(&set).resolve("main.xml").unwrap();
+        assert!(window.root.children[0].children.is_empty());
+        assert!(window.problems.is_empty());
+    }
+
+    #[test]
+    fn caps_nested_includes() {
+        // 每层重复 MAX_INCLUDE_COUNT 次，不设上限时要展开上亿个节点
+        let mut files = vec![(
+            "level0.xml".to_string(),
+            r#"<Window><Include source="level1.xml" count="256"/></Window>"#.to_string(),
+        )];
+        for level in 1..5 {
+            files.push((
+                format!("level{}.xml", level),
+                format!(
+                    r#"<VerticalLayout><Include source="level{}.xml" count="256"/></VerticalLayout>"#,
+                    level + 1
+                ),
+            ));
+        }
+        files.push(("level5.xml".to_string(), "<Control/>".to_string()));
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(path, content)| (path.as_str(), content.as_str()))
+            .collect();
+        let set = skin_set(&files);
+
+        let window = Resolver::new(&set).resolve("level0.xml").unwrap();
+        assert!(node_count(&window.root) <= MAX_EXPANDED_NODES + 1);
+        assert_eq!(window.problems.len(), 1);
+        assert!(window.problems[0].contains("展开的节点超过"));
+        assert_eq!(
+            reference(&window, ResourceKind::Xml, "level5.xml").status,
+            ResourceStatus::Found
+        );
+    }
+
+    #[test]
+    fn reports_include_cycles() {
+        let set = skin_set(&[
+            ("a.xml", r#"<Window><Include source="b.xml"/></Window>"#),
+            (
+                "b.xml",
+                r#"<VerticalLayout><Include source="a.xml"/></VerticalLayout>"#,
+            ),
+        ]);
+        let window = Resolver::new(&set).resolve("a.xml").unwrap();
+        assert_eq!(window.problems.len(), 1);
+        assert!(window.problems[0].contains("循环引用 a.xml -> b.xml -> a.xml"));
+    }
+
+    #[test]
+    fn checks_fonts_and_images() {
+        let set = skin_set(&[(
+            "main.xml",
+            r#"<Window>
+  <Font id="0" name="微软雅黑" size="12"/>
+  <Label font="0" bkimage="res='101' restype='PNG'"/>
+  <Label font="3" normalimage="b.png"/>
+  <Label font="-1"/>
+  <Include source="missing.xml"/>
+</Window>"#,
+        )]);
+        let window = Resolver::new(&set).resolve("main.xml").unwrap();
+        let fonts: Vec<(&str, ResourceStatus)> = window
+            .references
+            .iter()
+            .filter(|reference| reference.kind == ResourceKind::Font)
+            .map(|reference| (reference.name.as_str(), reference.status))
+            .collect();
+        assert_eq!(
+            fonts,
+            [("0", ResourceStatus::Found), ("3", ResourceStatus::Missing)]
+        );
+        assert_eq!(
+            reference(&window, ResourceKind::Image, "101").status,
+            ResourceStatus::Embedded
+        );
+        assert_eq!(
+            reference(&window, ResourceKind::Image, "b.png").status,
+            ResourceStatus::Unchecked
+        );
+        let missing: Vec<&str> = window
+            .missing()
+            .map(|reference| reference.name.as_str())
+            .collect();
+        assert_eq!(missing, ["missing.xml", "3"]);
+    }
+
+    #[test]
+    fn resolves_corpus_login_window() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus/skin");
+        let set = SkinSet::from_dump_dir(&dir).unwrap();
+        let resolver = Resolver::new(&set);
+        assert_eq!(resolver.windows(), ["login\\LoginWnd.xml"]);
+        let window = resolver.resolve("login\\LoginWnd.xml").unwrap();
+        assert!(window.problems.is_empty(), "{:?}", window.problems);
+        for source in ["common\\defaults.xml", "common\\titlebar.xml"] {
+            assert_eq!(
+                reference(&window, ResourceKind::Xml, source).status,
+                ResourceStatus::Found
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_every_corpus_set() {
+        // corpus 下每个目录是一组界面XML，新加入的样例也会被检查
+        let corpus = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus");
+        let mut checked = Vec::new();
+        for entry in std::fs::read_dir(&corpus).unwrap() {
+            let dir = entry.unwrap().path();
+            if !dir.is_dir() {
+                continue;
+            }
+            let set = SkinSet::from_dump_dir(&dir).unwrap();
+            for (_, file) in set.files() {
+                if let Err(e) = SkinDocument::parse(&file.content) {
+                    panic!("{} {}: {}", dir.display(), file.path, e);
+                }
+            }
+            let resolver = Resolver::new(&set);
+            assert!(!resolver.windows().is_empty(), "{}", dir.display());
+            for path in resolver.windows() {
+                let window = resolver.resolve(path).unwrap();
+                // 抓取时只保存客户端加载过的文件，引用的文件缺少不算错误
+                assert!(
+                    window.problems.is_empty(),
+                    "{}: {:?}",
+                    path,
+                    window.problems
+                );
+            }
+            checked.push(dir.file_name().unwrap().to_string_lossy().into_owned());
+        }
+        checked.sort();
+        assert_eq!(checked, ["skin", "skin_next"]);
+    }
+}
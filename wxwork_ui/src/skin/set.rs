@@ -0,0 +1,181 @@
+use crate::archive::{Archive, MANIFEST_FILE_NAME};
+use crate::dump_path::{list_dump_files, sanitize_resource_path};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// 一组界面XML文件，来自存档中某个版本的快照或者按目录保存的结果
+///
+/// 按规范化后的路径查找：与保存时一样清理路径，`/` 分隔，不区分大小写（Windows 的路径不区分大小写），
+/// 因此存档中的客户端路径和保存目录下的相对路径可以互相对应。
+#[derive(Debug, Clone, Default)]
+pub struct SkinSet {
+    files: BTreeMap<String, SkinFile>,
+}
+
+/// 组中的一个文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkinFile {
+    pub path: String, // 客户端路径或保存目录下的相对路径，用于显示
+    pub content: String,
+}
+
+impl SkinSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 存档中某个版本的快照
+    pub fn from_archive(archive: &Archive, client_version: &str) -> Result<Self, String> {
+        let mut set = SkinSet::new();
+        for (path, entry) in archive.snapshot(client_version) {
+            let content = archive.read_blob(&entry.hash)?;
+            set.insert(path, String::from_utf8_lossy(&content).into_owned());
+        }
+        Ok(set)
+    }
+
+    /// 保存目录下的所有 `.xml` 文件，开头的 BOM 去掉
+    pub fn from_dump_dir(dir: &Path) -> Result<Self, String> {
+        let mut set = SkinSet::new();
+        for (path, file) in list_dump_files(dir)? {
+            if !path.to_ascii_lowercase().ends_with(".xml") {
+                continue;
+            }
+            let content =
+                std::fs::read(&file).map_err(|e| format!("读取 {} 失败: {}", file.display(), e))?;
+            let content = String::from_utf8_lossy(&content);
+            let content = content.strip_prefix('\u{FEFF}').unwrap_or(&content);
+            set.insert(&path, content.to_string());
+        }
+        Ok(set)
+    }
+
+    /// 按命令行的写法读取：`<存档目录>@<客户端版本>` 读取存档中的快照，否则按保存目录读取
+    pub fn load_spec(spec: &str) -> Result<Self, String> {
+        let archived = spec
+            .rsplit_once('@')
+            .filter(|(root, _)| Path::new(root).join(MANIFEST_FILE_NAME).is_file());
+        if let Some((root, version)) = archived {
+            let archive = Archive::open(root)?;
+            if !archive.versions().contains(version) {
+                return Err(format!("存档 {} 中没有版本 {}", root, version));
+            }
+            return Self::from_archive(&archive, version);
+        }
+        let dir = Path::new(spec);
+        if !dir.is_dir() {
+            return Err(format!("{} 不是目录，也不是 <存档目录>@<版本>", spec));
+        }
+        Self::from_dump_dir(dir)
+    }
+
+    /// 加入一个文件，路径无法规范化（例如包含 `..`）时忽略，返回是否加入
+    pub fn insert(&mut self, path: &str, content: String) -> bool {
+        let Some(key) = normalize_key(path) else {
+            return false;
+        };
+        self.files.insert(
+            key,
+            SkinFile {
+                path: path.to_string(),
+                content,
+            },
+        );
+        true
+    }
+
+    /// 按路径查找，`path` 可以是客户端路径，也可以是保存目录下的相对路径
+    pub fn get(&self, path: &str) -> Option<&SkinFile> {
+        self.files.get(&normalize_key(path)?)
+    }
+
+    /// 所有文件，按规范化后的路径排序
+    pub fn files(&self) -> impl Iterator<Item = (&str, &SkinFile)> {
+        self.files.iter().map(|(key, file)| (key.as_str(), file))
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// 规范化后的路径，用于比较两个来源中的同一个文件
+pub fn normalize_key(path: &str) -> Option<String> {
+    let relative = sanitize_resource_path(path).ok()?;
+    Some(
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_lowercase())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(
+            normalize_key("Login\\LoginWnd.XML").as_deref(),
+            Some("login/loginwnd.xml")
+        );
+        assert_eq!(
+            normalize_key("login/LoginWnd.xml"),
+            normalize_key("login\\LoginWnd.xml")
+        );
+        assert_eq!(normalize_key("..\\secret.xml"), None);
+    }
+
+    #[test]
+    fn finds_files_by_either_path() {
+        let mut set = SkinSet::new();
+        assert!(set.insert("login\\LoginWnd.xml", "<Window />".to_string()));
+        assert!(!set.insert("..\\LoginWnd.xml", String::new()));
+        assert_eq!(set.len(), 1);
+        let file = set.get("LOGIN/loginwnd.xml").unwrap();
+        assert_eq!(file.path, "login\\LoginWnd.xml");
+        assert!(set.get("login\\other.xml").is_none());
+    }
+
+    #[test]
+    fn reads_dump_dir() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("login")).unwrap();
+        std::fs::write(
+            dir.path().join("login").join("LoginWnd.xml"),
+            "\u{FEFF}<Window />",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("readme.txt"), "not xml").unwrap();
+        let set = SkinSet::from_dump_dir(dir.path()).unwrap();
+        assert_eq!(set.len(), 1);
+        assert_eq!(
+            set.get("login\\LoginWnd.xml").unwrap().content,
+            "<Window />"
+        );
+
+        let spec = dir.path().to_str().unwrap();
+        assert_eq!(SkinSet::load_spec(spec).unwrap().len(), 1);
+        assert!(SkinSet::load_spec(&format!("{}@4.1.38.6006", spec)).is_err());
+    }
+
+    #[test]
+    fn loads_archive_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let mut archive = Archive::open(dir.path()).unwrap();
+        archive
+            .store("login\\LoginWnd.xml", b"<Window />", "4.1.38.6006", 0, 1)
+            .unwrap();
+        let root = dir.path().to_str().unwrap();
+        let set = SkinSet::load_spec(&format!("{}@4.1.38.6006", root)).unwrap();
+        assert_eq!(set.get("login/loginwnd.xml").unwrap().content, "<Window />");
+        assert!(SkinSet::load_spec(&format!("{}@4.1.39.0", root)).is_err());
+    }
+}
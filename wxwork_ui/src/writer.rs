@@ -0,0 +1,216 @@
+use crate::archive::Archive;
+use crate::dump_path::DumpLayout;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+
+/// 一次 LoadXML 读到的内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpJob {
+    pub path: String,    // 客户端传给 LoadXML 的路径
+    pub content: String, // 解码后的内容
+    pub flag: i32,       // LoadXML 的第二个参数
+    pub seen_at: u64,    // 读到的时间，Unix 毫秒
+}
+
+/// 在后台线程上保存读到的 XML：写到保存目录，并按版本去重存档
+///
+/// `submit` 只把内容放进有界队列，计算哈希和读写文件都在后台线程上，
+/// 被 hook 的界面线程不会因为磁盘变慢而卡住；队列满时丢弃并计数。
+/// 每保存一次调用 `on_event` 报告结果或错误。
+pub struct DumpWriter {
+    sender: Option<SyncSender<DumpJob>>,
+    dropped: Arc<AtomicU64>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl DumpWriter {
+    /// 启动后台线程，`archive` 为存档和当前客户端版本，为 None 时不存档
+    pub fn start(
+        layout: DumpLayout,
+        archive: Option<(Archive, String)>,
+        capacity: usize,
+        on_event: impl Fn(String) + Send + 'static,
+    ) -> Result<Self, String> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(capacity.max(1));
+        let worker = std::thread::Builder::new()
+            .name("xml_dump".to_string())
+            .spawn(move || run_worker(receiver, layout, archive, on_event))
+            .map_err(|e| format!("启动XML保存线程失败: {}", e))?;
+        Ok(DumpWriter {
+            sender: Some(sender),
+            dropped: Arc::new(AtomicU64::new(0)),
+            worker: Some(worker),
+        })
+    }
+
+    /// 提交一次读到的内容，队列满或后台线程已退出时丢弃并返回 false
+    pub fn submit(&self, job: DumpJob) -> bool {
+        let Some(sender) = &self.sender else {
+            return false;
+        };
+        match sender.try_send(job) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// 丢弃的条数
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// 保存完队列中剩余的内容后结束后台线程
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    /// 关闭队列但不等待后台线程，用于不能等待的地方（如 DllMain）
+    pub fn close(mut self) {
+        self.sender = None;
+        self.worker = None;
+    }
+
+    fn stop(&mut self) {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for DumpWriter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run_worker(
+    receiver: Receiver<DumpJob>,
+    layout: DumpLayout,
+    mut archive: Option<(Archive, String)>,
+    on_event: impl Fn(String),
+) {
+    for job in receiver {
+        // 路径来自客户端，规范化后保存在保存目录下，包含 .. 的路径拒绝保存
+        match layout.write(&job.path, &job.content) {
+            Ok(file_path) => on_event(format!("XML内容已保存到: {}", file_path.display())),
+            Err(e) => on_event(format!("保存XML文件失败: {}", e)),
+        }
+
+        let Some((archive, client_version)) = archive.as_mut() else {
+            continue;
+        };
+        match archive.store(
+            &job.path,
+            job.content.as_bytes(),
+            client_version,
+            job.flag,
+            job.seen_at,
+        ) {
+            Ok(outcome) if outcome.new_entry => on_event(format!(
+                "XML已存档: {} ({}{})",
+                job.path,
+                &outcome.hash[..12],
+                if outcome.new_blob { "，新内容" } else { "" }
+            )),
+            Ok(_) => {}
+            Err(e) => on_event(format!("XML存档失败: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+
+    fn job(path: &str, content: &str, seen_at: u64) -> DumpJob {
+        DumpJob {
+            path: path.to_string(),
+            content: content.to_string(),
+            flag: 0,
+            seen_at,
+        }
+    }
+
+    #[test]
+    fn saves_and_archives_in_background() {
+        let dir = TempDir::new().unwrap();
+        let layout = DumpLayout::new(dir.path().join("dump"), true);
+        let archive = Archive::open(dir.path().join("archive")).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let reported = events.clone();
+        let writer = DumpWriter::start(
+            layout.clone(),
+            Some((archive, "4.1.38.6006".to_string())),
+            16,
+            move |event| reported.lock().unwrap().push(event),
+        )
+        .unwrap();
+        assert!(writer.submit(job("login\\a.xml", "<A/>", 1)));
+        assert!(writer.submit(job("login\\a.xml", "<B/>", 2)));
+        assert!(writer.submit(job("login\\a.xml", "<A/>", 3)));
+        assert!(writer.submit(job("..\\escape.xml", "<X/>", 4)));
+        writer.shutdown();
+
+        let saved = std::fs::read(layout.path_for("login\\a.xml").unwrap()).unwrap();
+        assert_eq!(saved, b"\xEF\xBB\xBF<A/>");
+        let archive = Archive::open(dir.path().join("archive")).unwrap();
+        assert_eq!(archive.entries().len(), 4);
+        assert_eq!(archive.snapshot("4.1.38.6006")["login\\a.xml"].seq, 3);
+        let events = events.lock().unwrap();
+        assert!(
+            events
+                .iter()
+                .any(|event| event.starts_with("保存XML文件失败"))
+        );
+        assert!(!dir.path().join("escape.xml").exists());
+    }
+
+    #[test]
+    fn close_does_not_wait() {
+        let dir = TempDir::new().unwrap();
+        let layout = DumpLayout::new(dir.path(), false);
+        let writer = DumpWriter::start(layout.clone(), None, 16, |_| {}).unwrap();
+        writer.submit(job("a.xml", "<A/>", 1));
+        writer.close();
+        let path = layout.path_for("a.xml").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !path.exists() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn full_queue_drops_instead_of_blocking() {
+        let dir = TempDir::new().unwrap();
+        // 后台线程卡在第一条的回调里，队列只能再放一条
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        let blocked = Mutex::new(blocked);
+        let writer = DumpWriter::start(DumpLayout::new(dir.path(), false), None, 1, move |_| {
+            let _ = blocked.lock().unwrap().recv();
+        })
+        .unwrap();
+        assert!(writer.submit(job("a.xml", "<A/>", 1)));
+        let start = Instant::now();
+        let mut accepted = 0;
+        for index in 0..4 {
+            if writer.submit(job(&format!("{}.xml", index), "<B/>", 2)) {
+                accepted += 1;
+            }
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(accepted <= 1);
+        assert_eq!(writer.dropped(), 4 - accepted);
+        drop(release);
+        writer.shutdown();
+    }
+}